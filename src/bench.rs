@@ -0,0 +1,123 @@
+//! `inlyne bench` runs the rendering pipeline repeatedly in a hidden window and reports timings
+//!
+//! Useful for getting reproducible numbers to attach to performance regression reports
+
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use crate::encoding::read_to_string;
+use crate::interpreter::HtmlInterpreter;
+use crate::metrics::{histogram, with_local_recorder, HistTag, LogRecorder};
+use crate::opts::Opts;
+use crate::renderer::Renderer;
+use crate::selection::Selection;
+use crate::utils::ImageCache;
+use crate::{Inlyne, InlyneEvent};
+
+use anyhow::Context;
+use parking_lot::Mutex;
+use winit::event_loop::EventLoopBuilder;
+use winit::window::WindowBuilder;
+
+/// Formats the timings recorded for a stage
+fn report(name: &str, recorder: &LogRecorder, tag: HistTag) -> String {
+    let Some(summary) = recorder.histogram_summary(tag).filter(|s| !s.is_empty()) else {
+        return format!("{name:>9} | no samples");
+    };
+    let quantile = |q| {
+        let secs = summary.quantile(q).expect("Has values");
+        Duration::from_secs_f64(secs)
+    };
+    let (p50, p90, p99) = (quantile(0.5), quantile(0.9), quantile(0.99));
+    let max = Duration::from_secs_f64(summary.max());
+
+    format!("{name:>9} | p50 {p50:.02?} | p90 {p90:.02?} | p99 {p99:.02?} | max {max:.02?}")
+}
+
+#[allow(clippy::print_stdout)]
+pub fn run(opts: Opts, iterations: u32) -> anyhow::Result<()> {
+    let file_path = opts.history.get_path().to_owned();
+    let md_string = read_to_string(&file_path)
+        .with_context(|| format!("Could not read file at '{}'", file_path.display()))?;
+
+    let event_loop = EventLoopBuilder::<InlyneEvent>::with_user_event().build();
+    let window = Arc::new(
+        WindowBuilder::new()
+            .with_visible(false)
+            .build(&event_loop)
+            .context("Failed creating a window to render in")?,
+    );
+    let event_loop_proxy = event_loop.create_proxy();
+
+    let mut renderer = pollster::block_on(Renderer::new(
+        &window,
+        opts.theme.clone(),
+        opts.scale.unwrap_or(window.scale_factor() as f32),
+        opts.page_width.unwrap_or(f32::MAX),
+        opts.font_opts.clone(),
+        opts.wide_gamut,
+    ))?;
+    renderer.element_spacing = opts.element_spacing;
+    let image_cache = ImageCache::default();
+
+    // Timings go through the same histograms as the live metrics, collected by a recorder that
+    // only lives for the run
+    let recorder = LogRecorder::default();
+    with_local_recorder(&recorder, || -> anyhow::Result<()> {
+        for _ in 0..iterations {
+            let element_queue = Arc::new(Mutex::new(Vec::new()));
+            let interpreter = HtmlInterpreter::new(
+                Arc::clone(&window),
+                Arc::clone(&element_queue),
+                renderer.theme.clone(),
+                renderer.surface_format,
+                renderer.hidpi_scale,
+                Arc::clone(&image_cache),
+                event_loop_proxy.clone(),
+                opts.color_scheme,
+                true,
+                false,
+                false,
+                true,
+                true,
+                true,
+            )
+            .with_opts(&opts);
+            // Closing the channel lets the interpreter return after this one document
+            let (md_sender, md_receiver) = mpsc::channel();
+            md_sender.send(md_string.clone().into())?;
+            drop(md_sender);
+            let start = Instant::now();
+            interpreter.interpret_md(md_receiver);
+            histogram!(HistTag::Interpret).record(start.elapsed());
+
+            // The positioner samples each pass over the queue, so time the whole layout instead
+            let mut elements = Vec::new();
+            renderer.positioner.reserved_height =
+                opts.element_spacing.paragraph * renderer.hidpi_scale;
+            renderer.positioner.anchors.clear();
+            let start = Instant::now();
+            while Inlyne::position_queued_elements(&element_queue, &mut renderer, &mut elements) {}
+            histogram!(HistTag::Layout).record(start.elapsed());
+
+            // Each run lays the document out fresh, so it gets drawn fresh too
+            renderer.invalidate_document();
+            let start = Instant::now();
+            renderer.redraw(&mut elements, &mut Selection::new())?;
+            histogram!(HistTag::Redraw).record(start.elapsed());
+        }
+        Ok(())
+    })?;
+
+    println!("{iterations} iterations of {}", file_path.display());
+    let stages = [
+        ("interpret", HistTag::Interpret),
+        ("position", HistTag::Layout),
+        ("redraw", HistTag::Redraw),
+    ];
+    for (name, tag) in stages {
+        println!("{}", report(name, &recorder, tag));
+    }
+
+    Ok(())
+}
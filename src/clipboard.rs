@@ -33,6 +33,13 @@ impl Clipboard {
         Self(clipboard)
     }
 
+    pub fn get_contents(&mut self) -> Option<String> {
+        self.0
+            .get_contents()
+            .map_err(|err| tracing::warn!("Unable to read text from clipboard: {}", err))
+            .ok()
+    }
+
     pub fn set_contents(&mut self, text: impl Into<String>) {
         self.0.set_contents(text.into()).unwrap_or_else(|err| {
             tracing::warn!("Unable to store text in clipboard: {}", err);
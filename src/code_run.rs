@@ -0,0 +1,188 @@
+//! Running fenced code blocks tagged with `run` e.g. ```` ```python run ````, for languages that
+//! have a command in the `[run-commands]` config section
+//!
+//! Nothing runs until the block's run link gets clicked and the prompt that follows confirmed.
+//! Snippets then run in an empty temporary directory with a cleared environment, and get killed
+//! once they pass a timeout. That keeps a stray snippet from poking around the document's folder,
+//! but it's no substitute for a real sandbox
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::plugins::replace_fenced_blocks;
+use crate::subprocess;
+
+use anyhow::Context;
+use parking_lot::Mutex;
+
+/// Run links point at this followed by the snippet's key
+const SCHEME: &str = "inlyne-run:";
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Output past this is dropped, per stream
+const MAX_OUTPUT: u64 = 64 * 1024;
+/// The only variables that snippets get to see
+const KEPT_VARS: &[&str] = &["PATH", "LANG", "SYSTEMROOT"];
+
+#[derive(Clone, Debug)]
+struct Snippet {
+    lang: String,
+    command: Vec<String>,
+    body: String,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    snippets: HashMap<String, Snippet>,
+    outputs: HashMap<String, String>,
+}
+
+/// Fenced block languages mapped to the command that runs them, along with the output of any
+/// snippets that ran
+#[derive(Clone, Debug, Default)]
+pub struct CodeRunner {
+    commands: Arc<HashMap<String, Vec<String>>>,
+    state: Arc<Mutex<State>>,
+}
+
+impl CodeRunner {
+    pub fn new(commands: HashMap<String, Vec<String>>) -> Self {
+        Self {
+            commands: Arc::new(commands),
+            state: Default::default(),
+        }
+    }
+
+    /// Adds a run link below each runnable block, followed by its output once it has run
+    pub fn add_run_links(&self, md: String) -> String {
+        if self.commands.is_empty() {
+            return md;
+        }
+
+        replace_fenced_blocks(&md, |block| {
+            if !block.info.split_whitespace().any(|word| word == "run") {
+                return None;
+            }
+            let command = self.commands.get(block.lang)?;
+            let key = format!("{:016x}", fxhash::hash64(&(command, block.body)));
+            let snippet = Snippet {
+                lang: block.lang.to_owned(),
+                command: command.to_owned(),
+                body: block.body.to_owned(),
+            };
+
+            let mut state = self.state.lock();
+            state.snippets.entry(key.clone()).or_insert(snippet);
+            let mut replaced = format!("{}\n[▶ Run {}]({SCHEME}{key})\n", block.source, block.lang);
+            if let Some(output) = state.outputs.get(&key) {
+                // The fence has to outlast any run of backticks in the output
+                let longest = output.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+                let fence = "`".repeat(longest.max(2) + 1);
+                let newline = if output.ends_with('\n') { "" } else { "\n" };
+                replaced.push_str(&format!("\n{fence}text\n{output}{newline}{fence}\n"));
+            }
+            Some(replaced)
+        })
+    }
+
+    /// The snippet that a run link points at
+    pub fn key(&self, link: &str) -> Option<String> {
+        let key = link.strip_prefix(SCHEME)?;
+        self.state
+            .lock()
+            .snippets
+            .contains_key(key)
+            .then(|| key.to_owned())
+    }
+
+    /// What gets asked before running a snippet
+    pub fn prompt(&self, key: &str) -> Option<String> {
+        let state = self.state.lock();
+        let Snippet { lang, command, .. } = state.snippets.get(key)?;
+        Some(format!(
+            "Run this {lang} block with `{}`? Press y to run or any other key to cancel",
+            command.join(" ")
+        ))
+    }
+
+    /// Runs a snippet in the background, calling `on_finish` once its output is in
+    pub fn run(&self, key: String, on_finish: impl FnOnce() + Send + 'static) {
+        let mut state = self.state.lock();
+        let Some(snippet) = state.snippets.get(&key).cloned() else {
+            return;
+        };
+        state.outputs.insert(key.clone(), "Running...".to_owned());
+        drop(state);
+
+        let state = Arc::clone(&self.state);
+        thread::spawn(move || {
+            let output = execute(&key, &snippet).unwrap_or_else(|err| format!("{err:#}"));
+            state.lock().outputs.insert(key, output);
+            on_finish();
+        });
+    }
+}
+
+fn execute(key: &str, snippet: &Snippet) -> anyhow::Result<String> {
+    let (program, args) = snippet.command.split_first().context("Empty run command")?;
+    let dir = std::env::temp_dir().join("inlyne-run").join(key);
+    std::fs::create_dir_all(&dir)?;
+    let kept_vars = KEPT_VARS
+        .iter()
+        .filter_map(|&name| Some((name, std::env::var_os(name)?)));
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .current_dir(&dir)
+        .env_clear()
+        .envs(kept_vars);
+    let finished = subprocess::run(
+        &mut command,
+        snippet.body.clone().into_bytes(),
+        TIMEOUT,
+        MAX_OUTPUT,
+    )
+    .with_context(|| format!("Failed running '{program}'"))?;
+
+    let mut output = String::from_utf8_lossy(&finished.stdout).into_owned();
+    output.push_str(&String::from_utf8_lossy(&finished.stderr));
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+    match finished.status {
+        Some(status) if status.success() => {}
+        Some(status) => output.push_str(&format!("[{program} exited with {status}]\n")),
+        None => output.push_str(&format!("[Killed after {}s]\n", TIMEOUT.as_secs())),
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_links_and_output() {
+        let commands =
+            HashMap::from([("python".to_owned(), vec!["python3".to_owned(), "-".into()])]);
+        let runner = CodeRunner::new(commands);
+        let md = "```python run\nprint(1)\n```\n\n```python\nprint(2)\n```\n";
+
+        let linked = runner.add_run_links(md.to_owned());
+        let (block, rest) = linked.split_once("\n[▶ Run python](").unwrap();
+        assert_eq!(block, "```python run\nprint(1)\n```\n");
+        let (link, rest) = rest.split_once(")\n").unwrap();
+        assert_eq!(rest, "\n```python\nprint(2)\n```\n");
+        let key = runner.key(link).unwrap();
+        assert!(runner.prompt(&key).unwrap().contains("`python3 -`"));
+
+        let output = "```\n1\n";
+        runner.state.lock().outputs.insert(key, output.to_owned());
+        let linked = runner.add_run_links(md.to_owned());
+        assert!(linked.contains(")\n\n````text\n```\n1\n````\n"));
+        assert_eq!(runner.key("inlyne-run:unknown"), None);
+    }
+}
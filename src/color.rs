@@ -23,33 +23,96 @@ fn hex_to_linear_rgba(c: u32) -> [f32; 4] {
     [f(c >> 16), f(c >> 8), f(c), 1.0]
 }
 
-pub fn native_color(c: u32, format: &TextureFormat) -> [f32; 4] {
+/// Whether `format` takes linear values. sRGB formats encode them for display, and float formats
+/// (used for wide-gamut output) are linear themselves. Everything else displays values as is, so
+/// it takes them already sRGB encoded
+fn expects_linear(format: &TextureFormat) -> bool {
     use wgpu::TextureFormat::*;
+    format.is_srgb() || matches!(format, Rgba16Float | Rgba32Float)
+}
+
+/// Converts a hex sRGB color, like the ones in the config, for drawing to `format`
+pub fn native_color(c: u32, format: &TextureFormat) -> [f32; 4] {
     let f = |xu: u32| (xu & 0xff) as f32 / 255.0;
 
-    match format {
-        Rgba8UnormSrgb | Bgra8UnormSrgb => hex_to_linear_rgba(c),
-        _ => [f(c >> 16), f(c >> 8), f(c), 1.0],
+    if expects_linear(format) {
+        hex_to_linear_rgba(c)
+    } else {
+        [f(c >> 16), f(c >> 8), f(c), 1.0]
     }
 }
 
+/// Like [`native_color()`], but see-through by `alpha` from `0.0` to `1.0`. Alpha isn't encoded
+/// so it's the same for every format
+pub fn native_color_alpha(c: u32, alpha: f32, format: &TextureFormat) -> [f32; 4] {
+    let [r, g, b, _] = native_color(c, format);
+    [r, g, b, alpha.clamp(0.0, 1.0)]
+}
+
+/// The format to upload images with, so that they go through the same transfer function as
+/// colors from `native_color`
+pub fn image_texture_format(surface_format: &TextureFormat) -> TextureFormat {
+    if expects_linear(surface_format) {
+        TextureFormat::Rgba8UnormSrgb
+    } else {
+        TextureFormat::Rgba8Unorm
+    }
+}
+
+/// Layout parameters shared by both color schemes along with the palette for the current one
 #[derive(Clone, Debug, PartialEq)]
 pub struct Theme {
+    pub scrollbar_width: u32,
+    pub page_margin: u32,
+    pub palette: Palette,
+}
+
+impl Theme {
+    pub fn dark_default() -> Self {
+        Self::with_palette(Palette::dark_default())
+    }
+
+    pub fn light_default() -> Self {
+        Self::with_palette(Palette::light_default())
+    }
+
+    fn with_palette(palette: Palette) -> Self {
+        Self {
+            scrollbar_width: 5,
+            page_margin: 100,
+            palette,
+        }
+    }
+
+    pub fn code_highlighter(mut self, theme: SyntectTheme) -> Self {
+        self.palette.code_highlighter = theme;
+        self
+    }
+}
+
+/// The colors for either the light or dark color scheme
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
     pub text_color: u32,
     pub background_color: u32,
     pub code_color: u32,
     pub quote_block_color: u32,
     pub link_color: u32,
     pub select_color: u32,
+    pub select_alpha: f32,
+    /// Every match of the search query
+    pub search_color: u32,
+    pub search_alpha: f32,
+    /// The search match that was jumped to last
+    pub match_color: u32,
+    pub match_alpha: f32,
     pub checkbox_color: u32,
     pub header_color: u32,
-    pub scrollbar_width: u32,
     pub scrollbar_color: u32,
-    pub page_margin: u32,
     pub code_highlighter: SyntectTheme,
 }
 
-impl Theme {
+impl Palette {
     pub fn dark_default() -> Self {
         static CACHED_CODE_HIGHLIGHTER: OnceLock<SyntectTheme> = OnceLock::new();
         // Initializing this is non-trivial. Cache so it only runs once
@@ -63,11 +126,14 @@ impl Theme {
             quote_block_color: 0x1D2025,
             link_color: 0x4182EB,
             select_color: 0x3675CB,
+            select_alpha: 1.0,
+            search_color: 0x3675CB,
+            search_alpha: 1.0,
+            match_color: 0x2E6B3A,
+            match_alpha: 1.0,
             checkbox_color: 0x0A5301,
             header_color: 0x9DACBB,
-            scrollbar_width: 5,
             scrollbar_color: 0x4D4D4D,
-            page_margin: 100,
             code_highlighter,
         }
     }
@@ -85,19 +151,17 @@ impl Theme {
             quote_block_color: 0xEEF9FE,
             link_color: 0x5466FF,
             select_color: 0xCDE8F0,
+            select_alpha: 1.0,
+            search_color: 0xCDE8F0,
+            search_alpha: 1.0,
+            match_color: 0xB5F0B5,
+            match_alpha: 1.0,
             checkbox_color: 0x96ECAE,
             header_color: 0x000000,
-            scrollbar_width: 5,
             scrollbar_color: 0xCCCCCC,
-            page_margin: 100,
             code_highlighter,
         }
     }
-
-    pub fn code_highlighter(mut self, theme: SyntectTheme) -> Self {
-        self.code_highlighter = theme;
-        self
-    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -295,3 +359,32 @@ impl From<ThemeDefaults> for SyntectTheme {
         theme
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_function_matches_surface() {
+        use wgpu::TextureFormat::*;
+
+        const GRAY: u32 = 0x808080;
+        let encoded = 128.0 / 255.0;
+        let linear = 0.21586;
+
+        for (format, expected, image_format) in [
+            (Bgra8UnormSrgb, linear, Rgba8UnormSrgb),
+            (Rgba16Float, linear, Rgba8UnormSrgb),
+            (Bgra8Unorm, encoded, Rgba8Unorm),
+        ] {
+            let [r, g, b, a] = native_color(GRAY, &format);
+            assert!((r - expected).abs() < 1e-4, "{format:?}: {r}");
+            assert_eq!((r, r, 1.0), (g, b, a));
+            assert_eq!(image_texture_format(&format), image_format);
+
+            let [_, _, _, a] = native_color_alpha(GRAY, 0.5, &format);
+            assert_eq!(a, 0.5);
+        }
+        assert_eq!(native_color_alpha(GRAY, 2.0, &Bgra8Unorm)[3], 1.0);
+    }
+}
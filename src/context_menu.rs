@@ -0,0 +1,81 @@
+//! The menu that right-clicking a table or code block opens in the banner, listing the ways that
+//! it can be copied
+
+use crate::table::TableFormat;
+use crate::Element;
+
+struct Entry {
+    label: &'static str,
+    /// What goes to the clipboard when the entry gets picked
+    contents: String,
+}
+
+pub struct ContextMenu {
+    title: &'static str,
+    entries: Vec<Entry>,
+    selected: usize,
+}
+
+impl ContextMenu {
+    /// The menu for an element, if it's one that has anything to offer
+    pub fn for_element(element: &Element) -> Option<Self> {
+        let (title, entries) = match element {
+            Element::Table(table) => {
+                let formats = [
+                    ("Copy table as Markdown", TableFormat::Markdown),
+                    ("Copy table as CSV", TableFormat::Csv),
+                    ("Copy table as TSV", TableFormat::Tsv),
+                ];
+                let entries = formats
+                    .into_iter()
+                    .map(|(label, format)| Entry {
+                        label,
+                        contents: table.serialize(format),
+                    })
+                    .collect();
+                ("Table", entries)
+            }
+            Element::TextBox(text_box) if text_box.is_code_block => {
+                let code = text_box.plain_text();
+                let entry = Entry {
+                    label: "Copy code",
+                    contents: code.trim_end_matches('\n').to_owned(),
+                };
+                ("Code block", vec![entry])
+            }
+            _ => return None,
+        };
+        Some(Self {
+            title,
+            entries,
+            selected: 0,
+        })
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.entries.len() - 1);
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn selected(&self) -> &str {
+        &self.entries[self.selected].contents
+    }
+
+    /// The contents of the n-th (0-indexed) entry
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|entry| entry.contents.as_str())
+    }
+
+    /// Text for the banner with the entries numbered for picking them directly
+    pub fn overlay(&self) -> String {
+        let mut overlay = format!("{} (Enter to copy, Esc to close)", self.title);
+        for (i, entry) in self.entries.iter().enumerate() {
+            let marker = if i == self.selected { '>' } else { ' ' };
+            overlay.push_str(&format!("\n{marker} {}. {}", i + 1, entry.label));
+        }
+        overlay
+    }
+}
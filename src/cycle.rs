@@ -0,0 +1,75 @@
+//! `--cycle` rotates through several documents on a timer, for status boards and the like
+//!
+//! Only the document on screen is watched, but each one gets read again when its turn comes up
+//! so changes made in the meantime still show. Every document keeps its own scroll position
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub struct Cycle {
+    files: Vec<PathBuf>,
+    scroll_ys: Vec<f32>,
+    current: usize,
+    interval: Duration,
+    last_switch: Instant,
+    /// Where to scroll back to once the document on screen is laid out far enough
+    restore: Option<f32>,
+}
+
+impl Cycle {
+    pub fn new(files: Vec<PathBuf>, interval: Duration) -> Self {
+        Self {
+            scroll_ys: vec![0.; files.len()],
+            files,
+            current: 0,
+            interval,
+            last_switch: Instant::now(),
+            restore: None,
+        }
+    }
+
+    pub fn next_switch(&self) -> Instant {
+        self.last_switch + self.interval
+    }
+
+    /// Gives the current document another full interval
+    pub fn restart_timer(&mut self) {
+        self.last_switch = Instant::now();
+    }
+
+    /// Moves on to the next document, remembering where the current one was scrolled to
+    pub fn advance(&mut self, scroll_y: f32) -> &Path {
+        self.restart_timer();
+        self.scroll_ys[self.current] = scroll_y;
+        self.current = (self.current + 1) % self.files.len();
+        let scroll_y = self.scroll_ys[self.current];
+        self.restore = (scroll_y > 0.).then_some(scroll_y);
+        &self.files[self.current]
+    }
+
+    /// The scroll position to go back to, once the document can scroll that far
+    pub fn take_restore(&mut self, max_scroll_y: f32) -> Option<f32> {
+        let scroll_y = self.restore.filter(|&scroll_y| scroll_y <= max_scroll_y)?;
+        self.restore = None;
+        Some(scroll_y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scroll_follows_each_document() {
+        let files = vec![PathBuf::from("a.md"), PathBuf::from("b.md")];
+        let mut cycle = Cycle::new(files, Duration::from_secs(30));
+
+        assert_eq!(cycle.advance(120.), Path::new("b.md"));
+        assert_eq!(cycle.take_restore(1_000.), None);
+        assert_eq!(cycle.advance(0.), Path::new("a.md"));
+        // Waits for the document to get long enough
+        assert_eq!(cycle.take_restore(50.), None);
+        assert_eq!(cycle.take_restore(1_000.), Some(120.));
+        assert_eq!(cycle.take_restore(1_000.), None);
+    }
+}
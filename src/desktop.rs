@@ -0,0 +1,186 @@
+//! `inlyne install-desktop` associates markdown files with inlyne, so that double-clicking them
+//! opens them in the viewer
+//!
+//! - Linux (and other freedesktop platforms) get a `.desktop` file that's set as the default for
+//!   `text/markdown`
+//! - Windows gets per-user registry entries that add inlyne to the "Open with" list
+//! - macOS isn't supported since files opened from Finder are passed through Apple Events, which
+//!   we don't handle
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::Context;
+
+pub fn install(dry_run: bool) -> anyhow::Result<()> {
+    let exe = std::env::current_exe().context("Failed to find the inlyne executable")?;
+    let installer = Installer { dry_run };
+    platform::install(&installer, &exe)
+}
+
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+struct Installer {
+    dry_run: bool,
+}
+
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+impl Installer {
+    #[cfg_attr(windows, allow(dead_code))]
+    fn write(&self, path: &Path, contents: &str) -> anyhow::Result<()> {
+        if self.dry_run {
+            println!("Would write '{}':\n{contents}", path.display());
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed writing '{}'", path.display()))?;
+        println!("Wrote '{}'", path.display());
+        Ok(())
+    }
+
+    /// Runs a helper program, only warning on failure since they're not always installed
+    fn run(&self, program: &str, args: &[&str]) {
+        if self.dry_run {
+            println!("Would run: {program} {}", args.join(" "));
+            return;
+        }
+
+        match Command::new(program).args(args).status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => tracing::warn!("`{program}` exited with {status}"),
+            Err(err) => tracing::warn!("Failed running `{program}`: {err}"),
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use std::path::Path;
+
+    use super::Installer;
+
+    use anyhow::Context;
+
+    pub fn install(installer: &Installer, exe: &Path) -> anyhow::Result<()> {
+        let applications = dirs::data_dir()
+            .context("Failed to find the data directory")?
+            .join("applications");
+        installer.write(&applications.join("inlyne.desktop"), &desktop_entry(exe))?;
+
+        let applications = applications.to_string_lossy();
+        installer.run("update-desktop-database", &[&applications]);
+        installer.run("xdg-mime", &["default", "inlyne.desktop", "text/markdown"]);
+        Ok(())
+    }
+
+    /// Our bundled desktop entry pointing at `exe` instead of relying on `$PATH`
+    pub fn desktop_entry(exe: &Path) -> String {
+        let exe = exe.to_string_lossy();
+        // Quoted as an argument of `Exec`, where a lone `%` would start a field code
+        let mut quoted = String::from("\"");
+        for c in exe.chars() {
+            match c {
+                '"' | '`' | '$' | '\\' => quoted.extend(['\\', c]),
+                '%' => quoted.push_str("%%"),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        let quoted = escape_string(&quoted);
+
+        include_str!("../assets/inlyne.desktop")
+            .lines()
+            .map(|line| {
+                if line.starts_with("Exec=") {
+                    format!("Exec={quoted} view %f\n")
+                } else {
+                    format!("{line}\n")
+                }
+            })
+            .collect()
+    }
+
+    /// Escapes a value of the `string` type. This happens after quoting for `Exec`, so each
+    /// backslash from there ends up doubled again
+    fn escape_string(value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\t' => escaped.push_str("\\t"),
+                '\r' => escaped.push_str("\\r"),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn exec_uses_the_full_path() {
+            let entry = desktop_entry(Path::new("/opt/my apps/inlyne"));
+            assert!(entry.contains("\nExec=\"/opt/my apps/inlyne\" view %f\n"));
+            assert!(entry.contains("\nMimeType=text/markdown;\n"));
+        }
+
+        #[test]
+        fn exec_escapes_both_levels() {
+            let entry = desktop_entry(Path::new("/opt/100%/$x\\y\"/inlyne"));
+            let exec = r#"Exec="/opt/100%%/\\$x\\\\y\\"/inlyne" view %f"#;
+            assert!(entry.contains(&format!("\n{exec}\n")));
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::path::Path;
+
+    use super::Installer;
+
+    const PROG_ID: &str = "Inlyne.Markdown";
+
+    pub fn install(installer: &Installer, exe: &Path) -> anyhow::Result<()> {
+        let classes = r"HKCU\Software\Classes";
+        let prog_id = format!(r"{classes}\{PROG_ID}");
+        let command_key = format!(r"{prog_id}\shell\open\command");
+        let command = format!("\"{}\" view \"%1\"", exe.display());
+        installer.run(
+            "reg",
+            &["add", &prog_id, "/ve", "/d", "Markdown Document", "/f"],
+        );
+        installer.run("reg", &["add", &command_key, "/ve", "/d", &command, "/f"]);
+        for ext in [".md", ".markdown"] {
+            let open_with = format!(r"{classes}\{ext}\OpenWithProgids");
+            installer.run(
+                "reg",
+                &["add", &open_with, "/v", PROG_ID, "/t", "REG_NONE", "/f"],
+            );
+        }
+
+        // Windows doesn't allow programs to set themselves as the default
+        println!("Inlyne is now listed under \"Open with\" for markdown files");
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::path::Path;
+
+    use super::Installer;
+
+    pub fn install(_: &Installer, _: &Path) -> anyhow::Result<()> {
+        anyhow::bail!(
+            "Desktop integration isn't supported on macOS yet since files opened from Finder are \
+            passed through Apple Events"
+        )
+    }
+}
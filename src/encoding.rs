@@ -0,0 +1,86 @@
+//! Documents don't always come in UTF-8. Files with a UTF-16 byte order mark get transcoded, and
+//! anything else that isn't valid UTF-8 is taken to be Latin-1, which every byte sequence is valid
+//! in, instead of refusing to open it
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+            Self::Latin1 => "Latin-1",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Reads a file as text in whatever encoding it turns out to be in
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    read_with_encoding(path).map(|(text, _)| text)
+}
+
+pub fn read_with_encoding(path: &Path) -> io::Result<(String, Encoding)> {
+    std::fs::read(path).map(decode)
+}
+
+pub fn decode(bytes: Vec<u8>) -> (String, Encoding) {
+    match bytes.as_slice() {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => {
+            (String::from_utf8_lossy(rest).into_owned(), Encoding::Utf8)
+        }
+        [0xFF, 0xFE, rest @ ..] => (decode_utf16(rest, u16::from_le_bytes), Encoding::Utf16Le),
+        [0xFE, 0xFF, rest @ ..] => (decode_utf16(rest, u16::from_be_bytes), Encoding::Utf16Be),
+        _ => match String::from_utf8(bytes) {
+            Ok(text) => (text, Encoding::Utf8),
+            // Latin-1 maps each byte straight onto the code point with the same value
+            Err(err) => {
+                let text = err.into_bytes().into_iter().map(char::from).collect();
+                (text, Encoding::Latin1)
+            }
+        },
+    }
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> String {
+    let units: Vec<_> = bytes
+        .chunks_exact(2)
+        .map(|pair| to_unit([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_and_transcode() {
+        let utf8 = "# Café".as_bytes().to_owned();
+        assert_eq!(decode(utf8.clone()), ("# Café".to_owned(), Encoding::Utf8));
+        let bom = [&[0xEF, 0xBB, 0xBF], utf8.as_slice()].concat();
+        assert_eq!(decode(bom), ("# Café".to_owned(), Encoding::Utf8));
+
+        let le: Vec<u8> = "# Café".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let le = [&[0xFF, 0xFE], le.as_slice()].concat();
+        assert_eq!(decode(le), ("# Café".to_owned(), Encoding::Utf16Le));
+        let be: Vec<u8> = "# Café".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let be = [&[0xFE, 0xFF], be.as_slice()].concat();
+        assert_eq!(decode(be), ("# Café".to_owned(), Encoding::Utf16Be));
+
+        let latin1 = b"# Caf\xE9".to_vec();
+        assert_eq!(decode(latin1), ("# Café".to_owned(), Encoding::Latin1));
+    }
+}
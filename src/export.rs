@@ -0,0 +1,104 @@
+//! `inlyne export html` writes a markdown file out as a standalone HTML page styled after the
+//! current theme
+//!
+//! The markdown goes through the same conversion as the viewer, so code blocks keep their syntax
+//! highlighting. Images and relative links are left as they were written, so they only resolve
+//! when the page sits beside the original file
+
+use std::fmt::Write;
+
+use crate::color::Theme;
+use crate::utils::markdown_to_html;
+
+pub fn html(
+    md: &str,
+    title: &str,
+    theme: &Theme,
+    page_width: Option<f32>,
+    smart_punctuation: bool,
+) -> String {
+    let code_highlighter = theme.palette.code_highlighter.clone();
+    let body = markdown_to_html(md, code_highlighter, smart_punctuation);
+    let mut page = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(page, "<title>{}</title>", escape(title));
+    let _ = writeln!(page, "<style>\n{}</style>", css(theme, page_width));
+    let _ = write!(page, "</head>\n<body>\n{body}</body>\n</html>\n");
+    page
+}
+
+fn css(theme: &Theme, page_width: Option<f32>) -> String {
+    let palette = &theme.palette;
+    let hex = |color: u32| format!("#{color:06x}");
+    let alpha = (palette.select_alpha.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let max_width = page_width.map_or("none".to_owned(), |width| format!("{width}px"));
+    format!(
+        "\
+body {{
+  color: {text};
+  background-color: {background};
+  font-family: sans-serif;
+  line-height: 1.5;
+  max-width: {max_width};
+  margin: 0 auto;
+  padding: 1em {margin}px;
+}}
+h1, h2, h3, h4, h5, h6 {{ color: {header}; }}
+a {{ color: {link}; }}
+code {{ color: {code}; font-family: monospace; }}
+pre {{ padding: 0.75em; overflow-x: auto; }}
+pre code {{ color: inherit; }}
+blockquote {{
+  background-color: {quote};
+  margin: 0;
+  padding: 0.25em 1em;
+}}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid {scrollbar}; padding: 0.25em 0.75em; }}
+input[type=checkbox] {{ accent-color: {checkbox}; }}
+::selection {{ background-color: {select}; }}
+",
+        text = hex(palette.text_color),
+        background = hex(palette.background_color),
+        margin = theme.page_margin,
+        header = hex(palette.header_color),
+        link = hex(palette.link_color),
+        code = hex(palette.code_color),
+        quote = hex(palette.quote_block_color),
+        scrollbar = hex(palette.scrollbar_color),
+        checkbox = hex(palette.checkbox_color),
+        select = format!("{}{alpha:02x}", hex(palette.select_color)),
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standalone_page() {
+        let theme = Theme::light_default();
+        let page = html("# Hi\n\nSome *text*", "a <b>", &theme, Some(800.0), true);
+        assert!(page.starts_with("<!DOCTYPE html>\n"));
+        assert!(page.contains("<title>a &lt;b&gt;</title>"));
+        assert!(page.contains("background-color: #ffffff;"));
+        assert!(page.contains("::selection { background-color: #cde8f0ff; }"));
+        assert!(page.contains("max-width: 800px;"));
+        assert!(page.contains("<em>text</em>"));
+    }
+
+    #[test]
+    fn footnote_anchors() {
+        let theme = Theme::light_default();
+        let md = "A claim[^1]\n\n[^1]: The source\n";
+        let page = html(md, "notes", &theme, None, true);
+        assert!(page.contains(r##"href="#fn-1" id="fnref-1""##));
+        assert!(page.contains(r#"<li id="fn-1">"#));
+        assert!(page.contains(r##"href="#fnref-1""##));
+    }
+}
@@ -0,0 +1,200 @@
+//! Rendering fenced code blocks to images with external programs e.g. `dot = ["dot", "-Tsvg"]`
+//! in the `[external-renderers]` config section
+//!
+//! Matching blocks are swapped out for an image whose source points back at the block. The
+//! program then runs while the image loads, so slow renderers don't hold up the rest of the
+//! document. Output is cached on disk by a hash of the command and the block's contents
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::plugins::replace_fenced_blocks;
+use crate::subprocess;
+
+use anyhow::Context;
+use parking_lot::Mutex;
+
+/// Image sources of rendered blocks start with this followed by their cache key
+const SCHEME: &str = "inlyne-render:";
+/// Renders run in the background, but a stuck one would still pin its image as loading forever
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Rendered images are read into memory whole, so anything past this gets refused
+const MAX_OUTPUT: u64 = 32 * 1024 * 1024;
+
+#[derive(Clone, Debug)]
+struct Job {
+    command: Vec<String>,
+    body: String,
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("inlyne").join("renders"))
+}
+
+/// Keys are the hex hash of a job, so anything else can't have come from a rendered block
+fn is_key(key: &str) -> bool {
+    key.len() == 16
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Fenced block languages mapped to the command that renders them to an SVG or PNG, along with
+/// the blocks of the current document
+///
+/// Clones share their blocks, but each interpreter gets its own set from [`Self::new`]
+#[derive(Clone, Debug, Default)]
+pub struct ExternalRenderers {
+    commands: Arc<HashMap<String, Vec<String>>>,
+    /// Blocks seen while interpreting, keyed by the cache key used in their image source
+    jobs: Arc<Mutex<HashMap<String, Job>>>,
+}
+
+impl ExternalRenderers {
+    pub fn new(commands: HashMap<String, Vec<String>>) -> Self {
+        Self {
+            commands: Arc::new(commands),
+            jobs: Default::default(),
+        }
+    }
+
+    /// Swaps fenced blocks in any of the configured languages for an image of their output
+    pub fn replace_blocks(&self, md: String) -> String {
+        if self.commands.is_empty() {
+            return md;
+        }
+
+        replace_fenced_blocks(&md, |block| {
+            let command = self.commands.get(block.lang)?;
+            let key = format!("{:016x}", fxhash::hash64(&(command, block.body)));
+            let job = Job {
+                command: command.to_owned(),
+                body: block.body.to_owned(),
+            };
+            self.jobs.lock().entry(key.clone()).or_insert(job);
+            Some(format!("![{}]({SCHEME}{key})\n", block.lang))
+        })
+    }
+
+    /// Drops the blocks of the previous document, before a new one gets interpreted
+    pub fn forget_blocks(&self) {
+        self.jobs.lock().clear();
+    }
+
+    /// Renders the block behind an image source, returning `None` for sources that aren't from
+    /// rendered blocks
+    pub fn load(&self, src: &str) -> Option<anyhow::Result<Vec<u8>>> {
+        let key = src.strip_prefix(SCHEME)?;
+        if !is_key(key) {
+            return Some(Err(anyhow::anyhow!("Invalid render key '{key}'")));
+        }
+        Some(self.render(key))
+    }
+
+    fn render(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        let cached = cache_dir().map(|dir| dir.join(key));
+        if let Some(bytes) = cached.as_ref().and_then(|path| fs::read(path).ok()) {
+            return Ok(bytes);
+        }
+
+        let job = self
+            .jobs
+            .lock()
+            .get(key)
+            .cloned()
+            .with_context(|| format!("No block to render for '{key}'"))?;
+        let bytes = run(&job)?;
+        if let Some(path) = cached {
+            let saved = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::write(&path, &bytes));
+            if let Err(err) = saved {
+                tracing::warn!("Failed caching render at '{}': {err}", path.display());
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+fn run(job: &Job) -> anyhow::Result<Vec<u8>> {
+    let (program, args) = job.command.split_first().context("Empty render command")?;
+    let mut command = Command::new(program);
+    command.args(args);
+    // One byte past the cap tells a render that fits apart from one that got cut off
+    let output = subprocess::run(
+        &mut command,
+        job.body.clone().into_bytes(),
+        TIMEOUT,
+        MAX_OUTPUT + 1,
+    )
+    .with_context(|| format!("Failed running '{program}'"))?;
+
+    let status = output.status.with_context(|| {
+        format!(
+            "'{program}' was killed after running for {}s",
+            TIMEOUT.as_secs()
+        )
+    })?;
+    anyhow::ensure!(
+        status.success(),
+        "'{program}' exited with {status}: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    anyhow::ensure!(
+        output.stdout.len() as u64 <= MAX_OUTPUT,
+        "'{program}' output more than {} MiB",
+        MAX_OUTPUT / 1024 / 1024
+    );
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_become_images() {
+        let commands = HashMap::from([("dot".to_owned(), vec!["dot".to_owned(), "-Tsvg".into()])]);
+        let renderers = ExternalRenderers::new(commands.clone());
+        let md = "```dot\ndigraph { a -> b }\n```\n\n```rust\nfn main() {}\n```\n";
+
+        let replaced = renderers.replace_blocks(md.to_owned());
+        let (image, rest) = replaced.split_once('\n').unwrap();
+        let key = image
+            .strip_prefix(&format!("![dot]({SCHEME}"))
+            .and_then(|image| image.strip_suffix(')'))
+            .unwrap();
+        assert_eq!(renderers.jobs.lock()[key].body, "digraph { a -> b }\n");
+        assert_eq!(rest, "\n```rust\nfn main() {}\n```\n");
+        assert!(renderers.load("assets/image.png").is_none());
+
+        // Other interpreters keep their own blocks
+        let other = ExternalRenderers::new(commands);
+        other.replace_blocks("```dot\ndigraph { c }\n```\n".to_owned());
+        other.forget_blocks();
+        assert!(renderers.jobs.lock().contains_key(key));
+        assert!(other.jobs.lock().is_empty());
+    }
+
+    #[test]
+    fn keys_stay_in_the_cache() {
+        let renderers = ExternalRenderers::default();
+        for src in [
+            "../../etc/passwd",
+            "0123456789ABCDEF",
+            "0123456789abcdef/..",
+        ] {
+            let loaded = renderers.load(&format!("{SCHEME}{src}")).unwrap();
+            assert!(loaded
+                .unwrap_err()
+                .to_string()
+                .starts_with("Invalid render key"));
+        }
+        assert!(is_key("0123456789abcdef"));
+    }
+}
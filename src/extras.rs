@@ -0,0 +1,186 @@
+//! Reference-style link definitions, footnote definitions and HTML comments, which tend to pile
+//! up in generated documents
+//!
+//! Link definitions and comments never render on their own, so with `collapse-extras` set it's
+//! the footnotes that get left out along with their references. Revealing the extras brings the
+//! footnotes back and lists the raw source of every extra at the end of the document
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REVEALED: AtomicBool = AtomicBool::new(false);
+
+#[must_use]
+pub fn get_revealed() -> bool {
+    REVEALED.load(Ordering::SeqCst)
+}
+
+pub fn set_revealed(b: bool) {
+    REVEALED.store(b, Ordering::SeqCst);
+}
+
+/// An extra's raw source along with the label for footnote definitions
+struct Extra {
+    source: String,
+    footnote: Option<String>,
+}
+
+/// Collapses or reveals the extras in `md` depending on the settings
+pub fn process(md: String, collapse: bool, revealed: bool) -> String {
+    if !collapse && !revealed {
+        return md;
+    }
+
+    // Chunks of the document along with whether they sit outside of code
+    let mut kept: Vec<(&str, bool)> = Vec::new();
+    let mut extras: Vec<Extra> = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut lines = md.split_inclusive('\n').peekable();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        if let Some(open) = fence {
+            if trimmed.starts_with(open) {
+                fence = None;
+            }
+            kept.push((line, false));
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            kept.push((line, false));
+            continue;
+        }
+
+        if trimmed.starts_with("<!--") {
+            let mut source = line.to_owned();
+            kept.push((line, false));
+            let mut closed = line.contains("-->");
+            while !closed {
+                let Some(next) = lines.next() else {
+                    break;
+                };
+                source.push_str(next);
+                kept.push((next, false));
+                closed = next.contains("-->");
+            }
+            extras.push(Extra {
+                source,
+                footnote: None,
+            });
+        } else if let Some(label) = definition_label(line) {
+            let footnote = label.strip_prefix('^').map(ToOwned::to_owned);
+            let mut definition = vec![line];
+            // Footnotes continue on indented lines
+            while footnote.is_some() {
+                let continues = lines
+                    .peek()
+                    .is_some_and(|next| next.starts_with("    ") || next.starts_with('\t'));
+                if !continues {
+                    break;
+                }
+                definition.extend(lines.next());
+            }
+            if footnote.is_none() || revealed {
+                kept.extend(definition.iter().map(|&line| (line, false)));
+            }
+            extras.push(Extra {
+                source: definition.concat(),
+                footnote,
+            });
+        } else {
+            kept.push((line, true));
+        }
+    }
+
+    if revealed {
+        let mut kept: String = kept.into_iter().map(|(chunk, _)| chunk).collect();
+        if !extras.is_empty() {
+            let sources: String = extras.iter().map(|extra| extra.source.as_str()).collect();
+            let fence = "`".repeat(longest_backtick_run(&sources).max(2) + 1);
+            let newline = if kept.ends_with('\n') { "" } else { "\n" };
+            kept.push_str(&format!(
+                "{newline}\n---\n\n**Hidden extras**\n\n{fence}text\n{sources}{fence}\n"
+            ));
+        }
+        kept
+    } else {
+        // Dangling references would show up as literal `[^label]` text
+        let references: Vec<_> = extras
+            .iter()
+            .filter_map(|extra| Some(format!("[^{}]", extra.footnote.as_ref()?)))
+            .collect();
+        kept.into_iter()
+            .map(|(chunk, prose)| {
+                let mut chunk = chunk.to_owned();
+                if prose {
+                    for reference in &references {
+                        chunk = chunk.replace(reference, "");
+                    }
+                }
+                chunk
+            })
+            .collect()
+    }
+}
+
+/// The label of a link or footnote definition e.g. `[label]: https://example.com`
+fn definition_label(line: &str) -> Option<&str> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let (label, rest) = line[indent..].strip_prefix('[')?.split_once("]:")?;
+    let valid = !label.trim().is_empty() && !label.contains(['[', ']']);
+    (valid && !rest.trim().is_empty()).then_some(label)
+}
+
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MD: &str = "\
+<!-- generated by a tool -->
+Some text[^1] with [a link][docs]
+
+[docs]: https://example.com
+[^1]: The footnote
+    continues here
+
+```md
+[^1]: Not a definition
+```
+";
+
+    #[test]
+    fn collapses_footnotes() {
+        assert_eq!(process(MD.to_owned(), false, false), MD);
+        assert_eq!(
+            process(MD.to_owned(), true, false),
+            "\
+<!-- generated by a tool -->
+Some text with [a link][docs]
+
+[docs]: https://example.com
+
+```md
+[^1]: Not a definition
+```
+"
+        );
+    }
+
+    #[test]
+    fn reveals_raw_sources() {
+        let revealed = process(MD.to_owned(), true, true);
+        let (document, extras) = revealed.split_once("\n---\n").unwrap();
+        assert_eq!(document, MD);
+        assert_eq!(
+            extras,
+            "\n**Hidden extras**\n\n```text\n<!-- generated by a tool -->\n\
+            [docs]: https://example.com\n[^1]: The footnote\n    continues here\n```\n"
+        );
+    }
+}
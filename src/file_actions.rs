@@ -0,0 +1,57 @@
+//! Acting on the file being viewed itself, rather than on its contents
+//!
+//! - Copying its full path
+//! - Revealing it in the system's file manager, which selects it on macOS (Finder) and Windows
+//!   (Explorer). Elsewhere its folder gets opened with `xdg-open` or the like
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::links;
+
+/// The file's full path with any symlinks resolved, or the path as it is if that fails
+pub fn full_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+/// Shows `path` in the system's file manager. `report` gets a message to show if that fails
+pub fn reveal(path: &Path, report: impl FnOnce(String) + Send + 'static) {
+    let path = full_path(path);
+    let Some(mut command) = reveal_command(&path) else {
+        let dir = path.parent().unwrap_or(&path).display().to_string();
+        links::open_detached(dir, HashMap::new(), report);
+        return;
+    };
+    match command.spawn() {
+        // Reaped off of the event loop since file managers can take their time exiting
+        Ok(mut child) => drop(std::thread::spawn(move || child.wait())),
+        Err(err) => {
+            let message = format!("Could not reveal {}: {err}", path.display());
+            tracing::warn!("{message}");
+            report(message);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn reveal_command(path: &Path) -> Option<Command> {
+    let mut command = Command::new("open");
+    command.arg("-R").arg(path);
+    Some(command)
+}
+
+#[cfg(windows)]
+fn reveal_command(path: &Path) -> Option<Command> {
+    let mut select = std::ffi::OsString::from("/select,");
+    select.push(path);
+    let mut command = Command::new("explorer");
+    command.arg(select);
+    Some(command)
+}
+
+/// There's no common way to select a file, so only its folder gets opened
+#[cfg(not(any(target_os = "macos", windows)))]
+fn reveal_command(_: &Path) -> Option<Command> {
+    None
+}
@@ -1,22 +1,29 @@
 #[cfg(test)]
 mod tests;
 
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::InlyneEvent;
 
 use notify::event::{EventKind, ModifyKind};
 use notify::{RecommendedWatcher, RecursiveMode, Watcher as _};
+use notify_debouncer_full::file_id::{get_file_id, FileId};
 use notify_debouncer_full::{
     new_debouncer, DebounceEventHandler, DebounceEventResult, Debouncer, FileIdMap,
 };
 use winit::event_loop::EventLoopProxy;
 
+// How long a file can be missing before we consider it lost instead of mid atomic-save
+const LOST_FILE_GRACE: Duration = Duration::from_secs(1);
+
 trait Callback: Send + 'static {
     fn file_reload(&self);
     fn file_change(&self, contents: String);
+    fn file_lost(&self);
+    fn file_renamed(&self, new_path: PathBuf);
 }
 
 impl Callback for EventLoopProxy<InlyneEvent> {
@@ -27,6 +34,14 @@ impl Callback for EventLoopProxy<InlyneEvent> {
     fn file_change(&self, contents: String) {
         let _ = self.send_event(InlyneEvent::FileChange { contents });
     }
+
+    fn file_lost(&self) {
+        let _ = self.send_event(InlyneEvent::FileLost);
+    }
+
+    fn file_renamed(&self, new_path: PathBuf) {
+        let _ = self.send_event(InlyneEvent::FileRenamed(new_path));
+    }
 }
 
 struct FileChange {
@@ -119,6 +134,58 @@ impl Watcher {
     }
 }
 
+// Looks for a file in the same directory that's the same file on disk. This is how we follow a
+// plain rename since watching the file itself doesn't tell us where it went
+fn find_renamed(file_path: &Path, file_id: &FileId) -> Option<PathBuf> {
+    let dir = file_path.parent()?;
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| get_file_id(path).is_ok_and(|id| &id == file_id))
+}
+
+/// Keeps trying to watch `file_path` again after it was renamed or removed
+///
+/// Most editors save through an atomic rename, so the file normally shows back up quickly. If it
+/// stays missing then we try to follow a rename and otherwise report the file as lost. Returns the
+/// new path if the file was renamed
+fn poll_registering_watcher<C: Callback>(
+    watcher: &mut RecommendedWatcher,
+    file_path: &Path,
+    file_id: Option<&FileId>,
+    callback: &C,
+) -> Option<PathBuf> {
+    let start = Instant::now();
+    let mut checked_missing = false;
+    loop {
+        std::thread::sleep(Duration::from_millis(15));
+
+        let _ = watcher.unwatch(file_path);
+        if watcher
+            .watch(file_path, RecursiveMode::NonRecursive)
+            .is_ok()
+        {
+            return None;
+        }
+
+        if !checked_missing && start.elapsed() > LOST_FILE_GRACE {
+            checked_missing = true;
+            if let Some(new_path) = file_id.and_then(|id| find_renamed(file_path, id)) {
+                if watcher
+                    .watch(&new_path, RecursiveMode::NonRecursive)
+                    .is_ok()
+                {
+                    return Some(new_path);
+                }
+            }
+
+            tracing::warn!("Lost track of file: {}", file_path.display());
+            callback.file_lost();
+        }
+    }
+}
+
 fn endlessly_handle_messages<C: Callback>(
     mut watcher: Debouncer<RecommendedWatcher, FileIdMap>,
     msg_rx: mpsc::Receiver<WatcherMsg>,
@@ -129,26 +196,30 @@ fn endlessly_handle_messages<C: Callback>(
     watcher
         .watch(&file_path, RecursiveMode::NonRecursive)
         .unwrap();
-
-    let poll_registering_watcher = |watcher: &mut RecommendedWatcher, file_path: &Path| loop {
-        std::thread::sleep(Duration::from_millis(15));
-
-        let _ = watcher.unwatch(file_path);
-        if watcher
-            .watch(file_path, RecursiveMode::NonRecursive)
-            .is_ok()
-        {
-            break;
-        }
-    };
+    let mut file_id = get_file_id(&file_path).ok();
 
     while let Ok(msg) = msg_rx.recv() {
         match msg {
             WatcherMsg::Action(DebouncerAction::ReregisterWatcher) => {
                 tracing::debug!("File may have been renamed/removed. Falling back to polling");
-                poll_registering_watcher(watcher, &file_path);
-                tracing::debug!("Successfully re-registered file watcher");
-                reload_callback.file_reload();
+                let renamed = poll_registering_watcher(
+                    watcher,
+                    &file_path,
+                    file_id.as_ref(),
+                    &reload_callback,
+                );
+                match renamed {
+                    Some(new_path) => {
+                        tracing::info!("Following renamed file: {}", new_path.display());
+                        file_path = new_path;
+                        reload_callback.file_renamed(file_path.clone());
+                    }
+                    None => {
+                        tracing::debug!("Successfully re-registered file watcher");
+                        file_id = get_file_id(&file_path).ok();
+                        reload_callback.file_reload();
+                    }
+                }
             }
             WatcherMsg::Action(DebouncerAction::FileReload) => {
                 tracing::debug!("Reloading file");
@@ -157,8 +228,9 @@ fn endlessly_handle_messages<C: Callback>(
             WatcherMsg::FileChange(FileChange { new_path, contents }) => {
                 tracing::info!("Updating file watcher path: {}", new_path.display());
                 let _ = watcher.unwatch(&file_path);
-                poll_registering_watcher(watcher, &new_path);
+                let _ = poll_registering_watcher(watcher, &new_path, None, &reload_callback);
                 file_path = new_path;
+                file_id = get_file_id(&file_path).ok();
                 reload_callback.file_change(contents);
             }
         }
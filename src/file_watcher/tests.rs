@@ -15,6 +15,12 @@ impl Callback for mpsc::Sender<()> {
     fn file_change(&self, _: String) {
         self.send(()).unwrap();
     }
+
+    fn file_lost(&self) {}
+
+    fn file_renamed(&self, _: PathBuf) {
+        self.send(()).unwrap();
+    }
 }
 
 fn touch(file: &Path) {
@@ -135,6 +141,7 @@ gen_watcher_test!(
     (sanity, sanity_fn),
     (update_moves_watcher, update_moves_watcher_fn),
     (slowly_swap_file, slowly_swap_file_fn),
+    (follows_rename, follows_rename_fn),
 );
 
 fn sanity_fn(
@@ -195,3 +202,21 @@ fn slowly_swap_file_fn(
     touch(&main_file);
     delays.assert_at_least_one_message(&callback_rx);
 }
+
+fn follows_rename_fn(
+    TestEnv {
+        base_dir,
+        callback_rx,
+        main_file,
+        ..
+    }: TestEnv,
+    delays: Delays,
+) {
+    let renamed_file = base_dir.join("renamed.md");
+
+    // A file that stays renamed gets followed to its new path
+    fs::rename(&main_file, &renamed_file).unwrap();
+    delays.assert_at_least_one_message(&callback_rx);
+    touch(&renamed_file);
+    delays.assert_at_least_one_message(&callback_rx);
+}
@@ -0,0 +1,239 @@
+//! Folding away the content under headings
+//!
+//! A folded heading becomes the summary of a hidden [`Section`], the same as a `<details>`
+//! element's, so from then on it opens and closes like one
+
+use std::cell::RefCell;
+
+use crate::positioner::{Positioned, Section};
+use crate::utils::Point;
+use crate::Element;
+
+/// The level of a heading, or of the heading that a fold is made from
+fn heading_level(element: &Positioned<Element>) -> Option<u8> {
+    match &element.inner {
+        Element::TextBox(text_box) => text_box.header_level,
+        Element::Section(section) => match &*section.summary {
+            Some(Positioned {
+                inner: Element::TextBox(text_box),
+                ..
+            }) => text_box.header_level,
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `element` is a fold made from a heading rather than e.g. a `<details>` element
+fn is_fold(element: &Positioned<Element>) -> bool {
+    matches!(element.inner, Element::Section(_)) && heading_level(element).is_some()
+}
+
+/// Wraps the heading at `index` and everything up to the next heading of the same or a higher
+/// level into a hidden section
+fn fold(elements: &mut Vec<Positioned<Element>>, index: usize) {
+    let Element::TextBox(heading) = &elements[index].inner else {
+        return;
+    };
+    let Some(level) = heading.header_level else {
+        return;
+    };
+    let hidpi_scale = heading.hidpi_scale;
+    let end = elements[index + 1..]
+        .iter()
+        .position(|element| heading_level(element).is_some_and(|other| other <= level))
+        .map_or(elements.len(), |offset| index + 1 + offset);
+
+    let mut contents: Vec<_> = elements.drain(index..end).collect();
+    let heading = contents.remove(0);
+    let section = Section {
+        elements: contents,
+        hidpi_scale,
+        hidden: RefCell::new(true),
+        summary: Box::new(Some(heading)),
+    };
+    elements.insert(index, Positioned::new(section));
+}
+
+/// The headings that aren't folded away, including the ones that folds are made from
+pub fn visible_headings(elements: &[Positioned<Element>]) -> Vec<&Positioned<Element>> {
+    let mut headings = Vec::new();
+    for element in elements {
+        match &element.inner {
+            Element::TextBox(text_box) if text_box.is_header => headings.push(element),
+            Element::Section(section) => {
+                if let Some(summary) = &*section.summary {
+                    if heading_level(summary).is_some() {
+                        headings.push(summary);
+                    }
+                }
+                if !*section.hidden.borrow() {
+                    headings.append(&mut visible_headings(&section.elements));
+                }
+            }
+            _ => {}
+        }
+    }
+    headings
+}
+
+/// The H1 or H2 whose section is showing at `y` in document coordinates, along with where the
+/// next one starts, if there is one
+pub fn sticky_heading(
+    elements: &[Positioned<Element>],
+    y: f32,
+) -> Option<(&Positioned<Element>, Option<f32>)> {
+    let mut current = None;
+    for heading in visible_headings(elements) {
+        if !heading_level(heading).is_some_and(|level| level <= 2) {
+            continue;
+        }
+        let Some(bounds) = &heading.bounds else {
+            continue;
+        };
+        if bounds.pos.1 >= y {
+            return current.map(|current| (current, Some(bounds.pos.1)));
+        }
+        current = Some(heading);
+    }
+    current.map(|current| (current, None))
+}
+
+/// Folds or unfolds the heading at `loc`, looking through open sections. Returns whether there
+/// was one
+pub fn toggle_at(elements: &mut Vec<Positioned<Element>>, loc: Point) -> bool {
+    let contains = |element: &Positioned<Element>| {
+        element
+            .bounds
+            .as_ref()
+            .is_some_and(|bounds| bounds.contains(loc))
+    };
+    for index in 0..elements.len() {
+        let element = &mut elements[index];
+        if !contains(element) {
+            continue;
+        }
+        if heading_level(element).is_some() && matches!(element.inner, Element::TextBox(_)) {
+            fold(elements, index);
+            return true;
+        }
+        let is_fold = is_fold(element);
+        if let Element::Section(section) = &mut element.inner {
+            if (*section.summary).as_ref().is_some_and(contains) {
+                if is_fold {
+                    let mut hidden = section.hidden.borrow_mut();
+                    *hidden = !*hidden;
+                    return true;
+                }
+            } else if !*section.hidden.borrow() && toggle_at(&mut section.elements, loc) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Folds every heading, including the ones nested under other headings
+pub fn fold_all(elements: &mut Vec<Positioned<Element>>) {
+    // Folding shortens `elements`, so this can't iterate over a fixed range
+    let mut index = 0;
+    while index < elements.len() {
+        if matches!(&elements[index].inner, Element::TextBox(tb) if tb.header_level.is_some()) {
+            fold(elements, index);
+        }
+        let is_fold = is_fold(&elements[index]);
+        if let Element::Section(section) = &mut elements[index].inner {
+            if is_fold {
+                *section.hidden.borrow_mut() = true;
+            }
+            fold_all(&mut section.elements);
+        }
+        index += 1;
+    }
+}
+
+/// Opens every fold that [`fold_all`] or clicking a heading made, leaving `<details>` as they are
+pub fn unfold_all(elements: &mut [Positioned<Element>]) {
+    for element in elements {
+        let is_fold = is_fold(element);
+        if let Element::Section(section) = &mut element.inner {
+            if is_fold {
+                *section.hidden.borrow_mut() = false;
+            }
+            unfold_all(&mut section.elements);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::positioner::Spacer;
+    use crate::text::{Text, TextBox};
+    use crate::utils::Rect;
+
+    fn heading(level: u8) -> Positioned<Element> {
+        let mut text_box = TextBox::new(vec![Text::new(format!("h{level}"), 1., [0.; 4])], 1.);
+        text_box.is_header = true;
+        text_box.header_level = Some(level);
+        Positioned::new(text_box)
+    }
+
+    fn folded_lengths(elements: &[Positioned<Element>]) -> Vec<Option<usize>> {
+        elements
+            .iter()
+            .map(|element| match &element.inner {
+                Element::Section(section) => Some(section.elements.len()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sticks_the_last_heading_scrolled_past() {
+        let elements: Vec<_> = [heading(1), heading(3), heading(2)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut heading)| {
+                heading.bounds = Some(Rect::new((0., i as f32 * 100.), (50., 20.)));
+                heading
+            })
+            .collect();
+        let sticky = |y| {
+            sticky_heading(&elements, y)
+                .map(|(heading, next)| (heading.bounds.as_ref().unwrap().pos.1, next))
+        };
+
+        assert_eq!(sticky(0.), None);
+        // The H3 doesn't take over from the H1 above it
+        assert_eq!(sticky(150.), Some((0., Some(200.))));
+        assert_eq!(sticky(250.), Some((200., None)));
+    }
+
+    #[test]
+    fn folds_until_same_or_higher_level() {
+        let mut elements = vec![
+            heading(2),
+            Positioned::new(Spacer::invisible()),
+            heading(3),
+            Positioned::new(Spacer::invisible()),
+            heading(2),
+            heading(1),
+        ];
+        fold(&mut elements, 0);
+        assert_eq!(folded_lengths(&elements), [Some(3), None, None]);
+
+        fold_all(&mut elements);
+        assert_eq!(folded_lengths(&elements), [Some(3), Some(0), Some(0)]);
+        let Element::Section(section) = &elements[0].inner else {
+            unreachable!();
+        };
+        assert_eq!(folded_lengths(&section.elements), [None, Some(1)]);
+
+        unfold_all(&mut elements);
+        assert!(elements.iter().all(|element| match &element.inner {
+            Element::Section(section) => !*section.hidden.borrow(),
+            _ => false,
+        }));
+    }
+}
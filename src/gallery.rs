@@ -0,0 +1,149 @@
+//! `inlyne theme preview` shows a sample document that can be cycled through the built-in themes
+//! along with any set in the user's config
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use crate::color::Theme;
+use crate::opts::{OptionalLayout, OptionalTheme, ResolvedTheme};
+
+const SAMPLE: &str = include_str!("../assets/theme-preview.md");
+
+#[derive(Debug)]
+pub struct ThemeEntry {
+    pub name: String,
+    pub color_scheme: ResolvedTheme,
+    pub theme: Theme,
+    /// Whether the theme comes from the user's config instead of being built-in
+    pub from_config: bool,
+}
+
+#[derive(Debug)]
+pub struct ThemeGallery {
+    entries: Vec<ThemeEntry>,
+    index: usize,
+}
+
+impl ThemeGallery {
+    pub fn new(
+        layout: &OptionalLayout,
+        light_theme: Option<OptionalTheme>,
+        dark_theme: Option<OptionalTheme>,
+    ) -> anyhow::Result<Self> {
+        let mut entries = Vec::new();
+        for (color_scheme, custom) in [
+            (ResolvedTheme::Light, light_theme),
+            (ResolvedTheme::Dark, dark_theme),
+        ] {
+            let (name, default) = match color_scheme {
+                ResolvedTheme::Light => ("Light", Theme::light_default()),
+                ResolvedTheme::Dark => ("Dark", Theme::dark_default()),
+            };
+            let default = layout.merge(default);
+            entries.push(ThemeEntry {
+                name: name.to_owned(),
+                color_scheme,
+                theme: default.clone(),
+                from_config: false,
+            });
+            if let Some(custom) = custom {
+                entries.push(ThemeEntry {
+                    name: format!("{name} (from config)"),
+                    color_scheme,
+                    theme: custom.merge(default)?,
+                    from_config: true,
+                });
+            }
+        }
+
+        Ok(Self { entries, index: 0 })
+    }
+
+    /// The file that the preview window gets opened on
+    pub fn placeholder_file() -> PathBuf {
+        std::env::temp_dir().join("inlyne-theme-preview.md")
+    }
+
+    pub fn current(&self) -> &ThemeEntry {
+        &self.entries[self.index]
+    }
+
+    pub fn select_next(&mut self) {
+        self.index = (self.index + 1) % self.entries.len();
+    }
+
+    pub fn select_prev(&mut self) {
+        self.index = (self.index + self.entries.len() - 1) % self.entries.len();
+    }
+
+    /// The sample document headed by the current theme's name
+    pub fn document(&self) -> String {
+        let mut doc = format!(
+            "# {}\n\n*Theme {} of {}. Press `←`/`→` to switch themes, `Enter` to pick one, or \
+            `Esc` to leave*\n\n",
+            self.current().name,
+            self.index + 1,
+            self.entries.len(),
+        );
+        doc.push_str(SAMPLE);
+        doc
+    }
+
+    /// The config needed to use the current theme
+    pub fn config_snippet(&self) -> String {
+        let ThemeEntry {
+            color_scheme,
+            from_config,
+            ..
+        } = self.current();
+        let (value, section) = match color_scheme {
+            ResolvedTheme::Light => ("Light", "light-theme"),
+            ResolvedTheme::Dark => ("Dark", "dark-theme"),
+        };
+
+        let mut snippet = format!("theme = \"{value}\"\n");
+        let overridden = self
+            .entries
+            .iter()
+            .any(|entry| entry.from_config && entry.color_scheme == *color_scheme);
+        if overridden && !from_config {
+            let _ = writeln!(
+                snippet,
+                "# Remove the `[{section}]` section to use the built-in colors"
+            );
+        }
+        snippet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycles_through_custom_themes() {
+        let custom_dark = OptionalTheme {
+            text_color: Some(0x123456),
+            ..Default::default()
+        };
+        let mut gallery =
+            ThemeGallery::new(&OptionalLayout::default(), None, Some(custom_dark)).unwrap();
+
+        let mut names = Vec::new();
+        for _ in 0..3 {
+            names.push(gallery.current().name.clone());
+            gallery.select_next();
+        }
+        assert_eq!(names, ["Light", "Dark", "Dark (from config)"]);
+        assert_eq!(gallery.current().name, "Light");
+        gallery.select_prev();
+        assert_eq!(gallery.current().theme.palette.text_color, 0x123456);
+        assert_eq!(gallery.config_snippet(), "theme = \"Dark\"\n");
+
+        gallery.select_prev();
+        assert_eq!(
+            gallery.config_snippet(),
+            "theme = \"Dark\"\n# Remove the `[dark-theme]` section to use the built-in colors\n"
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! A palette listing the current document's headings, narrowed down with a fuzzy query
+
+/// How many matches get listed at once
+const MAX_LISTED: usize = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heading {
+    pub text: String,
+    pub anchor: String,
+}
+
+#[derive(Debug)]
+pub struct HeadingPalette {
+    headings: Vec<Heading>,
+    pub query: String,
+    /// Index into the current matches
+    selected: usize,
+}
+
+impl HeadingPalette {
+    pub fn new(headings: Vec<Heading>) -> Self {
+        Self {
+            headings,
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    /// Headings matching the query, best match first
+    ///
+    /// Every character of the query has to show up in order, ignoring case. Matches that start
+    /// earlier and skip fewer characters rank higher, and ties stay in document order
+    pub fn matches(&self) -> Vec<&Heading> {
+        let mut scored: Vec<_> = self
+            .headings
+            .iter()
+            .filter_map(|heading| Some((fuzzy_score(&self.query, &heading.text)?, heading)))
+            .collect();
+        scored.sort_by_key(|(score, _)| *score);
+        scored.into_iter().map(|(_, heading)| heading).collect()
+    }
+
+    pub fn selected(&self) -> Option<&Heading> {
+        self.matches().get(self.selected).copied()
+    }
+
+    pub fn select_next(&mut self) {
+        let last = self.matches().len().min(MAX_LISTED).saturating_sub(1);
+        self.selected = (self.selected + 1).min(last);
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    /// Text for the banner with the query followed by the best matches
+    pub fn overlay(&self) -> String {
+        let matches = self.matches();
+        let mut overlay = format!(
+            "Go to heading: {}_ (Enter to jump, Esc to close)",
+            self.query
+        );
+        if matches.is_empty() {
+            overlay.push_str("\n  No matching headings");
+        }
+        for (i, heading) in matches.iter().take(MAX_LISTED).enumerate() {
+            let marker = if i == self.selected { '>' } else { ' ' };
+            overlay.push_str(&format!("\n{marker} {}", heading.text));
+        }
+        overlay
+    }
+}
+
+/// Lower is better, or `None` if `query` isn't a subsequence of `text`
+fn fuzzy_score(query: &str, text: &str) -> Option<usize> {
+    let mut chars = text.chars().flat_map(char::to_lowercase).enumerate();
+    let mut first = None;
+    let mut last = 0;
+    for q in query.chars().flat_map(char::to_lowercase) {
+        let (i, _) = chars.find(|&(_, c)| c == q)?;
+        first.get_or_insert(i);
+        last = i;
+    }
+    // The span covered by the match, weighted towards starting early
+    let first = first.unwrap_or(0);
+    Some(first * 2 + (last - first))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette(texts: &[&str]) -> HeadingPalette {
+        let headings = texts
+            .iter()
+            .map(|text| Heading {
+                text: text.to_string(),
+                anchor: format!("#{}", text.to_lowercase().replace(' ', "-")),
+            })
+            .collect();
+        HeadingPalette::new(headings)
+    }
+
+    #[test]
+    fn fuzzy_matching() {
+        let mut palette = palette(&["Installation", "Usage", "Config Files", "Contributing"]);
+        assert_eq!(palette.matches().len(), 4);
+
+        "conf".chars().for_each(|c| palette.push(c));
+        let texts: Vec<_> = palette.matches().iter().map(|h| h.text.as_str()).collect();
+        assert_eq!(texts, ["Config Files"]);
+
+        palette.pop();
+        palette.pop();
+        palette.select_next();
+        assert_eq!(palette.selected().unwrap().anchor, "#contributing");
+        palette.select_next();
+        assert_eq!(palette.selected().unwrap().anchor, "#contributing");
+
+        palette.push('z');
+        assert_eq!(palette.selected(), None);
+        assert!(palette.overlay().ends_with("No matching headings"));
+    }
+}
@@ -1,11 +1,18 @@
+use std::fs;
 use std::path::{Path, PathBuf};
 
 use anyhow::Context;
 
+pub const DEFAULT_LIMIT: usize = 100;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct History {
     history: Vec<PathBuf>,
     index: usize,
+    /// The most entries to keep before dropping the oldest ones
+    limit: usize,
+    /// Where this window's history gets saved to once restored from a previous session
+    session: Option<PathBuf>,
 }
 
 impl History {
@@ -16,9 +23,71 @@ impl History {
         Ok(Self {
             history: vec![canonicalized],
             index: 0,
+            limit: DEFAULT_LIMIT,
+            session: None,
         })
     }
 
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(1);
+        self.prune();
+        self
+    }
+
+    /// The directory that sessions are saved in
+    pub fn sessions_dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("inlyne").join("history"))
+    }
+
+    /// Restores the stack saved by the last window opened on the same file, keeping that file as
+    /// the current entry. Saved entries that no longer exist get dropped
+    pub fn restore_session(&mut self, sessions_dir: &Path) {
+        let opened = self.get_path().to_owned();
+        let session = sessions_dir.join(format!("{:016x}", fxhash::hash64(&opened)));
+        if let Ok(saved) = fs::read_to_string(&session) {
+            let saved: Vec<_> = saved
+                .lines()
+                .map(PathBuf::from)
+                .filter(|path| path.is_file())
+                .collect();
+            if let Some(index) = saved.iter().position(|path| *path == opened) {
+                self.history = saved;
+                self.index = index;
+                self.prune();
+            }
+        }
+        self.session = Some(session);
+    }
+
+    /// Saves the stack for the next window opened on the same file
+    pub fn save_session(&self) -> anyhow::Result<()> {
+        let Some(session) = &self.session else {
+            return Ok(());
+        };
+        if let Some(dir) = session.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut contents = String::new();
+        for path in &self.history {
+            let Some(path) = path.to_str() else {
+                continue;
+            };
+            contents.push_str(path);
+            contents.push('\n');
+        }
+        fs::write(session, contents)
+            .with_context(|| format!("Failed saving history to '{}'", session.display()))
+    }
+
+    /// Drops the oldest entries past the limit
+    fn prune(&mut self) {
+        let excess = self.history.len().saturating_sub(self.limit);
+        // Never drop the current entry
+        let excess = excess.min(self.index);
+        self.history.drain(..excess);
+        self.index -= excess;
+    }
+
     pub fn get_path(&self) -> &Path {
         self.history
             .get(self.index)
@@ -26,12 +95,58 @@ impl History {
             .as_path()
     }
 
+    /// Pushes a new current entry like when following a link. Any forward entries get pruned,
+    /// same as a browser
     pub fn make_next(&mut self, file_path: PathBuf) {
         let file_path = file_path.canonicalize().unwrap();
+        // Following a link to the current file shouldn't take a step
+        if file_path == self.get_path() {
+            return;
+        }
 
         self.history.truncate(self.index + 1);
         self.history.push(file_path);
         self.index += 1;
+        self.prune();
+    }
+
+    /// Points the current entry at a new path, like when the file gets renamed
+    pub fn replace_current(&mut self, file_path: PathBuf) {
+        self.history[self.index] = file_path;
+    }
+
+    /// Every entry from oldest to newest
+    pub fn entries(&self) -> &[PathBuf] {
+        &self.history
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Jumps straight to the entry at `index` while keeping the rest of the stack intact
+    pub fn jump(&mut self, index: usize) -> Option<&Path> {
+        if index >= self.history.len() || index == self.index {
+            None
+        } else {
+            self.index = index;
+            Some(self.get_path())
+        }
+    }
+
+    /// Text for the history list overlay with both the `selected` and current entries marked
+    pub fn overlay(&self, selected: usize) -> String {
+        let mut overlay = String::from("History (Enter or 1-9 to open, Esc to close)");
+        for (i, path) in self.history.iter().enumerate() {
+            let marker = if i == selected { '>' } else { ' ' };
+            let current = if i == self.index { " (current)" } else { "" };
+            overlay.push_str(&format!(
+                "\n{marker} {}. {}{current}",
+                i + 1,
+                path.display()
+            ));
+        }
+        overlay
     }
 
     #[allow(clippy::should_implement_trait)]
@@ -91,4 +206,72 @@ mod tests {
         assert_eq!(hist.next().unwrap(), fork2);
         assert_eq!(hist.next(), None);
     }
+
+    fn temp_files(names: &[&str]) -> (tempfile::TempDir, Vec<PathBuf>) {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("inlyne-tests-")
+            .tempdir()
+            .unwrap();
+        let temp_path = temp_dir.path().canonicalize().unwrap();
+        let files: Vec<_> = names.iter().map(|name| temp_path.join(name)).collect();
+        for file in &files {
+            fs::write(file, "").unwrap();
+        }
+        (temp_dir, files)
+    }
+
+    #[test]
+    fn limit_drops_oldest() {
+        let (_dir, files) = temp_files(&["a", "b", "c", "d"]);
+        let mut hist = History::new(&files[0]).unwrap().with_limit(2);
+        hist.make_next(files[1].clone());
+        hist.make_next(files[2].clone());
+        hist.make_next(files[2].clone());
+        assert_eq!(hist.entries(), &files[1..3]);
+
+        // Going back and following a new link prunes the forward entries
+        hist.previous();
+        hist.make_next(files[3].clone());
+        assert_eq!(hist.entries(), [files[1].clone(), files[3].clone()]);
+        assert_eq!(hist.get_path(), files[3]);
+    }
+
+    #[test]
+    fn sessions() {
+        let (dir, files) = temp_files(&["a", "b", "c"]);
+        let sessions_dir = dir.path().join("sessions");
+
+        let mut hist = History::new(&files[0]).unwrap();
+        hist.restore_session(&sessions_dir);
+        hist.make_next(files[1].clone());
+        hist.make_next(files[2].clone());
+        hist.save_session().unwrap();
+
+        // Reopening the first file brings back its forward history
+        let mut hist = History::new(&files[0]).unwrap();
+        hist.restore_session(&sessions_dir);
+        assert_eq!(hist.entries(), files);
+        assert_eq!(hist.index(), 0);
+
+        // While other files start fresh
+        let mut hist = History::new(&files[1]).unwrap();
+        hist.restore_session(&sessions_dir);
+        assert_eq!(hist.entries(), &files[1..2]);
+    }
+
+    #[test]
+    fn jump() {
+        let (_dir, files) = temp_files(&["a", "b", "c"]);
+        let mut hist = History::new(&files[0]).unwrap();
+        hist.make_next(files[1].clone());
+        hist.make_next(files[2].clone());
+
+        assert_eq!(hist.jump(0).unwrap(), files[0]);
+        assert_eq!(hist.jump(0), None);
+        assert_eq!(hist.jump(3), None);
+        // Jumping back doesn't prune the forward entries
+        assert_eq!(hist.entries(), files);
+        assert_eq!(hist.next().unwrap(), files[1]);
+        assert_eq!(hist.index(), 1);
+    }
 }
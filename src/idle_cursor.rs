@@ -0,0 +1,42 @@
+//! Hides the mouse cursor once it's been still for a while, so that it doesn't sit over the text
+//! during long reads
+
+use std::time::{Duration, Instant};
+
+use winit::window::Window;
+
+pub struct IdleCursor {
+    delay: Duration,
+    last_moved: Instant,
+    hidden: bool,
+}
+
+impl IdleCursor {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            last_moved: Instant::now(),
+            hidden: false,
+        }
+    }
+
+    /// When the event loop should wake up to hide the cursor, if it's still showing
+    pub fn next_hide(&self) -> Option<Instant> {
+        (!self.hidden).then_some(self.last_moved + self.delay)
+    }
+
+    /// Brings the cursor back and starts waiting all over again
+    pub fn moved(&mut self, window: &Window) {
+        self.last_moved = Instant::now();
+        if std::mem::take(&mut self.hidden) {
+            window.set_cursor_visible(true);
+        }
+    }
+
+    pub fn tick(&mut self, window: &Window) {
+        if !self.hidden && self.last_moved + self.delay <= Instant::now() {
+            self.hidden = true;
+            window.set_cursor_visible(false);
+        }
+    }
+}
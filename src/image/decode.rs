@@ -4,7 +4,7 @@ use std::time::Instant;
 use crate::metrics::{histogram, HistTag};
 use crate::utils::usize_in_mib;
 
-use image::GenericImageView;
+use image::{GenericImageView, ImageFormat};
 use lz4_flex::frame::{BlockSize, FrameDecoder, FrameEncoder, FrameInfo};
 
 pub fn lz4_compress<R: io::Read>(reader: &mut R) -> anyhow::Result<Vec<u8>> {
@@ -41,3 +41,27 @@ pub fn decode_and_compress(contents: &[u8]) -> anyhow::Result<ImageParts> {
     );
     lz4_compress(&mut io::Cursor::new(image_data)).map(|lz4_blob| (lz4_blob, dimensions))
 }
+
+/// Whether the image is laid out so that a lower quality version of the whole thing can be shown
+/// before all of it arrives i.e. a progressive JPEG or an interlaced PNG
+pub fn is_progressive(contents: &[u8]) -> bool {
+    match image::guess_format(contents) {
+        Ok(ImageFormat::Jpeg) => is_progressive_jpeg(contents),
+        // The interlace method is the last byte of the `IHDR` chunk that always comes first
+        Ok(ImageFormat::Png) => contents.get(28) == Some(&1),
+        _ => false,
+    }
+}
+
+fn is_progressive_jpeg(contents: &[u8]) -> bool {
+    // Walks the segments after the start of image marker until reaching the start of frame
+    let mut pos = 2;
+    while let Some(&[0xFF, marker, len_hi, len_lo]) = contents.get(pos..pos + 4) {
+        match marker {
+            0xC2 | 0xC6 | 0xCA | 0xCE => return true,
+            0xC0 | 0xC1 | 0xC3 | 0xC5 | 0xC7 | 0xC9 | 0xCB | 0xCD | 0xCF => return false,
+            _ => pos += 2 + usize::from(u16::from_be_bytes([len_hi, len_lo])),
+        }
+    }
+    false
+}
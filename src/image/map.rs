@@ -0,0 +1,124 @@
+//! Client-side image maps i.e. `<img usemap="#name">` along with a `<map name="name">` full of
+//! `<area>`s that each link somewhere different
+
+use crate::utils::{Point, Rect};
+
+/// A clickable region of an image in the image's own pixels
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Rect(Rect),
+    Circle {
+        center: Point,
+        radius: f32,
+    },
+    Polygon(Vec<Point>),
+    /// Everything that isn't covered by an earlier area
+    Default,
+}
+
+impl Shape {
+    /// Parses an `<area>`'s `shape` and `coords` attributes. Shapes without enough coordinates
+    /// don't cover anything, so they're dropped
+    pub fn new(shape: Option<&str>, coords: &str) -> Option<Self> {
+        let coords: Vec<f32> = coords
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|coord| !coord.is_empty())
+            .map(|coord| coord.parse().ok())
+            .collect::<Option<_>>()?;
+
+        let shape = match shape.map(str::to_ascii_lowercase).as_deref() {
+            None | Some("rect" | "rectangle") => match coords[..] {
+                [x1, y1, x2, y2, ..] => Self::Rect(Rect::from_min_max(
+                    (x1.min(x2), y1.min(y2)),
+                    (x1.max(x2), y1.max(y2)),
+                )),
+                _ => return None,
+            },
+            Some("circle" | "circ") => match coords[..] {
+                [x, y, radius, ..] if radius > 0. => Self::Circle {
+                    center: (x, y),
+                    radius,
+                },
+                _ => return None,
+            },
+            Some("poly" | "polygon") if coords.len() >= 6 => Self::Polygon(
+                coords
+                    .chunks_exact(2)
+                    .map(|point| (point[0], point[1]))
+                    .collect(),
+            ),
+            Some("default") => Self::Default,
+            _ => return None,
+        };
+        Some(shape)
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        match self {
+            Self::Rect(rect) => rect.contains(point),
+            Self::Circle { center, radius } => {
+                (point.0 - center.0).hypot(point.1 - center.1) <= *radius
+            }
+            Self::Polygon(points) => {
+                // Even-odd rule: count the edges that a ray going right from the point crosses
+                let mut inside = false;
+                let mut prev = points[points.len() - 1];
+                for &next in points {
+                    if (next.1 > point.1) != (prev.1 > point.1)
+                        && point.0
+                            < (prev.0 - next.0) * (point.1 - next.1) / (prev.1 - next.1) + next.0
+                    {
+                        inside = !inside;
+                    }
+                    prev = next;
+                }
+                inside
+            }
+            Self::Default => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapArea {
+    pub shape: Shape,
+    pub href: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_shapes() {
+        assert_eq!(
+            Shape::new(None, "10, 20, 0, 0"),
+            Some(Shape::Rect(Rect::from_min_max((0., 0.), (10., 20.))))
+        );
+        assert_eq!(
+            Shape::new(Some("CIRCLE"), "5,5,2"),
+            Some(Shape::Circle {
+                center: (5., 5.),
+                radius: 2.
+            })
+        );
+        assert_eq!(Shape::new(Some("poly"), "0,0,1,1"), None);
+        assert_eq!(Shape::new(Some("rect"), "1,2,three,4"), None);
+        assert_eq!(Shape::new(Some("star"), "1,2,3,4"), None);
+        assert_eq!(Shape::new(Some("default"), ""), Some(Shape::Default));
+    }
+
+    #[test]
+    fn hit_testing() {
+        let circle = Shape::new(Some("circle"), "10,10,5").unwrap();
+        assert!(circle.contains((13., 13.)));
+        assert!(!circle.contains((14., 14.)));
+
+        // A "C" shape, where the notch in the middle isn't part of it
+        let polygon = Shape::new(Some("poly"), "0,0 10,0 10,3 3,3 3,7 10,7 10,10 0,10").unwrap();
+        assert!(polygon.contains((1., 5.)));
+        assert!(polygon.contains((8., 1.)));
+        assert!(!polygon.contains((8., 5.)));
+        assert!(!polygon.contains((11., 5.)));
+    }
+}
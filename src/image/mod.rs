@@ -1,26 +1,31 @@
 mod decode;
+mod map;
 #[cfg(test)]
 mod tests;
 
+pub use map::{MapArea, Shape};
+
 use std::borrow::Cow;
 use std::ops::Deref;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::{Arc, OnceLock};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use std::{
     fs,
     io::{self, Read},
 };
 
 use crate::debug_impls::{DebugBytesPrefix, DebugInline};
+use crate::external_render::ExternalRenderers;
 use crate::interpreter::ImageCallback;
 use crate::metrics::{histogram, HistTag};
 use crate::positioner::DEFAULT_MARGIN;
-use crate::utils::{usize_in_mib, Align, Point, Size};
+use crate::utils::{usize_in_mib, Align, Point, Rect, Size};
 
 use anyhow::Context;
 use bytemuck::{Pod, Zeroable};
+use image::imageops::FilterType;
 use image::{ImageBuffer, RgbaImage};
 use parking_lot::Mutex;
 use resvg::{tiny_skia, usvg};
@@ -53,6 +58,34 @@ pub enum ImageSize {
     PxHeight(Px),
 }
 
+/// How much of the viewport an image can take up, as fractions of its width and height
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MaxSize {
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+}
+
+impl MaxSize {
+    /// Fractions outside of `(0, 1]` don't make for a useful limit, so they're treated as no
+    /// limit and the whole viewport respectively
+    pub fn new(width: Option<f32>, height: Option<f32>) -> Self {
+        let fraction = |f: Option<f32>| f.filter(|&f| f > 0.).map(|f| f.min(1.));
+        Self {
+            width: fraction(width),
+            height: fraction(height),
+        }
+    }
+
+    /// Scales `size` down until it fits within the limits for `viewport`, keeping its aspect
+    /// ratio
+    pub fn fit(self, size: Size, viewport: Size) -> Size {
+        let width_scale = self.width.map_or(1., |max| max * viewport.0 / size.0);
+        let height_scale = self.height.map_or(1., |max| max * viewport.1 / size.1);
+        let scale = width_scale.min(height_scale).min(1.);
+        (size.0 * scale, size.1 * scale)
+    }
+}
+
 impl ImageSize {
     pub fn width<P: Into<Px>>(px: P) -> Self {
         Self::PxWidth(px.into())
@@ -70,6 +103,10 @@ pub struct ImageData {
     scale: bool,
     #[debug(wrapper = DebugInline)]
     dimensions: (u32, u32),
+    /// A preview from an image that's still downloading, which gets replaced once more of it
+    /// arrives
+    #[debug(skip)]
+    partial: bool,
 }
 
 impl ImageData {
@@ -79,9 +116,32 @@ impl ImageData {
             lz4_blob,
             scale,
             dimensions,
+            partial: false,
         })
     }
 
+    /// Decodes however much of a progressive JPEG or interlaced PNG has arrived so far, which
+    /// covers the whole image at a lower quality. Other images would only show their top rows, so
+    /// they're left for when they're complete
+    pub fn load_partial(bytes: &[u8], scale: bool) -> Option<Self> {
+        if !decode::is_progressive(bytes) {
+            return None;
+        }
+        let (lz4_blob, dimensions) = decode::decode_and_compress(bytes).ok()?;
+        Some(Self {
+            lz4_blob,
+            scale,
+            dimensions,
+            partial: true,
+        })
+    }
+
+    /// A single pixel of one color, for drawing flat shapes that need blending through the image
+    /// pipeline
+    pub fn solid(rgba: [u8; 4]) -> Self {
+        Self::new(RgbaImage::from_pixel(1, 1, image::Rgba(rgba)), false)
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         decode::lz4_decompress(&self.lz4_blob, self.rgba_image_byte_size())
             .expect("Size matches and I/O is in memory")
@@ -104,6 +164,7 @@ impl ImageData {
             dimensions,
             lz4_blob,
             scale,
+            partial: false,
         }
     }
 
@@ -127,6 +188,9 @@ pub struct Image {
     pub bind_group: Option<Arc<wgpu::BindGroup>>,
     #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
     pub is_link: Option<String>,
+    /// Regions from a `usemap` that link somewhere other than the image as a whole
+    #[debug(skip_fn = Vec::is_empty)]
+    pub map: Vec<MapArea>,
     #[debug(skip)]
     pub hidpi_scale: f32,
 }
@@ -141,6 +205,7 @@ impl PartialEq for Image {
             size,
             bind_group,
             is_link,
+            map,
             hidpi_scale,
         } = self;
         let Self {
@@ -149,6 +214,7 @@ impl PartialEq for Image {
             size: other_size,
             bind_group: other_bind_group,
             is_link: other_is_link,
+            map: other_map,
             hidpi_scale: other_hidpi_scale,
         } = other;
 
@@ -164,6 +230,7 @@ impl PartialEq for Image {
             && is_aligned == other_is_aligned
             && size == other_size
             && is_link == other_is_link
+            && map == other_map
             && hidpi_scale == other_hidpi_scale
             && bind_group_variant_matches
     }
@@ -180,6 +247,8 @@ impl Image {
         queue: &wgpu::Queue,
         sampler: &wgpu::Sampler,
         bindgroup_layout: &wgpu::BindGroupLayout,
+        texture_format: wgpu::TextureFormat,
+        max_texture_size: u32,
     ) -> Option<Arc<BindGroup>> {
         let dimensions = self.buffer_dimensions()?;
         if dimensions.0 == 0 || dimensions.1 == 0 {
@@ -188,13 +257,14 @@ impl Image {
         }
 
         let start = Instant::now();
-        let rgba_image = self
+        let (rgba_image, partial) = self
             .image_data
             .lock()
             .as_ref()
-            .map(|image| image.to_bytes())?;
+            .map(|image| (image.to_bytes(), image.partial))?;
 
         tracing::debug!("Decompressing image: Time {:.2?}", start.elapsed());
+        let (rgba_image, dimensions) = fit_texture(rgba_image, dimensions, max_texture_size);
 
         let texture_size = wgpu::Extent3d {
             width: dimensions.0,
@@ -206,7 +276,7 @@ impl Image {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format: texture_format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             label: Some("Image Texture"),
             view_formats: &[],
@@ -246,7 +316,10 @@ impl Image {
             label: Some("Image Bind Group"),
         });
         let bind_group = Arc::new(bind_group);
-        self.bind_group = Some(bind_group.clone());
+        // Previews get swapped out as the download goes on, so they're made fresh for each frame
+        if !partial {
+            self.bind_group = Some(bind_group.clone());
+        }
         Some(bind_group)
     }
 
@@ -254,6 +327,7 @@ impl Image {
         src: String,
         hidpi_scale: f32,
         image_callback: Box<dyn ImageCallback + Send>,
+        external_renderers: ExternalRenderers,
     ) -> anyhow::Result<Image> {
         let image_data = Arc::new(Mutex::new(None::<ImageData>));
         let image_data_clone = image_data.clone();
@@ -268,12 +342,27 @@ impl Image {
                 }
             }
 
-            let image_data = if let Ok(img_file) = fs::read(&src_path) {
+            let image_data = if let Some(rendered) = external_renderers.load(&src) {
+                match rendered {
+                    Ok(bytes) => bytes,
+                    Err(err) => {
+                        tracing::warn!("Failed rendering {src}: {err:#}");
+                        return;
+                    }
+                }
+            } else if let Ok(img_file) = fs::read(&src_path) {
                 img_file
-            } else if let Ok(bytes) = http_get_image(&src) {
+            } else if let Ok(bytes) = http_get_image(&src, |partial| {
+                if let Some(preview) = ImageData::load_partial(partial, true) {
+                    *image_data_clone.lock() = Some(preview);
+                    image_callback.image_progress();
+                }
+            }) {
                 bytes
             } else {
                 tracing::warn!("Request for image from {} failed", src_path.display());
+                // Don't leave a preview up for an image that stopped downloading partway
+                *image_data_clone.lock() = None;
                 return;
             };
 
@@ -348,6 +437,27 @@ impl Image {
         self.is_link = Some(link);
     }
 
+    pub fn set_map(&mut self, map: Vec<MapArea>) {
+        self.map = map;
+    }
+
+    /// The image map area under `loc` for the image drawn within `bounds`
+    pub fn area_at(&self, loc: Point, bounds: &Rect) -> Option<&MapArea> {
+        if self.map.is_empty() {
+            return None;
+        }
+        // Coordinates are in the image's pixels at whatever size the document asked for
+        let (width, height) = match &self.size {
+            Some(size) => self.dimensions_from_image_size(size)?,
+            None => self.buffer_dimensions()?,
+        };
+        let point = (
+            (loc.0 - bounds.pos.0) / bounds.size.0 * width as f32,
+            (loc.1 - bounds.pos.1) / bounds.size.1 * height as f32,
+        );
+        self.map.iter().find(|area| area.shape.contains(point))
+    }
+
     pub fn with_align(mut self, align: Align) -> Self {
         self.is_aligned = Some(align);
         self
@@ -358,7 +468,7 @@ impl Image {
         self
     }
 
-    pub fn dimensions_from_image_size(&mut self, size: &ImageSize) -> Option<(u32, u32)> {
+    pub fn dimensions_from_image_size(&self, size: &ImageSize) -> Option<(u32, u32)> {
         let image_dimensions = self.buffer_dimensions()?;
         match size {
             ImageSize::PxWidth(px_width) => Some((
@@ -378,7 +488,7 @@ impl Image {
         Some(self.image_data.lock().as_ref()?.dimensions)
     }
 
-    fn dimensions(&mut self, screen_size: Size, zoom: f32) -> Option<(u32, u32)> {
+    fn dimensions(&self, screen_size: Size, zoom: f32) -> Option<(u32, u32)> {
         let buffer_size = self.buffer_dimensions()?;
         let mut buffer_size = (buffer_size.0 as f32 * zoom, buffer_size.1 as f32 * zoom);
         if let Some(image) = self.image_data.lock().deref() {
@@ -413,13 +523,27 @@ impl Image {
         Some(dimensions)
     }
 
-    pub fn size(&mut self, screen_size: Size, zoom: f32) -> Option<Size> {
+    pub fn size(&self, screen_size: Size, zoom: f32) -> Option<Size> {
         self.dimensions(screen_size, zoom)
             .map(|d| (d.0 as f32, d.1 as f32))
     }
+
+    /// Where the image goes when it's expanded over the whole window. It's as big as it would be
+    /// without any limits while still fitting within the window's margins
+    pub fn lightbox_bounds(&self, screen_size: Size, zoom: f32) -> Option<Rect> {
+        let size = self.size(screen_size, zoom)?;
+        let max_height = screen_size.1 - 2. * DEFAULT_MARGIN;
+        let scale = (max_height / size.1).min(1.);
+        let size = (size.0 * scale, size.1 * scale);
+        let pos = ((screen_size.0 - size.0) / 2., (screen_size.1 - size.1) / 2.);
+        Some(Rect::new(pos, size))
+    }
 }
 
-pub fn http_get_image(url: &str) -> anyhow::Result<Vec<u8>> {
+/// Calls `progress` with everything downloaded so far every time this much time passes
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+pub fn http_get_image(url: &str, mut progress: impl FnMut(&[u8])) -> anyhow::Result<Vec<u8>> {
     const USER_AGENT: &str = concat!(
         "inlyne ",
         env!("CARGO_PKG_VERSION"),
@@ -433,9 +557,22 @@ pub fn http_get_image(url: &str) -> anyhow::Result<Vec<u8>> {
         .header("Content-Length")
         .and_then(|len| len.parse::<usize>().ok());
     let mut body = Vec::with_capacity(len.unwrap_or(0).clamp(0, LIMIT));
-    resp.into_reader()
-        .take(u64::try_from(LIMIT).unwrap())
-        .read_to_end(&mut body)?;
+    let mut reader = resp.into_reader().take(u64::try_from(LIMIT).unwrap());
+    let mut chunk = [0; 16 * 1_024];
+    let mut last_progress = Instant::now();
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        body.extend_from_slice(&chunk[..read]);
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            progress(&body);
+            last_progress = Instant::now();
+        }
+    }
     Ok(body)
 }
 
@@ -451,6 +588,40 @@ pub struct ImageRenderer {
     pub index_buf: wgpu::Buffer,
     pub bindgroup_layout: wgpu::BindGroupLayout,
     pub sampler: wgpu::Sampler,
+    pub texture_format: wgpu::TextureFormat,
+    /// The most texels an image's texture can have on either side. Bigger images get scaled down
+    /// to fit
+    pub max_texture_size: u32,
+}
+
+/// Scales down images that are bigger than a texture can be on either side, keeping their aspect
+/// ratio. They still get drawn at their full size, just with less detail
+fn fit_texture(rgba: Vec<u8>, dimensions: (u32, u32), max_size: u32) -> (Vec<u8>, (u32, u32)) {
+    let fitted = fitted_dimensions(dimensions, max_size);
+    if fitted == dimensions {
+        return (rgba, dimensions);
+    }
+
+    tracing::warn!(
+        "Image is {}x{}, but textures can be at most {max_size} on a side. Scaling it down to {}x{}",
+        dimensions.0,
+        dimensions.1,
+        fitted.0,
+        fitted.1,
+    );
+    let image = RgbaImage::from_raw(dimensions.0, dimensions.1, rgba).expect("Size matches");
+    let resized = image::imageops::resize(&image, fitted.0, fitted.1, FilterType::Triangle);
+    (resized.into_raw(), fitted)
+}
+
+fn fitted_dimensions((width, height): (u32, u32), max_size: u32) -> (u32, u32) {
+    let largest = width.max(height);
+    if largest <= max_size {
+        return (width, height);
+    }
+    let scale = max_size as f64 / largest as f64;
+    let fit = |side: u32| ((side as f64 * scale).round() as u32).clamp(1, max_size);
+    (fit(width), fit(height))
 }
 
 pub fn point(x: f32, y: f32, position: Point, size: Size, screen: Size) -> [f32; 3] {
@@ -553,10 +724,21 @@ impl ImageRenderer {
             index_buf,
             bindgroup_layout: texture_bind_group_layout,
             sampler,
+            texture_format: crate::color::image_texture_format(format),
+            max_texture_size: device.limits().max_texture_dimension_2d,
+        }
+    }
+
+    /// Lowers the largest texture size below what the GPU allows, e.g. to save video memory
+    pub fn limit_texture_size(&mut self, limit: Option<u32>) {
+        if let Some(limit) = limit {
+            self.max_texture_size = self.max_texture_size.min(limit.max(1));
         }
     }
 
     pub fn vertex_buf(device: &Device, pos: Point, size: Size, screen_size: Size) -> wgpu::Buffer {
+        // Keep texels aligned to physical pixels so images stay sharp while scrolling
+        let Rect { pos, size } = Rect::new(pos, size).snap_to_pixels();
         let vertices: &[ImageVertex] = &[
             // TOP LEFT
             ImageVertex {
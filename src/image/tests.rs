@@ -1,7 +1,7 @@
 use std::path::Path;
 use std::{fmt, fs};
 
-use super::{ImageData, Px};
+use super::{decode, fitted_dimensions, ImageData, MaxSize, Px};
 use crate::test_utils::log;
 
 #[test]
@@ -10,6 +10,51 @@ fn px_parsing() {
     assert_eq!("500px".parse::<Px>().unwrap(), Px(500));
 }
 
+#[test]
+fn max_size_fit() {
+    let viewport = (1_000., 800.);
+    let unlimited = MaxSize::default();
+    assert_eq!(unlimited.fit((900., 2_000.), viewport), (900., 2_000.));
+
+    // The tighter of the two limits wins and the aspect ratio stays the same
+    let limited = MaxSize::new(Some(0.5), Some(0.5));
+    assert_eq!(limited.fit((900., 2_000.), viewport), (180., 400.));
+    assert_eq!(limited.fit((2_000., 800.), viewport), (500., 200.));
+    // Small images are left alone
+    assert_eq!(limited.fit((100., 100.), viewport), (100., 100.));
+
+    assert_eq!(
+        MaxSize::new(Some(0.), Some(2.)),
+        MaxSize::new(None, Some(1.))
+    );
+}
+
+#[test]
+fn oversized_textures_get_scaled_down() {
+    assert_eq!(fitted_dimensions((800, 600), 8_192), (800, 600));
+    assert_eq!(fitted_dimensions((1_000, 40_000), 8_192), (205, 8_192));
+    assert_eq!(fitted_dimensions((20_000, 10_000), 4_096), (4_096, 2_048));
+    // Slivers keep at least a pixel
+    assert_eq!(fitted_dimensions((1, 100_000), 8_192), (1, 8_192));
+}
+
+#[test]
+fn progressive_detection() {
+    // SOI, an APP0 segment, then the start of frame
+    let mut jpeg: Vec<u8> = vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 4, 0, 0, 0xFF, 0xC2, 0, 2];
+    assert!(decode::is_progressive(&jpeg));
+    jpeg[9] = 0xC0;
+    assert!(!decode::is_progressive(&jpeg));
+
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    png.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 1]);
+    assert!(decode::is_progressive(&png));
+    *png.last_mut().unwrap() = 0;
+    assert!(!decode::is_progressive(&png));
+
+    assert!(!decode::is_progressive(b"GIF89a"));
+}
+
 // Checks that the image crate converting to RGBA8 is the same as our technique
 fn check(input_path: &Path) {
     let bytes = fs::read(input_path).unwrap();
@@ -0,0 +1,178 @@
+//! Describes how a document got laid out, for debugging the positioner
+//!
+//! Pairs with `render-element-bounds` (or `--debug-bounds`), which outlines each element's bounds
+//! in the window
+
+use std::fmt::{self, Write};
+
+use crate::positioner::{ElementSpacing, Positioned};
+use crate::utils::{Point, Rect};
+use crate::Element;
+
+/// One laid out element along with everything it contains
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementInfo {
+    pub kind: &'static str,
+    pub bounds: Option<Rect>,
+    /// The space left after it, before scaling
+    pub spacing: f32,
+    /// The first and last lines of the markdown that it came from, when they're known
+    pub source_lines: Option<(usize, usize)>,
+    pub children: Vec<ElementInfo>,
+}
+
+impl ElementInfo {
+    pub fn new(element: &Positioned<Element>, spacing: &ElementSpacing) -> Self {
+        let (kind, source_lines, children) = match &element.inner {
+            Element::TextBox(text_box) => {
+                let kind = if text_box.header_level.is_some() {
+                    "Heading"
+                } else if text_box.is_code_block {
+                    "Code block"
+                } else if text_box.is_list_item {
+                    "List item"
+                } else {
+                    "Text"
+                };
+                (kind, text_box.source_lines, Vec::new())
+            }
+            Element::Spacer(_) => ("Spacer", None, Vec::new()),
+            Element::Image(_) => ("Image", None, Vec::new()),
+            Element::Table(_) => ("Table", None, Vec::new()),
+            Element::Math(_) => ("Math", None, Vec::new()),
+            Element::Row(row) => ("Row", None, layout_tree(&row.elements, spacing)),
+            Element::Section(section) => {
+                let summary = section.summary.iter();
+                let children = summary.chain(&section.elements);
+                let children = children.map(|child| Self::new(child, spacing)).collect();
+                ("Section", None, children)
+            }
+        };
+        Self {
+            kind,
+            bounds: element.bounds.clone(),
+            spacing: spacing.after(&element.inner),
+            source_lines,
+            children,
+        }
+    }
+
+    /// The innermost element containing `loc`, this one included
+    fn innermost(&self, loc: Point) -> Option<&Self> {
+        if !self.bounds.as_ref()?.contains(loc) {
+            return None;
+        }
+        let child = self.children.iter().find_map(|child| child.innermost(loc));
+        Some(child.unwrap_or(self))
+    }
+}
+
+impl fmt::Display for ElementInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.kind)?;
+        match &self.bounds {
+            Some(Rect { pos, size }) => write!(
+                f,
+                " at ({:.0}, {:.0}), {:.0}x{:.0}",
+                pos.0, pos.1, size.0, size.1
+            )?,
+            None => f.write_str(" (not positioned)")?,
+        }
+        write!(f, ", {}px after", self.spacing)?;
+        if let Some((start, end)) = self.source_lines {
+            if start == end {
+                write!(f, ", line {start}")?;
+            } else {
+                write!(f, ", lines {start}-{end}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Describes every element and what's nested in them
+pub fn layout_tree(elements: &[Positioned<Element>], spacing: &ElementSpacing) -> Vec<ElementInfo> {
+    elements
+        .iter()
+        .map(|element| ElementInfo::new(element, spacing))
+        .collect()
+}
+
+/// Describes the innermost element at `loc` in document coordinates
+pub fn element_at(
+    elements: &[Positioned<Element>],
+    spacing: &ElementSpacing,
+    loc: Point,
+) -> Option<ElementInfo> {
+    let tree = layout_tree(elements, spacing);
+    tree.iter().find_map(|info| info.innermost(loc)).cloned()
+}
+
+/// Lists every element on its own line, indented by how deeply it's nested
+pub fn dump(tree: &[ElementInfo]) -> String {
+    fn dump_into(out: &mut String, tree: &[ElementInfo], depth: usize) {
+        for info in tree {
+            let _ = writeln!(out, "{:indent$}{info}", "", indent = depth * 2);
+            dump_into(out, &info.children, depth + 1);
+        }
+    }
+
+    let mut out = String::new();
+    dump_into(&mut out, tree, 0);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::positioner::{Row, Spacer};
+    use crate::text::{Text, TextBox};
+
+    fn positioned(
+        element: impl Into<Element>,
+        pos: Point,
+        size: (f32, f32),
+    ) -> Positioned<Element> {
+        Positioned {
+            inner: element.into(),
+            bounds: Some(Rect::new(pos, size)),
+        }
+    }
+
+    #[test]
+    fn finds_and_dumps_nested_elements() {
+        let mut text_box = TextBox::new(vec![Text::new("Hi".into(), 1., [0.; 4])], 1.);
+        text_box.source_lines = Some((3, 4));
+        let inner = positioned(text_box, (10., 10.), (50., 20.));
+        let row = Row {
+            elements: vec![inner],
+            hidpi_scale: 1.,
+        };
+        let elements = vec![
+            positioned(Spacer::invisible(), (0., 0.), (0., 5.)),
+            positioned(row, (0., 10.), (100., 20.)),
+        ];
+        let spacing = ElementSpacing::default();
+
+        let hovered = element_at(&elements, &spacing, (20., 15.)).unwrap();
+        assert_eq!(
+            hovered.to_string(),
+            "Text at (10, 10), 50x20, 2px after, lines 3-4"
+        );
+        assert_eq!(
+            element_at(&elements, &spacing, (80., 15.)).unwrap().kind,
+            "Row"
+        );
+        assert_eq!(element_at(&elements, &spacing, (80., 80.)), None);
+
+        let dumped = dump(&layout_tree(&elements, &spacing));
+        assert_eq!(
+            dumped,
+            "\
+Spacer at (0, 0), 0x5, 2px after
+Row at (0, 10), 100x20, 2px after
+  Text at (10, 10), 50x20, 2px after, lines 3-4
+"
+        );
+    }
+}
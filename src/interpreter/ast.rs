@@ -1,14 +1,16 @@
 use crate::color::{native_color, Theme};
-use crate::image::{Image, ImageSize};
+use crate::external_render::ExternalRenderers;
+use crate::image::{Image, ImageSize, MapArea, Shape};
 use crate::interpreter::hir::{Hir, HirNode, TextOrHirNode};
-use crate::interpreter::html::attr::PrefersColorScheme;
+use crate::interpreter::html::attr::{MathStyle, PrefersColorScheme};
 use crate::interpreter::html::picture::Builder;
 use crate::interpreter::html::style::{FontStyle, FontWeight, Style, TextDecoration};
 use crate::interpreter::html::{style, Attr, HeaderType, Picture, TagName};
-use crate::interpreter::{Span, WindowInteractor};
+use crate::interpreter::{get_expand_long_lines, Span, WindowInteractor, EXPAND_LONG_LINES_LINK};
+use crate::math::{self, Math};
 use crate::opts::ResolvedTheme;
 use crate::positioner::{Positioned, Row, Section, Spacer};
-use crate::table::Table;
+use crate::table::{CellSpan, Table, TableCards};
 use crate::text::{Text, TextBox};
 use crate::utils::{Align, ImageCache};
 use crate::Element;
@@ -16,11 +18,14 @@ use comrak::Anchorizer;
 use glyphon::FamilyOwned;
 use parking_lot::Mutex;
 use percent_encoding::percent_decode_str;
+use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 use std::rc::Rc;
 use std::sync::Arc;
 use wgpu::TextureFormat;
 
+const MAX_LINE_CHARS: usize = 2_000;
+
 #[derive(Debug, Clone, Default)]
 struct TextOptions {
     pub underline: bool,
@@ -39,6 +44,7 @@ struct TextOptions {
 #[derive(Debug, Clone, Default)]
 struct InheritedState {
     global_indent: f32,
+    in_list: bool,
     text_options: TextOptions,
     span: Span,
 }
@@ -95,6 +101,7 @@ impl Push<Element> for Vec<Element> {
 
             if content {
                 tb.indent = state.global_indent;
+                tb.is_list_item = state.in_list;
                 self.push_element(tb);
             }
         } else {
@@ -114,6 +121,7 @@ impl Push<Element> for Vec<Element> {
                 src,
                 global.opts.hidpi_scale,
                 global.opts.window.lock().image_callback(),
+                global.opts.external_renderers.clone(),
             )
             .unwrap(),
         }
@@ -125,6 +133,10 @@ impl Push<Element> for Vec<Element> {
         if let Some(size) = picture.inner.size {
             image = image.with_size(size);
         }
+        let map = picture.inner.map.as_ref();
+        if let Some(areas) = map.and_then(|name| global.image_maps.get(name)) {
+            image.set_map(areas.clone());
+        }
 
         if Align::Left == align {
             if let Some(Element::Row(row)) = self.iter_mut().next_back() {
@@ -159,8 +171,12 @@ pub struct AstOpts {
     pub add_spacers_after_tables: bool,
     pub add_spacers_after_paragraphs: bool,
     pub add_spacers_after_lists: bool,
+    /// Set for each document since its front matter can override the config
+    pub table_cards: Mutex<TableCards>,
+    pub code_line_numbers: bool,
 
     // needed for images
+    pub external_renderers: ExternalRenderers,
     pub color_scheme: Option<ResolvedTheme>,
     pub image_cache: ImageCache,
     pub window: Arc<Mutex<dyn WindowInteractor + Send>>,
@@ -179,23 +195,28 @@ impl Ast {
     pub fn new(opts: AstOpts, elements: Arc<Mutex<Vec<Element>>>) -> Self {
         Self { opts, elements }
     }
-    pub fn interpret(&self, hir: Hir) {
+    /// Interprets `hir` pushing elements as each top-level node finishes
+    ///
+    /// `is_canceled` is checked before each push while holding the element queue's lock. Returns
+    /// `false` if interpretation was cut short
+    pub fn interpret(&self, hir: Hir, mut is_canceled: impl FnMut() -> bool) -> bool {
         let mut nodes = hir.content();
 
         assert!(!nodes.is_empty(), "Hir should contain root");
         let mut root = std::mem::take(&mut nodes[0].content);
         let state =
-            InheritedState::with_span_color(self.opts.native_color(self.opts.theme.code_color));
+            InheritedState::with_span_color(self.opts.native_color(self.opts.theme.palette.code_color));
 
         let input = Input(&nodes);
 
         let global = Static {
             opts: &self.opts,
             input,
+            image_maps: image_maps(&nodes),
         };
 
-        root.drain(..)
-            .filter_map(|ton| match ton {
+        for ton in root.drain(..) {
+            let part = match ton {
                 TextOrHirNode::Hir(node) => {
                     let mut out = vec![];
                     let mut tb = TextBox::new(vec![], self.opts.hidpi_scale);
@@ -211,30 +232,77 @@ impl Ast {
                     if !tb.texts.is_empty() {
                         out.push_text_box(&global, &mut tb, state);
                     }
-                    Some(out)
+                    out
                 }
                 TextOrHirNode::Text(text) => {
                     if text.trim().is_empty() {
-                        return None;
+                        continue;
                     }
                     let hidpi_scale = self.opts.hidpi_scale;
-                    let color = global.opts.native_color(global.opts.theme.text_color);
+                    let color = global.opts.native_color(global.opts.theme.palette.text_color);
                     let paragraph = Text::new(text, hidpi_scale, color);
                     let text_box = TextBox::new(vec![paragraph], self.opts.hidpi_scale);
-                    self.elements.lock().push(text_box.into());
-                    None
+                    vec![text_box.into()]
                 }
-            })
-            .for_each(|part| {
-                self.elements.lock().extend(part);
-                self.opts.window.lock().request_redraw();
-            })
+            };
+
+            let mut elements = self.elements.lock();
+            if is_canceled() {
+                return false;
+            }
+            elements.extend(part);
+            drop(elements);
+            self.opts.window.lock().request_redraw();
+        }
+
+        true
     }
 }
 
 struct Static<'a> {
     input: Input<'a>,
     opts: Opts<'a>,
+    /// Every `<map>` by name, since images can come before the map they use
+    image_maps: HashMap<String, Vec<MapArea>>,
+}
+
+fn image_maps(nodes: &[HirNode]) -> HashMap<String, Vec<MapArea>> {
+    let mut maps = HashMap::new();
+    for node in nodes.iter().filter(|node| node.tag == TagName::ImageMap) {
+        let Some(name) = node.attributes.iter().find_map(|attr| match attr {
+            Attr::Name(name) => Some(name.to_owned()),
+            _ => None,
+        }) else {
+            continue;
+        };
+        let areas = node
+            .content
+            .iter()
+            .filter_map(|ton| match ton {
+                TextOrHirNode::Hir(index) => nodes.get(*index),
+                TextOrHirNode::Text(_) => None,
+            })
+            .filter(|node| node.tag == TagName::ImageMapArea)
+            .filter_map(|area| {
+                let (mut shape, mut coords, mut href) = (None, "", None);
+                for attr in &area.attributes {
+                    match attr {
+                        Attr::Shape(s) => shape = Some(s.as_str()),
+                        Attr::Coords(c) => coords = c.as_str(),
+                        Attr::Href(link) => href = percent_decode_str(link).decode_utf8().ok(),
+                        _ => {}
+                    }
+                }
+                Some(MapArea {
+                    shape: Shape::new(shape, coords)?,
+                    href: href?.into_owned(),
+                })
+            })
+            .collect();
+        // Like browsers, the first map with a name wins
+        maps.entry(name).or_insert(areas);
+    }
+    maps
 }
 
 enum State<'a> {
@@ -282,6 +350,42 @@ impl Clone for State<'_> {
     }
 }
 
+/// How many characters the last line of `element` has so far, or `None` when that line was
+/// already cut short
+fn line_chars(element: &mut TextBox) -> Option<usize> {
+    if expand_marker(element).is_some() {
+        return None;
+    }
+    let mut chars = 0;
+    for text in element.texts.iter().rev() {
+        match text.text.rfind('\n') {
+            Some(newline) => return Some(chars + text.text[newline + 1..].chars().count()),
+            None => chars += text.text.chars().count(),
+        }
+        // Past this the line gets cut short anyway, so there's no need to keep counting
+        if chars > MAX_LINE_CHARS {
+            break;
+        }
+    }
+    Some(chars)
+}
+
+/// The marker on the last line of `element`, if that line was cut short
+///
+/// Nothing gets added to a line after its marker, so only the end of the line is checked
+fn expand_marker(element: &mut TextBox) -> Option<&mut Text> {
+    let text = element.texts.last_mut()?;
+    (text.link.as_deref() == Some(EXPAND_LONG_LINES_LINK)).then_some(text)
+}
+
+fn expand_marker_text(cut: usize) -> String {
+    format!(" … [{cut} more characters]")
+}
+
+fn expand_marker_count(marker: &str) -> Option<usize> {
+    marker.strip_prefix(" … [")?.split(' ').next()?.parse().ok()
+}
+
 trait Process {
     type Context<'a>;
     fn process(
@@ -313,8 +417,72 @@ trait Process {
             }
         }
     }
-    fn text(global: &Static, element: &mut TextBox, state: State, mut string: &str) {
-        let text_native_color = global.opts.native_color(global.opts.theme.text_color);
+    /// Lines past `MAX_LINE_CHARS` (minified HTML, base64 blobs, etc.) get cut short with a
+    /// marker to expand them since shaping them can stall for seconds
+    ///
+    /// Syntax highlighting splits a line into a run of text per token, so the length is measured
+    /// across every run on the line rather than within each one
+    fn text(global: &Static, element: &mut TextBox, state: State, string: &str) {
+        if get_expand_long_lines() {
+            Self::text_inner(global, element, state, string);
+            return;
+        }
+
+        // `None` while dropping the rest of a line that was already cut short
+        let mut line_len = line_chars(element);
+        let mut kept = String::new();
+        for line in string.split_inclusive('\n') {
+            let content = line.strip_suffix('\n').unwrap_or(line);
+            match line_len {
+                None => {
+                    let cut = content.chars().count();
+                    if let Some(marker) = expand_marker(element) {
+                        let cut = cut + expand_marker_count(&marker.text).unwrap_or(0);
+                        marker.text = expand_marker_text(cut);
+                    }
+                }
+                Some(chars) => {
+                    let room = MAX_LINE_CHARS.saturating_sub(chars);
+                    match content.char_indices().nth(room) {
+                        Some((end, _)) => {
+                            kept.push_str(&content[..end]);
+                            if !kept.is_empty() {
+                                let kept = std::mem::take(&mut kept);
+                                Self::text_inner(global, element, state.borrow(), &kept);
+                            }
+
+                            let cut = content[end..].chars().count();
+                            let marker = Text::new(
+                                expand_marker_text(cut),
+                                global.opts.hidpi_scale,
+                                global
+                                    .opts
+                                    .native_color(global.opts.theme.palette.link_color),
+                            )
+                            .with_link(EXPAND_LONG_LINES_LINK.to_owned())
+                            .make_italic(true);
+                            element.texts.push(marker);
+                            line_len = None;
+                        }
+                        None => {
+                            kept.push_str(content);
+                            line_len = Some(chars + content.chars().count());
+                        }
+                    }
+                }
+            }
+            if line.ends_with('\n') {
+                kept.push('\n');
+                line_len = Some(0);
+            }
+        }
+        if !kept.is_empty() {
+            Self::text_inner(global, element, state, &kept);
+        }
+    }
+
+    fn text_inner(global: &Static, element: &mut TextBox, state: State, mut string: &str) {
+        let text_native_color = global.opts.native_color(global.opts.theme.palette.text_color);
         if string.trim().is_empty() {
             if state.text_options.pre_formatted {
                 element.texts.push(Text::new(
@@ -367,7 +535,7 @@ trait Process {
             }
             if let Some(ref link) = state.text_options.link {
                 text = text.with_link(link.to_string());
-                text = text.with_color(global.opts.native_color(global.opts.theme.link_color));
+                text = text.with_color(global.opts.native_color(global.opts.theme.palette.link_color));
             }
             if state.text_options.bold {
                 text = text.make_bold(true);
@@ -401,6 +569,12 @@ impl Process for FlowProcess {
         output: &mut impl Push<Element>,
     ) {
         let attributes = &node.attributes;
+        // Text that's already there came from an earlier block
+        if element.texts.is_empty() {
+            if let Some(lines) = attributes.iter().find_map(Attr::to_source_lines) {
+                element.source_lines = Some(lines);
+            }
+        }
         match node.tag {
             TagName::Paragraph => {
                 state.set_align_from_attributes(attributes);
@@ -500,17 +674,69 @@ impl Process for FlowProcess {
             TagName::Break => output.push_text_box(global, element, state),
             TagName::Code => {
                 state.text_options.code = true;
+                if let Some(line_numbers) = attributes.iter().find_map(Attr::to_line_numbers) {
+                    element.set_line_numbers(line_numbers);
+                }
+                if element.is_code_block {
+                    element.language = attributes.iter().find_map(Attr::to_language);
+                }
                 FlowProcess::process_content(global, element, state, &node.content, output);
             }
             TagName::Details => {
                 DetailsProcess::process(global, (), state, node, output);
             }
             TagName::Summary => tracing::warn!("Summary can only be in an Details element"),
-            TagName::Section => {}
+            TagName::Section => {
+                output.push_text_box(global, element, state.borrow());
+                // Set the footnote list apart from the end of the document
+                let is_footnotes = attributes
+                    .iter()
+                    .any(|attr| matches!(attr, Attr::IsFootnotes));
+                if is_footnotes {
+                    output.push_element(Spacer::visible());
+                }
+                FlowProcess::process_content(global, element, state, &node.content, output);
+            }
             TagName::EmphasisOrItalic => {
                 state.text_options.italic = true;
                 FlowProcess::process_content(global, element, state, &node.content, output);
             }
+            TagName::Figure => {
+                output.push_text_box(global, element, state.borrow());
+                // Figures center both their image and caption
+                state.text_options.align = Some(Align::Center);
+                FlowProcess::process_content(
+                    global,
+                    element,
+                    state.borrow(),
+                    &node.content,
+                    output,
+                );
+                output.push_text_box(global, element, state);
+            }
+            TagName::FigureCaption => {
+                output.push_text_box(global, element, state.borrow());
+                element.set_align_or_default(state.text_options.align);
+                state.text_options.italic = true;
+                FlowProcess::process_content(
+                    global,
+                    element,
+                    state.borrow(),
+                    &node.content,
+                    output,
+                );
+
+                // Captions can be linked to as `#figure-<caption>`
+                let caption: String = element.texts.iter().flat_map(|t| t.text.chars()).collect();
+                let anchor = global
+                    .opts
+                    .anchorizer
+                    .lock()
+                    .anchorize(format!("figure {}", caption.trim()));
+                element.set_anchor(format!("#{anchor}"));
+                output.push_text_box(global, element, state);
+                output.push_spacer();
+            }
             TagName::Header(header) => {
                 // Only push the existing text box if it has content
                 if !element.texts.is_empty() {
@@ -524,9 +750,10 @@ impl Process for FlowProcess {
                 element.set_align_or_default(state.text_options.align);
 
                 state.text_options.bold = true;
-                state.text_options.header_color = Some(global.opts.native_color(global.opts.theme.header_color));
+                state.text_options.header_color = Some(global.opts.native_color(global.opts.theme.palette.header_color));
                 element.font_size *= header.size_multiplier();
                 element.is_header = true;
+                element.header_level = Some(header.level());
 
                 if header == HeaderType::H1 {
                     state.text_options.underline = true;
@@ -551,21 +778,92 @@ impl Process for FlowProcess {
             TagName::Picture => PictureProcess::process(global, (), state, node, output),
             TagName::Source => tracing::warn!("Source tag can only be inside an Picture."),
             TagName::Image => ImageProcess::process(global, None, state, node, output),
+            // Picked up ahead of time by the images that use them
+            TagName::ImageMap | TagName::ImageMapArea => {}
             TagName::Input => {
                 let mut is_checkbox = false;
+                let mut is_radio = false;
                 let mut is_checked = false;
+                let mut input_type = None;
+                let mut value = None;
+                let mut placeholder = None;
                 for attr in attributes {
                     match attr {
                         Attr::IsCheckbox => is_checkbox = true,
+                        Attr::IsRadio => is_radio = true,
                         Attr::IsChecked => is_checked = true,
+                        Attr::Type(ty) => input_type = Some(ty.as_str()),
+                        Attr::Value(v) => value = Some(v.as_str()),
+                        Attr::Placeholder(p) => placeholder = Some(p.as_str()),
                         _ => {}
                     }
                 }
                 if is_checkbox {
                     element.set_checkbox(is_checked);
+                } else if is_radio {
+                    let marker = if is_checked { "◉ " } else { "○ " };
+                    Self::text(global, element, state.borrow(), marker);
+                } else {
+                    match input_type {
+                        Some("hidden") => {}
+                        Some(ty @ ("submit" | "reset" | "button")) => {
+                            let label = value.unwrap_or(match ty {
+                                "submit" => "Submit",
+                                "reset" => "Reset",
+                                _ => "",
+                            });
+                            Self::widget(global, element, state.borrow(), label);
+                        }
+                        _ => {
+                            let label = value.or(placeholder).unwrap_or("________");
+                            Self::widget(global, element, state.borrow(), label);
+                        }
+                    }
                 }
                 FlowProcess::process_content(global, element, state, &node.content, output);
             }
+            TagName::Button => {
+                state.text_options.code = true;
+                Self::text(global, element, state.borrow(), "[");
+                FlowProcess::process_content(
+                    global,
+                    element,
+                    state.borrow(),
+                    &node.content,
+                    output,
+                );
+                Self::text(global, element, state, "]");
+            }
+            TagName::Select => {
+                let options: Vec<_> = node
+                    .content
+                    .iter()
+                    .filter_map(|child| match child {
+                        TextOrHirNode::Hir(index) => Some(global.input.get(*index)),
+                        TextOrHirNode::Text(_) => None,
+                    })
+                    .filter(|child| child.tag == TagName::SelectOption)
+                    .collect();
+                let selected = options
+                    .iter()
+                    .find(|option| {
+                        option
+                            .attributes
+                            .iter()
+                            .any(|attr| matches!(attr, Attr::IsSelected))
+                    })
+                    .or(options.first());
+                let label: String = selected
+                    .into_iter()
+                    .flat_map(|option| &option.content)
+                    .filter_map(|child| match child {
+                        TextOrHirNode::Text(text) => Some(text.as_str()),
+                        TextOrHirNode::Hir(_) => None,
+                    })
+                    .collect();
+                Self::widget(global, element, state, &format!("{} ▾", label.trim()));
+            }
+            TagName::SelectOption => tracing::warn!("Option can only be in an Select element"),
             TagName::ListItem => tracing::warn!("ListItem can only be in an List element"),
             TagName::OrderedList => {
                 OrderedListProcess::process(global, element, state, node, output)
@@ -587,6 +885,7 @@ impl Process for FlowProcess {
                 }
                 state.text_options.pre_formatted = true;
                 element.set_code_block(true);
+                element.set_line_numbers(global.opts.code_line_numbers);
                 FlowProcess::process_content(
                     global,
                     element,
@@ -602,6 +901,28 @@ impl Process for FlowProcess {
                 state.text_options.small = true;
                 FlowProcess::process_content(global, element, state, &node.content, output);
             }
+            TagName::Span if attributes.iter().any(|attr| attr.to_math_style().is_some()) => {
+                let source: String = node
+                    .content
+                    .iter()
+                    .filter_map(|content| match content {
+                        TextOrHirNode::Text(text) => Some(text.as_str()),
+                        TextOrHirNode::Hir(_) => None,
+                    })
+                    .collect();
+                let display = attributes
+                    .iter()
+                    .any(|attr| attr.to_math_style() == Some(MathStyle::Display));
+                if display {
+                    let font_size = element.font_size;
+                    output.push_text_box(global, element, state);
+                    let color = global.opts.native_color(global.opts.theme.palette.text_color);
+                    let formula = Math::new(source, font_size, color, global.opts.hidpi_scale);
+                    output.push_element(formula);
+                } else {
+                    Self::text(global, element, state, &math::linear(&source));
+                }
+            }
             TagName::Span => {
                 let style_str = attributes
                     .iter()
@@ -620,6 +941,11 @@ impl Process for FlowProcess {
                 }
                 FlowProcess::process_content(global, element, state, &node.content, output);
             }
+            // Text can't be raised, so superscripts (like footnote references) are just shrunk
+            TagName::Superscript => {
+                state.text_options.small = true;
+                FlowProcess::process_content(global, element, state, &node.content, output);
+            }
             TagName::Strikethrough => {
                 state.text_options.strike_through = true;
                 FlowProcess::process_content(global, element, state, &node.content, output);
@@ -672,6 +998,14 @@ impl Process for FlowProcess {
     }
 }
 
+impl FlowProcess {
+    /// Renders a form control as a static `[label]` in the inline code style
+    fn widget(global: &Static, element: &mut TextBox, mut state: State, label: &str) {
+        state.text_options.code = true;
+        Self::text(global, element, state, &format!("[{label}]"));
+    }
+}
+
 struct DetailsProcess;
 impl Process for DetailsProcess {
     type Context<'a> = ();
@@ -748,6 +1082,7 @@ impl Process for OrderedListProcess {
         }
         output.push_text_box(global, element, state.borrow());
         state.global_indent += global.opts.page_margin / 2.;
+        state.in_list = true;
 
         Self::process_with(
             global,
@@ -784,6 +1119,7 @@ impl Process for UnorderedListProcess {
     ) {
         output.push_text_box(global, element, state.borrow());
         state.global_indent += global.opts.page_margin / 2.;
+        state.in_list = true;
 
         Self::process_with(
             global,
@@ -815,6 +1151,9 @@ impl Process for ListItemProcess {
         if let Some(anchor) = anchor {
             element.set_anchor(anchor)
         }
+        if let Some(lines) = node.attributes.iter().find_map(Attr::to_source_lines) {
+            element.source_lines = Some(lines);
+        }
         let first_child_is_checkbox = if let Some(TextOrHirNode::Hir(node)) = node.content.first() {
             let node = global.input.get(*node);
             if node.tag == TagName::Input {
@@ -837,7 +1176,7 @@ impl Process for ListItemProcess {
                 Text::new(
                     prefix,
                     global.opts.hidpi_scale,
-                    global.opts.native_color(global.opts.theme.text_color),
+                    global.opts.native_color(global.opts.theme.palette.text_color),
                 )
                 .make_bold(true),
             )
@@ -874,6 +1213,7 @@ impl Process for ImageProcess {
                 Attr::Width(w) => builder.set_size(ImageSize::width(*w)),
                 Attr::Height(h) => builder.set_size(ImageSize::height(*h)),
                 Attr::Src(s) => builder.set_src(s.to_owned()),
+                Attr::UseMap(name) => builder.set_map(name.to_owned()),
                 _ => {}
             }
         }
@@ -967,6 +1307,7 @@ impl Process for TableProcess {
         output: &mut impl Push<Element>,
     ) {
         let mut table = Table::new();
+        table.cards = *global.opts.table_cards.lock();
         Self::process_with(
             global,
             &node.content,
@@ -1086,16 +1427,39 @@ impl Process for TableCellProcess {
         node: &HirNode,
         _output: &mut impl Push<Element>,
     ) {
-        let row = table
+        let index = table
             .rows
-            .last_mut()
-            .expect("There should be at least one row.");
+            .last()
+            .expect("There should be at least one row.")
+            .len();
+        let column = table.next_column();
+        let attributes = &node.attributes;
+        let span = CellSpan {
+            cols: attributes.iter().find_map(Attr::to_col_span).unwrap_or(1),
+            rows: attributes.iter().find_map(Attr::to_row_span).unwrap_or(1),
+        };
+        if span != CellSpan::default() {
+            table.spans.insert((table.rows.len() - 1, index), span);
+        }
         if is_header {
             state.text_options.bold = true;
         }
 
+        // The first row sets each column's alignment, which the cells below fall back to
+        let align = if table.rows.len() == 1 {
+            let align = state.text_options.align.unwrap_or_default();
+            let aligns = std::iter::repeat(align).take(span.cols.into());
+            table.column_aligns.extend(aligns);
+            Some(align)
+        } else {
+            attributes
+                .iter()
+                .find_map(Attr::to_align)
+                .or_else(|| table.column_aligns.get(column).copied())
+                .or(state.text_options.align)
+        };
         let mut tb = TextBox::new(vec![], global.opts.hidpi_scale);
-        tb.set_align_or_default(state.text_options.align);
+        tb.set_align_or_default(align);
 
         FlowProcess::process_content(
             global,
@@ -1105,7 +1469,11 @@ impl Process for TableCellProcess {
             &mut Dummy, // TODO allow anything inside tables not only text.
         );
 
-        row.push(tb);
+        table
+            .rows
+            .last_mut()
+            .expect("There should be at least one row.")
+            .push(tb);
     }
 }
 
@@ -27,12 +27,32 @@ impl Iterator for Iter<'_> {
                 local_name!("src") => Some(Attr::Src(value.to_string())),
                 local_name!("start") => value.parse().ok().map(Attr::Start),
                 local_name!("style") => Some(Attr::Style(value.to_string())),
-                local_name!("type") => {
-                    (value.to_string() == "checkbox").then_some(Attr::IsCheckbox)
-                }
+                local_name!("type") => Some(match &**value {
+                    "checkbox" => Attr::IsCheckbox,
+                    "radio" => Attr::IsRadio,
+                    other => Attr::Type(other.to_owned()),
+                }),
+                local_name!("class") => language(value).map(Attr::Language),
                 local_name!("checked") => Some(Attr::IsChecked),
+                local_name!("selected") => Some(Attr::IsSelected),
+                local_name!("value") => Some(Attr::Value(value.to_string())),
+                local_name!("placeholder") => Some(Attr::Placeholder(value.to_string())),
                 local_name!("media") => PrefersColorScheme::new(value).map(Attr::Media),
                 local_name!("srcset") => Some(Attr::SrcSet(value.to_string())),
+                local_name!("usemap") => value
+                    .strip_prefix('#')
+                    .map(|name| Attr::UseMap(name.to_owned())),
+                local_name!("name") => Some(Attr::Name(value.to_string())),
+                local_name!("shape") => Some(Attr::Shape(value.to_string())),
+                local_name!("coords") => Some(Attr::Coords(value.to_string())),
+                local_name!("colspan") => span(value).map(Attr::ColSpan),
+                local_name!("rowspan") => span(value).map(Attr::RowSpan),
+                ref local if &**local == "data-math-style" => MathStyle::new(value).map(Attr::Math),
+                ref local if &**local == "data-footnotes" => Some(Attr::IsFootnotes),
+                ref local if &**local == "data-meta" => line_numbers(value).map(Attr::LineNumbers),
+                ref local if &**local == "data-sourcepos" => {
+                    source_lines(value).map(Attr::SourceLines)
+                }
                 _ => continue,
             };
 
@@ -54,9 +74,64 @@ pub enum Attr {
     Start(usize),
     Style(String),
     IsCheckbox,
+    IsRadio,
     IsChecked,
+    IsSelected,
+    Type(String),
+    Value(String),
+    Placeholder(String),
     Media(PrefersColorScheme),
     SrcSet(String),
+    /// The `<map>` an image takes its clickable areas from, without the leading `#`
+    UseMap(String),
+    Name(String),
+    Shape(String),
+    Coords(String),
+    /// How many columns a table cell stretches across
+    ColSpan(u16),
+    /// How many rows a table cell stretches down
+    RowSpan(u16),
+    Math(MathStyle),
+    /// Marks the section holding the footnote list
+    IsFootnotes,
+    /// A code block's own choice of whether to number its lines
+    LineNumbers(bool),
+    /// The language a code block is written in, from its `language-*` class
+    Language(String),
+    /// The first and last lines of the markdown that a block came from
+    SourceLines((usize, usize)),
+}
+
+/// The language from a `language-rust` class, leaving off anything after a comma like in
+/// `rust,ignore`
+fn language(class: &str) -> Option<String> {
+    class
+        .split_whitespace()
+        .find_map(|class| class.strip_prefix("language-"))
+        .map(|lang| lang.split_once(',').map_or(lang, |(lang, _)| lang))
+        .filter(|lang| !lang.is_empty())
+        .map(str::to_owned)
+}
+
+/// Picks out `line-numbers` or `no-line-numbers` from the rest of a fenced block's info string
+fn line_numbers(meta: &str) -> Option<bool> {
+    meta.split_whitespace().rev().find_map(|word| match word {
+        "line-numbers" => Some(true),
+        "no-line-numbers" => Some(false),
+        _ => None,
+    })
+}
+
+/// A `colspan` or `rowspan`, leaving out `0` and anything else that doesn't span at least one
+fn span(value: &str) -> Option<u16> {
+    value.trim().parse().ok().filter(|&span| span > 0)
+}
+
+/// The lines out of comrak's `data-sourcepos` e.g. `3:1-5:12` for lines 3 to 5
+fn source_lines(sourcepos: &str) -> Option<(usize, usize)> {
+    let (start, end) = sourcepos.split_once('-')?;
+    let line = |pos: &str| pos.split_once(':')?.0.parse().ok();
+    Some((line(start)?, line(end)?))
 }
 
 impl Attr {
@@ -81,6 +156,65 @@ impl Attr {
             None
         }
     }
+    pub fn to_math_style(&self) -> Option<MathStyle> {
+        if let Self::Math(style) = self {
+            Some(*style)
+        } else {
+            None
+        }
+    }
+    pub fn to_language(&self) -> Option<String> {
+        if let Self::Language(language) = self {
+            Some(language.to_owned())
+        } else {
+            None
+        }
+    }
+    pub fn to_source_lines(&self) -> Option<(usize, usize)> {
+        if let Self::SourceLines(lines) = self {
+            Some(*lines)
+        } else {
+            None
+        }
+    }
+    pub fn to_col_span(&self) -> Option<u16> {
+        if let Self::ColSpan(span) = self {
+            Some(*span)
+        } else {
+            None
+        }
+    }
+    pub fn to_row_span(&self) -> Option<u16> {
+        if let Self::RowSpan(span) = self {
+            Some(*span)
+        } else {
+            None
+        }
+    }
+    pub fn to_line_numbers(&self) -> Option<bool> {
+        if let Self::LineNumbers(line_numbers) = self {
+            Some(*line_numbers)
+        } else {
+            None
+        }
+    }
+}
+
+/// How math between dollar signs gets laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathStyle {
+    Inline,
+    Display,
+}
+
+impl MathStyle {
+    pub fn new(s: &str) -> Option<Self> {
+        match s {
+            "inline" => Some(Self::Inline),
+            "display" => Some(Self::Display),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -29,4 +29,16 @@ impl HeaderType {
             HeaderType::H6 => 0.67,
         }
     }
+
+    /// The heading's rank where `H1` is 1, which headings fold by
+    pub fn level(&self) -> u8 {
+        match self {
+            HeaderType::H1 => 1,
+            HeaderType::H2 => 2,
+            HeaderType::H3 => 3,
+            HeaderType::H4 => 4,
+            HeaderType::H5 => 5,
+            HeaderType::H6 => 6,
+        }
+    }
 }
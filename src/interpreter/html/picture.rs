@@ -10,6 +10,8 @@ pub struct Inner {
     pub dark_variant: Option<String>,
     pub light_variant: Option<String>,
     pub size: Option<ImageSize>,
+    /// The name of the `<map>` holding the image's clickable areas
+    pub map: Option<String>,
 }
 
 #[derive(Debug, Default)]
@@ -35,6 +37,10 @@ impl Builder {
         self.inner.size = Some(size);
     }
 
+    pub fn set_map(&mut self, name: String) {
+        self.inner.map = Some(name);
+    }
+
     pub fn set_src(&mut self, src: String) {
         self.src = Some(src);
     }
@@ -9,25 +9,33 @@ pub enum TagName {
     BlockQuote,
     BoldOrStrong,
     Break,
+    Button,
     Code,
     Details,
     Div,
     EmphasisOrItalic,
+    Figure,
+    FigureCaption,
     Header(HeaderType),
     HorizontalRuler,
     Picture,
     Source,
     Image,
+    ImageMap,
+    ImageMapArea,
     Input,
     ListItem,
     OrderedList,
     Paragraph,
     PreformattedText,
     Section,
+    Select,
+    SelectOption,
     Small,
     Span,
     Strikethrough,
     Summary,
+    Superscript,
     Table,
     TableBody,
     TableCaption,
@@ -47,27 +55,35 @@ impl TagName {
             TagName::Break
             | TagName::HorizontalRuler
             | TagName::Image
+            | TagName::ImageMapArea
             | TagName::Input
             | TagName::Source => true,
 
             TagName::Anchor
             | TagName::BlockQuote
             | TagName::BoldOrStrong
+            | TagName::Button
             | TagName::Code
             | TagName::Details
             | TagName::Div
             | TagName::EmphasisOrItalic
+            | TagName::Figure
+            | TagName::FigureCaption
             | TagName::Header(_)
             | TagName::Picture
+            | TagName::ImageMap
             | TagName::ListItem
             | TagName::OrderedList
             | TagName::Paragraph
             | TagName::PreformattedText
             | TagName::Section
+            | TagName::Select
+            | TagName::SelectOption
             | TagName::Small
             | TagName::Span
             | TagName::Strikethrough
             | TagName::Summary
+            | TagName::Superscript
             | TagName::Table
             | TagName::TableBody
             | TagName::TableCaption
@@ -90,10 +106,13 @@ impl TryFrom<&Atom<LocalNameStaticSet>> for TagName {
             &local_name!("blockquote") => Self::BlockQuote,
             &local_name!("b") | &local_name!("strong") => Self::BoldOrStrong,
             &local_name!("br") => Self::Break,
+            &local_name!("button") => Self::Button,
             &local_name!("code") | &local_name!("kbd") => Self::Code,
             &local_name!("details") => Self::Details,
             &local_name!("div") => Self::Div,
             &local_name!("em") | &local_name!("i") => Self::EmphasisOrItalic,
+            &local_name!("figure") => Self::Figure,
+            &local_name!("figcaption") => Self::FigureCaption,
             &local_name!("h1") => Self::Header(HeaderType::H1),
             &local_name!("h2") => Self::Header(HeaderType::H2),
             &local_name!("h3") => Self::Header(HeaderType::H3),
@@ -104,16 +123,21 @@ impl TryFrom<&Atom<LocalNameStaticSet>> for TagName {
             &local_name!("picture") => Self::Picture,
             &local_name!("source") => Self::Source,
             &local_name!("img") => Self::Image,
+            &local_name!("map") => Self::ImageMap,
+            &local_name!("area") => Self::ImageMapArea,
             &local_name!("input") => Self::Input,
             &local_name!("li") => Self::ListItem,
             &local_name!("ol") => Self::OrderedList,
             &local_name!("p") => Self::Paragraph,
             &local_name!("pre") => Self::PreformattedText,
             &local_name!("section") => Self::Section,
+            &local_name!("select") => Self::Select,
+            &local_name!("option") => Self::SelectOption,
             &local_name!("small") => Self::Small,
             &local_name!("span") => Self::Span,
             &local_name!("s") | &local_name!("del") => Self::Strikethrough,
             &local_name!("summary") => Self::Summary,
+            &local_name!("sup") => Self::Superscript,
             &local_name!("table") => Self::Table,
             &local_name!("tbody") => Self::TableBody,
             &local_name!("caption") => Self::TableCaption,
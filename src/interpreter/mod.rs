@@ -5,12 +5,19 @@ mod html;
 mod tests;
 
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{mpsc, Arc};
 
+use crate::code_run::CodeRunner;
 use crate::color::Theme;
+use crate::external_render::ExternalRenderers;
+use crate::extras;
 use crate::image::ImageData;
-use crate::opts::ResolvedTheme;
-use crate::utils::markdown_to_html;
+use crate::opts::{Opts, ResolvedTheme};
+use crate::plugins::Plugins;
+use crate::table::TableCards;
+use crate::toc;
+use crate::utils::{markdown_to_html, markdown_to_html_with_sourcepos, Directives};
 use crate::{Element, ImageCache, InlyneEvent};
 use html::style::{FontStyle, FontWeight, TextDecoration};
 
@@ -23,6 +30,67 @@ use wgpu::TextureFormat;
 use winit::event_loop::EventLoopProxy;
 use winit::window::Window;
 
+/// Link on the marker left in place of a long line's cut off text. Clicking it expands them all
+pub const EXPAND_LONG_LINES_LINK: &str = "inlyne:expand-long-lines";
+
+static EXPAND_LONG_LINES: AtomicBool = AtomicBool::new(false);
+
+#[must_use]
+pub fn get_expand_long_lines() -> bool {
+    EXPAND_LONG_LINES.load(Ordering::SeqCst)
+}
+
+pub fn set_expand_long_lines(b: bool) {
+    EXPAND_LONG_LINES.store(b, Ordering::SeqCst);
+}
+
+/// Markdown sent to the interpreter
+#[derive(Debug, Clone, PartialEq)]
+pub enum MdUpdate {
+    /// A new document to replace the displayed one
+    Replace(String),
+    /// Blocks added to the end of the displayed document, which is left as is
+    Append(String),
+}
+
+impl MdUpdate {
+    /// Combines this with a `newer` update that was sent after it
+    fn then(self, newer: Self) -> Self {
+        match (self, newer) {
+            (Self::Replace(mut md), Self::Append(appended)) => {
+                md.push_str(&appended);
+                Self::Replace(md)
+            }
+            (Self::Append(mut md), Self::Append(appended)) => {
+                md.push_str(&appended);
+                Self::Append(md)
+            }
+            (_, newer @ Self::Replace(_)) => newer,
+        }
+    }
+}
+
+impl From<String> for MdUpdate {
+    fn from(md: String) -> Self {
+        Self::Replace(md)
+    }
+}
+
+/// The markdown added to the end of `previous` to get `contents`, as long as it only adds new
+/// blocks instead of continuing the last one
+pub fn appended_blocks<'a>(previous: &str, contents: &'a str) -> Option<&'a str> {
+    let appended = contents.strip_prefix(previous)?;
+    let fences = previous
+        .lines()
+        .map(str::trim_start)
+        .filter(|line| line.starts_with("```") || line.starts_with("~~~"))
+        .count();
+    // An indented start could still belong to the last list item or code block
+    let starts_block = !appended.starts_with([' ', '\t']);
+    let ends_block = previous.ends_with("\n\n") && fences % 2 == 0;
+    (!appended.is_empty() && starts_block && ends_block).then_some(appended)
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 struct Span {
     color: [f32; 4],
@@ -45,6 +113,8 @@ impl Span {
 // Images are loaded in a separate thread and use a callback to indicate when they're finished
 pub trait ImageCallback {
     fn loaded_image(&self, src: String, image_data: Arc<Mutex<Option<ImageData>>>);
+    /// A preview of an image that's still downloading got swapped in
+    fn image_progress(&self) {}
 }
 
 // External state from the interpreter that we want to stub out for testing
@@ -61,6 +131,10 @@ impl ImageCallback for EventLoopCallback {
         let event = InlyneEvent::LoadedImage(src, image_data);
         self.0.send_event(event).unwrap();
     }
+
+    fn image_progress(&self) {
+        self.0.send_event(InlyneEvent::Reposition).unwrap();
+    }
 }
 
 // A real interactive window that is being used with `HtmlInterpreter`
@@ -85,10 +159,66 @@ impl WindowInteractor for LiveWindow {
     }
 }
 
+/// Outstanding work for an interpreter running without a window, being the document itself and
+/// any images that are still loading
+#[derive(Clone)]
+pub struct Pending(Arc<AtomicU32>);
+
+impl Default for Pending {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU32::new(1)))
+    }
+}
+
+impl Pending {
+    pub fn is_done(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == 0
+    }
+
+    fn add_one(&self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn done_one(&self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Stands in for a window when rendering headlessly
+struct HeadlessWindow(Pending);
+
+impl WindowInteractor for HeadlessWindow {
+    fn finished_single_doc(&self) {
+        self.0.done_one();
+    }
+
+    fn request_redraw(&self) {}
+
+    fn image_callback(&self) -> Box<dyn ImageCallback + Send> {
+        self.0.add_one();
+        Box::new(HeadlessCallback(self.0.clone()))
+    }
+}
+
+struct HeadlessCallback(Pending);
+
+impl ImageCallback for HeadlessCallback {
+    fn loaded_image(&self, _: String, _: Arc<Mutex<Option<ImageData>>>) {
+        self.0.done_one();
+    }
+}
+
 pub struct HtmlInterpreter {
     window: Arc<Mutex<dyn WindowInteractor + Send>>,
     theme: Theme,
     ast: Ast,
+    plugins: Arc<Plugins>,
+    code_runner: CodeRunner,
+    smart_punctuation: bool,
+    collapse_extras: bool,
+    table_cards: TableCards,
+    toc_depth: u8,
+    source_positions: bool,
 }
 
 impl HtmlInterpreter {
@@ -132,6 +262,33 @@ impl HtmlInterpreter {
         )
     }
 
+    /// An interpreter that isn't tied to a window, with `pending` tracking when it's done
+    pub fn headless(
+        element_queue: Arc<Mutex<Vec<Element>>>,
+        theme: Theme,
+        surface_format: TextureFormat,
+        hidpi_scale: f32,
+        image_cache: ImageCache,
+        color_scheme: Option<ResolvedTheme>,
+        pending: Pending,
+    ) -> Self {
+        Self::new_with_interactor(
+            element_queue,
+            theme,
+            surface_format,
+            hidpi_scale,
+            image_cache,
+            Arc::new(Mutex::new(HeadlessWindow(pending))),
+            color_scheme,
+            true,
+            false,
+            false,
+            true,
+            true,
+            true,
+        )
+    }
+
     // TODO: fix in a later refactor (consolidate a lot of junk)
     #[allow(clippy::too_many_arguments)]
     fn new_with_interactor(
@@ -162,6 +319,9 @@ impl HtmlInterpreter {
                 add_spacers_after_tables,
                 add_spacers_after_paragraphs,
                 add_spacers_after_lists,
+                table_cards: Default::default(),
+                code_line_numbers: false,
+                external_renderers: Default::default(),
                 image_cache,
                 window: Arc::clone(&window),
                 color_scheme,
@@ -169,22 +329,129 @@ impl HtmlInterpreter {
             element_queue,
         );
 
-        Self { theme, window, ast }
+        Self {
+            theme,
+            window,
+            ast,
+            plugins: Default::default(),
+            code_runner: Default::default(),
+            smart_punctuation: true,
+            collapse_extras: false,
+            table_cards: TableCards::default(),
+            toc_depth: toc::DEFAULT_DEPTH,
+            source_positions: false,
+        }
+    }
+
+    /// Runs each document through the plugins before interpreting it
+    pub fn with_plugins(mut self, plugins: Arc<Plugins>) -> Self {
+        self.plugins = plugins;
+        self
+    }
+
+    /// Renders fenced blocks with external programs, after any plugins ran
+    pub fn with_external_renderers(mut self, renderers: ExternalRenderers) -> Self {
+        self.ast.opts.external_renderers = renderers;
+        self
+    }
+
+    /// Adds run links to runnable fenced blocks, after any plugins ran
+    pub fn with_code_runner(mut self, runner: CodeRunner) -> Self {
+        self.code_runner = runner;
+        self
+    }
+
+    pub fn with_smart_punctuation(mut self, smart_punctuation: bool) -> Self {
+        self.smart_punctuation = smart_punctuation;
+        self
+    }
+
+    /// Leaves footnotes out until the extras get revealed
+    pub fn with_collapse_extras(mut self, collapse_extras: bool) -> Self {
+        self.collapse_extras = collapse_extras;
+        self
+    }
+
+    /// The default for documents that don't set `table-cards` in their front matter
+    pub fn with_table_cards(mut self, table_cards: TableCards) -> Self {
+        self.table_cards = table_cards;
+        self
+    }
+
+    /// Numbers the lines of code blocks that don't say otherwise in their info string
+    pub fn with_code_line_numbers(mut self, code_line_numbers: bool) -> Self {
+        self.ast.opts.code_line_numbers = code_line_numbers;
+        self
+    }
+
+    /// How many heading levels get listed where a table of contents is asked for
+    pub fn with_toc_depth(mut self, toc_depth: u8) -> Self {
+        self.toc_depth = toc_depth;
+        self
+    }
+
+    /// Keeps track of which lines of the markdown each text box came from. Going to a line,
+    /// syncing with the source pane, editing, and the inspector all go by these
+    pub fn with_source_positions(mut self, source_positions: bool) -> Self {
+        self.source_positions = source_positions;
+        self
+    }
+
+    /// Applies every document setting from `opts`, so that each place that interprets a document
+    /// renders it the same way
+    pub fn with_opts(self, opts: &Opts) -> Self {
+        self.with_smart_punctuation(opts.smart_punctuation)
+            .with_collapse_extras(opts.collapse_extras)
+            .with_table_cards(opts.table_cards)
+            .with_toc_depth(opts.toc_depth)
+            .with_code_line_numbers(opts.code_line_numbers)
+            .with_source_positions(true)
+            .with_external_renderers(ExternalRenderers::new(opts.external_renderers.clone()))
     }
 
-    pub fn interpret_md(self, receiver: mpsc::Receiver<String>) {
+    pub fn interpret_md(self, receiver: mpsc::Receiver<MdUpdate>) {
         let mut input = BufferQueue::default();
 
-        let code_highlighter = self.theme.code_highlighter.clone();
+        let code_highlighter = self.theme.palette.code_highlighter.clone();
         let mut tok = Tokenizer::new(Hir::new(), TokenizerOpts::default());
 
-        for md_string in receiver {
+        // Contents that arrived while interpreting the previous document
+        let mut newer: Option<MdUpdate> = None;
+        loop {
+            let update = match newer.take() {
+                Some(update) => update,
+                None => match receiver.recv() {
+                    Ok(update) => update,
+                    Err(_) => break,
+                },
+            };
+            // Skip straight to the newest contents if several reloads queued up
+            let update = receiver.try_iter().fold(update, MdUpdate::then);
+            // Appended blocks keep the settings of the document they're added to
+            if let MdUpdate::Replace(md_string) = &update {
+                let directives = Directives::from_md(md_string);
+                *self.ast.opts.table_cards.lock() =
+                    directives.table_cards.unwrap_or(self.table_cards);
+                self.ast.opts.external_renderers.forget_blocks();
+            }
+            let (MdUpdate::Replace(md_string) | MdUpdate::Append(md_string)) = update;
             tracing::debug!(
                 "Received markdown for interpretation: {} bytes",
                 md_string.len()
             );
 
-            let htmlified = markdown_to_html(&md_string, code_highlighter.clone());
+            let md_string = self.plugins.preprocess(md_string);
+            let md_string = toc::insert(md_string, self.toc_depth, self.smart_punctuation);
+            let md_string =
+                extras::process(md_string, self.collapse_extras, extras::get_revealed());
+            let md_string = self.code_runner.add_run_links(md_string);
+            let md_string = self.ast.opts.external_renderers.replace_blocks(md_string);
+            let to_html = if self.source_positions {
+                markdown_to_html_with_sourcepos
+            } else {
+                markdown_to_html
+            };
+            let htmlified = to_html(&md_string, code_highlighter.clone(), self.smart_punctuation);
 
             input.push_back(
                 Tendril::from_str(&htmlified)
@@ -197,8 +464,21 @@ impl HtmlInterpreter {
             assert!(input.is_empty());
             tok.end();
 
-            self.ast.interpret(std::mem::take(&mut tok.sink));
-            self.window.lock().finished_single_doc();
+            let finished = self.ast.interpret(std::mem::take(&mut tok.sink), || {
+                for update in receiver.try_iter() {
+                    newer = Some(match newer.take() {
+                        Some(pending) => pending.then(update),
+                        None => update,
+                    });
+                }
+                // Appended blocks wait until the current document is done
+                matches!(newer, Some(MdUpdate::Replace(_)))
+            });
+            if finished {
+                self.window.lock().finished_single_doc();
+            } else {
+                tracing::debug!("Canceled interpretation in favor of newer contents");
+            }
         }
     }
 }
@@ -5,11 +5,15 @@ use std::sync::{
 use std::time::{Duration, Instant};
 use std::{env, thread};
 
-use super::{HtmlInterpreter, ImageCallback, WindowInteractor};
+use super::{
+    appended_blocks, HtmlInterpreter, ImageCallback, MdUpdate, WindowInteractor,
+    EXPAND_LONG_LINES_LINK,
+};
 use crate::color::{Theme, ThemeDefaults};
-use crate::image::{Image, ImageData};
+use crate::image::{Image, ImageData, MapArea, Shape};
 use crate::opts::ResolvedTheme;
 use crate::positioner::Spacer;
+use crate::table::{CellSpan, TableCards};
 use crate::test_utils::image::{Sample, SamplePng};
 use crate::test_utils::{log, server};
 use crate::text::{Text, TextBox};
@@ -77,6 +81,7 @@ struct InterpreterOpts {
     theme: Theme,
     fail_after: Duration,
     color_scheme: Option<ResolvedTheme>,
+    source_positions: bool,
 }
 
 impl Default for InterpreterOpts {
@@ -85,6 +90,7 @@ impl Default for InterpreterOpts {
             theme: Theme::light_default(),
             fail_after: Duration::from_secs(8),
             color_scheme: None,
+            source_positions: false,
         }
     }
 }
@@ -109,11 +115,17 @@ impl InterpreterOpts {
         self.color_scheme = Some(color_scheme);
     }
 
+    fn source_positions(mut self) -> Self {
+        self.source_positions = true;
+        self
+    }
+
     fn finish(self, counter: AtomicCounter) -> (HtmlInterpreter, Arc<Mutex<Vec<Element>>>) {
         let Self {
             theme,
             fail_after: _,
             color_scheme,
+            source_positions,
         } = self;
         let element_queue = Arc::default();
         let surface_format = TextureFormat::Bgra8UnormSrgb;
@@ -134,7 +146,8 @@ impl InterpreterOpts {
             true,  // Add spacers after tables for tests
             true,  // Add spacers after paragraphs for tests
             true,  // Add spacers after lists for tests
-        );
+        )
+        .with_source_positions(source_positions);
 
         (interpreter, element_queue)
     }
@@ -158,7 +171,7 @@ impl From<ThemeOpts> for Theme {
         let ThemeOpts { code_highlighter } = opts;
         let mut theme = Theme::light_default();
         if let Some(code_highlighter) = code_highlighter {
-            theme.code_highlighter = code_highlighter;
+            theme.palette.code_highlighter = code_highlighter;
         }
         theme
     }
@@ -180,7 +193,7 @@ fn interpret_md_with_opts(text: &str, opts: InterpreterOpts) -> Vec<Element> {
     let counter = AtomicCounter::new();
     let (interpreter, element_queue) = opts.finish(counter.clone());
     let (md_tx, md_rx) = mpsc::channel();
-    md_tx.send(text.to_owned()).unwrap();
+    md_tx.send(text.to_owned().into()).unwrap();
     let interpreter_handle = std::thread::spawn(|| {
         interpreter.interpret_md(md_rx);
     });
@@ -220,7 +233,8 @@ macro_rules! snapshot_interpreted_elements {
 
                 let htmlified = $crate::utils::markdown_to_html(
                     text,
-                    opts.theme.code_highlighter.clone(),
+                    opts.theme.palette.code_highlighter.clone(),
+                    true,
                 );
                 let description = format!(" --- md\n\n{text}\n\n --- html\n\n{htmlified}");
 
@@ -234,7 +248,6 @@ macro_rules! snapshot_interpreted_elements {
     }
 }
 
-#[allow(unused)]
 const FOOTNOTES_LIST_PREFIX: &str = "\
 This sentence[^1] has two footnotes[^2]
 
@@ -375,7 +388,7 @@ This paragraph follows the table.
 "#;
 
 snapshot_interpreted_elements!(
-    // (footnotes_list_prefix, FOOTNOTES_LIST_PREFIX),
+    (footnotes_list_prefix, FOOTNOTES_LIST_PREFIX),
     (checklist_has_no_text_prefix, CHECKLIST_HAS_NO_TEXT_PREFIX),
     (para_in_ordered_list, PARA_IN_ORDERED_LIST),
     (code_in_ordered_list, CODE_IN_ORDERED_LIST),
@@ -934,6 +947,33 @@ fn centered_image_with_size_align_and_link() {
     "#);
 }
 
+#[test]
+fn image_map_areas() {
+    log::init();
+
+    // The map can come after the image, and areas without a link or a valid shape get dropped
+    let text = r##"
+<img src="missing.png" usemap="#shapes">
+<map name="shapes">
+  <area shape="circle" coords="50,50,25" href="circle.md">
+  <area shape="rect" coords="0,0,10,10">
+  <area shape="star" coords="1,2,3" href="star.md">
+</map>"##;
+
+    let elems = interpret_md(text);
+    let image = find_image(&elems).unwrap();
+    assert_eq!(
+        image.map,
+        [MapArea {
+            shape: Shape::Circle {
+                center: (50., 50.),
+                radius: 25.,
+            },
+            href: "circle.md".to_owned(),
+        }]
+    );
+}
+
 // TODO: change this to test against the image cache so that we can inspect the error?
 #[test]
 fn image_loading_fails_gracefully() {
@@ -1215,3 +1255,277 @@ fn spaced_out_checkboxes_are_checkboxes() {
         .collect();
     assert_eq!(checkbox_markers, [Some(false), Some(true)]);
 }
+
+const FORM_ELEMENTS: &str = "\
+<p>
+<input type=\"radio\" checked> On <input type=\"radio\"> Off
+<input type=\"text\" placeholder=\"Name\">
+<select><option>First</option><option selected>Second</option></select>
+<button>Send</button>
+<input type=\"hidden\" value=\"secret\">
+</p>
+";
+
+#[test]
+fn form_elements_render_as_static_widgets() {
+    log::init();
+
+    let text: String = interpret_md(FORM_ELEMENTS)
+        .iter()
+        .filter_map(elem_as_text_box)
+        .flat_map(|text_box| text_box.texts.iter())
+        .map(|text| text.text.as_str())
+        .collect();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    insta::assert_snapshot!(text, @"◉ On ○ Off [Name] [Second ▾] [Send]");
+}
+
+const FOOTNOTE_IN_TABLE: &str = "\
+| Claim | Source |
+| ----- | ------ |
+| Water is wet | Citation[^1] |
+
+[^1]: Trust me
+";
+
+#[test]
+fn footnote_reference_in_table_cell() {
+    log::init();
+
+    let elems = interpret_md(FOOTNOTE_IN_TABLE);
+    let table = elems
+        .iter()
+        .find_map(|elem| match elem {
+            Element::Table(table) => Some(table),
+            _ => None,
+        })
+        .expect("Table should be rendered");
+    let cell = &table.rows[1][1];
+    assert_eq!(cell.is_anchor.as_deref(), Some("#fnref-1"));
+    let reference = cell
+        .texts
+        .iter()
+        .find(|text| text.text == "1")
+        .expect("Footnote reference should be in the cell");
+    assert_eq!(reference.link.as_deref(), Some("#fn-1"));
+}
+
+#[test]
+fn image_title_becomes_figure_caption() {
+    log::init();
+
+    let elems = interpret_md("![alt](assets/test_data/rgb8.png \"A caption\")");
+    let image_pos = elems
+        .iter()
+        .position(|elem| matches!(elem, Element::Image(_)))
+        .expect("Image should be rendered");
+    let caption = elems[image_pos..]
+        .iter()
+        .find_map(elem_as_text_box)
+        .expect("Caption should follow the image");
+    let text: String = caption.texts.iter().map(|text| text.text.as_str()).collect();
+    assert_eq!(text.trim(), "A caption");
+    assert_eq!(caption.align, Align::Center);
+    assert_eq!(caption.is_anchor.as_deref(), Some("#figure-a-caption"));
+}
+
+#[test]
+fn long_lines_are_truncated() {
+    log::init();
+
+    let md = format!("```\n{}\nshort\n```", "a".repeat(5_000));
+    let elems = interpret_md(&md);
+    let texts: Vec<_> = elems
+        .iter()
+        .filter_map(elem_as_text_box)
+        .flat_map(|text_box| text_box.texts.iter())
+        .collect();
+    let marker = texts
+        .iter()
+        .find(|text| text.link.as_deref() == Some(EXPAND_LONG_LINES_LINK))
+        .expect("Long line should have an expand marker");
+    assert_eq!(marker.text, " … [3000 more characters]");
+    let kept: String = texts
+        .iter()
+        .filter(|text| text.link.is_none())
+        .map(|text| text.text.as_str())
+        .collect();
+    assert_eq!(kept.matches('a').count(), 2_000);
+    assert!(kept.contains("short"));
+}
+
+#[test]
+fn long_highlighted_lines_are_truncated() {
+    log::init();
+
+    // Highlighting splits the line into a run per token, none of which are long on their own
+    let md = format!("```rust\n{}\nshort\n```", "let a = 1; ".repeat(500));
+    let elems = interpret_md(&md);
+    let texts: Vec<_> = elems
+        .iter()
+        .filter_map(elem_as_text_box)
+        .flat_map(|text_box| text_box.texts.iter())
+        .collect();
+    let markers: Vec<_> = texts
+        .iter()
+        .filter(|text| text.link.as_deref() == Some(EXPAND_LONG_LINES_LINK))
+        .collect();
+    assert_eq!(markers.len(), 1);
+    assert_eq!(markers[0].text, " … [3500 more characters]");
+    let kept: String = texts
+        .iter()
+        .filter(|text| text.link.is_none())
+        .map(|text| text.text.as_str())
+        .collect();
+    let (long_line, rest) = kept.split_once('\n').unwrap();
+    assert_eq!(long_line.chars().count(), 2_000);
+    assert!(rest.contains("short"));
+}
+
+#[test]
+fn table_cards_directive() {
+    log::init();
+
+    let table = "| a | b |\n|---|---|\n| 1 | 2 |\n";
+    let cards = |md: &str| {
+        interpret_md(md).into_iter().find_map(|elem| match elem {
+            Element::Table(table) => Some(table.cards),
+            _ => None,
+        })
+    };
+    assert_eq!(cards(table), Some(TableCards::Auto));
+    let md = format!("---\ntable-cards: always\n---\n{table}");
+    assert_eq!(cards(&md), Some(TableCards::Always));
+}
+
+#[test]
+fn html_table_spans() {
+    log::init();
+
+    let md = "\
+<table>
+<tr><th colspan=\"2\">Wide</th><th rowspan=\"2\">Tall</th></tr>
+<tr><td>a</td><td>b</td></tr>
+</table>
+";
+    let table = interpret_md(md)
+        .into_iter()
+        .find_map(|elem| match elem {
+            Element::Table(table) => Some(table),
+            _ => None,
+        })
+        .expect("Table should be rendered");
+    assert_eq!(table.span(0, 0), CellSpan { cols: 2, rows: 1 });
+    assert_eq!(table.span(0, 1), CellSpan { cols: 1, rows: 2 });
+    assert_eq!(table.span(1, 0), CellSpan::default());
+    assert_eq!(table.column_aligns.len(), 3);
+}
+
+#[test]
+fn line_numbers_from_info_string() {
+    log::init();
+
+    let line_numbers = |md: &str| {
+        interpret_md(md)
+            .iter()
+            .find_map(elem_as_text_box)
+            .map(|text_box| text_box.line_numbers)
+    };
+    assert_eq!(line_numbers("```rust\nfn main() {}\n```"), Some(false));
+    assert_eq!(
+        line_numbers("```rust line-numbers\nfn main() {}\n```"),
+        Some(true)
+    );
+    assert_eq!(
+        line_numbers("```rust no-line-numbers\nfn main() {}\n```"),
+        Some(false)
+    );
+}
+
+#[test]
+fn code_block_language() {
+    log::init();
+
+    let language = |md: &str| {
+        interpret_md(md)
+            .iter()
+            .find_map(elem_as_text_box)
+            .and_then(|text_box| text_box.language.clone())
+    };
+    assert_eq!(language("```python\npass\n```").as_deref(), Some("python"));
+    assert_eq!(
+        language("```rust,ignore\nmain()\n```").as_deref(),
+        Some("rust")
+    );
+    assert_eq!(language("```\nplain\n```"), None);
+    assert_eq!(language("Some `inline` code"), None);
+}
+
+#[test]
+fn math() {
+    log::init();
+
+    let elems = interpret_md("Inline $x^2 + \\alpha$ math\n\n$$\\frac{a}{b}$$");
+    let inline: String = elems
+        .iter()
+        .find_map(elem_as_text_box)
+        .expect("Inline math should stay in the paragraph")
+        .texts
+        .iter()
+        .map(|text| text.text.as_str())
+        .collect();
+    assert_eq!(inline, "Inline x² + α math");
+    let display = elems
+        .iter()
+        .find_map(|elem| match elem {
+            Element::Math(math) => Some(math),
+            _ => None,
+        })
+        .expect("Display math should get its own element");
+    assert_eq!(display.source, "\\frac{a}{b}");
+}
+
+#[test]
+fn appended_blocks_start_new_blocks() {
+    let log = "# Build log\n\nStarted\n\n";
+    let grown = format!("{log}Finished\n");
+    assert_eq!(appended_blocks(log, &grown), Some("Finished\n"));
+
+    // Anything that could continue the last block needs the whole document
+    assert_eq!(appended_blocks("Started\n", "Started\nstill going\n"), None);
+    assert_eq!(appended_blocks("- a\n\n", "- a\n\n  more a\n"), None);
+    assert_eq!(appended_blocks("```\nout\n\n", "```\nout\n\nmore\n"), None);
+    assert_eq!(appended_blocks(log, "# Something else\n"), None);
+    assert_eq!(appended_blocks(log, log), None);
+}
+
+#[test]
+fn queued_updates_combine() {
+    let replace = |md: &str| MdUpdate::Replace(md.to_owned());
+    let append = |md: &str| MdUpdate::Append(md.to_owned());
+
+    assert_eq!(replace("a\n\n").then(append("b\n")), replace("a\n\nb\n"));
+    assert_eq!(append("b\n\n").then(append("c\n")), append("b\n\nc\n"));
+    assert_eq!(append("b\n").then(replace("x\n")), replace("x\n"));
+}
+
+#[test]
+fn source_lines_of_blocks() {
+    log::init();
+
+    let md = "# Title\n\nSome\ntext\n\n- item\n";
+    let elements = interpret_md_with_opts(md, InterpreterOpts::new().source_positions());
+    let source_lines: Vec<_> = elements
+        .iter()
+        .filter_map(elem_as_text_box)
+        .map(|text_box| text_box.source_lines)
+        .collect();
+    assert_eq!(source_lines, [Some((1, 1)), Some((3, 4)), Some((6, 6))]);
+
+    // Only kept track of when asked for
+    let elements = interpret_md(md);
+    assert!(elements
+        .iter()
+        .filter_map(elem_as_text_box)
+        .all(|text_box| text_box.source_lines.is_none()));
+}
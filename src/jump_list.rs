@@ -0,0 +1,72 @@
+//! Where the view was before jumping around the document, so that following an in-document link
+//! doesn't lose your place
+//!
+//! Positions are kept as a fraction of the document's height so that they still line up after a
+//! zoom or resize
+
+/// The most positions to keep before forgetting the oldest ones
+const LIMIT: usize = 100;
+
+#[derive(Debug, Default)]
+pub struct JumpList {
+    back: Vec<f32>,
+    forward: Vec<f32>,
+}
+
+impl JumpList {
+    /// Records `progress` as the place that a jump is being made from. Anything that could be
+    /// jumped forward to gets dropped, like with a browser's history
+    pub fn push(&mut self, progress: f32) {
+        self.forward.clear();
+        self.back.push(progress);
+        let excess = self.back.len().saturating_sub(LIMIT);
+        self.back.drain(..excess);
+    }
+
+    /// Returns where to go back to, remembering `progress` to come forward to again
+    pub fn back(&mut self, progress: f32) -> Option<f32> {
+        let target = self.back.pop()?;
+        self.forward.push(progress);
+        Some(target)
+    }
+
+    /// Returns where to go forward to, remembering `progress` to come back to again
+    pub fn forward(&mut self, progress: f32) -> Option<f32> {
+        let target = self.forward.pop()?;
+        self.back.push(progress);
+        Some(target)
+    }
+
+    /// Forgets every position, for when a different file gets opened
+    pub fn clear(&mut self) {
+        self.back.clear();
+        self.forward.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn back_and_forward() {
+        let mut jumps = JumpList::default();
+        assert_eq!(jumps.back(0.), None);
+
+        jumps.push(0.1);
+        jumps.push(0.5);
+        assert_eq!(jumps.back(0.9), Some(0.5));
+        assert_eq!(jumps.back(0.5), Some(0.1));
+        assert_eq!(jumps.back(0.1), None);
+        assert_eq!(jumps.forward(0.1), Some(0.5));
+
+        // A new jump drops the way forward
+        jumps.push(0.5);
+        assert_eq!(jumps.forward(0.7), None);
+        assert_eq!(jumps.back(0.7), Some(0.5));
+        assert_eq!(jumps.forward(0.5), Some(0.7));
+
+        jumps.clear();
+        assert_eq!(jumps.back(0.7), None);
+    }
+}
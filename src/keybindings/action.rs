@@ -1,11 +1,50 @@
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Action {
     History(HistDirection),
+    HistoryList,
+    /// Jumps to the n-th (1-indexed) entry of the history list
+    HistoryJump(usize),
+    /// Goes back or forward through the places that in-document links were followed from
+    Jump(HistDirection),
     ToEdge(VertDirection),
     Scroll(VertDirection),
+    /// Scrolls by one line of the text at the top of the view, keeping its rows lined up
+    ScrollVisualLine(VertDirection),
     Page(VertDirection),
+    HalfPage(VertDirection),
+    /// Scrolls to the next or previous heading relative to the top of the view
+    JumpHeading(VertDirection),
+    /// Folds or unfolds the content under the heading below the cursor
+    ToggleFold,
+    /// Folds every heading
+    FoldAll,
+    /// Unfolds every folded heading
+    UnfoldAll,
     Zoom(Zoom),
     Copy,
+    /// Copies the current file's full path
+    CopyFilePath,
+    /// Shows the current file in the system's file manager
+    RevealInFileManager,
+    Search,
+    CopyMatches,
+    /// Jumps to the next or previous search match, scrolling it into view
+    CycleMatch(VertDirection),
+    /// Switches between smart case and always ignoring case when searching
+    ToggleSmartCase,
+    /// Switches between matching whole words only and matching anywhere when searching
+    ToggleWholeWord,
+    GoToHeading,
+    SplitView,
+    EditMode,
+    /// Shows footnotes, link definitions and comments along with their raw source
+    Extras,
+    /// Resizes the window to the document's natural width
+    FitWindowToContent,
+    /// Switches the layout profile between the normal one and distraction-free reading
+    ZenMode,
+    /// Logs every laid out element with its bounds, spacing and source lines
+    DumpLayout,
     Help,
     Quit,
 }
@@ -27,4 +66,6 @@ pub enum Zoom {
     In,
     Out,
     Reset,
+    /// Makes the widest table, code block or image in view exactly fit the window
+    FitWidth,
 }
@@ -20,6 +20,106 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
             Action::Copy,
             KeyCombo(vec![ModifiedKey(Key::from(VirtKey::C), ctrl_or_command)]),
         ),
+        // Search: Ctrl+F / Command+F
+        (
+            Action::Search,
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::F), ctrl_or_command)]),
+        ),
+        // Copy search matches: Ctrl+Shift+C / Command+Shift+C
+        (
+            Action::CopyMatches,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::C),
+                ctrl_or_command | ModifiersState::SHIFT,
+            )]),
+        ),
+        // Next search match: F3
+        (
+            Action::CycleMatch(VertDirection::Down),
+            KeyCombo::from(VirtKey::F3),
+        ),
+        // Previous search match: Shift+F3
+        (
+            Action::CycleMatch(VertDirection::Up),
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::F3),
+                ModifiersState::SHIFT,
+            )]),
+        ),
+        // Toggle smart case search: Alt+C
+        (
+            Action::ToggleSmartCase,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::C),
+                ModifiersState::ALT,
+            )]),
+        ),
+        // Toggle whole word search: Alt+W
+        (
+            Action::ToggleWholeWord,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::W),
+                ModifiersState::ALT,
+            )]),
+        ),
+        // Copy the file's path: Alt+Shift+C
+        (
+            Action::CopyFilePath,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::C),
+                ModifiersState::ALT | ModifiersState::SHIFT,
+            )]),
+        ),
+        // Reveal the file in the file manager: Alt+Shift+R
+        (
+            Action::RevealInFileManager,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::R),
+                ModifiersState::ALT | ModifiersState::SHIFT,
+            )]),
+        ),
+        // Go to heading: Ctrl+P / Command+P
+        (
+            Action::GoToHeading,
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::P), ctrl_or_command)]),
+        ),
+        // Toggle split view: Ctrl+\ / Command+\
+        (
+            Action::SplitView,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::Backslash),
+                ctrl_or_command,
+            )]),
+        ),
+        // Toggle edit mode: Ctrl+E / Command+E
+        (
+            Action::EditMode,
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::E), ctrl_or_command)]),
+        ),
+        // Toggle hidden extras: Ctrl+Shift+X / Command+Shift+X
+        (
+            Action::Extras,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::X),
+                ctrl_or_command | ModifiersState::SHIFT,
+            )]),
+        ),
+        // Fit window to content: Ctrl+Shift+F / Command+Shift+F
+        (
+            Action::FitWindowToContent,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::F),
+                ctrl_or_command | ModifiersState::SHIFT,
+            )]),
+        ),
+        // Toggle zen mode: Ctrl+Shift+Z / Command+Shift+Z
+        (
+            Action::ZenMode,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::Z),
+                ctrl_or_command | ModifiersState::SHIFT,
+            )]),
+        ),
         // Zoom in: Ctrl+= / Command+=
         (
             Action::Zoom(Zoom::In),
@@ -36,6 +136,11 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
                 ctrl_or_command,
             )]),
         ),
+        // Zoom to fit the widest element in view: Ctrl+9 / Command+9
+        (
+            Action::Zoom(Zoom::FitWidth),
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::Key9), ctrl_or_command)]),
+        ),
         // Navigate to next file: Alt+Right
         (
             Action::History(HistDirection::Next),
@@ -52,6 +157,14 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
                 ModifiersState::ALT,
             )]),
         ),
+        // Show the history list: Alt+Down
+        (
+            Action::HistoryList,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::Down),
+                ModifiersState::ALT,
+            )]),
+        ),
         // Scroll up: Up-arrow
         (
             Action::Scroll(VertDirection::Up),
@@ -62,6 +175,22 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
             Action::Scroll(VertDirection::Down),
             KeyCombo::from(VirtKey::Down),
         ),
+        // Scroll up a line: Shift+Up-arrow
+        (
+            Action::ScrollVisualLine(VertDirection::Up),
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::Up),
+                ModifiersState::SHIFT,
+            )]),
+        ),
+        // Scroll down a line: Shift+Down-arrow
+        (
+            Action::ScrollVisualLine(VertDirection::Down),
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::Down),
+                ModifiersState::SHIFT,
+            )]),
+        ),
         // Page up: PageUp
         (
             Action::Page(VertDirection::Up),
@@ -97,6 +226,31 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
         (Action::Help, KeyCombo::from(VirtKey::H)),
         // Copy: y
         (Action::Copy, KeyCombo::from(VirtKey::Y)),
+        // Toggle edit mode: i
+        (Action::EditMode, KeyCombo::from(VirtKey::I)),
+        // Search: /
+        (Action::Search, KeyCombo::from(VirtKey::Slash)),
+        // Copy search matches: Y
+        (
+            Action::CopyMatches,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::Y),
+                ModifiersState::SHIFT,
+            )]),
+        ),
+        // Next search match: n
+        (
+            Action::CycleMatch(VertDirection::Down),
+            KeyCombo::from(VirtKey::N),
+        ),
+        // Previous search match: N
+        (
+            Action::CycleMatch(VertDirection::Up),
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::N),
+                ModifiersState::SHIFT,
+            )]),
+        ),
         // Scroll up: k
         (
             Action::Scroll(VertDirection::Up),
@@ -107,6 +261,78 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
             Action::Scroll(VertDirection::Down),
             KeyCombo::from(VirtKey::J),
         ),
+        // Half page up: Ctrl+u
+        (
+            Action::HalfPage(VertDirection::Up),
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::U),
+                ModifiersState::CTRL,
+            )]),
+        ),
+        // Half page down: Ctrl+d
+        (
+            Action::HalfPage(VertDirection::Down),
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::D),
+                ModifiersState::CTRL,
+            )]),
+        ),
+        // Next heading: ]]
+        (
+            Action::JumpHeading(VertDirection::Down),
+            KeyCombo(vec![
+                ModifiedKey::from(VirtKey::RBracket),
+                ModifiedKey::from(VirtKey::RBracket),
+            ]),
+        ),
+        // Previous heading: [[
+        (
+            Action::JumpHeading(VertDirection::Up),
+            KeyCombo(vec![
+                ModifiedKey::from(VirtKey::LBracket),
+                ModifiedKey::from(VirtKey::LBracket),
+            ]),
+        ),
+        // Fold the heading under the cursor: za
+        (
+            Action::ToggleFold,
+            KeyCombo(vec![
+                ModifiedKey::from(VirtKey::Z),
+                ModifiedKey::from(VirtKey::A),
+            ]),
+        ),
+        // Fold all headings: zM
+        (
+            Action::FoldAll,
+            KeyCombo(vec![
+                ModifiedKey::from(VirtKey::Z),
+                ModifiedKey(Key::from(VirtKey::M), ModifiersState::SHIFT),
+            ]),
+        ),
+        // Unfold all headings: zR
+        (
+            Action::UnfoldAll,
+            KeyCombo(vec![
+                ModifiedKey::from(VirtKey::Z),
+                ModifiedKey(Key::from(VirtKey::R), ModifiersState::SHIFT),
+            ]),
+        ),
+        // Jump back to where a link was followed from: Ctrl+o
+        (
+            Action::Jump(HistDirection::Prev),
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::O),
+                ModifiersState::CTRL,
+            )]),
+        ),
+        // Jump forward again: Ctrl+i
+        (
+            Action::Jump(HistDirection::Next),
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::I),
+                ModifiersState::CTRL,
+            )]),
+        ),
         // Go to top of doc: gg
         (
             Action::ToEdge(VertDirection::Up),
@@ -155,5 +381,13 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
                 ModifiedKey::from(VirtKey::P),
             ]),
         ),
+        // Show the history list: bl
+        (
+            Action::HistoryList,
+            KeyCombo(vec![
+                ModifiedKey::from(VirtKey::B),
+                ModifiedKey::from(VirtKey::L),
+            ]),
+        ),
     ]
 }
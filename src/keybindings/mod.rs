@@ -180,6 +180,20 @@ impl From<VirtKey> for KeyCombo {
 type Node = BTreeMap<ModifiedKey, Connection>;
 type Ptr = usize;
 const ROOT_INDEX: Ptr = 0;
+/// The largest count prefix that gets taken, so that mashing digits can't overflow it
+const MAX_COUNT: usize = 9_999;
+const DIGITS: [VirtKey; 10] = [
+    VirtKey::Key0,
+    VirtKey::Key1,
+    VirtKey::Key2,
+    VirtKey::Key3,
+    VirtKey::Key4,
+    VirtKey::Key5,
+    VirtKey::Key6,
+    VirtKey::Key7,
+    VirtKey::Key8,
+    VirtKey::Key9,
+];
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum Connection {
@@ -192,11 +206,15 @@ enum Connection {
 /// Internally this is implemented as a trie (a tree where prefixes are shared) where the
 /// "pointers" are all just indices into `storage`. Each entry in `storage` represents a node with
 /// its connections to other nodes stored in a map
+///
+/// Like in vim, a combo can be prefixed with a count e.g. `12j` to repeat it
 #[derive(Debug, Default)]
 pub struct KeyCombos {
     position: Ptr,
     storage: Vec<Node>,
     in_multikey_combo: bool,
+    /// The count typed out so far, if any
+    count: Option<usize>,
 }
 
 impl KeyCombos {
@@ -235,6 +253,7 @@ impl KeyCombos {
             position,
             storage,
             in_multikey_combo: false,
+            count: None,
         })
     }
 
@@ -275,8 +294,9 @@ impl KeyCombos {
         }
     }
 
-    /// Processes a modified key and emits the corresponding action if this completes a keycombo
-    pub fn munch(&mut self, modified_key: ModifiedKey) -> Option<Action> {
+    /// Processes a modified key and emits the corresponding action along with how many times to
+    /// repeat it if this completes a keycombo
+    pub fn munch(&mut self, modified_key: ModifiedKey) -> Option<(Action, usize)> {
         // We ignore modifier keys since they aren't considered part of combos
         if let Key::Resolved(key) = &modified_key.0 {
             if [
@@ -297,13 +317,46 @@ impl KeyCombos {
 
         tracing::debug!("Received key: {modified_key}");
 
+        if let Some(digit) = self.count_digit(modified_key) {
+            let count = self.count.unwrap_or(0).saturating_mul(10) + digit;
+            self.count = Some(count.min(MAX_COUNT));
+            return None;
+        }
+
         let maybe_action = self.munch_(modified_key);
+        // The count carries through a multi-key combo, but anything else uses it up
+        let count = if self.in_multikey_combo {
+            self.count
+        } else {
+            self.count.take()
+        };
 
-        if let Some(action) = maybe_action {
-            tracing::debug!("Emitting action: {:?}", action);
-        }
+        maybe_action.map(|action| {
+            let count = count.unwrap_or(1);
+            tracing::debug!("Emitting action: {action:?} x{count}");
+            (action, count)
+        })
+    }
 
-        maybe_action
+    /// The digit that a key adds onto the count. Digits that are bound to something themselves
+    /// keep doing that instead
+    fn count_digit(&self, modified_key: ModifiedKey) -> Option<usize> {
+        let ModifiedKey(Key::Resolved(key), modifiers) = modified_key else {
+            return None;
+        };
+        if !modifiers.is_empty() || self.in_multikey_combo {
+            return None;
+        }
+        let digit = DIGITS.iter().position(|&digit| digit == key)?;
+        // Like in vim a count can't start with a zero
+        if digit == 0 && self.count.is_none() {
+            return None;
+        }
+        let is_bound = self
+            .storage
+            .get(ROOT_INDEX)
+            .is_some_and(|root| root.contains_key(&modified_key));
+        (!is_bound).then_some(digit)
     }
 
     fn munch_(&mut self, modified_key: ModifiedKey) -> Option<Action> {
@@ -334,7 +387,7 @@ impl KeyCombos {
     }
 
     fn reset(&mut self) {
-        // Wipe everything, but the nodes
+        // Wipe everything, but the nodes and the count
         self.position = ROOT_INDEX;
         self.in_multikey_combo = false;
     }
@@ -17,16 +17,45 @@ impl<'de> Deserialize<'de> for Action {
         enum FlatAction {
             HistoryNext,
             HistoryPrevious,
+            HistoryList,
+            HistoryJump(usize),
             ToTop,
             ToBottom,
             ScrollUp,
             ScrollDown,
+            ScrollVisualLineUp,
+            ScrollVisualLineDown,
             PageUp,
             PageDown,
+            HalfPageUp,
+            HalfPageDown,
+            NextHeading,
+            PrevHeading,
+            ToggleFold,
+            FoldAll,
+            UnfoldAll,
+            JumpBack,
+            JumpForward,
             ZoomIn,
             ZoomOut,
             ZoomReset,
+            ZoomFitWidth,
             Copy,
+            CopyFilePath,
+            RevealInFileManager,
+            Search,
+            CopyMatches,
+            NextMatch,
+            PrevMatch,
+            ToggleSmartCase,
+            ToggleWholeWord,
+            GoToHeading,
+            ToggleSplitView,
+            ToggleEditMode,
+            ToggleExtras,
+            FitWindowToContent,
+            ToggleZenMode,
+            DumpLayout,
             Help,
             Quit,
         }
@@ -34,16 +63,45 @@ impl<'de> Deserialize<'de> for Action {
         let action = match FlatAction::deserialize(deserializer)? {
             FlatAction::HistoryNext => Action::History(HistDirection::Next),
             FlatAction::HistoryPrevious => Action::History(HistDirection::Prev),
+            FlatAction::HistoryList => Action::HistoryList,
+            FlatAction::HistoryJump(n) => Action::HistoryJump(n),
             FlatAction::ToTop => Action::ToEdge(VertDirection::Up),
             FlatAction::ToBottom => Action::ToEdge(VertDirection::Down),
             FlatAction::ScrollUp => Action::Scroll(VertDirection::Up),
             FlatAction::ScrollDown => Action::Scroll(VertDirection::Down),
+            FlatAction::ScrollVisualLineUp => Action::ScrollVisualLine(VertDirection::Up),
+            FlatAction::ScrollVisualLineDown => Action::ScrollVisualLine(VertDirection::Down),
             FlatAction::PageUp => Action::Page(VertDirection::Up),
             FlatAction::PageDown => Action::Page(VertDirection::Down),
+            FlatAction::HalfPageUp => Action::HalfPage(VertDirection::Up),
+            FlatAction::HalfPageDown => Action::HalfPage(VertDirection::Down),
+            FlatAction::NextHeading => Action::JumpHeading(VertDirection::Down),
+            FlatAction::PrevHeading => Action::JumpHeading(VertDirection::Up),
+            FlatAction::ToggleFold => Action::ToggleFold,
+            FlatAction::FoldAll => Action::FoldAll,
+            FlatAction::UnfoldAll => Action::UnfoldAll,
+            FlatAction::JumpBack => Action::Jump(HistDirection::Prev),
+            FlatAction::JumpForward => Action::Jump(HistDirection::Next),
             FlatAction::ZoomIn => Action::Zoom(Zoom::In),
             FlatAction::ZoomOut => Action::Zoom(Zoom::Out),
             FlatAction::ZoomReset => Action::Zoom(Zoom::Reset),
+            FlatAction::ZoomFitWidth => Action::Zoom(Zoom::FitWidth),
             FlatAction::Copy => Action::Copy,
+            FlatAction::CopyFilePath => Action::CopyFilePath,
+            FlatAction::RevealInFileManager => Action::RevealInFileManager,
+            FlatAction::Search => Action::Search,
+            FlatAction::CopyMatches => Action::CopyMatches,
+            FlatAction::NextMatch => Action::CycleMatch(VertDirection::Down),
+            FlatAction::PrevMatch => Action::CycleMatch(VertDirection::Up),
+            FlatAction::ToggleSmartCase => Action::ToggleSmartCase,
+            FlatAction::ToggleWholeWord => Action::ToggleWholeWord,
+            FlatAction::GoToHeading => Action::GoToHeading,
+            FlatAction::ToggleSplitView => Action::SplitView,
+            FlatAction::ToggleEditMode => Action::EditMode,
+            FlatAction::ToggleExtras => Action::Extras,
+            FlatAction::FitWindowToContent => Action::FitWindowToContent,
+            FlatAction::ToggleZenMode => Action::ZenMode,
+            FlatAction::DumpLayout => Action::DumpLayout,
             FlatAction::Help => Action::Help,
             FlatAction::Quit => Action::Quit,
         };
@@ -41,7 +41,60 @@ base = [
     ];
 
     for (key, maybe_action) in test_vectors {
-        assert_eq!(key_combos.munch(key), maybe_action);
+        assert_eq!(
+            key_combos.munch(key),
+            maybe_action.map(|action| (action, 1))
+        );
+    }
+}
+
+#[test]
+fn count_prefix() {
+    log::init();
+
+    let config = r#"
+[keybindings]
+base = [
+    ["ToTop", ["g", "g"]],
+    ["ScrollDown", "j"],
+    [{ HistoryJump = 1 }, "1"],
+]
+"#;
+
+    let Config { keybindings, .. } = Config::load_from_str(config).unwrap();
+    let mut key_combos = KeyCombos::new(keybindings).unwrap();
+
+    let g: ModifiedKey = VirtKey::G.into();
+    let j: ModifiedKey = VirtKey::J.into();
+    let zero: ModifiedKey = VirtKey::Key0.into();
+    let one: ModifiedKey = VirtKey::Key1.into();
+    let two: ModifiedKey = VirtKey::Key2.into();
+    let three: ModifiedKey = VirtKey::Key3.into();
+    let ctrl_two = ModifiedKey(two.0, ModifiersState::CTRL);
+    let scroll_down = Action::Scroll(VertDirection::Down);
+
+    let test_vectors = [
+        // `20j`
+        (two, None),
+        (zero, None),
+        (j, Some((scroll_down, 20))),
+        // The count got used up
+        (j, Some((scroll_down, 1))),
+        // A count can't start with zero
+        (zero, None),
+        (j, Some((scroll_down, 1))),
+        // and carries through a multi-key combo
+        (three, None),
+        (g, None),
+        (g, Some((Action::ToEdge(VertDirection::Up), 3))),
+        // Bound digits and modified ones don't count
+        (one, Some((Action::HistoryJump(1), 1))),
+        (ctrl_two, None),
+        (j, Some((scroll_down, 1))),
+    ];
+
+    for (key, expected) in test_vectors {
+        assert_eq!(key_combos.munch(key), expected);
     }
 }
 
@@ -54,12 +107,30 @@ const DEFAULTS_TEMPLATE: &str = r#"
 base = [
     # Regular
     ["Copy", { key = "c", mod = "CTRL_OR_CMD" }],
+    ["Search", { key = "f", mod = "CTRL_OR_CMD" }],
+    ["CopyMatches", { key = "c", mod = ["CTRL_OR_CMD", "Shift"] }],
+    ["NextMatch", "F3"],
+    ["PrevMatch", { key = "F3", mod = ["Shift"] }],
+    ["ToggleSmartCase", { key = "c", mod = "Alt" }],
+    ["ToggleWholeWord", { key = "w", mod = "Alt" }],
+    ["CopyFilePath", { key = "c", mod = ["Alt", "Shift"] }],
+    ["RevealInFileManager", { key = "r", mod = ["Alt", "Shift"] }],
+    ["GoToHeading", { key = "p", mod = "CTRL_OR_CMD" }],
+    ["ToggleSplitView", { key = "\\", mod = "CTRL_OR_CMD" }],
+    ["ToggleEditMode", { key = "e", mod = "CTRL_OR_CMD" }],
+    ["ToggleExtras", { key = "x", mod = ["CTRL_OR_CMD", "Shift"] }],
+    ["FitWindowToContent", { key = "f", mod = ["CTRL_OR_CMD", "Shift"] }],
+    ["ToggleZenMode", { key = "z", mod = ["CTRL_OR_CMD", "Shift"] }],
     ["ZoomIn", { key = "=", mod = "CTRL_OR_CMD" }],
     ["ZoomOut", { key = "-", mod = "CTRL_OR_CMD" }],
+    ["ZoomFitWidth", { key = "9", mod = "CTRL_OR_CMD" }],
     ["HistoryNext", { key = "Right", mod = "Alt" }],
     ["HistoryPrevious", { key = "Left", mod = "Alt" }],
+    ["HistoryList", { key = "Down", mod = "Alt" }],
     ["ScrollUp", "Up"],
     ["ScrollDown", "Down"],
+    ["ScrollVisualLineUp", { key = "Up", mod = ["Shift"] }],
+    ["ScrollVisualLineDown", { key = "Down", mod = ["Shift"] }],
     ["PageUp", "PageUp"],
     ["PageDown", "PageDown"],
     ["ToTop", "Home"],
@@ -69,8 +140,22 @@ base = [
     # Vim-like
     ["Help", "h"],
     ["Copy", "y"],
+    ["ToggleEditMode", "i"],
+    ["Search", "/"],
+    ["CopyMatches", "Y"],
+    ["NextMatch", "n"],
+    ["PrevMatch", "N"],
     ["ScrollUp", "k"],
     ["ScrollDown", "j"],
+    ["HalfPageUp", { key = "u", mod = ["Ctrl"] }],
+    ["HalfPageDown", { key = "d", mod = ["Ctrl"] }],
+    ["NextHeading", ["]", "]"]],
+    ["PrevHeading", ["[", "["]],
+    ["ToggleFold", ["z", "a"]],
+    ["FoldAll", ["z", "M"]],
+    ["UnfoldAll", ["z", "R"]],
+    ["JumpBack", { key = "o", mod = ["Ctrl"] }],
+    ["JumpForward", { key = "i", mod = ["Ctrl"] }],
     ["ToTop", ["g", "g"]],
     ["ToBottom", "G"],
     ["Quit", "q"],
@@ -78,6 +163,7 @@ base = [
     ["Quit", ["Z", "Q"]],
     ["HistoryNext", ["b", "n"]],
     ["HistoryPrevious", ["b", "p"]],
+    ["HistoryList", ["b", "l"]],
 ]
 "#;
 
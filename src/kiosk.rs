@@ -0,0 +1,100 @@
+//! `--kiosk` shows a document without taking any input besides a quit combo, for dashboards and
+//! other screens that nobody sits in front of
+//!
+//! The view can optionally scroll through the document on its own, lingering at the end for a
+//! bit before starting over from the top
+
+use std::time::{Duration, Instant};
+
+use winit::event::{ElementState, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent};
+
+use crate::renderer::Renderer;
+
+/// How often the view moves while auto-scrolling
+const TICK: Duration = Duration::from_millis(33);
+/// How long the end of the document stays up before going back to the top
+const LINGER: Duration = Duration::from_secs(5);
+
+pub struct Kiosk {
+    /// Logical pixels per second to auto-scroll by
+    scroll_speed: Option<f32>,
+    last_tick: Instant,
+    reached_end: Option<Instant>,
+}
+
+impl Kiosk {
+    pub fn new(scroll_speed: Option<f32>) -> Self {
+        Self {
+            scroll_speed,
+            last_tick: Instant::now(),
+            reached_end: None,
+        }
+    }
+
+    /// When the event loop should wake up to move the view next, if ever
+    pub fn next_tick(&self) -> Option<Instant> {
+        self.scroll_speed.map(|_| self.last_tick + TICK)
+    }
+
+    /// Scrolls on by however far the view should've moved since the last tick. Returns whether
+    /// the view moved
+    pub fn tick(&mut self, renderer: &mut Renderer) -> bool {
+        let now = Instant::now();
+        let elapsed = now - self.last_tick;
+        self.last_tick = now;
+        let Some(speed) = self.scroll_speed else {
+            return false;
+        };
+
+        if renderer.scroll_y < renderer.max_scroll_y().floor() {
+            renderer.scroll_by(speed * renderer.hidpi_scale * elapsed.as_secs_f32());
+            return true;
+        }
+        let reached_end = *self.reached_end.get_or_insert(now);
+        if now - reached_end < LINGER {
+            return false;
+        }
+        self.reached_end = None;
+        renderer.set_scroll_y(0.);
+        true
+    }
+}
+
+/// Whether the event is input that kiosk mode ignores
+pub fn is_input(event: &WindowEvent) -> bool {
+    matches!(
+        event,
+        WindowEvent::KeyboardInput { .. }
+            | WindowEvent::ReceivedCharacter(_)
+            | WindowEvent::Ime(_)
+            | WindowEvent::MouseWheel { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::CursorMoved { .. }
+            | WindowEvent::Touch(_)
+            | WindowEvent::TouchpadMagnify { .. }
+            | WindowEvent::TouchpadRotate { .. }
+            | WindowEvent::DroppedFile(_)
+            | WindowEvent::HoveredFile(_)
+    )
+}
+
+/// Ctrl+Alt+Q (Command+Alt+Q on macOS), the only way out of kiosk mode besides closing the
+/// window
+pub fn is_quit_combo(event: &WindowEvent, modifiers: ModifiersState) -> bool {
+    let ctrl_or_command = if cfg!(target_os = "macos") {
+        ModifiersState::LOGO
+    } else {
+        ModifiersState::CTRL
+    };
+    matches!(
+        event,
+        WindowEvent::KeyboardInput {
+            input: KeyboardInput {
+                state: ElementState::Pressed,
+                virtual_keycode: Some(VirtualKeyCode::Q),
+                ..
+            },
+            ..
+        }
+    ) && modifiers == ctrl_or_command | ModifiersState::ALT
+}
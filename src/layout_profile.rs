@@ -0,0 +1,98 @@
+//! Layout profiles switch how the document gets laid out and drawn at runtime, on top of the
+//! margins and colors from the theme
+//!
+//! Zen mode is for reading: the scrollbar goes away, lines are kept to a comfortable measure, and
+//! everything besides body text fades back towards the background
+
+use crate::Element;
+
+/// How many characters wide lines get in zen mode unless configured otherwise
+pub const DEFAULT_MEASURE: f32 = 68.;
+/// How much of the way to the background color dimmed elements get pulled
+pub const DIM_AMOUNT: f32 = 0.6;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LayoutProfile {
+    #[default]
+    Normal,
+    Zen {
+        /// The most characters of body text that fit on a line
+        measure: f32,
+    },
+}
+
+impl LayoutProfile {
+    /// Switches between the normal profile and zen mode
+    pub fn toggle_zen(self, measure: f32) -> Self {
+        match self {
+            Self::Normal => Self::Zen { measure },
+            Self::Zen { .. } => Self::Normal,
+        }
+    }
+
+    pub fn hides_scrollbar(self) -> bool {
+        matches!(self, Self::Zen { .. })
+    }
+
+    /// The widest the page can get, margins included, given how wide a character of body text is
+    /// on average
+    pub fn page_width(self, page_width: f32, char_width: f32, page_margin: f32) -> f32 {
+        match self {
+            Self::Normal => page_width,
+            Self::Zen { measure } => page_width.min(measure * char_width + 2. * page_margin),
+        }
+    }
+
+    /// Whether the element fades into the background. Rows and sections leave it up to what's
+    /// inside of them
+    pub fn dims(self, element: &Element) -> bool {
+        if self == Self::Normal {
+            return false;
+        }
+        match element {
+            Element::TextBox(text_box) => {
+                text_box.is_header || text_box.is_code_block || text_box.is_quote_block.is_some()
+            }
+            Element::Spacer(_) | Element::Image(_) | Element::Table(_) | Element::Math(_) => true,
+            Element::Row(_) | Element::Section(_) => false,
+        }
+    }
+}
+
+/// The color of the veil drawn over dimmed elements, as RGBA bytes
+pub fn veil_color(background_color: u32) -> [u8; 4] {
+    let [_, r, g, b] = background_color.to_be_bytes();
+    [r, g, b, (DIM_AMOUNT * 255.).round() as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::TextBox;
+
+    #[test]
+    fn zen_mode() {
+        let zen = LayoutProfile::Normal.toggle_zen(60.);
+        assert_eq!(zen, LayoutProfile::Zen { measure: 60. });
+        assert_eq!(zen.toggle_zen(60.), LayoutProfile::Normal);
+
+        // The configured page width still wins when it's narrower
+        assert_eq!(
+            LayoutProfile::Normal.page_width(f32::MAX, 8., 100.),
+            f32::MAX
+        );
+        assert_eq!(zen.page_width(f32::MAX, 8., 100.), 680.);
+        assert_eq!(zen.page_width(500., 8., 100.), 500.);
+
+        let paragraph = Element::TextBox(TextBox::default());
+        let heading = Element::TextBox(TextBox {
+            is_header: true,
+            ..Default::default()
+        });
+        assert!(!zen.dims(&paragraph));
+        assert!(zen.dims(&heading));
+        assert!(!LayoutProfile::Normal.dims(&heading));
+
+        assert_eq!(veil_color(0x1A1D22), [0x1A, 0x1D, 0x22, 153]);
+    }
+}
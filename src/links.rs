@@ -0,0 +1,118 @@
+//! Opening external links, with support for user-declared custom URI scheme handlers
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::opts::LinkHandler;
+
+use anyhow::Context;
+
+/// How long opening a link gets before its handler is reported as stuck
+const OPEN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opens `link` off of the event loop, so that a misbehaving handler can't stall the window.
+/// `report` gets a message to show if opening the link fails or takes too long
+pub fn open_detached(
+    link: String,
+    handlers: HashMap<String, LinkHandler>,
+    report: impl FnOnce(String) + Send + 'static,
+) {
+    std::thread::spawn(move || {
+        let (sender, receiver) = mpsc::channel();
+        let opened = link.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(open(&opened, &handlers));
+        });
+        let message = match receiver.recv_timeout(OPEN_TIMEOUT) {
+            Ok(Ok(())) => return,
+            Ok(Err(err)) => format!("Could not open {link}: {err:#}"),
+            Err(_) => format!("Still waiting on {link} to open, its handler may be stuck"),
+        };
+        tracing::warn!("{message}");
+        report(message);
+    });
+}
+
+/// Opens `link` with its scheme's handler if there is one, falling back to the system handler
+pub fn open(link: &str, handlers: &HashMap<String, LinkHandler>) -> anyhow::Result<()> {
+    let Some((scheme, rest)) = split_scheme(link) else {
+        return open::that(link).map_err(Into::into);
+    };
+    let Some(handler) = handlers.get(&scheme.to_ascii_lowercase()) else {
+        return open::that(link).map_err(Into::into);
+    };
+
+    match handler {
+        LinkHandler::Rewrite(template) => {
+            let rewritten = fill_template(template, link, rest);
+            tracing::debug!("Rewrote link {link} -> {rewritten}");
+            open::that(rewritten)?;
+        }
+        LinkHandler::Command(template) => {
+            let mut args = template.iter().map(|arg| fill_template(arg, link, rest));
+            let program = args.next().context("Link handler has an empty command")?;
+            let mut child = Command::new(&program)
+                .args(args)
+                .spawn()
+                .with_context(|| format!("Failed running link handler '{program}'"))?;
+            // Reap the handler in the background instead of blocking the event loop on it
+            std::thread::spawn(move || child.wait());
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits `jira:PROJ-123` into `("jira", "PROJ-123")` and `zotero://select/items/1` into
+/// `("zotero", "select/items/1")`
+///
+/// Single letter schemes are skipped, so that Windows drive letters aren't mistaken for one
+fn split_scheme(link: &str) -> Option<(&str, &str)> {
+    let (scheme, rest) = link.split_once(':')?;
+    let is_scheme = scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    is_scheme.then(|| (scheme, rest.trim_start_matches("//")))
+}
+
+/// Substitutes `{link}` with the full link and `{path}` with everything after the scheme
+fn fill_template(template: &str, link: &str, path: &str) -> String {
+    template.replace("{link}", link).replace("{path}", path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scheme_splitting() {
+        assert_eq!(split_scheme("jira:PROJ-123"), Some(("jira", "PROJ-123")));
+        assert_eq!(
+            split_scheme("zotero://select/items/1"),
+            Some(("zotero", "select/items/1"))
+        );
+        assert_eq!(split_scheme(r"C:\docs\file.md"), None);
+        assert_eq!(split_scheme("not a scheme: at all"), None);
+        assert_eq!(split_scheme("relative/path.md"), None);
+    }
+
+    #[test]
+    fn template_filling() {
+        assert_eq!(
+            fill_template(
+                "https://jira.example.com/browse/{path}",
+                "jira:PROJ-123",
+                "PROJ-123"
+            ),
+            "https://jira.example.com/browse/PROJ-123"
+        );
+        assert_eq!(
+            fill_template("{link}", "zotero://select/items/1", "select/items/1"),
+            "zotero://select/items/1"
+        );
+    }
+}
@@ -9,70 +9,137 @@
     clippy::print_stdout, clippy::print_stderr,
 )]
 
+mod bench;
 mod clipboard;
+mod code_run;
 pub mod color;
+mod context_menu;
+mod cycle;
 mod debug_impls;
+mod desktop;
+mod encoding;
+mod export;
+mod external_render;
+mod extras;
+mod file_actions;
 mod file_watcher;
+mod fold;
 pub mod fonts;
+mod gallery;
+mod heading_palette;
+mod idle_cursor;
 pub mod history;
 pub mod image;
+pub mod inspect;
 pub mod interpreter;
+mod jump_list;
 mod keybindings;
+mod kiosk;
+mod layout_profile;
+mod links;
+mod math;
 mod metrics;
+mod notification;
 pub mod opts;
 mod panic_hook;
+mod plugins;
+mod print;
+mod remote;
 pub mod positioner;
 pub mod renderer;
+mod screenshot;
+mod scroll_memory;
+mod search;
+mod section_link;
+mod serve;
 pub mod selection;
+mod source_edit;
+mod source_map;
+mod subprocess;
 pub mod table;
 #[cfg(test)]
 pub mod test_utils;
 pub mod text;
+mod toast;
+mod toc;
 pub mod utils;
 
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::mpsc::{self, channel};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use code_run::CodeRunner;
+use color::native_color;
+use context_menu::ContextMenu;
+use cycle::Cycle;
+use encoding::{read_to_string, read_with_encoding, Encoding};
 use file_watcher::Watcher;
-use image::{Image, ImageData};
-use interpreter::HtmlInterpreter;
+use gallery::ThemeGallery;
+use heading_palette::{Heading, HeadingPalette};
+use history::History;
+use idle_cursor::IdleCursor;
+use image::{Image, ImageData, MapArea};
+use interpreter::{HtmlInterpreter, MdUpdate};
+use jump_list::JumpList;
 use keybindings::action::{Action, HistDirection, VertDirection, Zoom};
 use keybindings::{Key, KeyCombos, ModifiedKey};
+use kiosk::Kiosk;
+use math::Math;
 use metrics::{histogram, HistTag};
-use opts::{Cli, Config, Opts};
+use opts::{Cli, Config, LayeredConfig, Opts};
 use parking_lot::Mutex;
-use positioner::{Positioned, Row, Section, Spacer, DEFAULT_MARGIN};
+use plugins::Plugins;
+use positioner::{Positioned, Row, Section, Spacer, Viewport, DEFAULT_MARGIN};
+use remote::{Follow, ViewState};
 use raw_window_handle::HasRawDisplayHandle;
-use renderer::Renderer;
+use renderer::{Renderer, MAX_ZOOM, MIN_ZOOM};
+use scroll_memory::{SavedView, ScrollMemory};
+use search::{FindBadge, MatchAnchor, Search, SearchHistory, SearchOptions};
+use source_edit::Edit;
 use table::Table;
 use text::{Text, TextBox, TextSystem};
+use toast::Toast;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::util::SubscriberInitExt;
 use utils::{ImageCache, Point, Rect, Size};
 
-use crate::opts::{Commands, ConfigCmd, MetricsExporter};
+use crate::opts::{
+    Bench, Commands, ConfigCmd, ExportCmd, ExportHtml, InstallDesktop, MetricsExporter, Print,
+    ReloadAttention, RemoteCmd, Screenshot, Serve, ThemeCmd, ThemePreview, View,
+};
 use crate::selection::Selection;
 use anyhow::Context;
 use clap::Parser;
+use glyphon::FamilyOwned;
 use taffy::Taffy;
 use winit::event::{
-    ElementState, Event, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, WindowEvent,
+    ElementState, Event, Ime, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+    StartCause, VirtualKeyCode, WindowEvent,
 };
 use winit::event_loop::{ControlFlow, EventLoop, EventLoopBuilder, EventLoopProxy};
-use winit::window::{CursorIcon, Window, WindowBuilder};
+use winit::window::{CursorIcon, UserAttentionType, Window, WindowBuilder};
+
+/// Time spent positioning queued elements before yielding back to the event loop for a redraw
+const POSITION_BUDGET: Duration = Duration::from_millis(8);
+/// Consecutive failed reloads before we tell the user about it
+const MAX_RELOAD_FAILURES: u32 = 3;
 
 pub enum InlyneEvent {
     LoadedImage(String, Arc<Mutex<Option<ImageData>>>),
     FileReload,
     FileChange { contents: String },
+    FileLost,
+    FileRenamed(PathBuf),
     Reposition,
     PositionQueue,
+    FollowView(ViewState),
+    CodeRunFinished,
+    /// A message about something that happened in the background
+    Toast(String),
 }
 
 impl Debug for InlyneEvent {
@@ -83,6 +150,8 @@ impl Debug for InlyneEvent {
 
 pub enum Hoverable<'a> {
     Image(&'a Image),
+    /// A region of an image map, which takes over from the image's own link
+    Area(&'a MapArea),
     Text(&'a Text),
     Summary(&'a Section),
 }
@@ -93,6 +162,7 @@ pub enum Element {
     Spacer(Spacer),
     Image(Image),
     Table(Table),
+    Math(Math),
     Row(Row),
     Section(Section),
 }
@@ -133,6 +203,20 @@ impl From<Table> for Element {
     }
 }
 
+impl From<Math> for Element {
+    fn from(math: Math) -> Self {
+        Element::Math(math)
+    }
+}
+
+/// The document that `--side-by-side` shows in the right half of the window. Its layout and
+/// scroll position live in the renderer's side pane
+struct SideDocument {
+    element_queue: Arc<Mutex<Vec<Element>>>,
+    // Keeps the interpreter running
+    _interpreter_sender: mpsc::Sender<MdUpdate>,
+}
+
 pub struct Inlyne {
     opts: Opts,
     window: Arc<Window>,
@@ -144,7 +228,7 @@ pub struct Inlyne {
     elements: Vec<Positioned<Element>>,
     lines_to_scroll: f32,
     image_cache: ImageCache,
-    interpreter_sender: mpsc::Sender<String>,
+    interpreter_sender: mpsc::Sender<MdUpdate>,
     keycombos: KeyCombos,
     need_repositioning: bool,
     watcher: Watcher,
@@ -154,19 +238,92 @@ pub struct Inlyne {
     help_element_queue: Arc<Mutex<Vec<Element>>>,
     saved_scroll_y: f32,
     current_file_content: String,
+    /// What the current file was decoded from
+    encoding: Encoding,
+    /// Hash of the contents that the document was last loaded from
+    loaded_hash: u64,
+    /// The document's own title for the window title, along with the hash of the contents it
+    /// was found in
+    doc_title: (u64, Option<String>),
     event_loop_proxy: EventLoopProxy<InlyneEvent>,
+    reload_failures: u32,
+    /// Source line to jump to once the current file finishes laying out
+    goto_line: Option<usize>,
+    /// Resize the window to the document's width once it finishes laying out
+    fit_pending: bool,
+    /// Set in kiosk mode, where all input besides the quit combo gets ignored
+    kiosk: Option<Kiosk>,
+    /// Set when rotating through documents with `--cycle`
+    cycle: Option<Cycle>,
+    /// Set when the cursor should hide after sitting still for a while
+    idle_cursor: Option<IdleCursor>,
+    search: Option<Search>,
+    search_history: SearchHistory,
+    /// Whether the input method is turned on, which it is while a search query is being typed
+    ime_allowed: bool,
+    /// Which of the search query's matches was jumped to last
+    current_match: Option<usize>,
+    /// The current match from before a reload, to pick back out once the new document is laid out
+    reloaded_match: Option<MatchAnchor>,
+    /// The `--find` query's match count, which stays up after its search closes
+    find_badge: Option<FindBadge>,
+    /// Clicking a paragraph starts editing its source
+    edit_mode: bool,
+    editing: Option<Edit>,
+    /// The index of the text box being edited, along with how it looked before editing started
+    edited_element: Option<(usize, TextBox)>,
+    /// The selected entry while the history list is open
+    history_list: Option<usize>,
+    /// Set while previewing themes with `inlyne theme preview`
+    gallery: Option<ThemeGallery>,
+    follow: Option<Follow>,
+    /// The scroll position and zoom that other windows last heard about in follow mode
+    followed_view: (f32, f32),
+    /// Where the view was last pinned to the end of the file in tail mode. Unset once the user
+    /// scrolls away
+    tail_pinned_at: Option<f32>,
+    code_runner: CodeRunner,
+    /// The snippet waiting on confirmation before it runs
+    confirm_run: Option<String>,
+    heading_palette: Option<HeadingPalette>,
+    /// Ways to copy the table or code block that was right-clicked
+    context_menu: Option<ContextMenu>,
+    side_document: Option<SideDocument>,
+    /// Shown in the banner when nothing else is using it
+    toast: Option<Toast>,
+    /// Why the watched file went missing, shown in the banner when nothing else is using it
+    lost_file: Option<String>,
+    scroll_memory: Option<ScrollMemory>,
+    /// How far down the document to scroll once it finishes laying out, from 0 to 1
+    restore_progress: Option<f32>,
+    /// Where the view was before following in-document links
+    jump_list: JumpList,
 }
 
 impl Inlyne {
-    pub fn new(opts: Opts) -> anyhow::Result<Self> {
+    pub fn new(mut opts: Opts) -> anyhow::Result<Self> {
         let keycombos = KeyCombos::new(opts.keybindings.clone())?;
+        if opts.persist_history {
+            if let Some(sessions_dir) = History::sessions_dir() {
+                opts.history.restore_session(&sessions_dir);
+            }
+        }
 
         let file_path = opts.history.get_path().to_owned();
+        let (md_string, encoding) = read_with_encoding(&file_path)
+            .with_context(|| format!("Could not read file at '{}'", file_path.display()))?;
 
         let event_loop = EventLoopBuilder::<InlyneEvent>::with_user_event().build();
 
+        let doc_title = utils::document_title(&md_string);
         let window = {
-            let mut wb = WindowBuilder::new().with_title(utils::format_title(&file_path));
+            let title = utils::format_title(
+                &opts.title_template,
+                &file_path,
+                doc_title.as_deref(),
+                encoding,
+            );
+            let mut wb = WindowBuilder::new().with_title(title);
 
             if let Some(decorations) = opts.decorations {
                 wb = wb.with_decorations(decorations);
@@ -185,6 +342,10 @@ impl Inlyne {
 
             Arc::new(wb.build(&event_loop).unwrap())
         };
+        if opts.kiosk {
+            window.set_cursor_visible(false);
+            opts.theme.scrollbar_width = 0;
+        }
 
         let mut renderer = pollster::block_on(Renderer::new(
             &window,
@@ -192,16 +353,24 @@ impl Inlyne {
             opts.scale.unwrap_or(window.scale_factor() as f32),
             opts.page_width.unwrap_or(f32::MAX),
             opts.font_opts.clone(),
+            opts.wide_gamut,
         ))?;
 
         let element_queue = Arc::new(Mutex::new(Vec::new()));
         let image_cache = Arc::new(Mutex::new(HashMap::new()));
-        let md_string = read_to_string(&file_path)
-            .with_context(|| format!("Could not read file at '{}'", file_path.display()))?;
+        let code_runner = CodeRunner::new(opts.run_commands.clone());
 
         let event_loop_proxy = event_loop.create_proxy();
         // Set element padding from options
-        renderer.element_padding = opts.element_padding;
+        renderer.element_spacing = opts.element_spacing;
+        renderer.low_memory = opts.low_memory;
+        renderer.sticky_headings = opts.sticky_headings;
+        renderer.code_wrap_guide = opts.code_wrap_guide;
+        renderer.code_wrap_indicator = opts.code_wrap_indicator;
+        renderer.positioner.image_max_size = opts.image_max_size;
+        renderer
+            .image_renderer
+            .limit_texture_size(opts.image_max_texture_size);
         
         let interpreter = HtmlInterpreter::new(
             window.clone(),
@@ -218,20 +387,109 @@ impl Inlyne {
             true,   // Add spacers after tables for separation
             true,   // Add spacers after paragraphs for better flow
             true,   // Add spacers after lists for better flow
-        );
+        )
+        .with_opts(&opts)
+        .with_plugins(Arc::new(Plugins::load_from_system(&opts.plugins)))
+        .with_code_runner(code_runner.clone());
 
         let (interpreter_sender, interpreter_receiver) = channel();
         std::thread::spawn(move || interpreter.interpret_md(interpreter_receiver));
 
-        interpreter_sender.send(md_string.clone())?;
+        interpreter_sender.send(md_string.clone().into())?;
+
+        let side_document = match &opts.side_by_side {
+            Some(side_path) => {
+                let side_md = read_to_string(side_path)
+                    .with_context(|| format!("Could not read file at '{}'", side_path.display()))?;
+                let side_queue = Arc::new(Mutex::new(Vec::new()));
+                let side_interpreter = HtmlInterpreter::new(
+                    window.clone(),
+                    side_queue.clone(),
+                    renderer.theme.clone(),
+                    renderer.surface_format,
+                    renderer.hidpi_scale,
+                    image_cache.clone(),
+                    event_loop_proxy.clone(),
+                    opts.color_scheme,
+                    true,  // Add spacers before headers for separation from previous content
+                    false, // NO spacers after headers - keep tables close
+                    false, // NO spacers before tables - keep close to headers
+                    true,  // Add spacers after tables for separation
+                    true,  // Add spacers after paragraphs for better flow
+                    true,  // Add spacers after lists for better flow
+                )
+                .with_opts(&opts)
+                .with_plugins(Arc::new(Plugins::load_from_system(&opts.plugins)))
+                .with_code_runner(code_runner.clone());
+                let (side_sender, side_receiver) = channel();
+                std::thread::spawn(move || side_interpreter.interpret_md(side_receiver));
+                side_sender.send(side_md.into())?;
+                renderer.open_side_pane();
+                Some(SideDocument {
+                    element_queue: side_queue,
+                    _interpreter_sender: side_sender,
+                })
+            }
+            None => None,
+        };
 
         let lines_to_scroll = opts.lines_to_scroll;
+        let search_options = opts.search_options;
+        let mut find_badge = opts
+            .find
+            .clone()
+            .map(|query| FindBadge::new(query, search_options));
+        if let Some(badge) = &mut find_badge {
+            badge.update(&md_string, fxhash::hash64(&md_string));
+        }
+        let goto_line = opts
+            .goto_line
+            .or_else(|| find_badge.as_ref().and_then(FindBadge::first_line));
+        let tail_pinned_at = opts.tail.then_some(0.0);
+        let fit_pending = opts.fit;
+        let kiosk = opts.kiosk.then(|| Kiosk::new(opts.kiosk_scroll));
+        let cycle = (!opts.cycle.is_empty())
+            .then(|| Cycle::new(opts.cycle.clone(), opts.cycle_interval));
+        // Kiosk mode already keeps the cursor hidden for good
+        let idle_cursor = opts
+            .hide_cursor_after
+            .filter(|_| !opts.kiosk)
+            .map(IdleCursor::new);
+        let search = opts
+            .find
+            .clone()
+            .map(|query| Search::with_query(query, search_options));
+        let search_history = SearchHistory::new(opts.search_history_limit);
+        let search_history = match SearchHistory::default_file() {
+            Some(file) => search_history.load(file),
+            None => search_history,
+        };
+        // Cycling and tailing already decide where the view goes
+        let scroll_memory = (opts.remember_scroll && cycle.is_none() && !opts.tail)
+            .then(ScrollMemory::state_file)
+            .flatten()
+            .map(|state_file| ScrollMemory::load(state_file, file_path.clone()));
+        let saved_view = scroll_memory
+            .as_ref()
+            .and_then(ScrollMemory::current_view)
+            .filter(|_| goto_line.is_none());
+        if let Some(view) = saved_view {
+            renderer.zoom = view.zoom;
+        }
 
         let watcher = Watcher::spawn(event_loop_proxy.clone(), file_path.clone());
+        if opts.remote_preview {
+            remote::listen(event_loop_proxy.clone())?;
+        }
+        let follow = if opts.follow {
+            Some(Follow::join(event_loop_proxy.clone())?)
+        } else {
+            None
+        };
 
         let _ = file_path.parent().map(std::env::set_current_dir);
 
-        Ok(Self {
+        let mut inlyne = Self {
             opts,
             window,
             event_loop: Some(event_loop),
@@ -249,9 +507,93 @@ impl Inlyne {
             help_elements: Vec::new(),
             help_element_queue: Arc::new(Mutex::new(Vec::new())),
             saved_scroll_y: 0.0,
+            loaded_hash: fxhash::hash64(&md_string),
+            doc_title: (fxhash::hash64(&md_string), doc_title),
             current_file_content: md_string,
+            encoding,
             event_loop_proxy,
-        })
+            reload_failures: 0,
+            goto_line,
+            fit_pending,
+            kiosk,
+            cycle,
+            idle_cursor,
+            search,
+            search_history,
+            ime_allowed: false,
+            current_match: None,
+            reloaded_match: None,
+            find_badge,
+            edit_mode: false,
+            editing: None,
+            edited_element: None,
+            history_list: None,
+            gallery: None,
+            follow,
+            followed_view: (0.0, 1.0),
+            tail_pinned_at,
+            code_runner,
+            confirm_run: None,
+            heading_palette: None,
+            context_menu: None,
+            side_document,
+            toast: None,
+            lost_file: None,
+            scroll_memory,
+            restore_progress: saved_view.map(|view| view.progress),
+            jump_list: JumpList::default(),
+        };
+        inlyne.update_prompt_banner();
+        Ok(inlyne)
+    }
+
+    /// Shows the theme gallery's sample document instead of the opened file
+    pub fn with_gallery(mut self, gallery: ThemeGallery) -> Self {
+        self.gallery = Some(gallery);
+        self.show_gallery_theme();
+        self
+    }
+
+    /// Switches to the gallery's current theme. The interpreter holds onto the theme that it was
+    /// created with, so a new one gets started
+    fn show_gallery_theme(&mut self) {
+        let Some(gallery) = &self.gallery else {
+            return;
+        };
+        let entry = gallery.current();
+        let document = gallery.document();
+        self.opts.theme = entry.theme.clone();
+        self.opts.color_scheme = Some(entry.color_scheme);
+        self.renderer.theme = entry.theme.clone();
+        self.renderer.positioner.page_margin = entry.theme.page_margin as f32;
+
+        // A fresh queue keeps the old interpreter from pushing elements in the previous theme
+        self.element_queue = Arc::new(Mutex::new(Vec::new()));
+        let interpreter = HtmlInterpreter::new(
+            Arc::clone(&self.window),
+            Arc::clone(&self.element_queue),
+            self.renderer.theme.clone(),
+            self.renderer.surface_format,
+            self.renderer.hidpi_scale,
+            Arc::clone(&self.image_cache),
+            self.event_loop_proxy.clone(),
+            self.opts.color_scheme,
+            true,   // Add spacers before headers for separation from previous content
+            false,  // NO spacers after headers - keep tables close
+            false,  // NO spacers before tables - keep close to headers
+            true,   // Add spacers after tables for separation
+            true,   // Add spacers after paragraphs for better flow
+            true,   // Add spacers after lists for better flow
+        )
+        .with_opts(&self.opts)
+        .with_plugins(Arc::new(Plugins::load_from_system(&self.opts.plugins)))
+        .with_code_runner(self.code_runner.clone());
+        let (interpreter_sender, interpreter_receiver) = channel();
+        std::thread::spawn(move || interpreter.interpret_md(interpreter_receiver));
+        self.interpreter_sender = interpreter_sender;
+
+        self.renderer.set_scroll_y(0.0);
+        self.load_file(document);
     }
 
     fn get_help_html(&self) -> String {
@@ -267,16 +609,45 @@ impl Inlyne {
             let action_name = match action {
                 Action::Scroll(VertDirection::Up) => "Scroll Up",
                 Action::Scroll(VertDirection::Down) => "Scroll Down",
+                Action::ScrollVisualLine(VertDirection::Up) => "Scroll Up a Line",
+                Action::ScrollVisualLine(VertDirection::Down) => "Scroll Down a Line",
                 Action::Page(VertDirection::Up) => "Page Up",
                 Action::Page(VertDirection::Down) => "Page Down",
+                Action::HalfPage(VertDirection::Up) => "Half Page Up",
+                Action::HalfPage(VertDirection::Down) => "Half Page Down",
+                Action::JumpHeading(VertDirection::Up) => "Previous Heading",
+                Action::JumpHeading(VertDirection::Down) => "Next Heading",
+                Action::ToggleFold => "Fold Heading",
+                Action::FoldAll => "Fold All Headings",
+                Action::UnfoldAll => "Unfold All Headings",
                 Action::ToEdge(VertDirection::Up) => "Go to Top",
                 Action::ToEdge(VertDirection::Down) => "Go to Bottom",
                 Action::Zoom(Zoom::In) => "Zoom In",
                 Action::Zoom(Zoom::Out) => "Zoom Out",
                 Action::Zoom(Zoom::Reset) => "Reset Zoom",
+                Action::Zoom(Zoom::FitWidth) => "Zoom to Fit Width",
                 Action::History(HistDirection::Next) => "Next File",
                 Action::History(HistDirection::Prev) => "Previous File",
+                Action::HistoryList => "Show History",
+                Action::HistoryJump(_) => "Jump to History Entry",
+                Action::Jump(HistDirection::Prev) => "Jump Back",
+                Action::Jump(HistDirection::Next) => "Jump Forward",
                 Action::Copy => "Copy Selection",
+                Action::CopyFilePath => "Copy File Path",
+                Action::RevealInFileManager => "Reveal in File Manager",
+                Action::Search => "Search",
+                Action::CopyMatches => "Copy Search Matches",
+                Action::CycleMatch(VertDirection::Down) => "Next Match",
+                Action::CycleMatch(VertDirection::Up) => "Previous Match",
+                Action::ToggleSmartCase => "Toggle Smart Case",
+                Action::ToggleWholeWord => "Toggle Whole Word",
+                Action::GoToHeading => "Go to Heading",
+                Action::SplitView => "Toggle Split View",
+                Action::EditMode => "Toggle Edit Mode",
+                Action::Extras => "Toggle Hidden Extras",
+                Action::FitWindowToContent => "Fit Window to Content",
+                Action::ZenMode => "Toggle Zen Mode",
+                Action::DumpLayout => "Dump Layout",
                 Action::Help => "Toggle Help",
                 Action::Quit => "Quit",
             };
@@ -301,8 +672,11 @@ impl Inlyne {
         content.push_str("|--------|------|\n");
         
         let nav_actions = [
-            "Scroll Up", "Scroll Down", "Page Up", "Page Down", 
-            "Go to Top", "Go to Bottom"
+            "Scroll Up", "Scroll Down", "Scroll Up a Line", "Scroll Down a Line",
+            "Page Up", "Page Down", 
+            "Half Page Up", "Half Page Down", "Previous Heading", "Next Heading",
+            "Fold Heading", "Fold All Headings", "Unfold All Headings",
+            "Jump Back", "Jump Forward", "Go to Top", "Go to Bottom"
         ];
         for action in &nav_actions {
             content.push_str("| ");
@@ -324,7 +698,7 @@ impl Inlyne {
         content.push_str("## Zoom\n| Action | Keys |\n");
         content.push_str("|--------|------|\n");
         
-        let zoom_actions = ["Zoom In", "Zoom Out", "Reset Zoom"];
+        let zoom_actions = ["Zoom In", "Zoom Out", "Reset Zoom", "Zoom to Fit Width"];
         for action in &zoom_actions {
             content.push_str("| ");
             content.push_str(action);
@@ -345,7 +719,22 @@ impl Inlyne {
         content.push_str("## File Operations\n| Action | Keys |\n");
         content.push_str("|--------|------|\n");
         
-        let file_actions = ["Next File", "Previous File", "Copy Selection"];
+        let file_actions = [
+            "Next File",
+            "Previous File",
+            "Show History",
+            "Jump to History Entry",
+            "Copy File Path",
+            "Reveal in File Manager",
+            "Copy Selection",
+            "Search",
+            "Copy Search Matches",
+            "Next Match",
+            "Previous Match",
+            "Toggle Smart Case",
+            "Toggle Whole Word",
+            "Go to Heading",
+        ];
         for action in &file_actions {
             content.push_str("| ");
             content.push_str(action);
@@ -366,7 +755,15 @@ impl Inlyne {
         content.push_str("## Application\n| Action | Keys |\n");
         content.push_str("|--------|------|\n");
         
-        let app_actions = ["Toggle Help", "Quit"];
+        let app_actions = [
+            "Toggle Help",
+            "Toggle Split View",
+            "Toggle Edit Mode",
+            "Toggle Hidden Extras",
+            "Fit Window to Content",
+            "Toggle Zen Mode",
+            "Quit",
+        ];
         for action in &app_actions {
             content.push_str("| ");
             content.push_str(action);
@@ -391,12 +788,19 @@ impl Inlyne {
         content
     }
 
+    /// Positions the elements waiting in `element_queue`
+    ///
+    /// Positioning always continues until the current viewport is filled so that the first screen
+    /// can be drawn right away. Past that point it stops once `POSITION_BUDGET` is spent and puts
+    /// the leftovers back at the front of the queue. Returns `true` if elements are still pending
+    #[must_use]
     pub fn position_queued_elements(
         element_queue: &Arc<Mutex<Vec<Element>>>,
         renderer: &mut Renderer,
         elements: &mut Vec<Positioned<Element>>,
-    ) {
+    ) -> bool {
         let positioning_start = Instant::now();
+        let viewport_bottom = renderer.scroll_y + renderer.screen_size().1;
 
         let mut elements_vec: Vec<Element> = element_queue.lock().drain(..).collect();
         
@@ -450,18 +854,15 @@ impl Inlyne {
             let element = elements_vec.remove(0);
             let mut positioned_element = Positioned::new(element);
             
-            // Ensure minimum 12px spacing before headers (except at document start)
-            if matches!(&positioned_element.inner, Element::TextBox(tb) if tb.is_header) {
-                if renderer.positioner.reserved_height > renderer.element_padding * renderer.hidpi_scale {
-                    // Not at document start, ensure minimum spacing
-                    let min_spacing = 12.0 * renderer.hidpi_scale * renderer.zoom;
-                    let last_element_bottom = renderer.positioner.reserved_height;
-                    
-                    // Add spacing to ensure header has breathing room
-                    renderer.positioner.reserved_height = last_element_bottom + min_spacing;
-                }
+            let spacing = renderer.element_spacing;
+            let scale = renderer.hidpi_scale * renderer.zoom;
+
+            // Give headings some room above them (except at document start)
+            if renderer.positioner.reserved_height > spacing.paragraph * renderer.hidpi_scale {
+                renderer.positioner.reserved_height +=
+                    spacing.before(&positioned_element.inner) * scale;
             }
-            
+
             // Position the element
             renderer
                 .positioner
@@ -469,53 +870,68 @@ impl Inlyne {
                     &mut renderer.text_system,
                     &mut positioned_element,
                     renderer.zoom,
-                    renderer.element_padding,
+                    &spacing,
                 )
                 .unwrap();
-            
+
             let element_bounds = positioned_element.bounds.as_ref().unwrap();
             let element_height = element_bounds.size.1;
+
+            renderer.positioner.reserved_height +=
+                element_height + spacing.after(&positioned_element.inner) * scale;
             
-            renderer.positioner.reserved_height += element_height;
-            
-            // Determine padding based on element type
-            let padding = if let Element::TextBox(ref tb) = positioned_element.inner {
-                if tb.is_header {
-                    // Headers always get at least 2px padding after them
-                    let min_header_padding = 2.0 * renderer.hidpi_scale * renderer.zoom;
-                    let normal_padding = renderer.element_padding * renderer.hidpi_scale * renderer.zoom;
-                    normal_padding.max(min_header_padding)
-                } else {
-                    // Normal padding for non-header text
-                    renderer.element_padding * renderer.hidpi_scale * renderer.zoom
-                }
-            } else if matches!(&positioned_element.inner, Element::Table(_)) {
-                // Tables always get at least 6px padding for consistency
-                let min_table_padding = 6.0 * renderer.hidpi_scale * renderer.zoom;
-                let normal_padding = renderer.element_padding * renderer.hidpi_scale * renderer.zoom;
-                normal_padding.max(min_table_padding)
-            } else {
-                // Normal padding for other elements
-                renderer.element_padding * renderer.hidpi_scale * renderer.zoom
-            };
-            
-            if padding > 0.0 {
-                renderer.positioner.reserved_height += padding;
-            }
-            
+            let is_header =
+                matches!(&positioned_element.inner, Element::TextBox(tb) if tb.is_header);
             elements.push(positioned_element);
+
+            // Keep headers together with whatever follows them
+            if !elements_vec.is_empty()
+                && !is_header
+                && renderer.positioner.reserved_height > viewport_bottom
+                && positioning_start.elapsed() > POSITION_BUDGET
+            {
+                let mut queue = element_queue.lock();
+                elements_vec.append(&mut queue);
+                *queue = elements_vec;
+                histogram!(HistTag::Positioner).record(positioning_start.elapsed());
+                return true;
+            }
         }
 
         histogram!(HistTag::Positioner).record(positioning_start.elapsed());
+        false
     }
 
     fn load_file(&mut self, contents: String) {
+        // The edited paragraph goes away along with the rest of the old document
+        if self.editing.take().is_some() {
+            self.edited_element = None;
+            self.show_toast("The document reloaded, so the edit was dropped".to_owned());
+        }
+        self.loaded_hash = fxhash::hash64(&contents);
         self.current_file_content = contents.clone();
-        self.element_queue.lock().clear();
+        // A reload that lands before the last one finished keeps the match it was after
+        if let (Some(index), None) = (self.current_match, self.reloaded_match) {
+            let matches = self
+                .renderer
+                .search_matches(&self.elements)
+                .unwrap_or_default();
+            self.reloaded_match = MatchAnchor::new(&matches, index);
+        }
         self.elements.clear();
-        self.renderer.positioner.reserved_height = self.opts.element_padding * self.renderer.hidpi_scale;
+        self.renderer.positioner.reserved_height =
+            self.opts.element_spacing.paragraph * self.renderer.hidpi_scale;
         self.renderer.positioner.anchors.clear();
-        self.interpreter_sender.send(contents).unwrap();
+        // Send while holding the queue's lock, so that the interpreter sees the newer contents
+        // before it pushes anything else from a stale document
+        let mut queue = self.element_queue.lock();
+        queue.clear();
+        self.interpreter_sender.send(contents.into()).unwrap();
+        drop(queue);
+        if self.prompt_open() {
+            self.update_prompt_banner();
+        }
+        self.update_source_pane();
     }
     
     fn show_help(&mut self) {
@@ -542,20 +958,22 @@ impl Inlyne {
             true,   // Add spacers after tables in help
             true,   // Add spacers after paragraphs in help
             true,   // Add spacers after lists in help
-        );
+        )
+        .with_opts(&self.opts);
         
-        // Use same element padding as regular documents (from opts)
-        self.renderer.element_padding = self.opts.element_padding;
+        // Use same element spacing as regular documents (from opts)
+        self.renderer.element_spacing = self.opts.element_spacing;
         
         // Load help content in separate thread
         let help_content = self.get_help_html();
         let (help_sender, help_receiver) = channel();
         std::thread::spawn(move || help_interpreter.interpret_md(help_receiver));
-        help_sender.send(help_content).unwrap();
+        help_sender.send(help_content.into()).unwrap();
         
         // Reset scroll and positioning for help view
-        self.renderer.scroll_y = 0.0;
-        self.renderer.positioner.reserved_height = self.opts.element_padding * self.renderer.hidpi_scale;
+        self.renderer.set_scroll_y(0.0);
+        self.renderer.positioner.reserved_height =
+            self.opts.element_spacing.paragraph * self.renderer.hidpi_scale;
         self.window.request_redraw();
     }
     
@@ -565,236 +983,1277 @@ impl Inlyne {
         self.help_element_queue.lock().clear();
         
         // Restore document state
-        // Restore padding for normal document (same as help - from opts)
-        self.renderer.element_padding = self.opts.element_padding;
-        // Need to recalculate the document's reserved height since it was reset for help
-        let mut total_height = self.renderer.element_padding * self.renderer.hidpi_scale;
-        for element in &self.elements {
-            if let Some(bounds) = &element.bounds {
-                total_height += bounds.size.1 + self.renderer.element_padding * self.renderer.hidpi_scale * self.renderer.zoom;
-            }
-        }
-        self.renderer.positioner.reserved_height = total_height;
+        // Restore spacing for normal document (same as help - from opts)
+        self.renderer.element_spacing = self.opts.element_spacing;
+        // Help reset the reserved height, and the window may have been resized or zoomed while
+        // it was open, so lay the document out again
+        self.renderer.reposition(&mut self.elements).unwrap();
         
         // Restore scroll position
         self.renderer.set_scroll_y(self.saved_scroll_y);
         self.window.request_redraw();
     }
 
-    fn update_file(&mut self, path: &Path, contents: String) {
-        self.window.set_title(&utils::format_title(path));
-        self.watcher.update_file(path, contents);
-        self.renderer.set_scroll_y(0.0);
+    fn report_lost_file(&mut self, reason: &str) {
+        let path = self.opts.history.get_path().display().to_string();
+        notification::send("Inlyne lost track of a file", &format!("{path} {reason}"));
+
+        self.lost_file = Some(format!(
+            "{path} {reason}. Click here to retry or Shift+Click to browse its folder"
+        ));
+        self.update_prompt_banner();
     }
 
-    pub fn run(mut self) {
-        let mut pending_resize = None;
-        let mut scrollbar_held = None;
-        let mut mouse_down = false;
-        let mut modifiers = ModifiersState::empty();
-        let mut mouse_position: Point = Point::default();
+    fn clear_lost_file(&mut self) {
+        self.reload_failures = 0;
+        if self.lost_file.take().is_some() {
+            self.update_prompt_banner();
+        }
+    }
 
-        let event_loop = self.event_loop.take().unwrap();
-        let event_loop_proxy = event_loop.create_proxy();
-        // SAFETY: Since this takes a pointer to the winit event loop, it MUST be dropped first,
-        // which is done by `move` into event loop.
-        let mut clipboard = unsafe { clipboard::Clipboard::new(event_loop.raw_display_handle()) };
-        event_loop.run(move |event, _, control_flow| {
-            *control_flow = ControlFlow::Wait;
+    /// Whether the banner is showing the lost file message, which is the only one that reacts
+    /// to clicks
+    fn banner_shows_lost_file(&self) -> bool {
+        self.lost_file.is_some() && self.toast.is_none() && !self.prompt_open()
+    }
 
-            match event {
-                Event::UserEvent(inlyne_event) => match inlyne_event {
-                    InlyneEvent::LoadedImage(src, image_data) => {
-                        self.image_cache.lock().insert(src, image_data);
-                        self.need_repositioning = true;
-                    }
-                    InlyneEvent::FileReload => match read_to_string(self.opts.history.get_path()) {
-                        Ok(contents) => {
-                            // Always update the content
-                            self.current_file_content = contents.clone();
-                            // Only reload if help isn't visible
-                            if !self.help_visible {
-                                self.load_file(contents);
-                            }
-                        }
-                        Err(err) => {
-                            tracing::warn!(
-                                "Failed reloading file at {}\nError: {}",
-                                self.opts.history.get_path().display(),
-                                err
-                            );
-                        }
-                    },
-                    InlyneEvent::FileChange { contents } => {
-                        // Always update the content
-                        self.current_file_content = contents.clone();
-                        // Only reload if help isn't visible
-                        if !self.help_visible {
-                            self.load_file(contents);
-                        }
-                    }
-                    InlyneEvent::Reposition => {
-                        self.need_repositioning = true;
-                    }
-                    InlyneEvent::PositionQueue => {
-                        Self::position_queued_elements(
-                            &self.element_queue,
-                            &mut self.renderer,
-                            &mut self.elements,
-                        );
-                        self.window.request_redraw()
-                    }
-                },
-                Event::RedrawRequested(_) => {
-                    let redraw_start = Instant::now();
-                    
-                    // Position the appropriate elements based on what's visible
-                    if self.help_visible {
-                        Self::position_queued_elements(
-                            &self.help_element_queue,
-                            &mut self.renderer,
-                            &mut self.help_elements,
-                        );
-                    } else {
-                        Self::position_queued_elements(
-                            &self.element_queue,
-                            &mut self.renderer,
-                            &mut self.elements,
-                        );
-                    }
-                    
-                    self.renderer.set_scroll_y(self.renderer.scroll_y);
-                    
-                    // Render the appropriate elements
-                    let elements_to_render = if self.help_visible {
-                        &mut self.help_elements
-                    } else {
-                        &mut self.elements
-                    };
-                    
-                    self.renderer
-                        .redraw(elements_to_render, &mut self.selection)
-                        .context("Renderer failed to redraw the screen")
-                        .unwrap();
+    /// Whether the banner is showing a prompt instead of other messages
+    fn prompt_open(&self) -> bool {
+        self.search.is_some()
+            || self.edit_mode
+            || self.history_list.is_some()
+            || self.confirm_run.is_some()
+            || self.heading_palette.is_some()
+            || self.context_menu.is_some()
+    }
 
-                    histogram!(HistTag::Redraw).record(redraw_start.elapsed());
-                }
-                Event::WindowEvent { event, .. } => match event {
-                    WindowEvent::Resized(size) => pending_resize = Some(size),
-                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::MouseWheel { delta, .. } => match delta {
-                        MouseScrollDelta::PixelDelta(pos) => {
-                            Self::scroll_pixels(&mut self.renderer, &self.window, pos.y as f32)
-                        }
-                        MouseScrollDelta::LineDelta(_, y_delta) => Self::scroll_lines(
-                            &mut self.renderer,
-                            &self.window,
-                            self.lines_to_scroll,
-                            y_delta,
-                        ),
-                    },
-                    WindowEvent::CursorMoved { position, .. } => {
-                        let screen_size = self.renderer.screen_size();
-                        let loc = (
-                            position.x as f32,
-                            position.y as f32 + self.renderer.scroll_y,
-                        );
+    /// Shows the run confirmation, heading palette, context menu, history list, edit, or search
+    /// prompt in the banner, falling back to the current toast and then the lost file message,
+    /// or removes it once none of them are open
+    fn update_prompt_banner(&mut self) {
+        let mut caret = None;
+        let prompt = if let Some(key) = &self.confirm_run {
+            self.code_runner.prompt(key)
+        } else if let Some(palette) = &self.heading_palette {
+            Some(palette.overlay())
+        } else if let Some(menu) = &self.context_menu {
+            Some(menu.overlay())
+        } else if let Some(selected) = self.history_list {
+            Some(self.opts.history.overlay(selected))
+        } else if let Some(edit) = &self.editing {
+            Some(edit.prompt())
+        } else if self.edit_mode {
+            Some("Edit mode: click a paragraph to edit it (Esc to leave)".to_owned())
+        } else if let Some(search) = &self.search {
+            caret = search.caret();
+            Some(search.prompt(&self.current_file_content))
+        } else {
+            self.toast
+                .as_ref()
+                .map(|toast| toast.message().to_owned())
+                .or_else(|| self.lost_file.clone())
+        };
 
-                        let cursor_icon = if let Some(hoverable) = Self::find_hoverable(
-                            &mut self.renderer.text_system,
-                            &mut self.renderer.positioner.taffy,
-                            &self.elements,
-                            loc,
-                            screen_size,
-                            self.renderer.zoom,
-                        ) {
-                            match hoverable {
-                                Hoverable::Image(Image { is_link: None, .. }) => {
-                                    CursorIcon::Default
-                                }
-                                Hoverable::Text(Text { link: None, .. }) => CursorIcon::Text,
-                                _some_link => CursorIcon::Hand,
-                            }
-                        } else {
-                            CursorIcon::Default
-                        };
-                        self.window.set_cursor_icon(cursor_icon);
+        let color = native_color(self.opts.theme.palette.text_color, &self.renderer.surface_format);
+        let hidpi_scale = self.renderer.hidpi_scale;
+        self.renderer.banner = prompt.map(|prompt| {
+            let text = Text::new(prompt, hidpi_scale, color);
+            TextBox::new(vec![text], hidpi_scale)
+        });
+        self.renderer.banner_caret = caret;
+        let typing_search = self.search.as_ref().is_some_and(|search| search.typing);
+        if typing_search != self.ime_allowed {
+            self.ime_allowed = typing_search;
+            self.window.set_ime_allowed(typing_search);
+        }
+        let caret_position = self.renderer.banner_caret_position();
+        if let Some((x, y)) = caret_position.filter(|_| typing_search) {
+            let position = winit::dpi::PhysicalPosition::new(x, y);
+            self.window.set_ime_position(position);
+        }
+        // An active search takes over from the `--find` query
+        let search_query = self
+            .search
+            .as_ref()
+            .map(|search| search.query.as_str())
+            .or_else(|| self.find_badge.as_ref().map(FindBadge::query))
+            .filter(|query| !query.is_empty())
+            .map(str::to_owned);
+        if search_query != self.renderer.search_query
+            || self.opts.search_options != self.renderer.search_options
+        {
+            self.current_match = None;
+            self.reloaded_match = None;
+            self.renderer.current_match.clear();
+        }
+        self.renderer.search_query = search_query;
+        self.renderer.search_options = self.opts.search_options;
+        self.window.request_redraw();
+    }
 
-                        let scrollbar_width = self.renderer.scrollbar_width();
-                        if scrollbar_held.is_some()
-                            || (Rect::new(
-                                (screen_size.0 - scrollbar_width, 0.),
-                                (scrollbar_width, screen_size.1),
-                            )
-                            .contains(position.into())
-                                && mouse_down)
-                        {
-                            let scrollbar_height = self.renderer.scrollbar_height();
-                            if scrollbar_held.is_none() {
-                                if Rect::new(
-                                    (
-                                        screen_size.0 - scrollbar_width,
-                                        ((self.renderer.scroll_y
-                                            / self.renderer.positioner.reserved_height)
-                                            * screen_size.1),
-                                    ),
-                                    (scrollbar_width, scrollbar_height),
-                                )
-                                .contains(position.into())
-                                {
-                                    // If we click in the bounds of the scrollbar, maintain the difference between the
-                                    // center of the scrollbar and the mouse
-                                    scrollbar_held = Some(
-                                        position.y as f32
-                                            - (((self.renderer.scroll_y
-                                                / self.renderer.positioner.reserved_height)
-                                                * screen_size.1)
-                                                + scrollbar_height / 2.),
-                                    );
-                                } else {
-                                    scrollbar_held = Some(0.);
-                                }
-                            }
+    /// Puts up a message in the banner that goes away on its own
+    fn show_toast(&mut self, message: String) {
+        self.toast = Some(Toast::new(message));
+        self.update_prompt_banner();
+    }
 
-                            let pos_y = if let Some(diff) = scrollbar_held {
-                                position.y as f32 - diff
-                            } else {
-                                position.y as f32
-                            };
-                            let target_scroll = ((pos_y - scrollbar_height / 2.) / screen_size.1)
-                                * self.renderer.positioner.reserved_height;
-                            self.renderer.set_scroll_y(target_scroll);
-                            self.window.request_redraw();
-                        } else if mouse_down && self.selection.handle_drag(loc) {
-                            self.window.request_redraw();
-                        }
-                        mouse_position = loc;
-                    }
-                    WindowEvent::MouseInput {
-                        state,
-                        button: MouseButton::Left,
-                        ..
-                    } => match state {
-                        ElementState::Pressed => {
-                            // Try to click a link
-                            let screen_size = self.renderer.screen_size();
+    /// Describes the element under `loc` in a toast, for debugging the layout
+    fn inspect_at(&mut self, loc: Point) {
+        let spacing = &self.renderer.element_spacing;
+        let Some(info) = inspect::element_at(&self.elements, spacing, loc) else {
+            return;
+        };
+        let message = info.to_string();
+        if self.toast.as_ref().map(Toast::message) != Some(message.as_str()) {
+            self.show_toast(message);
+        }
+    }
 
-                            let y = mouse_position.1 - self.renderer.scroll_y;
-                            let scrollbar_width = self.renderer.scrollbar_width();
-                            if Rect::new(
-                                (screen_size.0 - scrollbar_width, 0.),
-                                (scrollbar_width, screen_size.1),
-                            ).contains((mouse_position.0, y)) {
-                                let scrollbar_height = self.renderer.scrollbar_height();
+    /// Jumps to the next or previous search match, scrolling it to `search_match_offset` down the
+    /// window. Without a current match this starts from the view instead
+    fn goto_match(&mut self, direction: VertDirection) {
+        let matches = match self.renderer.search_matches(&self.elements) {
+            Ok(matches) => matches,
+            Err(err) => {
+                tracing::warn!("Failed finding search matches: {err}");
+                return;
+            }
+        };
+        if matches.is_empty() {
+            return;
+        }
 
-                                let target_scroll = ((y - scrollbar_height / 2.) / screen_size.1)
-                                    * self.renderer.positioner.reserved_height;
+        let scroll_y = self.renderer.scroll_y;
+        let top = |rects: &Vec<Rect>| rects[0].pos.1;
+        let index = match (self.current_match, direction) {
+            (Some(current), VertDirection::Down) => (current + 1) % matches.len(),
+            (Some(current), VertDirection::Up) => (current + matches.len() - 1) % matches.len(),
+            (None, VertDirection::Down) => matches
+                .iter()
+                .position(|rects| top(rects) >= scroll_y)
+                .unwrap_or(0),
+            (None, VertDirection::Up) => matches
+                .iter()
+                .rposition(|rects| top(rects) < scroll_y)
+                .unwrap_or(matches.len() - 1),
+        };
+        let rects = &matches[index];
+        let min_y = top(rects);
+        let max_y = rects.last().map_or(min_y, |rect| rect.max().1);
+        let offset = self.opts.search_match_offset * self.renderer.screen_height();
+        self.renderer.set_scroll_y((min_y + max_y) / 2. - offset);
+        self.current_match = Some(index);
+        self.reloaded_match = None;
+        self.renderer.current_match = rects.clone();
+        self.window.request_redraw();
+    }
 
-                                self.renderer.set_scroll_y(target_scroll);
-                                self.window.request_redraw();
+    /// Lays the current search match out again after the document moved around underneath it
+    fn refresh_current_match(&mut self) {
+        // The document is still being reloaded, so the match might not be there yet
+        if self.reloaded_match.is_some() {
+            return;
+        }
+        let Some(index) = self.current_match else {
+            return;
+        };
+        let matches = self
+            .renderer
+            .search_matches(&self.elements)
+            .unwrap_or_default();
+        match matches.get(index) {
+            Some(rects) => self.renderer.current_match = rects.clone(),
+            None => {
+                self.current_match = None;
+                self.renderer.current_match.clear();
+            }
+        }
+    }
+
+    /// Finds the current search match again once a reloaded document is laid out, keeping its
+    /// highlight instead of dropping it
+    fn remap_current_match(&mut self) {
+        let Some(anchor) = self.reloaded_match.take() else {
+            return self.refresh_current_match();
+        };
+        let matches = self
+            .renderer
+            .search_matches(&self.elements)
+            .unwrap_or_default();
+        self.current_match = anchor.find(&matches);
+        match self.current_match {
+            Some(index) => self.renderer.current_match = matches[index].clone(),
+            None => self.renderer.current_match.clear(),
+        }
+    }
+
+    /// Changes how searches match, including the one that's open and the `--find` badge's
+    fn set_search_options(&mut self, options: SearchOptions) {
+        self.opts.search_options = options;
+        if let Some(search) = &mut self.search {
+            search.options = options;
+        }
+        if let Some(badge) = &mut self.find_badge {
+            badge.set_options(options);
+        }
+        self.update_prompt_banner();
+    }
+
+    /// Refreshes the `--find` badge's match count and where the view is among the matches
+    fn update_find_badge(&mut self) {
+        let Some(badge) = &mut self.find_badge else {
+            return;
+        };
+        if self.help_visible {
+            self.renderer.badge = None;
+            return;
+        }
+
+        badge.update(&self.current_file_content, self.loaded_hash);
+        // The inverse of `scroll_to_line`'s mapping
+        let scroll_y = self.renderer.scroll_y;
+        let top_line = source_map::line_at(&self.elements, scroll_y).unwrap_or_else(|| {
+            let total_lines = self.current_file_content.lines().count().max(1);
+            let fraction = scroll_y / self.renderer.positioner.reserved_height.max(1.);
+            (fraction * total_lines as f32).round() as usize + 1
+        });
+
+        let color = native_color(self.opts.theme.palette.text_color, &self.renderer.surface_format);
+        let hidpi_scale = self.renderer.hidpi_scale;
+        let text = Text::new(badge.text(top_line), hidpi_scale, color);
+        self.renderer.badge = Some(TextBox::new(vec![text], hidpi_scale));
+    }
+
+    /// Swaps the text box at `index` for its markdown source, ready for editing
+    fn start_edit(&mut self, index: usize) {
+        let Some(Element::TextBox(text_box)) = self.elements.get(index).map(|e| &e.inner) else {
+            return;
+        };
+        let source = &self.current_file_content;
+        match Edit::for_paragraph(source, &text_box.plain_text(), text_box.source_lines) {
+            Ok(edit) => {
+                self.edited_element = Some((index, text_box.clone()));
+                self.editing = Some(edit);
+                self.update_inline_edit();
+            }
+            Err(err) => self.show_toast(format!("{err:#}")),
+        }
+    }
+
+    /// Shows the edited source where its paragraph was, with a caret at the end
+    fn update_inline_edit(&mut self) {
+        let (Some(edit), Some((index, original))) = (&self.editing, &self.edited_element) else {
+            return;
+        };
+        let palette = &self.opts.theme.palette;
+        let format = &self.renderer.surface_format;
+        let text = Text::new(
+            format!("{}▏", edit.text),
+            original.hidpi_scale,
+            native_color(palette.text_color, format),
+        )
+        .with_family(FamilyOwned::Monospace);
+        let mut editor = TextBox::new(vec![text], original.hidpi_scale);
+        editor.indent = original.indent;
+        editor.source_lines = original.source_lines;
+        editor.set_background_color(native_color(palette.quote_block_color, format));
+        if let Some(element) = self.elements.get_mut(*index) {
+            element.inner = Element::TextBox(editor);
+        }
+        self.renderer.reposition(&mut self.elements).unwrap();
+        self.update_prompt_banner();
+        self.window.request_redraw();
+    }
+
+    /// Puts the edited paragraph back the way it was
+    fn cancel_edit(&mut self) {
+        self.editing = None;
+        if let Some((index, original)) = self.edited_element.take() {
+            if let Some(element) = self.elements.get_mut(index) {
+                element.inner = Element::TextBox(original);
+            }
+            self.renderer.reposition(&mut self.elements).unwrap();
+            self.renderer.clamp_scroll_y();
+        }
+        self.update_prompt_banner();
+        self.window.request_redraw();
+    }
+
+    /// Writes the edited paragraph back to the file, which then reloads like any other change
+    fn save_edit(&mut self) {
+        let Some(edit) = &self.editing else {
+            return;
+        };
+        let path = self.opts.history.get_path();
+        let saved = edit.apply(&self.current_file_content).and_then(|contents| {
+            std::fs::write(path, contents)
+                .with_context(|| format!("Failed writing to '{}'", path.display()))
+        });
+        match saved {
+            // The edited source stays up until the reload replaces it
+            Ok(()) => {
+                self.editing = None;
+                self.edited_element = None;
+                self.update_prompt_banner();
+            }
+            Err(err) => {
+                tracing::error!("Could not save the edit: {err:#}");
+                self.cancel_edit();
+                self.show_toast(format!("Could not save the edit: {err:#}"));
+            }
+        }
+    }
+
+    /// Shows the markdown source beside the document, or goes back to only the document
+    fn toggle_split_view(&mut self) {
+        // Both halves are already taken while showing another document side by side
+        if self.renderer.side_pane.is_some() {
+            return;
+        }
+        let positioner = &mut self.renderer.positioner;
+        positioner.viewport = if positioner.viewport == Viewport::FULL {
+            Viewport::RIGHT_HALF
+        } else {
+            Viewport::FULL
+        };
+        self.update_source_pane();
+        self.renderer.reposition(&mut self.elements).unwrap();
+        self.renderer.clamp_scroll_y();
+        self.window.request_redraw();
+    }
+
+    fn update_source_pane(&mut self) {
+        let color = native_color(self.opts.theme.palette.code_color, &self.renderer.surface_format);
+        let hidpi_scale = self.renderer.hidpi_scale;
+        let split_view = self.renderer.positioner.viewport == Viewport::RIGHT_HALF;
+        self.renderer.source_pane = split_view.then(|| {
+            let text = Text::new(self.current_file_content.clone(), hidpi_scale, color)
+                .with_family(FamilyOwned::Monospace);
+            TextBox::new(vec![text], hidpi_scale)
+        });
+    }
+
+    /// Scrolls to where a line of the markdown source ended up
+    ///
+    /// Documents where no element knows its source lines fall back to mapping the line
+    /// proportionally onto the laid out document
+    fn scroll_to_line(&mut self, line: usize) {
+        let y = source_map::line_y(&self.elements, line).unwrap_or_else(|| {
+            let total_lines = self.current_file_content.lines().count().max(1);
+            let fraction = line.saturating_sub(1) as f32 / total_lines as f32;
+            fraction * self.renderer.positioner.reserved_height
+        });
+        self.renderer.set_scroll_y(y);
+        self.window.request_redraw();
+    }
+
+    /// Lets the user know about new contents while they're looking at some other window
+    fn request_reload_attention(&self) {
+        if self.window.has_focus() {
+            return;
+        }
+        match self.opts.reload_attention {
+            ReloadAttention::None => {}
+            ReloadAttention::Flash => {
+                self.window.request_user_attention(Some(UserAttentionType::Informational));
+            }
+            ReloadAttention::Raise => self.window.focus_window(),
+        }
+    }
+
+    /// Displays the new contents unless they're identical to what's already shown, like when an
+    /// editor only touched the file's mtime. This avoids the flicker of a full reload
+    fn reload_contents(&mut self, contents: String) {
+        if fxhash::hash64(&contents) == self.loaded_hash {
+            tracing::debug!("Skipping reload of unchanged contents");
+            self.current_file_content = contents;
+            return;
+        }
+
+        self.request_reload_attention();
+        // Always update the content
+        let previous = std::mem::replace(&mut self.current_file_content, contents.clone());
+        // Only reload if help isn't visible
+        if self.help_visible {
+            return;
+        }
+        // Only the new blocks get interpreted when a file grows in tail mode
+        let appended = if self.opts.tail && fxhash::hash64(&previous) == self.loaded_hash {
+            interpreter::appended_blocks(&previous, &contents)
+        } else {
+            None
+        };
+        match appended {
+            Some(appended) => {
+                self.loaded_hash = fxhash::hash64(&contents);
+                let update = MdUpdate::Append(appended.to_owned());
+                self.interpreter_sender.send(update).unwrap();
+                if self.prompt_open() {
+                    self.update_prompt_banner();
+                }
+                self.update_source_pane();
+            }
+            None => self.load_file(contents),
+        }
+    }
+
+    /// Keeps the view on the end of the document in tail mode until the user scrolls up, and
+    /// pins it again once they scroll back down
+    fn pin_to_tail(&mut self) {
+        if !self.opts.tail || self.help_visible {
+            return;
+        }
+        let renderer = &mut self.renderer;
+        let at_bottom = renderer.scroll_y >= renderer.max_scroll_y().floor();
+        // The document growing never moves the view up, so that was the user
+        let scrolled_up = self
+            .tail_pinned_at
+            .is_some_and(|pinned_at| renderer.scroll_y < pinned_at);
+        if scrolled_up && !at_bottom {
+            self.tail_pinned_at = None;
+        } else if self.tail_pinned_at.is_some() || at_bottom {
+            renderer.set_scroll_y(f32::INFINITY);
+            self.tail_pinned_at = Some(renderer.scroll_y);
+        }
+    }
+
+    fn update_file(&mut self, path: &Path, contents: String, encoding: Encoding) {
+        self.encoding = encoding;
+        // The document's own title gets filled in once it's been interpreted
+        let title = utils::format_title(&self.opts.title_template, path, None, encoding);
+        self.window.set_title(&title);
+        self.watcher.update_file(path, contents);
+        let view = self.saved_view();
+        let restore = self
+            .scroll_memory
+            .as_mut()
+            .and_then(|memory| memory.switch(path, view));
+        self.renderer.set_scroll_y(0.0);
+        self.jump_list.clear();
+        self.restore_progress = restore.map(|view| view.progress);
+        if let Some(view) = restore {
+            self.renderer.zoom = view.zoom;
+        }
+    }
+
+    /// Sets the window title, looking for the document's own title again if the contents
+    /// changed since the last time
+    fn update_title(&mut self) {
+        let (titled_hash, doc_title) = &mut self.doc_title;
+        if *titled_hash != self.loaded_hash {
+            *titled_hash = self.loaded_hash;
+            *doc_title = utils::document_title(&self.current_file_content);
+        }
+        let title = utils::format_title(
+            &self.opts.title_template,
+            self.opts.history.get_path(),
+            doc_title.as_deref(),
+            self.encoding,
+        );
+        self.window.set_title(&title);
+    }
+
+    /// Reads the current file from disk again
+    fn reload_file(&mut self) {
+        match read_with_encoding(self.opts.history.get_path()) {
+            Ok((contents, encoding)) => {
+                if encoding != self.encoding {
+                    self.encoding = encoding;
+                    self.update_title();
+                }
+                self.clear_lost_file();
+                self.reload_contents(contents);
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Failed reloading file at {}\nError: {}",
+                    self.opts.history.get_path().display(),
+                    err
+                );
+                self.reload_failures += 1;
+                if self.reload_failures == MAX_RELOAD_FAILURES {
+                    self.report_lost_file("repeatedly failed to load");
+                }
+            }
+        }
+    }
+
+    /// Where the view is in the current document, for picking back up there later
+    fn saved_view(&self) -> SavedView {
+        let renderer = &self.renderer;
+        SavedView::new(
+            renderer.scroll_y,
+            renderer.positioner.reserved_height,
+            renderer.zoom,
+        )
+    }
+
+    /// Shows the next document in the `--cycle` rotation
+    fn cycle_to_next(&mut self) {
+        let Some(cycle) = &mut self.cycle else {
+            return;
+        };
+        let file_path = cycle.advance(self.renderer.scroll_y).to_owned();
+        match read_with_encoding(&file_path) {
+            Ok((contents, encoding)) => {
+                self.update_file(&file_path, contents.clone(), encoding);
+                self.opts.history.replace_current(file_path.clone());
+                if let Some(parent) = file_path.parent() {
+                    let _ = std::env::set_current_dir(parent);
+                }
+                // Loaded right away so the scroll position gets restored against the new document
+                self.load_file(contents);
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Failed loading markdown file at {}\nError: {}",
+                    file_path.display(),
+                    err,
+                );
+            }
+        }
+    }
+
+    /// Loads the file that the history moved to
+    fn open_history_entry(&mut self, file_path: PathBuf) {
+        match read_with_encoding(&file_path) {
+            Ok((contents, encoding)) => {
+                self.update_file(&file_path, contents, encoding);
+                let parent = file_path.parent().expect("File should have parent directory");
+                std::env::set_current_dir(parent).expect("Could not set current directory.");
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Failed loading markdown file at {}\nError: {}",
+                    file_path.display(),
+                    err,
+                );
+            }
+        }
+    }
+
+    fn save_history(&self) {
+        if let Err(err) = self.opts.history.save_session() {
+            tracing::warn!("Failed saving history: {err}");
+        }
+    }
+
+    fn save_scroll_memory(&mut self) {
+        let view = self.saved_view();
+        if let Some(memory) = &mut self.scroll_memory {
+            memory.record(view);
+            if let Err(err) = memory.save() {
+                tracing::warn!("Failed saving scroll positions: {err}");
+            }
+        }
+    }
+
+    /// Cleans up before the window closes
+    fn on_exit(&mut self) {
+        self.save_history();
+        self.save_scroll_memory();
+        if let Some(follow) = &self.follow {
+            follow.leave();
+        }
+    }
+
+    /// Zooms while keeping the same part of the document in view
+    fn set_zoom(&mut self, zoom: f32) {
+        self.renderer.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.reposition_in_place();
+    }
+
+    /// Switches to or from distraction-free reading
+    fn toggle_zen_mode(&mut self) {
+        self.renderer.profile = self.renderer.profile.toggle_zen(self.opts.zen_measure);
+        self.reposition_in_place();
+    }
+
+    /// Lays everything out again, keeping the view at the same place relative to the document
+    fn reposition_in_place(&mut self) {
+        let old_reserved = self.renderer.positioner.reserved_height;
+        self.renderer.reposition(&mut self.elements).unwrap();
+        let new_reserved = self.renderer.positioner.reserved_height;
+        self.renderer.set_scroll_y(self.renderer.scroll_y * (new_reserved / old_reserved));
+        self.reposition_side_pane();
+        self.window.request_redraw();
+    }
+
+    /// Positions what the side-by-side document's interpreter has queued up. Returns `true` if
+    /// elements are still pending
+    #[must_use]
+    fn position_side_document(&mut self) -> bool {
+        let Some(side_document) = &self.side_document else {
+            return false;
+        };
+        let queue = &side_document.element_queue;
+        self.renderer
+            .with_side_pane(|renderer, elements| {
+                let pending = Self::position_queued_elements(queue, renderer, elements);
+                renderer.clamp_scroll_y();
+                pending
+            })
+            .unwrap_or_default()
+    }
+
+    /// Lays the side-by-side document out again, keeping it scrolled to the same spot
+    fn reposition_side_pane(&mut self) {
+        self.renderer.with_side_pane(|renderer, elements| {
+            let old_reserved = renderer.positioner.reserved_height;
+            renderer.reposition(elements).unwrap();
+            let new_reserved = renderer.positioner.reserved_height;
+            renderer.set_scroll_y(renderer.scroll_y * (new_reserved / old_reserved));
+        });
+    }
+
+    /// The zoom that makes the widest table, code block or image in view exactly fit the window
+    fn fit_width_zoom(&mut self) -> Option<f32> {
+        let elements = if self.help_visible {
+            &mut self.help_elements
+        } else {
+            &mut self.elements
+        };
+        let renderer = &mut self.renderer;
+        let positioner = &renderer.positioner;
+        let available = positioner.viewport_width().min(positioner.page_width)
+            - 2. * positioner.page_margin
+            - renderer.scrollbar_width();
+        let top = renderer.scroll_y;
+        let view = (top, top + renderer.screen_height());
+        if available <= 0. {
+            return None;
+        }
+
+        // Indents and padding don't scale along with the zoom, so the first guess gets refined
+        let mut zoom = renderer.zoom;
+        for _ in 0..2 {
+            let widest = Self::widest_in_view(renderer, elements, view, zoom);
+            if widest <= 0. {
+                return None;
+            }
+            zoom *= available / widest;
+        }
+        Some(zoom)
+    }
+
+    /// How wide the widest table, code block or image that's at least partly in view would be
+    /// laid out without a width limit
+    fn widest_in_view(
+        renderer: &mut Renderer,
+        elements: &mut [Positioned<Element>],
+        (top, bottom): (f32, f32),
+        zoom: f32,
+    ) -> f32 {
+        let mut widest: f32 = 0.;
+        for element in elements {
+            let Some(bounds) = &element.bounds else {
+                continue;
+            };
+            if bounds.pos.1 + bounds.size.1 < top || bounds.pos.1 > bottom {
+                continue;
+            }
+            let is_wide = match &mut element.inner {
+                Element::Table(_) | Element::Image(_) | Element::Row(_) => true,
+                Element::TextBox(text_box) => text_box.is_code_block,
+                Element::Section(section) => {
+                    if !*section.hidden.borrow() {
+                        let elements = &mut section.elements;
+                        let width = Self::widest_in_view(renderer, elements, (top, bottom), zoom);
+                        widest = widest.max(width);
+                    }
+                    false
+                }
+                Element::Spacer(_) | Element::Math(_) => false,
+            };
+            if !is_wide {
+                continue;
+            }
+            match renderer.positioner.natural_width(
+                &mut renderer.text_system,
+                std::slice::from_mut(element),
+                zoom,
+                &renderer.element_spacing,
+            ) {
+                Ok(width) => widest = widest.max(width),
+                Err(err) => tracing::warn!("Failed measuring an element: {err:#}"),
+            }
+        }
+        widest
+    }
+
+    /// Resizes the window to the document's natural width and height, within the bounds of the
+    /// monitor
+    fn fit_window_to_content(&mut self) {
+        let Some(monitor) = self.window.current_monitor() else {
+            return;
+        };
+        let elements = if self.help_visible {
+            &mut self.help_elements
+        } else {
+            &mut self.elements
+        };
+        let renderer = &mut self.renderer;
+        let content_width = match renderer.positioner.natural_width(
+            &mut renderer.text_system,
+            elements,
+            renderer.zoom,
+            &renderer.element_spacing,
+        ) {
+            Ok(width) => width,
+            Err(err) => {
+                tracing::warn!("Failed measuring the document: {err:#}");
+                return;
+            }
+        };
+
+        let positioner = &renderer.positioner;
+        let mut width = content_width.min(positioner.page_width)
+            + 2. * positioner.page_margin
+            + renderer.scrollbar_width();
+        width /= positioner.viewport.width();
+        // Leave some room for panels and window decorations
+        let limit = monitor.size();
+        let width = width.min(limit.width as f32 * 0.9);
+        let height = positioner.reserved_height.min(limit.height as f32 * 0.9);
+        self.window.set_inner_size(winit::dpi::PhysicalSize::new(
+            width.ceil() as u32,
+            height.ceil() as u32,
+        ));
+    }
+
+    /// Lets the other windows in follow mode know if this one moved since they last heard
+    fn send_followed_view(&mut self) {
+        let Some(follow) = &self.follow else {
+            return;
+        };
+        let view = (self.renderer.scroll_y, self.renderer.zoom);
+        if view != self.followed_view {
+            self.followed_view = view;
+            follow.send(ViewState {
+                scroll_fraction: self.renderer.scroll_fraction(),
+                zoom: self.renderer.zoom,
+            });
+        }
+    }
+
+    fn follow_view(&mut self, view: ViewState) {
+        if view.zoom != self.renderer.zoom {
+            self.set_zoom(view.zoom);
+        }
+        self.renderer.set_scroll_fraction(view.scroll_fraction);
+        // Following shouldn't get echoed back to the window that's leading
+        self.followed_view = (self.renderer.scroll_y, self.renderer.zoom);
+        self.window.request_redraw();
+    }
+
+    /// The laid out document's headings in order
+    fn headings(&self) -> Vec<Heading> {
+        fold::visible_headings(&self.elements)
+            .into_iter()
+            .filter_map(|element| match &element.inner {
+                Element::TextBox(text_box) => {
+                    let anchor = text_box.is_anchor.clone()?;
+                    let text = text_box.texts.iter().map(|t| t.text.as_str()).collect();
+                    Some(Heading { text, anchor })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Scrolls to the heading selected in the palette and closes it
+    fn jump_to_heading(&mut self) {
+        let Some(palette) = self.heading_palette.take() else {
+            return;
+        };
+        let anchor_pos = palette
+            .selected()
+            .and_then(|heading| self.renderer.positioner.anchors.get(&heading.anchor));
+        if let Some(&anchor_pos) = anchor_pos {
+            self.jump_to(anchor_pos);
+        }
+        self.update_prompt_banner();
+    }
+
+    /// Scrolls to `scroll_y`, remembering where the view was to be able to come back to it
+    fn jump_to(&mut self, scroll_y: f32) {
+        self.jump_list.push(self.saved_view().progress);
+        self.renderer.set_scroll_y(scroll_y);
+        self.window.request_redraw();
+    }
+
+    /// Goes back or forward through the places that in-document links were followed from
+    fn jump_back_or_forward(&mut self, direction: HistDirection) {
+        let progress = self.saved_view().progress;
+        let target = match direction {
+            HistDirection::Prev => self.jump_list.back(progress),
+            HistDirection::Next => self.jump_list.forward(progress),
+        };
+        if let Some(target) = target {
+            let reserved_height = self.renderer.positioner.reserved_height;
+            self.renderer.set_scroll_y(target * reserved_height);
+            self.window.request_redraw();
+        }
+    }
+
+    /// Moves `count` files through the history, stopping early at either end
+    fn step_history(&mut self, direction: HistDirection, count: usize) {
+        let mut changed_path = None;
+        for _ in 0..count {
+            let step = match direction {
+                HistDirection::Next => self.opts.history.next(),
+                HistDirection::Prev => self.opts.history.previous(),
+            };
+            match step {
+                Some(path) => changed_path = Some(path.to_owned()),
+                None => break,
+            }
+        }
+        if let Some(file_path) = changed_path {
+            self.open_history_entry(file_path);
+        }
+    }
+
+    /// Jumps to the history entry at `index`, closing the history list if it's open
+    fn jump_history(&mut self, index: usize) {
+        if self.history_list.take().is_some() {
+            self.update_prompt_banner();
+        }
+        if let Some(file_path) = self.opts.history.jump(index).map(ToOwned::to_owned) {
+            self.open_history_entry(file_path);
+        }
+    }
+
+    pub fn run(mut self) {
+        let mut pending_resize = None;
+        let mut scrollbar_held = None;
+        let mut mouse_down = false;
+        let mut modifiers = ModifiersState::empty();
+        let mut mouse_position: Point = Point::default();
+
+        let event_loop = self.event_loop.take().unwrap();
+        let event_loop_proxy = event_loop.create_proxy();
+        // SAFETY: Since this takes a pointer to the winit event loop, it MUST be dropped first,
+        // which is done by `move` into event loop.
+        let mut clipboard = unsafe { clipboard::Clipboard::new(event_loop.raw_display_handle()) };
+        event_loop.run(move |event, _, control_flow| {
+            *control_flow = ControlFlow::Wait;
+            let wake_up = [
+                self.kiosk.as_ref().and_then(Kiosk::next_tick),
+                self.cycle.as_ref().map(Cycle::next_switch),
+                self.idle_cursor.as_ref().and_then(IdleCursor::next_hide),
+                self.search.as_ref().and_then(Search::next_blink),
+                self.toast.as_ref().map(Toast::expires),
+            ];
+            if let Some(wake_up) = wake_up.into_iter().flatten().min() {
+                *control_flow = ControlFlow::WaitUntil(wake_up);
+            }
+            // Timers on their own only touch the banner or scroll position, which the renderer
+            // notices by itself, so those frames can reuse the document as it was last drawn
+            if matches!(
+                event,
+                Event::UserEvent(_) | Event::WindowEvent { .. } | Event::Resumed
+            ) {
+                self.renderer.invalidate_document();
+            }
+
+            match event {
+                Event::UserEvent(inlyne_event) => match inlyne_event {
+                    InlyneEvent::LoadedImage(src, image_data) => {
+                        self.image_cache.lock().insert(src, image_data);
+                        self.need_repositioning = true;
+                    }
+                    InlyneEvent::FileReload => self.reload_file(),
+                    InlyneEvent::FileChange { contents } => {
+                        self.clear_lost_file();
+                        self.reload_contents(contents);
+                    }
+                    InlyneEvent::FileLost => {
+                        self.report_lost_file("was moved or deleted");
+                    }
+                    InlyneEvent::FileRenamed(new_path) => {
+                        if let Some(parent) = new_path.parent() {
+                            let _ = std::env::set_current_dir(parent);
+                        }
+                        self.opts.history.replace_current(new_path);
+                        self.update_title();
+                        event_loop_proxy
+                            .send_event(InlyneEvent::FileReload)
+                            .unwrap();
+                    }
+                    InlyneEvent::Reposition => {
+                        self.need_repositioning = true;
+                    }
+                    InlyneEvent::PositionQueue => {
+                        if self.position_side_document() {
+                            event_loop_proxy
+                                .send_event(InlyneEvent::PositionQueue)
+                                .unwrap();
+                        }
+                        let (queue, elements) = if self.help_visible {
+                            (&self.help_element_queue, &mut self.help_elements)
+                        } else {
+                            (&self.element_queue, &mut self.elements)
+                        };
+                        if Self::position_queued_elements(queue, &mut self.renderer, elements) {
+                            event_loop_proxy
+                                .send_event(InlyneEvent::PositionQueue)
+                                .unwrap();
+                        } else if !self.help_visible {
+                            self.update_title();
+                            if let Some(progress) = self.restore_progress.take() {
+                                let reserved_height = self.renderer.positioner.reserved_height;
+                                self.renderer.set_scroll_y(progress * reserved_height);
+                            }
+                            if let Some(line) = self.goto_line.take() {
+                                self.scroll_to_line(line);
+                            }
+                            if std::mem::take(&mut self.fit_pending) {
+                                self.fit_window_to_content();
+                            }
+                            self.remap_current_match();
+                        }
+                        self.window.request_redraw()
+                    }
+                    InlyneEvent::FollowView(view) => self.follow_view(view),
+                    InlyneEvent::CodeRunFinished => {
+                        self.load_file(self.current_file_content.clone());
+                    }
+                    InlyneEvent::Toast(message) => self.show_toast(message),
+                },
+                Event::RedrawRequested(_) => {
+                    let redraw_start = Instant::now();
+                    
+                    // Position the appropriate elements based on what's visible
+                    let pending = if self.help_visible {
+                        Self::position_queued_elements(
+                            &self.help_element_queue,
+                            &mut self.renderer,
+                            &mut self.help_elements,
+                        )
+                    } else {
+                        Self::position_queued_elements(
+                            &self.element_queue,
+                            &mut self.renderer,
+                            &mut self.elements,
+                        )
+                    };
+                    let side_pending = self.position_side_document();
+                    // Draw what we have now and keep laying out the rest in the background
+                    if pending || side_pending {
+                        event_loop_proxy
+                            .send_event(InlyneEvent::PositionQueue)
+                            .unwrap();
+                    }
+                    
+                    self.renderer.clamp_scroll_y();
+                    self.pin_to_tail();
+                    if !self.help_visible {
+                        self.send_followed_view();
+                        let max_scroll_y = self.renderer.max_scroll_y();
+                        let restore = self
+                            .cycle
+                            .as_mut()
+                            .and_then(|cycle| cycle.take_restore(max_scroll_y));
+                        if let Some(scroll_y) = restore {
+                            self.renderer.set_scroll_y(scroll_y);
+                        }
+                    }
+                    
+                    self.update_find_badge();
+
+                    // Render the appropriate elements
+                    let elements_to_render = if self.help_visible {
+                        &mut self.help_elements
+                    } else {
+                        &mut self.elements
+                    };
+                    
+                    self.renderer
+                        .redraw(elements_to_render, &mut self.selection)
+                        .context("Renderer failed to redraw the screen")
+                        .unwrap();
+
+                    histogram!(HistTag::Redraw).record(redraw_start.elapsed());
+                }
+                Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                    if self.search.as_ref().is_some_and(|search| search.typing) {
+                        self.update_prompt_banner();
+                    }
+                    if let Some(idle_cursor) = &mut self.idle_cursor {
+                        idle_cursor.tick(&self.window);
+                    }
+                    if self.toast.as_ref().is_some_and(Toast::is_expired) {
+                        self.toast = None;
+                        self.update_prompt_banner();
+                    }
+                    if let Some(kiosk) = &mut self.kiosk {
+                        if kiosk.tick(&mut self.renderer) {
+                            self.window.request_redraw();
+                        }
+                    }
+                    if let Some(cycle) = &mut self.cycle {
+                        if cycle.next_switch() <= Instant::now() {
+                            if self.help_visible {
+                                cycle.restart_timer();
+                            } else {
+                                self.cycle_to_next();
+                                self.renderer.invalidate_document();
+                            }
+                        }
+                    }
+                }
+                Event::WindowEvent { ref event, .. }
+                    if self.kiosk.is_some() && kiosk::is_input(event) =>
+                {
+                    if kiosk::is_quit_combo(event, modifiers) {
+                        self.on_exit();
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+                Event::WindowEvent { event, .. } => match event {
+                    WindowEvent::Resized(size) => pending_resize = Some(size),
+                    WindowEvent::CloseRequested => {
+                        self.on_exit();
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    WindowEvent::MouseWheel { delta, .. } => {
+                        let direction = if self.opts.invert_scroll { -1.0 } else { 1.0 };
+                        let touchpad_multiplier = self.opts.touchpad_multiplier;
+                        let wheel_multiplier = self.opts.wheel_multiplier;
+                        let lines_to_scroll = self.lines_to_scroll;
+                        self.scroll_hovered(mouse_position.0, |renderer, window, _| match delta {
+                            MouseScrollDelta::PixelDelta(pos) => Self::scroll_pixels(
+                                renderer,
+                                window,
+                                pos.y as f32 * touchpad_multiplier * direction,
+                            ),
+                            MouseScrollDelta::LineDelta(_, y_delta) => Self::scroll_lines(
+                                renderer,
+                                window,
+                                lines_to_scroll,
+                                y_delta * wheel_multiplier * direction,
+                            ),
+                        });
+                    }
+                    WindowEvent::CursorMoved { position, .. } => {
+                        if let Some(idle_cursor) = &mut self.idle_cursor {
+                            idle_cursor.moved(&self.window);
+                        }
+                        let screen_size = self.renderer.screen_size();
+                        let loc = (
+                            position.x as f32,
+                            position.y as f32 + self.renderer.scroll_y,
+                        );
+
+                        let cursor_icon = if let Some(hoverable) = Self::find_hoverable(
+                            &mut self.renderer.text_system,
+                            &mut self.renderer.positioner.taffy,
+                            &self.elements,
+                            loc,
+                            screen_size,
+                            self.renderer.zoom,
+                        ) {
+                            match hoverable {
+                                Hoverable::Image(image @ Image { is_link: None, .. }) => {
+                                    let positioner = &self.renderer.positioner;
+                                    if positioner.is_image_shrunk(image, self.renderer.zoom) {
+                                        CursorIcon::ZoomIn
+                                    } else {
+                                        CursorIcon::Default
+                                    }
+                                }
+                                Hoverable::Text(Text { link: None, .. }) => CursorIcon::Text,
+                                _some_link => CursorIcon::Hand,
+                            }
+                        } else {
+                            CursorIcon::Default
+                        };
+                        self.window.set_cursor_icon(cursor_icon);
+
+                        if modifiers.alt() && opts::get_render_element_bounds() {
+                            self.inspect_at(loc);
+                        }
+
+                        let scrollbar_width = self.renderer.scrollbar_width();
+                        let scrollbar_x = screen_size.0
+                            - self.renderer.positioner.right_inset()
+                            - scrollbar_width;
+                        if scrollbar_held.is_some()
+                            || (Rect::new((scrollbar_x, 0.), (scrollbar_width, screen_size.1))
+                                .contains(position.into())
+                                && mouse_down)
+                        {
+                            let scrollbar_height = self.renderer.scrollbar_height();
+                            if scrollbar_held.is_none() {
+                                if Rect::new(
+                                    (
+                                        scrollbar_x,
+                                        ((self.renderer.scroll_y
+                                            / self.renderer.positioner.reserved_height)
+                                            * screen_size.1),
+                                    ),
+                                    (scrollbar_width, scrollbar_height),
+                                )
+                                .contains(position.into())
+                                {
+                                    // If we click in the bounds of the scrollbar, maintain the difference between the
+                                    // center of the scrollbar and the mouse
+                                    scrollbar_held = Some(
+                                        position.y as f32
+                                            - (((self.renderer.scroll_y
+                                                / self.renderer.positioner.reserved_height)
+                                                * screen_size.1)
+                                                + scrollbar_height / 2.),
+                                    );
+                                } else {
+                                    scrollbar_held = Some(0.);
+                                }
+                            }
+
+                            let pos_y = if let Some(diff) = scrollbar_held {
+                                position.y as f32 - diff
+                            } else {
+                                position.y as f32
+                            };
+                            let target_scroll = ((pos_y - scrollbar_height / 2.) / screen_size.1)
+                                * self.renderer.positioner.reserved_height;
+                            self.renderer.set_scroll_y(target_scroll);
+                            self.window.request_redraw();
+                        } else if mouse_down && self.selection.handle_drag(loc) {
+                            self.window.request_redraw();
+                        }
+                        mouse_position = loc;
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Right,
+                        ..
+                    } if !self.help_visible => {
+                        let clicked = self
+                            .elements
+                            .iter()
+                            .find(|element| element.contains(mouse_position));
+                        // Right-clicking a heading copies a link to its section
+                        let anchor = clicked.and_then(|element| match &element.inner {
+                            Element::TextBox(text_box) => text_box
+                                .is_anchor
+                                .as_deref()
+                                .filter(|anchor| anchor.starts_with('#')),
+                            _ => None,
+                        });
+                        if let Some(anchor) = anchor {
+                            let path = self.opts.history.get_path();
+                            let link = section_link::for_heading(path, anchor);
+                            tracing::info!("Copied link to section: {link}");
+                            clipboard.set_contents(link);
+                        } else if let Some(menu) =
+                            clicked.and_then(|element| ContextMenu::for_element(&element.inner))
+                        {
+                            self.context_menu = Some(menu);
+                            self.update_prompt_banner();
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Other(button),
+                        ..
+                    } => {
+                        // The back and forward buttons move through the history like a browser's
+                        if let Some(direction) = history_button(button) {
+                            self.step_history(direction, 1);
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } => match state {
+                        ElementState::Pressed => {
+                            // Any click closes an expanded image
+                            if self.renderer.lightbox.take().is_some() {
+                                self.window.request_redraw();
+                                return;
+                            }
+
+                            // Clicking the `--find` badge searches for its query again
+                            let screen_position =
+                                (mouse_position.0, mouse_position.1 - self.renderer.scroll_y);
+                            let badge_clicked = self
+                                .renderer
+                                .badge_bounds()
+                                .is_some_and(|bounds| bounds.contains(screen_position));
+                            if badge_clicked {
+                                let query = self.find_badge.as_ref().map(FindBadge::query);
+                                let query = query.unwrap_or_default().to_owned();
+                                let options = self.opts.search_options;
+                                self.search = Some(Search::with_query(query, options).typing());
+                                self.update_prompt_banner();
+                                return;
+                            }
+
+                            if let Some(banner_height) = self.renderer.banner_height() {
+                                if mouse_position.1 - self.renderer.scroll_y < banner_height {
+                                    // Other banners are read-only, so clicking them only keeps
+                                    // the click from reaching the document underneath
+                                    let lost_file = self.banner_shows_lost_file();
+                                    if lost_file && modifiers.shift() {
+                                        let path = self.opts.history.get_path();
+                                        let proxy = event_loop_proxy.clone();
+                                        file_actions::reveal(path, move |msg| {
+                                            let _ = proxy.send_event(InlyneEvent::Toast(msg));
+                                        });
+                                    } else if lost_file {
+                                        event_loop_proxy
+                                            .send_event(InlyneEvent::FileReload)
+                                            .unwrap();
+                                    }
+                                    return;
+                                }
+                            }
+
+                            if self.edit_mode && self.editing.is_none() {
+                                let clicked = self.elements.iter().position(|element| {
+                                    element.contains(mouse_position)
+                                        && matches!(element.inner, Element::TextBox(_))
+                                });
+                                if let Some(index) = clicked {
+                                    self.start_edit(index);
+                                    return;
+                                }
+                            }
+
+                            // Try to click a link
+                            let screen_size = self.renderer.screen_size();
+
+                            let y = mouse_position.1 - self.renderer.scroll_y;
+                            let scrollbar_width = self.renderer.scrollbar_width();
+                            let scrollbar_x = screen_size.0
+                                - self.renderer.positioner.right_inset()
+                                - scrollbar_width;
+                            if Rect::new((scrollbar_x, 0.), (scrollbar_width, screen_size.1))
+                                .contains((mouse_position.0, y))
+                            {
+                                let scrollbar_height = self.renderer.scrollbar_height();
+
+                                let target_scroll = ((y - scrollbar_height / 2.) / screen_size.1)
+                                    * self.renderer.positioner.reserved_height;
+
+                                self.renderer.set_scroll_y(target_scroll);
+                                self.window.request_redraw();
                             }
 
-                            if let Some(hoverable) = Self::find_hoverable(
+                            // Clicking around a code block's contents selects the whole block
+                            if let Some(top) = Self::find_code_block_background(
+                                &mut self.renderer.text_system,
+                                &self.elements,
+                                mouse_position,
+                                screen_size,
+                                self.renderer.zoom,
+                            ) {
+                                self.selection.select_block(top, modifiers.ctrl());
+                                self.window.request_redraw();
+                            } else if let Some(hoverable) = Self::find_hoverable(
                                 &mut self.renderer.text_system,
                                 &mut self.renderer.positioner.taffy,
                                 &self.elements,
@@ -803,30 +2262,67 @@ impl Inlyne {
                                 self.renderer.zoom,
                             ) {
                                 match hoverable {
+                                    Hoverable::Text(Text { link: Some(link), .. })
+                                        if link == interpreter::EXPAND_LONG_LINES_LINK =>
+                                    {
+                                        interpreter::set_expand_long_lines(true);
+                                        self.load_file(self.current_file_content.clone());
+                                    }
+                                    Hoverable::Text(Text { link: Some(link), .. })
+                                        if self.code_runner.key(link).is_some() =>
+                                    {
+                                        self.confirm_run = self.code_runner.key(link);
+                                        self.update_prompt_banner();
+                                    }
+                                    Hoverable::Image(image @ Image { is_link: None, .. })
+                                        if self
+                                            .renderer
+                                            .positioner
+                                            .is_image_shrunk(image, self.renderer.zoom) =>
+                                    {
+                                        self.renderer.lightbox = Some(Image::from_image_data(
+                                            image.image_data.clone(),
+                                            image.hidpi_scale,
+                                        ));
+                                        self.window.request_redraw();
+                                    }
                                     Hoverable::Image(Image { is_link: Some(link), .. }) |
+                                    Hoverable::Area(MapArea { href: link, .. }) |
                                     Hoverable::Text(Text { link: Some(link), .. }) => {
-                                        let path = PathBuf::from(link);
-
-                                        if  path.extension().is_some_and(|ext| ext == "md")
-                                            && !path.to_str().is_some_and(|s| s.starts_with("http")) {
+                                        let is_url = link.starts_with("http");
+                                        let (target, line) = if is_url {
+                                            (link.as_str(), None)
+                                        } else {
+                                            utils::split_line_suffix(link)
+                                        };
+                                        let path = PathBuf::from(target);
+                                        // Only local files lose their line suffix since links
+                                        // like `jira:123` are complete on their own
+                                        let external = if path.exists() { target } else { link.as_str() };
+
+                                        if  path.extension().is_some_and(|ext| ext == "md") && !is_url {
                                             // Open them in a new window, akin to what a browser does
                                             if modifiers.shift() {
+                                                // The line goes along as a flag so that the new
+                                                // instance gets a path to a file that exists
+                                                let args = Opts::program_args(&path, line);
                                                 std::thread::spawn(move || {
                                                     Command::new(
                                                         std::env::current_exe()
                                                             .unwrap_or_else(|_| "inlyne".into()),
                                                     )
-                                                        .args(Opts::program_args(&path))
+                                                        .args(args)
                                                         .spawn()
                                                         .expect("Couldn't spawn inlyne instance")
                                                         .wait()
                                                         .expect("Failed waiting on child");
                                                 });
                                             } else {
-                                                match read_to_string(&path) {
-                                                    Ok(contents) => {
-                                                        self.update_file(&path, contents);
+                                                match read_with_encoding(&path) {
+                                                    Ok((contents, encoding)) => {
+                                                        self.update_file(&path, contents, encoding);
                                                         self.opts.history.make_next(path);
+                                                        self.goto_line = line;
                                                     }
                                                     Err(err) => {
                                                         tracing::warn!(
@@ -837,14 +2333,21 @@ impl Inlyne {
                                                     }
                                                 }
                                             }
-                                        } else if let Some(anchor_pos) =
+                                        } else if let Some(&anchor_pos) =
                                             self.renderer.positioner.anchors.get(&link.to_lowercase())
                                         {
-                                            self.renderer.set_scroll_y(*anchor_pos);
-                                            self.window.request_redraw();
+                                            self.jump_to(anchor_pos);
                                             self.window.set_cursor_icon(CursorIcon::Default);
-                                        } else if let Err(e) = open::that(link) {
-                                            tracing::error!("Could not open link: {e} from {:?}", std::env::current_dir())
+                                        } else if self.opts.link_dry_run {
+                                            clipboard.set_contents(external.to_owned());
+                                            self.show_toast(format!("Copied {external}"));
+                                        } else {
+                                            let link = external.to_owned();
+                                            let handlers = self.opts.link_handlers.clone();
+                                            let proxy = event_loop_proxy.clone();
+                                            links::open_detached(link, handlers, move |msg| {
+                                                let _ = proxy.send_event(InlyneEvent::Toast(msg));
+                                            });
                                         }
                                     },
                                     Hoverable::Summary(summary) => {
@@ -856,12 +2359,28 @@ impl Inlyne {
                                         self.selection.add_position(mouse_position);
                                     },
                                     _ => {
-                                        self.selection.add_position(mouse_position);
+                                        if fold::toggle_at(&mut self.elements, mouse_position) {
+                                            event_loop_proxy
+                                                .send_event(InlyneEvent::Reposition)
+                                                .unwrap();
+                                        }
+                                        if modifiers.ctrl() {
+                                            self.selection.add_range(mouse_position);
+                                        } else {
+                                            self.selection.add_position(mouse_position);
+                                        }
                                         self.window.request_redraw();
                                     }
                                 };
                             } else {
-                                self.selection.add_position(mouse_position);
+                                if fold::toggle_at(&mut self.elements, mouse_position) {
+                                    event_loop_proxy.send_event(InlyneEvent::Reposition).unwrap();
+                                }
+                                if modifiers.ctrl() {
+                                    self.selection.add_range(mouse_position);
+                                } else {
+                                    self.selection.add_position(mouse_position);
+                                }
                                 self.window.request_redraw()
                             }
                             mouse_down = true;
@@ -873,6 +2392,44 @@ impl Inlyne {
                     },
                     WindowEvent::ModifiersChanged(new_state) => modifiers = new_state,
                     WindowEvent::ReceivedCharacter(c) => {
+                        if let Some(edit) = self.editing.as_mut() {
+                            if !c.is_control() {
+                                edit.text.push(c);
+                                self.update_inline_edit();
+                            }
+                            return;
+                        }
+                        if let Some(palette) = self.heading_palette.as_mut() {
+                            if !c.is_control() {
+                                palette.push(c);
+                                self.update_prompt_banner();
+                            }
+                            return;
+                        }
+                        if self.history_list.is_some() {
+                            if let Some(digit) = c.to_digit(10).filter(|&digit| digit > 0) {
+                                self.jump_history(digit as usize - 1);
+                            }
+                            return;
+                        }
+                        if let Some(menu) = &self.context_menu {
+                            let digit = c.to_digit(10).filter(|&digit| digit > 0);
+                            if let Some(contents) = digit.and_then(|d| menu.get(d as usize - 1)) {
+                                clipboard.set_contents(contents.to_owned());
+                                self.context_menu = None;
+                                self.update_prompt_banner();
+                            }
+                            return;
+                        }
+                        if let Some(search) = self.search.as_mut().filter(|search| search.typing) {
+                            // The `/` that opened the search arrives here as well
+                            let opening_slash = c == '/' && search.query.is_empty();
+                            if !c.is_control() && !opening_slash {
+                                search.insert(c.encode_utf8(&mut [0; 4]));
+                                self.update_prompt_banner();
+                            }
+                            return;
+                        }
                         // Handle '?' character directly for better keyboard layout compatibility
                         if c == '?' {
                             if !self.help_visible {
@@ -884,6 +2441,19 @@ impl Inlyne {
                             }
                         }
                     }
+                    WindowEvent::Ime(ime) => {
+                        let Some(search) = self.search.as_mut().filter(|search| search.typing)
+                        else {
+                            return;
+                        };
+                        match ime {
+                            Ime::Preedit(text, cursor) => search.compose(text, cursor),
+                            Ime::Commit(text) => search.insert(&text),
+                            Ime::Disabled => search.compose(String::new(), None),
+                            Ime::Enabled => {}
+                        }
+                        self.update_prompt_banner();
+                    }
                     WindowEvent::KeyboardInput {
                         input:
                             KeyboardInput {
@@ -894,63 +2464,334 @@ impl Inlyne {
                             },
                         ..
                     } => {
+                        // Unmodified keys go to the edit or search query instead of keybindings
+                        let shortcut = modifiers.ctrl() || modifiers.alt() || modifiers.logo();
+                        if let Some(key) = self.confirm_run.take() {
+                            if virtual_keycode == Some(VirtualKeyCode::Y) && !shortcut {
+                                let proxy = self.event_loop_proxy.clone();
+                                self.code_runner.run(key, move || {
+                                    let _ = proxy.send_event(InlyneEvent::CodeRunFinished);
+                                });
+                                self.load_file(self.current_file_content.clone());
+                            }
+                            self.update_prompt_banner();
+                            return;
+                        }
+                        if self.editing.is_some() && !shortcut {
+                            match virtual_keycode {
+                                Some(VirtualKeyCode::Escape) => self.cancel_edit(),
+                                Some(VirtualKeyCode::Return) if modifiers.shift() => {
+                                    self.editing.as_mut().unwrap().text.push('\n');
+                                    self.update_inline_edit();
+                                }
+                                Some(VirtualKeyCode::Return) => self.save_edit(),
+                                Some(VirtualKeyCode::Back) => {
+                                    self.editing.as_mut().unwrap().text.pop();
+                                    self.update_inline_edit();
+                                }
+                                _ => {}
+                            }
+                            return;
+                        }
+                        if self.renderer.lightbox.is_some()
+                            && virtual_keycode == Some(VirtualKeyCode::Escape)
+                        {
+                            self.renderer.lightbox = None;
+                            self.window.request_redraw();
+                            return;
+                        }
+                        if let Some(gallery) = self.gallery.as_mut().filter(|_| !shortcut) {
+                            match virtual_keycode {
+                                Some(VirtualKeyCode::Left | VirtualKeyCode::H) => {
+                                    gallery.select_prev();
+                                    self.show_gallery_theme();
+                                    return;
+                                }
+                                Some(VirtualKeyCode::Right | VirtualKeyCode::L) => {
+                                    gallery.select_next();
+                                    self.show_gallery_theme();
+                                    return;
+                                }
+                                Some(VirtualKeyCode::Return) => {
+                                    print!("{}", gallery.config_snippet());
+                                    *control_flow = ControlFlow::Exit;
+                                    return;
+                                }
+                                _ => {}
+                            }
+                        }
+                        if let Some(palette) = self.heading_palette.as_mut().filter(|_| !shortcut) {
+                            match virtual_keycode {
+                                Some(VirtualKeyCode::Escape) => self.heading_palette = None,
+                                Some(VirtualKeyCode::Return) => self.jump_to_heading(),
+                                Some(VirtualKeyCode::Up) => palette.select_prev(),
+                                Some(VirtualKeyCode::Down) => palette.select_next(),
+                                Some(VirtualKeyCode::Back) => palette.pop(),
+                                _ => {}
+                            }
+                            self.update_prompt_banner();
+                            return;
+                        }
+                        if let Some(menu) = self.context_menu.as_mut().filter(|_| !shortcut) {
+                            match virtual_keycode {
+                                Some(VirtualKeyCode::Escape) => self.context_menu = None,
+                                Some(VirtualKeyCode::Return) => {
+                                    clipboard.set_contents(menu.selected().to_owned());
+                                    self.context_menu = None;
+                                }
+                                Some(VirtualKeyCode::Up | VirtualKeyCode::K) => menu.select_prev(),
+                                Some(VirtualKeyCode::Down | VirtualKeyCode::J) => {
+                                    menu.select_next()
+                                }
+                                _ => {}
+                            }
+                            self.update_prompt_banner();
+                            return;
+                        }
+                        if let Some(selected) = self.history_list.filter(|_| !shortcut) {
+                            let last = self.opts.history.entries().len() - 1;
+                            match virtual_keycode {
+                                Some(VirtualKeyCode::Escape) => self.history_list = None,
+                                Some(VirtualKeyCode::Return) => {
+                                    self.jump_history(selected);
+                                    return;
+                                }
+                                Some(VirtualKeyCode::Up | VirtualKeyCode::K) => {
+                                    self.history_list = Some(selected.saturating_sub(1));
+                                }
+                                Some(VirtualKeyCode::Down | VirtualKeyCode::J) => {
+                                    self.history_list = Some((selected + 1).min(last));
+                                }
+                                _ => {}
+                            }
+                            self.update_prompt_banner();
+                            return;
+                        }
+                        let typing_search =
+                            self.search.as_ref().is_some_and(|search| search.typing);
+                        let ctrl_or_command = if cfg!(target_os = "macos") {
+                            modifiers.logo()
+                        } else {
+                            modifiers.ctrl()
+                        };
+                        // Deleting words and pasting are the only shortcuts that edit the query
+                        let editing_shortcut = match virtual_keycode {
+                            Some(VirtualKeyCode::W) => modifiers.ctrl(),
+                            Some(VirtualKeyCode::Back) => modifiers.ctrl() || modifiers.alt(),
+                            Some(VirtualKeyCode::V) => ctrl_or_command,
+                            _ => false,
+                        };
+                        if typing_search && (!shortcut || editing_shortcut) {
+                            let search = self.search.as_mut().unwrap();
+                            // Keys belong to the input method while it's composing
+                            if search.composing() {
+                                return;
+                            }
+                            match virtual_keycode {
+                                Some(VirtualKeyCode::Escape) => self.search = None,
+                                Some(VirtualKeyCode::Return) => {
+                                    search.typing = false;
+                                    if let Err(err) = self.search_history.push(&search.query) {
+                                        tracing::warn!("Failed saving search history: {err}");
+                                    }
+                                    let first_match = search
+                                        .matches(&self.current_file_content)
+                                        .first()
+                                        .map(|(line, _)| *line);
+                                    if let Some(line) = first_match {
+                                        self.scroll_to_line(line);
+                                    }
+                                }
+                                Some(VirtualKeyCode::W) if shortcut => search.delete_word(),
+                                Some(VirtualKeyCode::Back) if shortcut => search.delete_word(),
+                                Some(VirtualKeyCode::Back) => search.backspace(),
+                                Some(VirtualKeyCode::Delete) => search.delete(),
+                                Some(VirtualKeyCode::Up) => {
+                                    search.recall_older(&self.search_history);
+                                }
+                                Some(VirtualKeyCode::Down) => {
+                                    search.recall_newer(&self.search_history);
+                                }
+                                Some(VirtualKeyCode::Left) => search.left(),
+                                Some(VirtualKeyCode::Right) => search.right(),
+                                Some(VirtualKeyCode::Home) => search.home(),
+                                Some(VirtualKeyCode::End) => search.end(),
+                                Some(VirtualKeyCode::V) if shortcut => {
+                                    if let Some(text) = clipboard.get_contents() {
+                                        search.insert(&text);
+                                    }
+                                }
+                                _ => {}
+                            }
+                            self.update_prompt_banner();
+                            return;
+                        }
+
                         let key = Key::new(virtual_keycode, scancode);
                         let modified_key = ModifiedKey(key, modifiers);
-                        if let Some(action) = self.keycombos.munch(modified_key) {
+                        if let Some((action, count)) = self.keycombos.munch(modified_key) {
                             match action {
                                 Action::ToEdge(direction) => {
                                     let scroll = match direction {
                                         VertDirection::Up => 0.0,
                                         VertDirection::Down => f32::INFINITY,
                                     };
-                                    self.renderer.set_scroll_y(scroll);
-                                    self.window.request_redraw();
+                                    self.scroll_hovered(mouse_position.0, |renderer, window, _| {
+                                        renderer.set_scroll_y(scroll);
+                                        window.request_redraw();
+                                    });
                                 }
                                 Action::Scroll(direction) => {
                                     let lines = match direction {
-                                        VertDirection::Up => 1.0,
-                                        VertDirection::Down => -1.0,
+                                        VertDirection::Up => count as f32,
+                                        VertDirection::Down => -(count as f32),
                                     };
 
-                                    Self::scroll_lines(
-                                        &mut self.renderer,
-                                        &self.window,
-                                        self.lines_to_scroll,
-                                        lines,
-                                    )
+                                    let lines_to_scroll = self.lines_to_scroll;
+                                    self.scroll_hovered(mouse_position.0, |renderer, window, _| {
+                                        Self::scroll_lines(renderer, window, lines_to_scroll, lines)
+                                    });
                                 }
+                                Action::ScrollVisualLine(direction) => self.scroll_hovered(
+                                    mouse_position.0,
+                                    |renderer, window, elements| {
+                                        for _ in 0..count {
+                                            Self::scroll_visual_line(
+                                                renderer, window, elements, direction,
+                                            );
+                                        }
+                                    },
+                                ),
                                 Action::Page(direction) => {
-                                    // Move 90% of current page height
-                                    let scroll_amount = self.renderer.config.height as f32 * 0.9;
-                                    let scroll_with_direction = match direction {
-                                        VertDirection::Up => scroll_amount,
-                                        VertDirection::Down => -scroll_amount,
-                                    };
-
-                                    Self::scroll_pixels(
-                                        &mut self.renderer,
-                                        &self.window,
-                                        scroll_with_direction,
-                                    );
+                                    let fraction = self.opts.page_scroll_fraction;
+                                    let overlap_lines = self.opts.page_overlap_lines;
+                                    self.scroll_hovered(mouse_position.0, |renderer, window, _| {
+                                        for _ in 0..count {
+                                            Self::scroll_page(
+                                                renderer,
+                                                window,
+                                                fraction,
+                                                overlap_lines,
+                                                direction,
+                                            );
+                                        }
+                                    });
+                                }
+                                Action::HalfPage(direction) => {
+                                    self.scroll_hovered(mouse_position.0, |renderer, window, _| {
+                                        for _ in 0..count {
+                                            Self::scroll_page(
+                                                renderer, window, 0.5, 0.0, direction,
+                                            );
+                                        }
+                                    })
+                                }
+                                Action::JumpHeading(direction) => self.scroll_hovered(
+                                    mouse_position.0,
+                                    |renderer, window, elements| {
+                                        for _ in 0..count {
+                                            Self::jump_heading(
+                                                renderer, window, elements, direction,
+                                            );
+                                        }
+                                    },
+                                ),
+                                Action::ToggleFold => {
+                                    if fold::toggle_at(&mut self.elements, mouse_position) {
+                                        event_loop_proxy
+                                            .send_event(InlyneEvent::Reposition)
+                                            .unwrap();
+                                    }
+                                }
+                                Action::FoldAll => {
+                                    fold::fold_all(&mut self.elements);
+                                    event_loop_proxy.send_event(InlyneEvent::Reposition).unwrap();
+                                }
+                                Action::UnfoldAll => {
+                                    fold::unfold_all(&mut self.elements);
+                                    event_loop_proxy.send_event(InlyneEvent::Reposition).unwrap();
                                 }
                                 Action::Zoom(zoom_action) => {
                                     let zoom = match zoom_action {
                                         Zoom::In => self.renderer.zoom * 1.1,
                                         Zoom::Out => self.renderer.zoom * 0.9,
                                         Zoom::Reset => 1.0,
+                                        Zoom::FitWidth => {
+                                            self.fit_width_zoom().unwrap_or(self.renderer.zoom)
+                                        }
                                     };
 
-                                    self.renderer.zoom = zoom;
-                                    let old_reserved = self.renderer.positioner.reserved_height;
-                                    self.renderer.reposition(&mut self.elements).unwrap();
-                                    let new_reserved = self.renderer.positioner.reserved_height;
-                                    self.renderer.set_scroll_y(
-                                        self.renderer.scroll_y * (new_reserved / old_reserved),
-                                    );
-                                    self.window.request_redraw();
+                                    self.set_zoom(zoom);
                                 }
                                 Action::Copy => clipboard
                                     .set_contents(self.selection.text.trim().to_owned()),
+                                Action::CopyFilePath => {
+                                    let path = self.opts.history.get_path();
+                                    let path = file_actions::full_path(path);
+                                    clipboard.set_contents(path.display().to_string());
+                                    self.show_toast(format!("Copied {}", path.display()));
+                                }
+                                Action::RevealInFileManager => {
+                                    let proxy = event_loop_proxy.clone();
+                                    let path = self.opts.history.get_path();
+                                    file_actions::reveal(path, move |msg| {
+                                        let _ = proxy.send_event(InlyneEvent::Toast(msg));
+                                    });
+                                }
+                                Action::SplitView => self.toggle_split_view(),
+                                Action::EditMode => {
+                                    self.edit_mode = !self.edit_mode;
+                                    self.cancel_edit();
+                                }
+                                Action::Extras => {
+                                    extras::set_revealed(!extras::get_revealed());
+                                    self.load_file(self.current_file_content.clone());
+                                }
+                                Action::FitWindowToContent => self.fit_window_to_content(),
+                                Action::ZenMode => self.toggle_zen_mode(),
+                                Action::DumpLayout => {
+                                    let spacing = &self.renderer.element_spacing;
+                                    let tree = inspect::layout_tree(&self.elements, spacing);
+                                    tracing::info!("Layout:\n{}", inspect::dump(&tree));
+                                }
+                                Action::Search => {
+                                    let options = self.opts.search_options;
+                                    self.search = Some(Search::start(options));
+                                    self.update_prompt_banner();
+                                }
+                                Action::ToggleSmartCase => {
+                                    let mut options = self.opts.search_options;
+                                    options.smart_case = !options.smart_case;
+                                    self.set_search_options(options);
+                                }
+                                Action::ToggleWholeWord => {
+                                    let mut options = self.opts.search_options;
+                                    options.whole_word = !options.whole_word;
+                                    self.set_search_options(options);
+                                }
+                                Action::GoToHeading => {
+                                    self.heading_palette = match self.heading_palette {
+                                        Some(_) => None,
+                                        None => Some(HeadingPalette::new(self.headings())),
+                                    };
+                                    self.update_prompt_banner();
+                                }
+                                Action::CycleMatch(direction) => {
+                                    for _ in 0..count {
+                                        self.goto_match(direction);
+                                    }
+                                }
+                                Action::CopyMatches => {
+                                    if let Some(search) = &self.search {
+                                        let location = self
+                                            .opts
+                                            .copy_match_locations
+                                            .then(|| self.opts.history.get_path());
+                                        clipboard.set_contents(
+                                            search.copy_text(&self.current_file_content, location),
+                                        );
+                                    }
+                                }
                                 Action::Help => {
                                     if !self.help_visible {
                                         self.help_visible = true;
@@ -961,34 +2802,37 @@ impl Inlyne {
                                     }
                                 }
                                 Action::Quit => {
-                                    if self.help_visible {
+                                    if self.history_list.take().is_some() {
+                                        self.update_prompt_banner();
+                                    } else if self.help_visible {
                                         self.help_visible = false;
                                         self.hide_help();
+                                    } else if self.edit_mode {
+                                        self.edit_mode = false;
+                                        self.update_prompt_banner();
+                                    } else if self.search.take().is_some() {
+                                        self.update_prompt_banner();
                                     } else {
+                                        self.on_exit();
                                         *control_flow = ControlFlow::Exit;
                                     }
                                 }
-                                Action::History(hist_dir) => {
-                                    let changed_path = match hist_dir {
-                                        HistDirection::Next => self.opts.history.next(),
-                                        HistDirection::Prev => self.opts.history.previous(),
-                                    }.map(ToOwned::to_owned);
-                                    let Some(file_path) = changed_path else {
-                                        return;
+                                Action::History(hist_dir) => self.step_history(hist_dir, count),
+                                Action::HistoryList => {
+                                    self.history_list = match self.history_list {
+                                        Some(_) => None,
+                                        None => Some(self.opts.history.index()),
                                     };
-                                    match read_to_string(&file_path) {
-                                        Ok(contents) => {
-                                            self.update_file(&file_path, contents);
-                                            let parent = file_path.parent().expect("File should have parent directory");
-                                            std::env::set_current_dir(parent).expect("Could not set current directory.");
-                                        }
-                                        Err(err) => {
-                                            tracing::warn!(
-                                                "Failed loading markdown file at {}\nError: {}",
-                                                file_path.display(),
-                                                err,
-                                            );
-                                        }
+                                    self.update_prompt_banner();
+                                }
+                                Action::Jump(direction) => {
+                                    for _ in 0..count {
+                                        self.jump_back_or_forward(direction);
+                                    }
+                                }
+                                Action::HistoryJump(n) => {
+                                    if let Some(index) = n.checked_sub(1) {
+                                        self.jump_history(index);
                                     }
                                 }
                             }
@@ -1002,12 +2846,7 @@ impl Inlyne {
                     // with a bunch of costly resizes. (https://github.com/Inlyne-Project/inlyne/issues/25)
                     if let Some(size) = pending_resize.take() {
                         if size.width > 0 && size.height > 0 {
-                            self.renderer.config.width = size.width;
-                            self.renderer.config.height = size.height;
-                            self.renderer.positioner.screen_size = size.into();
-                            self.renderer
-                                .surface
-                                .configure(&self.renderer.device, &self.renderer.config);
+                            self.renderer.resize(size.width, size.height);
                             let old_reserved = self.renderer.positioner.reserved_height;
                             if self.help_visible {
                                 self.renderer.reposition(&mut self.help_elements).unwrap();
@@ -1018,6 +2857,7 @@ impl Inlyne {
                             self.renderer.set_scroll_y(
                                 self.renderer.scroll_y * (new_reserved / old_reserved),
                             );
+                            self.reposition_side_pane();
                             self.window.request_redraw();
                         }
                     }
@@ -1027,7 +2867,9 @@ impl Inlyne {
                             self.renderer.reposition(&mut self.help_elements).unwrap();
                         } else {
                             self.renderer.reposition(&mut self.elements).unwrap();
+                            self.refresh_current_match();
                         }
+                        self.reposition_side_pane();
                         self.window.request_redraw();
                         self.need_repositioning = false;
                     }
@@ -1037,6 +2879,26 @@ impl Inlyne {
         });
     }
 
+    /// Scrolls the side-by-side document instead of the main one while the cursor is over it
+    fn scroll_hovered(
+        &mut self,
+        cursor_x: f32,
+        scroll: impl FnOnce(&mut Renderer, &Window, &[Positioned<Element>]),
+    ) {
+        if self.renderer.side_pane_contains(cursor_x) {
+            let window = &self.window;
+            self.renderer
+                .with_side_pane(|renderer, elements| scroll(renderer, window, elements));
+        } else {
+            let elements = if self.help_visible {
+                &self.help_elements
+            } else {
+                &self.elements
+            };
+            scroll(&mut self.renderer, &self.window, elements);
+        }
+    }
+
     fn scroll_lines(
         renderer: &mut Renderer,
         window: &Window,
@@ -1047,8 +2909,122 @@ impl Inlyne {
         Self::scroll_pixels(renderer, window, num_pixels);
     }
 
+    // Moves to the next line boundary of the text box at the top of the view. Anything else at
+    // the top (images, tables, spacing) moves by the usual line estimate instead, stopping where
+    // the next text box's lines start
+    fn scroll_visual_line(
+        renderer: &mut Renderer,
+        window: &Window,
+        elements: &[Positioned<Element>],
+        direction: VertDirection,
+    ) {
+        // Slack for the scroll position getting rounded to whole pixels
+        const SLACK: f32 = 1.0;
+
+        let zoom = renderer.zoom;
+        // The top, line height and bottom of the last line of each text box
+        let grids: Vec<_> = elements
+            .iter()
+            .filter_map(|element| {
+                let Element::TextBox(text_box) = &element.inner else {
+                    return None;
+                };
+                let bounds = element.bounds.as_ref()?;
+                let padding = text_box.padding_height * text_box.hidpi_scale * zoom;
+                let bottom = bounds.pos.1 + bounds.size.1 - padding;
+                (!text_box.texts.is_empty()).then_some((
+                    bounds.pos.1,
+                    text_box.line_height(zoom),
+                    bottom,
+                ))
+            })
+            .collect();
+
+        let scroll_y = renderer.scroll_y;
+        let estimate = 16.0 * renderer.hidpi_scale * zoom;
+        let target = match direction {
+            VertDirection::Down => {
+                let current = grids.iter().find(|&&(top, _, bottom)| {
+                    top - SLACK <= scroll_y && scroll_y < bottom - SLACK
+                });
+                match current {
+                    Some(&(top, line_height, _)) => {
+                        let line = ((scroll_y - top + SLACK) / line_height).floor();
+                        top + (line + 1.) * line_height
+                    }
+                    None => grids
+                        .iter()
+                        .map(|&(top, _, _)| top)
+                        .filter(|&top| top > scroll_y + SLACK)
+                        .fold(scroll_y + estimate, f32::min),
+                }
+            }
+            VertDirection::Up => {
+                let current = grids.iter().find(|&&(top, _, bottom)| {
+                    top + SLACK < scroll_y && scroll_y <= bottom + SLACK
+                });
+                match current {
+                    Some(&(top, line_height, _)) => {
+                        let line = ((scroll_y - top - SLACK) / line_height).ceil();
+                        top + (line - 1.) * line_height
+                    }
+                    None => grids
+                        .iter()
+                        .map(|&(_, _, bottom)| bottom)
+                        .filter(|&bottom| bottom < scroll_y - SLACK)
+                        .fold(scroll_y - estimate, f32::max),
+                }
+            }
+        };
+
+        renderer.set_scroll_y(target);
+        window.request_redraw();
+    }
+
+    /// Scrolls so that the next or previous heading sits at the top of the view
+    fn jump_heading(
+        renderer: &mut Renderer,
+        window: &Window,
+        elements: &[Positioned<Element>],
+        direction: VertDirection,
+    ) {
+        // Slack for the scroll position getting rounded to whole pixels
+        const SLACK: f32 = 1.0;
+
+        let mut headings = fold::visible_headings(elements)
+            .into_iter()
+            .filter_map(|element| element.bounds.as_ref().map(|bounds| bounds.pos.1));
+        let scroll_y = renderer.scroll_y;
+        let target = match direction {
+            VertDirection::Down => headings.find(|&top| top > scroll_y + SLACK),
+            VertDirection::Up => headings.filter(|&top| top < scroll_y - SLACK).last(),
+        };
+        if let Some(target) = target {
+            renderer.set_scroll_y(target);
+            window.request_redraw();
+        }
+    }
+
+    // Moves by a fraction of the window height, keeping `overlap_lines` of the previous page
+    // visible
+    fn scroll_page(
+        renderer: &mut Renderer,
+        window: &Window,
+        fraction: f32,
+        overlap_lines: f32,
+        direction: VertDirection,
+    ) {
+        let overlap = overlap_lines * 16.0 * renderer.hidpi_scale * renderer.zoom;
+        let scroll_amount = (renderer.config.height as f32 * fraction - overlap).max(0.0);
+        let scroll_with_direction = match direction {
+            VertDirection::Up => scroll_amount,
+            VertDirection::Down => -scroll_amount,
+        };
+        Self::scroll_pixels(renderer, window, scroll_with_direction);
+    }
+
     fn scroll_pixels(renderer: &mut Renderer, window: &Window, num_pixels: f32) {
-        renderer.set_scroll_y(renderer.scroll_y - num_pixels);
+        renderer.scroll_by(-num_pixels);
         window.request_redraw();
     }
 
@@ -1067,10 +3043,12 @@ impl Inlyne {
             )
         };
 
+        // Elements can overlap, so a miss on the first one under the cursor falls through to the
+        // rest
         elements
             .iter()
-            .find(|&e| e.contains(loc) && !matches!(e.inner, Element::Spacer(_)))
-            .and_then(|element| match &element.inner {
+            .filter(|&e| e.contains(loc) && !matches!(e.inner, Element::Spacer(_)))
+            .find_map(|element| match &element.inner {
                 Element::TextBox(text_box) => {
                     let bounds = element.bounds.as_ref().unwrap();
                     text_box
@@ -1096,7 +3074,15 @@ impl Inlyne {
                         )
                         .map(Hoverable::Text)
                 }
-                Element::Image(image) => Some(Hoverable::Image(image)),
+                Element::Image(image) => {
+                    let bounds = element.bounds.as_ref().unwrap();
+                    Some(
+                        image
+                            .area_at(loc, bounds)
+                            .map_or(Hoverable::Image(image), Hoverable::Area),
+                    )
+                }
+                Element::Math(_) => None,
                 Element::Spacer(_) => unreachable!("Spacers are filtered"),
                 Element::Row(row) => {
                     Self::find_hoverable(text_system, taffy, &row.elements, loc, screen_size, zoom)
@@ -1124,6 +3110,90 @@ impl Inlyne {
                 }
             })
     }
+
+    /// Finds the top of the code block when `loc` is over its background rather than its code
+    fn find_code_block_background(
+        text_system: &mut TextSystem,
+        elements: &[Positioned<Element>],
+        loc: Point,
+        screen_size: Size,
+        zoom: f32,
+    ) -> Option<f32> {
+        elements.iter().find_map(|element| {
+            let bounds = element.bounds.as_ref()?;
+            if loc.1 < bounds.pos.1 || loc.1 > bounds.pos.1 + bounds.size.1 {
+                return None;
+            }
+            match &element.inner {
+                Element::TextBox(text_box) if text_box.is_code_block => {
+                    // Code blocks' backgrounds span the page's width with some padding on the left
+                    let on_background = loc.0 >= bounds.pos.0 - 10.
+                        && loc.0 <= screen_size.0 - DEFAULT_MARGIN
+                        && !text_box.is_over_text(
+                            text_system,
+                            loc,
+                            bounds.pos,
+                            (
+                                screen_size.0 - bounds.pos.0 - DEFAULT_MARGIN,
+                                screen_size.1,
+                            ),
+                            zoom,
+                        );
+                    on_background.then_some(bounds.pos.1)
+                }
+                Element::Section(section) if !*section.hidden.borrow() => {
+                    Self::find_code_block_background(
+                        text_system,
+                        &section.elements,
+                        loc,
+                        screen_size,
+                        zoom,
+                    )
+                }
+                _ => None,
+            }
+        })
+    }
+}
+
+/// The history direction for a mouse's back or forward button. winit doesn't name them yet, so
+/// they're told apart by the number that each platform reports them as
+fn history_button(button: u16) -> Option<HistDirection> {
+    let (back, forward): (&[u16], &[u16]) = if cfg!(target_os = "windows") {
+        (&[1], &[2])
+    } else if cfg!(target_os = "macos") {
+        (&[3], &[4])
+    } else {
+        // X11 and Wayland number them differently
+        (&[8, 0x113], &[9, 0x114])
+    };
+    if back.contains(&button) {
+        Some(HistDirection::Prev)
+    } else if forward.contains(&button) {
+        Some(HistDirection::Next)
+    } else {
+        None
+    }
+}
+
+fn load_config(config_path: Option<&Path>, file_path: &Path) -> anyhow::Result<Config> {
+    // A project's `.inlyne.toml` gets merged over the user's config
+    if let Some(project_path) = Config::find_project_config(file_path) {
+        tracing::info!("Using project config at '{}'", project_path.display());
+        return LayeredConfig::with_project(config_path, &project_path)?.into_config();
+    }
+
+    let config = match config_path {
+        Some(config_path) => Config::load_from_file(config_path)?,
+        None => Config::load_from_system().unwrap_or_else(|err| {
+            tracing::warn!(
+                "Failed reading config file. Falling back to defaults. Error: {}",
+                err
+            );
+            Config::default()
+        }),
+    };
+    Ok(config)
 }
 
 fn main() -> anyhow::Result<()> {
@@ -1142,16 +3212,7 @@ fn main() -> anyhow::Result<()> {
 
     match command {
         Commands::View(view) => {
-            let config = match &view.config {
-                Some(config_path) => Config::load_from_file(config_path)?,
-                None => Config::load_from_system().unwrap_or_else(|err| {
-                    tracing::warn!(
-                        "Failed reading config file. Falling back to defaults. Error: {}",
-                        err
-                    );
-                    Config::default()
-                }),
-            };
+            let config = load_config(view.config.as_deref(), &view.file_path)?;
             let opts = Opts::parse_and_load_from(view, config)?;
 
             if let Some(exporter) = &opts.metrics {
@@ -1175,6 +3236,119 @@ fn main() -> anyhow::Result<()> {
             let inlyne = Inlyne::new(opts)?;
             inlyne.run();
         }
+        Commands::Bench(Bench {
+            file_path,
+            iterations,
+            config,
+        }) => {
+            let config = load_config(config.as_deref(), &file_path)?;
+            let view = View {
+                file_path,
+                ..Default::default()
+            };
+            let opts = Opts::parse_and_load_from(view, config)?;
+            bench::run(opts, iterations)?;
+        }
+        Commands::Print(Print { file_path }) => {
+            let md = read_to_string(&file_path)
+                .with_context(|| format!("Could not read file at '{}'", file_path.display()))?;
+            let base_dir = file_path.parent().unwrap_or(Path::new("."));
+            print::run(&md, base_dir)?;
+        }
+        Commands::InstallDesktop(InstallDesktop { dry_run }) => desktop::install(dry_run)?,
+        Commands::Remote(RemoteCmd::Preview(_)) => remote::preview_stdin()?,
+        Commands::Theme(ThemeCmd::Preview(ThemePreview { config })) => {
+            let mut config = load_config(config.as_deref(), &std::env::current_dir()?)?;
+            let gallery = ThemeGallery::new(
+                &config.layout,
+                config.light_theme.take(),
+                config.dark_theme.take(),
+            )?;
+            let file_path = ThemeGallery::placeholder_file();
+            std::fs::write(&file_path, gallery.document())
+                .with_context(|| format!("Failed writing to '{}'", file_path.display()))?;
+
+            let view = View {
+                file_path,
+                ..Default::default()
+            };
+            let opts = Opts::parse_and_load_from(view, config)?;
+            Inlyne::new(opts)?.with_gallery(gallery).run();
+        }
+        Commands::Export(ExportCmd::Html(ExportHtml {
+            file_path,
+            output,
+            theme,
+            config,
+        })) => {
+            let config = load_config(config.as_deref(), &file_path)?;
+            let md = read_to_string(&file_path)
+                .with_context(|| format!("Could not read file at '{}'", file_path.display()))?;
+            let title = file_path
+                .file_stem()
+                .map_or_else(|| "Inlyne".into(), |stem| stem.to_string_lossy());
+            let view = View {
+                file_path: file_path.clone(),
+                theme,
+                ..Default::default()
+            };
+            let opts = Opts::parse_and_load_from(view, config)?;
+            let page = export::html(
+                &md,
+                &title,
+                &opts.theme,
+                opts.page_width,
+                opts.smart_punctuation,
+            );
+            match output {
+                Some(output) => std::fs::write(&output, page)
+                    .with_context(|| format!("Failed writing to '{}'", output.display()))?,
+                None => print!("{page}"),
+            }
+        }
+        Commands::Screenshot(Screenshot {
+            file_path,
+            output,
+            width,
+            theme,
+            scale,
+            config,
+        }) => {
+            let config = load_config(config.as_deref(), &file_path)?;
+            let view = View {
+                file_path,
+                theme,
+                scale,
+                ..Default::default()
+            };
+            let opts = Opts::parse_and_load_from(view, config)?;
+            screenshot::run(opts, &output, width)?;
+        }
+        Commands::Serve(Serve {
+            dir,
+            port,
+            theme,
+            config,
+        }) => {
+            let file_path = serve::default_file(&dir)
+                .with_context(|| format!("No markdown files found in '{}'", dir.display()))?;
+            let config = load_config(config.as_deref(), &file_path)?;
+            let view = View {
+                file_path,
+                theme,
+                ..Default::default()
+            };
+            let opts = Opts::parse_and_load_from(view, config)?;
+            let server = serve::DocsServer::new(
+                &dir,
+                opts.theme.clone(),
+                opts.page_width,
+                opts.smart_punctuation,
+            )?;
+            let addr = server.spawn(port)?;
+            println!("Serving '{}' at http://{addr}", dir.display());
+            Inlyne::new(opts)?.run();
+        }
         Commands::Config(ConfigCmd::Open) => {
             let config_path = dirs::config_dir()
                 .context("Failed to find the configuration directory")?
@@ -1196,6 +3370,9 @@ fn main() -> anyhow::Result<()> {
 
             std::fs::write(config_path, new_config)?;
         }
+        Commands::Config(ConfigCmd::Dump(args)) => {
+            print!("{}", LayeredConfig::load(&args)?.to_annotated_toml());
+        }
     }
 
     Ok(())
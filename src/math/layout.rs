@@ -0,0 +1,678 @@
+//! A small TeX-like typesetter for the subset of LaTeX that shows up in most documents
+//!
+//! Formulas get parsed into a tree of atoms, scripts, fractions and roots, which is then laid out
+//! in ems around a shared baseline. Measuring text is left to the caller, so nothing in here has
+//! to know about fonts
+
+/// Height of the tallest glyphs above the baseline
+const ASCENT: f32 = 0.72;
+/// Depth of descenders below the baseline
+const DESCENT: f32 = 0.22;
+/// Where fraction bars sit above the baseline, roughly the middle of a minus sign
+const AXIS: f32 = 0.25;
+/// Thickness of fraction bars and radical overlines
+pub const RULE: f32 = 0.05;
+const GAP: f32 = 0.12;
+const SCRIPT_SCALE: f32 = 0.7;
+const FRAC_SCALE: f32 = 0.9;
+const MIN_SCALE: f32 = 0.5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    /// A run of text drawn as is
+    Atom {
+        text: String,
+        italic: bool,
+    },
+    Row(Vec<Node>),
+    Scripts {
+        base: Box<Node>,
+        sup: Option<Box<Node>>,
+        sub: Option<Box<Node>>,
+    },
+    Frac {
+        num: Box<Node>,
+        den: Box<Node>,
+    },
+    Sqrt(Box<Node>),
+}
+
+impl Node {
+    fn upright(text: &str) -> Self {
+        Self::Atom {
+            text: text.to_owned(),
+            italic: false,
+        }
+    }
+
+    /// A single line approximation for places that can only hold plain text e.g. inline math
+    ///
+    /// Scripts use unicode superscript and subscript characters when every character has one
+    pub fn linear(&self) -> String {
+        match self {
+            Self::Atom { text, .. } => text.to_owned(),
+            Self::Row(nodes) => nodes.iter().map(Self::linear).collect(),
+            Self::Scripts { base, sup, sub } => {
+                let mut linear = base.linear();
+                if let Some(sub) = sub {
+                    linear.push_str(&script(&sub.linear(), '_', to_subscript));
+                }
+                if let Some(sup) = sup {
+                    linear.push_str(&script(&sup.linear(), '^', to_superscript));
+                }
+                linear
+            }
+            Self::Frac { num, den } => format!("{}/{}", num.grouped(), den.grouped()),
+            Self::Sqrt(body) => format!("√{}", body.grouped()),
+        }
+    }
+
+    fn grouped(&self) -> String {
+        let linear = self.linear();
+        if linear.chars().count() > 1 && !matches!(self, Self::Atom { .. }) {
+            format!("({})", linear.trim())
+        } else {
+            linear
+        }
+    }
+}
+
+fn script(text: &str, marker: char, convert: fn(char) -> Option<char>) -> String {
+    let text = text.trim();
+    text.chars()
+        .map(convert)
+        .collect::<Option<String>>()
+        .unwrap_or_else(|| match text.chars().count() {
+            1 => format!("{marker}{text}"),
+            _ => format!("{marker}({text})"),
+        })
+}
+
+fn to_superscript(c: char) -> Option<char> {
+    let sup = match c {
+        '0' => '⁰',
+        '1' => '¹',
+        '2' => '²',
+        '3' => '³',
+        '4' => '⁴',
+        '5' => '⁵',
+        '6' => '⁶',
+        '7' => '⁷',
+        '8' => '⁸',
+        '9' => '⁹',
+        '+' => '⁺',
+        '−' | '-' => '⁻',
+        '=' => '⁼',
+        '(' => '⁽',
+        ')' => '⁾',
+        'n' => 'ⁿ',
+        'i' => 'ⁱ',
+        '′' => '′',
+        _ => return None,
+    };
+    Some(sup)
+}
+
+fn to_subscript(c: char) -> Option<char> {
+    let sub = match c {
+        '0' => '₀',
+        '1' => '₁',
+        '2' => '₂',
+        '3' => '₃',
+        '4' => '₄',
+        '5' => '₅',
+        '6' => '₆',
+        '7' => '₇',
+        '8' => '₈',
+        '9' => '₉',
+        '+' => '₊',
+        '−' | '-' => '₋',
+        '=' => '₌',
+        '(' => '₍',
+        ')' => '₎',
+        'i' => 'ᵢ',
+        'j' => 'ⱼ',
+        'n' => 'ₙ',
+        'x' => 'ₓ',
+        _ => return None,
+    };
+    Some(sub)
+}
+
+pub fn parse(source: &str) -> Node {
+    let mut parser = Parser {
+        chars: source.chars().peekable(),
+    };
+    Node::Row(parser.row())
+}
+
+struct Parser<I: Iterator<Item = char>> {
+    chars: std::iter::Peekable<I>,
+}
+
+impl<I: Iterator<Item = char>> Parser<I> {
+    /// Parses until the end of the current group
+    fn row(&mut self) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        while let Some(&c) = self.chars.peek() {
+            match c {
+                '}' => {
+                    self.chars.next();
+                    break;
+                }
+                '^' | '_' => {
+                    self.chars.next();
+                    let base = nodes.pop().unwrap_or(Node::Row(Vec::new()));
+                    let script = Box::new(self.argument());
+                    let scripts = match base {
+                        Node::Scripts { base, sup, sub } if c == '^' && sup.is_none() => {
+                            Node::Scripts {
+                                base,
+                                sup: Some(script),
+                                sub,
+                            }
+                        }
+                        Node::Scripts { base, sup, sub } if c == '_' && sub.is_none() => {
+                            Node::Scripts {
+                                base,
+                                sup,
+                                sub: Some(script),
+                            }
+                        }
+                        base => {
+                            let (sup, sub) = match c {
+                                '^' => (Some(script), None),
+                                _ => (None, Some(script)),
+                            };
+                            Node::Scripts {
+                                base: Box::new(base),
+                                sup,
+                                sub,
+                            }
+                        }
+                    };
+                    nodes.push(scripts);
+                }
+                _ => nodes.extend(self.atom()),
+            }
+        }
+        nodes
+    }
+
+    /// Parses a single atom or a braced group, like the arguments to `\frac`
+    fn argument(&mut self) -> Node {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.atom().unwrap_or(Node::Row(Vec::new()))
+    }
+
+    fn atom(&mut self) -> Option<Node> {
+        let c = self.chars.next()?;
+        let node = match c {
+            '{' => Node::Row(self.row()),
+            '\\' => self.command(),
+            '0'..='9' | '.' => {
+                let mut number = c.to_string();
+                while let Some(digit) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                    number.push(digit);
+                }
+                Node::upright(&number)
+            }
+            '+' | '=' | '<' | '>' => Node::upright(&format!(" {c} ")),
+            '-' => Node::upright(" − "),
+            '*' => Node::upright(" ∗ "),
+            ',' | ';' => Node::upright(&format!("{c} ")),
+            '\'' => Node::upright("′"),
+            // Alignment markers don't mean anything on a single line
+            '&' => return None,
+            c if c.is_whitespace() => return None,
+            c if c.is_alphabetic() => Node::Atom {
+                text: c.to_string(),
+                italic: true,
+            },
+            c => Node::upright(&c.to_string()),
+        };
+        Some(node)
+    }
+
+    fn command(&mut self) -> Node {
+        let mut name = String::new();
+        while let Some(c) = self.chars.next_if(char::is_ascii_alphabetic) {
+            name.push(c);
+        }
+        if name.is_empty() {
+            // Control symbols like `\,` or `\{`
+            let Some(c) = self.chars.next() else {
+                return Node::upright("\\");
+            };
+            return match c {
+                ',' | ':' | ';' | ' ' => Node::upright(" "),
+                '!' => Node::Row(Vec::new()),
+                '\\' => Node::upright("  "),
+                c => Node::upright(&c.to_string()),
+            };
+        }
+
+        match name.as_str() {
+            "frac" | "dfrac" | "tfrac" => {
+                let num = Box::new(self.argument());
+                let den = Box::new(self.argument());
+                Node::Frac { num, den }
+            }
+            "sqrt" => {
+                // The degree of an nth root gets dropped
+                if self.chars.next_if_eq(&'[').is_some() {
+                    while self.chars.next().is_some_and(|c| c != ']') {}
+                }
+                Node::Sqrt(Box::new(self.argument()))
+            }
+            "text" | "textrm" | "mathrm" | "operatorname" | "mbox" => {
+                Node::upright(&self.raw_argument())
+            }
+            "mathbf" | "mathit" | "mathsf" | "mathtt" | "boldsymbol" => self.argument(),
+            "left" | "right" | "big" | "Big" | "bigg" | "Bigg" => {
+                match self.argument() {
+                    // `\left.` leaves that side empty
+                    Node::Atom { text, .. } if text == "." => Node::Row(Vec::new()),
+                    delimiter => delimiter,
+                }
+            }
+            "quad" => Node::upright("  "),
+            "qquad" => Node::upright("    "),
+            name if FUNCTIONS.contains(&name) => Node::upright(&format!("{name} ")),
+            name => match symbol(name) {
+                Some(symbol) => Node::Atom {
+                    text: symbol.to_string(),
+                    // Lowercase greek letters are variables too
+                    italic: symbol.chars().all(char::is_lowercase),
+                },
+                None => Node::upright(&format!("\\{name}")),
+            },
+        }
+    }
+
+    /// The contents of a braced argument as written
+    fn raw_argument(&mut self) -> String {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if self.chars.next_if_eq(&'{').is_none() {
+            return self.chars.next().map(String::from).unwrap_or_default();
+        }
+        let mut depth = 0;
+        let mut raw = String::new();
+        for c in self.chars.by_ref() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            raw.push(c);
+        }
+        raw
+    }
+}
+
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "log", "ln", "lg", "exp", "lim", "max", "min", "sup", "inf", "det", "dim", "gcd", "deg", "arg",
+    "ker", "Pr",
+];
+
+fn symbol(name: &str) -> Option<&'static str> {
+    let symbol = match name {
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" => "ϵ",
+        "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" => "θ",
+        "vartheta" => "ϑ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "pi" => "π",
+        "varpi" => "ϖ",
+        "rho" => "ρ",
+        "varrho" => "ϱ",
+        "sigma" => "σ",
+        "varsigma" => "ς",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" => "ϕ",
+        "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Upsilon" => "Υ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        "sum" => "∑",
+        "prod" => "∏",
+        "coprod" => "∐",
+        "int" => "∫",
+        "iint" => "∬",
+        "iiint" => "∭",
+        "oint" => "∮",
+        "bigcup" => "⋃",
+        "bigcap" => "⋂",
+        "infty" => "∞",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "forall" => "∀",
+        "exists" => "∃",
+        "emptyset" | "varnothing" => "∅",
+        "hbar" => "ℏ",
+        "ell" => "ℓ",
+        "Re" => "ℜ",
+        "Im" => "ℑ",
+        "aleph" => "ℵ",
+        "prime" => "′",
+        "cdot" => " · ",
+        "cdots" => "⋯",
+        "ldots" | "dots" => "…",
+        "vdots" => "⋮",
+        "ddots" => "⋱",
+        "times" => " × ",
+        "div" => " ÷ ",
+        "pm" => " ± ",
+        "mp" => " ∓ ",
+        "ast" => " ∗ ",
+        "star" => " ⋆ ",
+        "circ" => " ∘ ",
+        "bullet" => " • ",
+        "oplus" => " ⊕ ",
+        "otimes" => " ⊗ ",
+        "cup" => " ∪ ",
+        "cap" => " ∩ ",
+        "setminus" => " ∖ ",
+        "wedge" | "land" => " ∧ ",
+        "vee" | "lor" => " ∨ ",
+        "neg" | "lnot" => "¬",
+        "le" | "leq" => " ≤ ",
+        "ge" | "geq" => " ≥ ",
+        "ne" | "neq" => " ≠ ",
+        "ll" => " ≪ ",
+        "gg" => " ≫ ",
+        "approx" => " ≈ ",
+        "sim" => " ∼ ",
+        "simeq" => " ≃ ",
+        "cong" => " ≅ ",
+        "equiv" => " ≡ ",
+        "propto" => " ∝ ",
+        "in" => " ∈ ",
+        "notin" => " ∉ ",
+        "ni" => " ∋ ",
+        "subset" => " ⊂ ",
+        "subseteq" => " ⊆ ",
+        "supset" => " ⊃ ",
+        "supseteq" => " ⊇ ",
+        "perp" => " ⊥ ",
+        "parallel" => " ∥ ",
+        "mid" => " ∣ ",
+        "to" | "rightarrow" => " → ",
+        "leftarrow" | "gets" => " ← ",
+        "leftrightarrow" => " ↔ ",
+        "Rightarrow" | "implies" => " ⇒ ",
+        "Leftarrow" => " ⇐ ",
+        "Leftrightarrow" | "iff" => " ⇔ ",
+        "mapsto" => " ↦ ",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "vert" => "|",
+        "Vert" => "‖",
+        _ => return None,
+    };
+    Some(symbol)
+}
+
+/// A run of text placed relative to the start of the formula's baseline
+#[derive(Clone, Debug, PartialEq)]
+pub struct Glyphs {
+    pub text: String,
+    pub italic: bool,
+    /// Relative to the formula's font size
+    pub scale: f32,
+    pub x: f32,
+    /// The baseline's offset with positive being further down
+    pub y: f32,
+}
+
+/// A horizontal line centered on `y`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub thickness: f32,
+}
+
+/// A laid out formula with every measurement in ems
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Layout {
+    pub glyphs: Vec<Glyphs>,
+    pub rules: Vec<Rule>,
+    pub width: f32,
+    pub ascent: f32,
+    pub descent: f32,
+}
+
+impl Layout {
+    /// Places `other` with its baseline starting at `(x, y)`
+    fn place(&mut self, other: Layout, x: f32, y: f32) {
+        self.glyphs
+            .extend(other.glyphs.into_iter().map(|glyphs| Glyphs {
+                x: glyphs.x + x,
+                y: glyphs.y + y,
+                ..glyphs
+            }));
+        self.rules.extend(other.rules.into_iter().map(|rule| Rule {
+            x: rule.x + x,
+            y: rule.y + y,
+            ..rule
+        }));
+        self.width = self.width.max(x + other.width);
+        self.ascent = self.ascent.max(other.ascent - y);
+        self.descent = self.descent.max(other.descent + y);
+    }
+}
+
+/// Lays out `node` with `measure` giving the width in ems of some text at the formula's size
+pub fn layout(node: &Node, measure: &mut dyn FnMut(&str, bool) -> f32) -> Layout {
+    lay(node, 1.0, measure)
+}
+
+fn lay(node: &Node, scale: f32, measure: &mut dyn FnMut(&str, bool) -> f32) -> Layout {
+    match node {
+        Node::Atom { text, italic } => Layout {
+            glyphs: vec![Glyphs {
+                text: text.to_owned(),
+                italic: *italic,
+                scale,
+                x: 0.,
+                y: 0.,
+            }],
+            rules: Vec::new(),
+            width: measure(text, *italic) * scale,
+            ascent: ASCENT * scale,
+            descent: DESCENT * scale,
+        },
+        Node::Row(nodes) => {
+            let mut row = Layout::default();
+            for node in nodes {
+                let x = row.width;
+                row.place(lay(node, scale, measure), x, 0.);
+            }
+            row
+        }
+        Node::Scripts { base, sup, sub } => {
+            let script_scale = (scale * SCRIPT_SCALE).max(MIN_SCALE);
+            let base = lay(base, scale, measure);
+            let sup = sup.as_ref().map(|sup| lay(sup, script_scale, measure));
+            let sub = sub.as_ref().map(|sub| lay(sub, script_scale, measure));
+
+            let mut up = sup
+                .as_ref()
+                .map_or(0., |sup| (0.42 * scale).max(base.ascent - sup.ascent * 0.6));
+            let down = sub.as_ref().map_or(0., |sub| {
+                (0.2 * scale).max(base.descent - sub.descent * 0.4)
+            });
+            if let (Some(sup), Some(sub)) = (&sup, &sub) {
+                // Keep the scripts from running into each other
+                let clearance = (up - sup.descent) - (sub.ascent - down);
+                up += (0.1 * scale - clearance).max(0.);
+            }
+
+            let x = base.width + 0.05 * scale;
+            let mut scripts = Layout::default();
+            scripts.place(base, 0., 0.);
+            if let Some(sup) = sup {
+                scripts.place(sup, x, -up);
+            }
+            if let Some(sub) = sub {
+                scripts.place(sub, x, down);
+            }
+            scripts
+        }
+        Node::Frac { num, den } => {
+            let part_scale = (scale * FRAC_SCALE).max(MIN_SCALE);
+            let num = lay(num, part_scale, measure);
+            let den = lay(den, part_scale, measure);
+            let padding = 0.1 * scale;
+            let width = num.width.max(den.width) + 2. * padding;
+            let axis = AXIS * scale;
+            let half_rule = RULE * scale / 2.;
+
+            let mut frac = Layout {
+                rules: vec![Rule {
+                    x: 0.,
+                    y: -axis,
+                    width,
+                    thickness: RULE * scale,
+                }],
+                width,
+                ..Default::default()
+            };
+            let num_y = -(axis + half_rule + GAP * scale + num.descent);
+            let den_y = -axis + half_rule + GAP * scale + den.ascent;
+            let (num_x, den_x) = ((width - num.width) / 2., (width - den.width) / 2.);
+            frac.place(num, num_x, num_y);
+            frac.place(den, den_x, den_y);
+            frac
+        }
+        Node::Sqrt(body) => {
+            let body = lay(body, scale, measure);
+            // Grow the radical sign to cover the whole body
+            let radical_scale = ((body.ascent + body.descent) / (ASCENT + DESCENT)).max(scale);
+            let radical = lay(&Node::upright("√"), radical_scale, measure);
+            let radical_y = body.descent - radical.descent;
+            let overline_y = -(body.ascent + GAP * scale);
+            let x = radical.width;
+
+            let mut sqrt = Layout {
+                rules: vec![Rule {
+                    x,
+                    y: overline_y,
+                    width: body.width + 0.05 * scale,
+                    thickness: RULE * scale,
+                }],
+                width: x + body.width + 0.05 * scale,
+                ascent: -overline_y + RULE * scale,
+                ..Default::default()
+            };
+            sqrt.place(radical, 0., radical_y);
+            sqrt.place(body, x, 0.);
+            sqrt
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measure(text: &str, _: bool) -> f32 {
+        text.chars().count() as f32 * 0.5
+    }
+
+    #[test]
+    fn parsing() {
+        let italic = |text: &str| Node::Atom {
+            text: text.to_owned(),
+            italic: true,
+        };
+        let node = parse(r"\frac{a}{2} + x_i^2");
+        let Node::Row(nodes) = node else {
+            panic!("Expected a row");
+        };
+        assert_eq!(
+            nodes,
+            [
+                Node::Frac {
+                    num: Box::new(Node::Row(vec![italic("a")])),
+                    den: Box::new(Node::Row(vec![Node::upright("2")])),
+                },
+                Node::upright(" + "),
+                Node::Scripts {
+                    base: Box::new(italic("x")),
+                    sup: Some(Box::new(Node::upright("2"))),
+                    sub: Some(Box::new(italic("i"))),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn linear() {
+        assert_eq!(parse(r"x^2 + y_{10}").linear(), "x² + y₁₀");
+        assert_eq!(parse(r"\frac{a+b}{2}").linear(), "(a + b)/2");
+        assert_eq!(parse(r"\sqrt{x} \le \alpha^{k}").linear(), "√x ≤ α^k");
+        assert_eq!(parse(r"\text{if } \unknown").linear(), "if \\unknown");
+    }
+
+    #[test]
+    fn fraction_layout() {
+        let layout = layout(&parse(r"\frac{12}{c}"), &mut measure);
+        let [bar] = &layout.rules[..] else {
+            panic!("Expected a single fraction bar");
+        };
+        assert_eq!(bar.y, -AXIS);
+        assert!((layout.width - (0.9 + 0.2)).abs() < 1e-5);
+
+        let [num, den] = &layout.glyphs[..] else {
+            panic!("Expected a numerator and denominator");
+        };
+        assert!(num.y < bar.y && den.y > bar.y);
+        // The shorter denominator gets centered
+        assert!(den.x > num.x);
+        assert!(layout.ascent > ASCENT && layout.descent > DESCENT);
+    }
+
+    #[test]
+    fn scripts_layout() {
+        let layout = layout(&parse("x_i^2"), &mut measure);
+        let [base, sup, sub] = &layout.glyphs[..] else {
+            panic!("Expected a base and two scripts");
+        };
+        assert_eq!(base.y, 0.);
+        assert!(sup.y < 0. && sub.y > 0.);
+        assert_eq!(sup.x, sub.x);
+        assert_eq!(sup.scale, SCRIPT_SCALE);
+    }
+}
@@ -0,0 +1,96 @@
+//! Math written between `$` (inline) or `$$` (display) delimiters
+//!
+//! Display math gets typeset into its own element, while inline math has to live inside of a
+//! text box, so it's written out on a single line with unicode scripts instead
+
+mod layout;
+
+use crate::text::{Text, TextBox, TextSystem};
+use crate::utils::{Point, Rect, Size};
+
+/// Where the baseline sits from the top of a single line text box, in ems
+const BASELINE: f32 = 0.85;
+
+#[derive(Debug, PartialEq)]
+pub struct Math {
+    pub source: String,
+    node: layout::Node,
+    pub font_size: f32,
+    pub color: [f32; 4],
+    pub hidpi_scale: f32,
+}
+
+/// Every part of a formula positioned relative to its top-left corner
+pub struct MathLayout {
+    pub pieces: Vec<(Point, TextBox)>,
+    pub rules: Vec<Rect>,
+    pub size: Size,
+}
+
+impl Math {
+    pub fn new(source: String, font_size: f32, color: [f32; 4], hidpi_scale: f32) -> Self {
+        Self {
+            node: layout::parse(&source),
+            source,
+            font_size,
+            color,
+            hidpi_scale,
+        }
+    }
+
+    fn text_box(&self, text: &str, italic: bool, scale: f32) -> TextBox {
+        let text = Text::new(text.to_owned(), self.hidpi_scale, self.color).make_italic(italic);
+        TextBox {
+            font_size: self.font_size * scale,
+            ..TextBox::new(vec![text], self.hidpi_scale)
+        }
+    }
+
+    pub fn layout(&self, text_system: &mut TextSystem, zoom: f32) -> MathLayout {
+        let em = self.font_size * self.hidpi_scale * zoom;
+        let unbounded = (f32::INFINITY, f32::INFINITY);
+        let mut measure = |text: &str, italic: bool| {
+            let width = self
+                .text_box(text, italic, 1.)
+                .size(text_system, unbounded, zoom)
+                .0;
+            width / em
+        };
+        let layout = layout::layout(&self.node, &mut measure);
+
+        let pieces = layout
+            .glyphs
+            .iter()
+            .map(|glyphs| {
+                let pos = (
+                    glyphs.x * em,
+                    (layout.ascent + glyphs.y - BASELINE * glyphs.scale) * em,
+                );
+                (
+                    pos,
+                    self.text_box(&glyphs.text, glyphs.italic, glyphs.scale),
+                )
+            })
+            .collect();
+        let rules = layout
+            .rules
+            .iter()
+            .map(|rule| {
+                let top = layout.ascent + rule.y - rule.thickness / 2.;
+                // Keep thin rules from disappearing at small sizes
+                let thickness = (rule.thickness * em).max(self.hidpi_scale);
+                Rect::new((rule.x * em, top * em), (rule.width * em, thickness))
+            })
+            .collect();
+        MathLayout {
+            pieces,
+            rules,
+            size: (layout.width * em, (layout.ascent + layout.descent) * em),
+        }
+    }
+}
+
+/// The single line fallback used for inline math
+pub fn linear(source: &str) -> String {
+    layout::parse(source).linear()
+}
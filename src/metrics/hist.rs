@@ -11,6 +11,8 @@ use tracing::{debug, info, span, trace};
 pub enum Tag {
     ImageDecompress,
     ImageLoad,
+    Interpret,
+    Layout,
     Positioner,
     Redraw,
     Reposition,
@@ -29,6 +31,8 @@ impl Tag {
         match self {
             Tag::ImageDecompress => "image.decompress",
             Tag::ImageLoad => "image.load",
+            Tag::Interpret => "interpret",
+            Tag::Layout => "layout",
             Tag::Positioner => "positioner",
             Tag::Redraw => "redraw",
             Tag::Reposition => "reposition",
@@ -39,6 +43,8 @@ impl Tag {
         match self {
             Self::ImageDecompress => "Decompressing image data to render",
             Self::ImageLoad => "Reading, decoding, and compressing the raw image data",
+            Self::Interpret => "Interpreting a document into elements",
+            Self::Layout => "Positioning a whole document, over however many passes it takes",
             Self::Positioner => "Positioning all of the elements",
             Self::Redraw => "A full redraw",
             Self::Reposition => "Repositioning all of the elements in the queue",
@@ -49,6 +55,8 @@ impl Tag {
         match self {
             Self::ImageDecompress
             | Self::ImageLoad
+            | Self::Interpret
+            | Self::Layout
             | Self::Positioner
             | Self::Redraw
             | Self::Reposition => Unit::Seconds,
@@ -72,7 +80,9 @@ impl Iterator for TagIter {
         let next = std::mem::take(&mut self.0)?;
         self.0 = match next {
             Tag::ImageDecompress => Some(Tag::ImageLoad),
-            Tag::ImageLoad => Some(Tag::Positioner),
+            Tag::ImageLoad => Some(Tag::Interpret),
+            Tag::Interpret => Some(Tag::Layout),
+            Tag::Layout => Some(Tag::Positioner),
             Tag::Positioner => Some(Tag::Redraw),
             Tag::Redraw => Some(Tag::Reposition),
             Tag::Reposition => None,
@@ -1,9 +1,10 @@
 use std::sync::Arc;
 
-use super::{counter, gauge, hist, Unit};
+use super::{counter, gauge, hist, HistTag, Unit};
 
 use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, SharedString};
 use metrics_util::registry::{Registry, Storage};
+use metrics_util::Summary;
 
 struct MetricStore;
 
@@ -33,6 +34,15 @@ impl Default for LogRecorder {
     }
 }
 
+impl LogRecorder {
+    /// Everything recorded so far for a histogram
+    pub fn histogram_summary(&self, tag: HistTag) -> Option<Summary> {
+        let hist = self.0.get_histogram(&Key::from_name(tag))?;
+        let summary = hist.0.lock().value.clone();
+        Some(summary)
+    }
+}
+
 impl metrics::Recorder for LogRecorder {
     fn describe_gauge(&self, key: KeyName, unit: Option<Unit>, _desc: SharedString) {
         let key = Key::from_name(key);
@@ -5,7 +5,7 @@ use parking_lot::Mutex;
 use tracing::Level;
 
 // Re-exports from the actual `metrics` crate
-pub use metrics::{describe_histogram, histogram, set_global_recorder, Unit};
+pub use metrics::{describe_histogram, histogram, set_global_recorder, with_local_recorder, Unit};
 
 mod counter;
 mod gauge;
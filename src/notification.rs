@@ -0,0 +1,31 @@
+//! Best-effort desktop notifications using the tools that each platform already ships with
+
+use std::process::{Command, Stdio};
+
+pub fn send(summary: &str, body: &str) {
+    let mut cmd = if cfg!(target_os = "macos") {
+        // Debug formatting gives us a quoted and escaped string that AppleScript also accepts
+        let script = format!("display notification {body:?} with title {summary:?}");
+        let mut cmd = Command::new("osascript");
+        cmd.arg("-e").arg(script);
+        cmd
+    } else if cfg!(windows) {
+        tracing::debug!("Desktop notifications are not supported on Windows");
+        return;
+    } else {
+        let mut cmd = Command::new("notify-send");
+        cmd.args(["--app-name", "inlyne", summary, body]);
+        cmd
+    };
+
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    match cmd.spawn() {
+        Ok(mut child) => {
+            // Reap the child in the background so that we don't leave a zombie behind
+            std::thread::spawn(move || child.wait());
+        }
+        Err(err) => tracing::debug!("Failed sending desktop notification: {err}"),
+    }
+}
@@ -5,6 +5,7 @@ use serde::Deserialize;
 use std::array;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum ThemeType {
@@ -81,6 +82,34 @@ impl FromStr for Size {
     }
 }
 
+/// A span of time like `30s`, `5m` or `1h`. Plain numbers are taken as seconds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval(pub Duration);
+
+impl FromStr for Interval {
+    type Err = &'static str;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (number, unit) = match input.find(|c: char| c.is_ascii_alphabetic()) {
+            Some(unit_start) => input.split_at(unit_start),
+            None => (input, "s"),
+        };
+        let number: f64 = number
+            .parse()
+            .map_err(|_| "Invalid interval: expected a number followed by s, m or h")?;
+        let unit_secs = match unit {
+            "s" => 1.,
+            "m" => 60.,
+            "h" => 60. * 60.,
+            _ => return Err("Invalid interval unit: expected one of s, m or h"),
+        };
+        match Duration::try_from_secs_f64(number * unit_secs) {
+            Ok(duration) if !duration.is_zero() => Ok(Self(duration)),
+            _ => Err("Invalid interval: should be more than zero"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Parser)]
 #[command(version, about, arg_required_else_help(true))]
 #[clap(args_conflicts_with_subcommands = true)]
@@ -115,6 +144,17 @@ pub enum Commands {
     View(View),
     #[command(subcommand)]
     Config(ConfigCmd),
+    Bench(Bench),
+    Print(Print),
+    InstallDesktop(InstallDesktop),
+    #[command(subcommand)]
+    Remote(RemoteCmd),
+    #[command(subcommand)]
+    Theme(ThemeCmd),
+    #[command(subcommand)]
+    Export(ExportCmd),
+    Screenshot(Screenshot),
+    Serve(Serve),
 }
 
 /// View a markdown file with inlyne
@@ -152,6 +192,201 @@ pub struct View {
     /// Size of the opened window <width>x<height>
     #[arg(long = "win-size", value_parser = value_parser!(Size))]
     pub size: Option<Size>,
+
+    /// Resize the window to fit the document's width once it's laid out
+    #[arg(long = "fit")]
+    pub fit: bool,
+
+    /// Trade rendering speed for a smaller memory footprint on huge documents
+    #[arg(long = "low-memory")]
+    pub low_memory: bool,
+
+    /// Keep the view pinned to the end of the file as it grows, unless scrolled away from
+    #[arg(long = "tail")]
+    pub tail: bool,
+
+    /// Mirror the scroll position and zoom of other windows opened with `--follow`
+    #[arg(long = "follow")]
+    pub follow: bool,
+
+    /// Show the document without taking any input, except for Ctrl+Alt+Q to quit
+    #[arg(long = "kiosk")]
+    pub kiosk: bool,
+
+    /// Scroll through the document on a loop in kiosk mode at this many pixels per second
+    #[arg(long = "kiosk-scroll", value_name = "SPEED", requires = "kiosk")]
+    pub kiosk_scroll: Option<f32>,
+
+    /// Scroll to LINE of the markdown source once the file is laid out, like `FILE:LINE` does
+    #[arg(long = "line", value_name = "LINE")]
+    pub line: Option<usize>,
+
+    /// Start out searching for QUERY, keeping a count of its matches in view
+    #[arg(long = "find", value_name = "QUERY")]
+    pub find: Option<String>,
+
+    /// Rotate through FILE and these comma-separated files on a timer
+    #[arg(long = "cycle", value_name = "FILES", value_delimiter = ',')]
+    pub cycle: Vec<PathBuf>,
+
+    /// How long each document stays up with `--cycle` e.g. 30s, 5m or 1h [default: 30s]
+    #[arg(long = "interval", value_parser = value_parser!(Interval), requires = "cycle")]
+    pub interval: Option<Interval>,
+
+    /// Show another markdown file next to FILE, each scrolling on its own
+    #[arg(long = "side-by-side", value_name = "OTHER")]
+    pub side_by_side: Option<PathBuf>,
+
+    /// Listen for documents pushed with `inlyne remote preview`
+    #[arg(long = "remote-preview", hide = true)]
+    pub remote_preview: bool,
+
+    /// Outline every element, and describe the one under the cursor while Alt is held
+    #[arg(long = "debug-bounds")]
+    pub debug_bounds: bool,
+}
+
+/// Time interpreting, positioning, and rendering a file over several runs
+#[derive(ClapArgs, PartialEq, Debug, Clone)]
+pub struct Bench {
+    /// Path to the markdown file
+    #[arg(value_name = "FILE")]
+    pub file_path: PathBuf,
+
+    /// Number of times to render the file
+    #[arg(
+        short = 'n',
+        long = "iterations",
+        default_value_t = 10,
+        value_parser = value_parser!(u32).range(1..)
+    )]
+    pub iterations: u32,
+
+    /// Configuration file to use
+    #[arg(short = 'c', long = "config")]
+    pub config: Option<PathBuf>,
+}
+
+/// Print a markdown file to the terminal with colors and clickable links
+#[derive(ClapArgs, PartialEq, Debug, Clone)]
+pub struct Print {
+    /// Path to the markdown file
+    #[arg(value_name = "FILE")]
+    pub file_path: PathBuf,
+}
+
+/// Associate markdown files with inlyne so that double-clicking them opens the viewer
+#[derive(ClapArgs, PartialEq, Debug, Clone)]
+pub struct InstallDesktop {
+    /// Print what would be installed without changing anything
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+}
+
+/// Interact with other inlyne windows
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+pub enum RemoteCmd {
+    /// Shows a document in the preview window, opening one if needed
+    Preview(RemotePreview),
+}
+
+#[derive(ClapArgs, PartialEq, Debug, Clone)]
+pub struct RemotePreview {
+    /// Read the document from stdin, like an unsaved editor buffer
+    #[arg(long = "stdin", required = true)]
+    pub stdin: bool,
+}
+
+/// Theme related things
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+pub enum ThemeCmd {
+    /// Opens a sample document that can be switched between the available themes
+    Preview(ThemePreview),
+}
+
+#[derive(ClapArgs, PartialEq, Debug, Clone)]
+pub struct ThemePreview {
+    /// Configuration file to use
+    #[arg(short = 'c', long = "config")]
+    pub config: Option<PathBuf>,
+}
+
+/// Convert markdown files to other formats without opening a window
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+pub enum ExportCmd {
+    /// Writes a standalone HTML page styled like the viewer
+    Html(ExportHtml),
+}
+
+#[derive(ClapArgs, PartialEq, Debug, Clone)]
+pub struct ExportHtml {
+    /// Path to the markdown file
+    #[arg(value_name = "FILE")]
+    pub file_path: PathBuf,
+
+    /// Where to write the page [default: stdout]
+    #[arg(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+
+    /// Theme to style the page with
+    #[arg(short = 't', long = "theme", value_parser = value_parser!(ThemeType))]
+    pub theme: Option<ThemeType>,
+
+    /// Configuration file to use
+    #[arg(short = 'c', long = "config")]
+    pub config: Option<PathBuf>,
+}
+
+/// Render a markdown file to a PNG without opening a window
+#[derive(ClapArgs, PartialEq, Debug, Clone)]
+pub struct Screenshot {
+    /// Path to the markdown file
+    #[arg(value_name = "FILE")]
+    pub file_path: PathBuf,
+
+    /// Where to write the PNG
+    #[arg(short = 'o', long = "output", required = true)]
+    pub output: PathBuf,
+
+    /// Width of the image in pixels. The height fits the whole document
+    #[arg(
+        long = "width",
+        default_value_t = 800,
+        value_parser = value_parser!(u32).range(1..)
+    )]
+    pub width: u32,
+
+    /// Theme to use when rendering
+    #[arg(short = 't', long = "theme", value_parser = value_parser!(ThemeType))]
+    pub theme: Option<ThemeType>,
+
+    /// Factor to scale rendered file by
+    #[arg(short = 's', long = "scale")]
+    pub scale: Option<f32>,
+
+    /// Configuration file to use
+    #[arg(short = 'c', long = "config")]
+    pub config: Option<PathBuf>,
+}
+
+/// Preview a directory of docs in a window while serving them as HTML on localhost
+#[derive(ClapArgs, PartialEq, Debug, Clone)]
+pub struct Serve {
+    /// Path to the docs directory
+    #[arg(value_name = "DIR")]
+    pub dir: PathBuf,
+
+    /// Port to serve the docs on [default: any free port]
+    #[arg(long = "port", default_value_t = 0)]
+    pub port: u16,
+
+    /// Theme to use when rendering
+    #[arg(short = 't', long = "theme", value_parser = value_parser!(ThemeType))]
+    pub theme: Option<ThemeType>,
+
+    /// Configuration file to use
+    #[arg(short = 'c', long = "config")]
+    pub config: Option<PathBuf>,
 }
 
 /// Configuration related things
@@ -159,4 +394,49 @@ pub struct View {
 pub enum ConfigCmd {
     /// Opens the configuration file in the default text editor
     Open,
+    /// Prints the effective configuration, noting where each value came from
+    Dump(Dump),
+}
+
+#[derive(ClapArgs, PartialEq, Debug, Clone, Default)]
+pub struct Dump {
+    /// Configuration file to use
+    #[arg(short = 'c', long = "config")]
+    pub config: Option<PathBuf>,
+
+    /// Theme to use when rendering
+    #[arg(short = 't', long = "theme", value_parser = value_parser!(ThemeType))]
+    pub theme: Option<ThemeType>,
+
+    /// Enable decorations
+    #[arg(short = 'd', long = "decorations")]
+    pub decorations: Option<bool>,
+
+    /// Factor to scale rendered file by
+    #[arg(short = 's', long = "scale")]
+    pub scale: Option<f32>,
+
+    /// Maximum width of page in pixels
+    #[arg(short = 'w', long = "page-width")]
+    pub page_width: Option<f32>,
+}
+
+impl Dump {
+    /// The command line flags as they would be written in the config file
+    pub fn as_table(&self) -> toml::Table {
+        let mut table = toml::Table::new();
+        if let Some(theme) = self.theme {
+            table.insert("theme".into(), format!("{theme:?}").into());
+        }
+        if let Some(decorations) = self.decorations {
+            table.insert("decorations".into(), decorations.into());
+        }
+        if let Some(scale) = self.scale {
+            table.insert("scale".into(), f64::from(scale).into());
+        }
+        if let Some(page_width) = self.page_width {
+            table.insert("page-width".into(), f64::from(page_width).into());
+        }
+        table
+    }
 }
@@ -1,10 +1,15 @@
+use std::collections::HashMap;
 use std::fs::{create_dir_all, read_to_string};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
 use super::{Position, Size, ThemeType};
 use crate::color;
+use crate::history;
 use crate::keybindings::Keybindings;
+use crate::positioner::ElementSpacing;
+use crate::search;
+use crate::table::TableCards;
 
 use anyhow::Context;
 use serde::Deserialize;
@@ -28,6 +33,11 @@ pub struct OptionalTheme {
     pub quote_block_color: Option<u32>,
     pub link_color: Option<u32>,
     pub select_color: Option<u32>,
+    pub select_alpha: Option<f32>,
+    pub search_color: Option<u32>,
+    pub search_alpha: Option<f32>,
+    pub match_color: Option<u32>,
+    pub match_alpha: Option<f32>,
     pub checkbox_color: Option<u32>,
     pub header_color: Option<u32>,
     pub scrollbar_width: Option<u32>,
@@ -38,28 +48,82 @@ pub struct OptionalTheme {
 
 impl OptionalTheme {
     pub fn merge(self, other: color::Theme) -> anyhow::Result<color::Theme> {
+        let palette = other.palette;
         let code_highlighter = match self.code_highlighter {
             Some(theme) => SyntectTheme::try_from(theme)?,
-            None => other.code_highlighter,
+            None => palette.code_highlighter,
         };
 
         Ok(color::Theme {
-            text_color: self.text_color.unwrap_or(other.text_color),
-            background_color: self.background_color.unwrap_or(other.background_color),
-            code_color: self.code_color.unwrap_or(other.code_color),
-            quote_block_color: self.quote_block_color.unwrap_or(other.quote_block_color),
-            link_color: self.link_color.unwrap_or(other.link_color),
-            select_color: self.select_color.unwrap_or(other.select_color),
-            checkbox_color: self.checkbox_color.unwrap_or(other.checkbox_color),
-            header_color: self.header_color.unwrap_or(other.header_color),
             scrollbar_width: self.scrollbar_width.unwrap_or(other.scrollbar_width),
-            scrollbar_color: self.scrollbar_color.unwrap_or(other.scrollbar_color),
             page_margin: self.page_margin.unwrap_or(other.page_margin),
-            code_highlighter,
+            palette: color::Palette {
+                text_color: self.text_color.unwrap_or(palette.text_color),
+                background_color: self.background_color.unwrap_or(palette.background_color),
+                code_color: self.code_color.unwrap_or(palette.code_color),
+                quote_block_color: self.quote_block_color.unwrap_or(palette.quote_block_color),
+                link_color: self.link_color.unwrap_or(palette.link_color),
+                select_color: self.select_color.unwrap_or(palette.select_color),
+                select_alpha: self.select_alpha.unwrap_or(palette.select_alpha),
+                search_color: self.search_color.unwrap_or(palette.search_color),
+                search_alpha: self.search_alpha.unwrap_or(palette.search_alpha),
+                match_color: self.match_color.unwrap_or(palette.match_color),
+                match_alpha: self.match_alpha.unwrap_or(palette.match_alpha),
+                checkbox_color: self.checkbox_color.unwrap_or(palette.checkbox_color),
+                header_color: self.header_color.unwrap_or(palette.header_color),
+                scrollbar_color: self.scrollbar_color.unwrap_or(palette.scrollbar_color),
+                code_highlighter,
+            },
         })
     }
 }
 
+/// Layout settings shared by the light and dark themes. Either theme can still override them
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct OptionalLayout {
+    pub scrollbar_width: Option<u32>,
+    pub page_margin: Option<u32>,
+}
+
+impl OptionalLayout {
+    pub fn merge(&self, other: color::Theme) -> color::Theme {
+        color::Theme {
+            scrollbar_width: self.scrollbar_width.unwrap_or(other.scrollbar_width),
+            page_margin: self.page_margin.unwrap_or(other.page_margin),
+            ..other
+        }
+    }
+}
+
+/// Space left after each kind of element, in pixels
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct OptionalSpacing {
+    pub paragraph: Option<f32>,
+    /// One for each level from `h1` to `h6`
+    pub heading: Option<[f32; 6]>,
+    pub before_heading: Option<f32>,
+    pub list: Option<f32>,
+    pub table: Option<f32>,
+    pub code_block: Option<f32>,
+    pub image: Option<f32>,
+}
+
+impl OptionalSpacing {
+    pub fn merge(&self, other: ElementSpacing) -> ElementSpacing {
+        ElementSpacing {
+            paragraph: self.paragraph.unwrap_or(other.paragraph),
+            headings: self.heading.unwrap_or(other.headings),
+            before_heading: self.before_heading.unwrap_or(other.before_heading),
+            list: self.list.unwrap_or(other.list),
+            table: self.table.unwrap_or(other.table),
+            code_block: self.code_block.unwrap_or(other.code_block),
+            image: self.image.unwrap_or(other.image),
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, PartialEq)]
 pub struct LinesToScroll(pub f32);
 
@@ -75,6 +139,66 @@ impl Default for LinesToScroll {
     }
 }
 
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct PageScrollFraction(pub f32);
+
+impl From<PageScrollFraction> for f32 {
+    fn from(value: PageScrollFraction) -> Self {
+        value.0
+    }
+}
+
+impl Default for PageScrollFraction {
+    fn default() -> Self {
+        Self(0.9)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ScrollMultiplier(pub f32);
+
+impl From<ScrollMultiplier> for f32 {
+    fn from(value: ScrollMultiplier) -> Self {
+        value.0
+    }
+}
+
+impl Default for ScrollMultiplier {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct HistoryLimit(pub usize);
+
+impl Default for HistoryLimit {
+    fn default() -> Self {
+        Self(history::DEFAULT_LIMIT)
+    }
+}
+
+/// How the window asks for attention when the file it shows gets reloaded
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReloadAttention {
+    #[default]
+    None,
+    /// Flashes the taskbar entry, or sets the urgency hint on X11
+    Flash,
+    /// Brings the window to the front
+    Raise,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct SearchHistoryLimit(pub usize);
+
+impl Default for SearchHistoryLimit {
+    fn default() -> Self {
+        Self(search::DEFAULT_HISTORY_LIMIT)
+    }
+}
+
 #[derive(Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct KeybindingsSection {
     #[serde(default)]
@@ -103,6 +227,37 @@ pub struct Window {
     pub size: Option<Size>,
 }
 
+/// How to open links with a custom scheme like `jira:PROJ-123`
+///
+/// `{link}` is replaced with the full link and `{path}` with everything after the scheme
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkHandler {
+    /// Opens a rewritten link instead e.g. `"https://jira.example.com/browse/{path}"`
+    Rewrite(String),
+    /// Runs a command e.g. `["zotero-open", "{link}"]`
+    Command(Vec<String>),
+}
+
+/// Per-plugin settings from a `[plugins.<name>]` section
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct PluginSection {
+    pub enabled: bool,
+    /// Everything else is passed along to the plugin
+    #[serde(flatten)]
+    pub options: toml::Table,
+}
+
+impl Default for PluginSection {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            options: Default::default(),
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Default, PartialEq)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct Config {
@@ -111,12 +266,48 @@ pub struct Config {
     pub scale: Option<f32>,
     pub page_width: Option<f32>,
     pub lines_to_scroll: LinesToScroll,
+    pub page_scroll_fraction: PageScrollFraction,
+    pub page_overlap_lines: f32,
+    pub invert_scroll: bool,
+    pub wheel_multiplier: ScrollMultiplier,
+    pub touchpad_multiplier: ScrollMultiplier,
+    pub copy_match_locations: bool,
+    pub history_limit: HistoryLimit,
+    pub persist_history: bool,
+    pub wide_gamut: bool,
+    pub smart_punctuation: Option<bool>,
+    pub collapse_extras: bool,
+    pub table_cards: TableCards,
+    pub reload_attention: ReloadAttention,
+    pub search_smart_case: Option<bool>,
+    pub search_whole_word: bool,
+    pub search_history_limit: SearchHistoryLimit,
+    pub search_match_offset: Option<f32>,
+    pub hide_cursor_after: Option<f32>,
+    pub image_max_width: Option<f32>,
+    pub image_max_height: Option<f32>,
+    pub zen_measure: Option<f32>,
+    pub link_dry_run: bool,
+    pub remember_scroll: Option<bool>,
+    pub toc_depth: Option<u8>,
+    pub title_template: Option<String>,
+    pub code_line_numbers: bool,
+    pub image_max_texture_size: Option<u32>,
+    pub sticky_headings: Option<bool>,
+    pub code_wrap_guide: Option<u32>,
+    pub code_wrap_indicator: Option<bool>,
+    pub layout: OptionalLayout,
+    pub spacing: OptionalSpacing,
     pub light_theme: Option<OptionalTheme>,
     pub dark_theme: Option<OptionalTheme>,
     pub font_options: Option<FontOptions>,
     pub keybindings: KeybindingsSection,
     pub debug: DebugSection,
     pub window: Option<Window>,
+    pub plugins: HashMap<String, PluginSection>,
+    pub link_handlers: HashMap<String, LinkHandler>,
+    pub external_renderers: HashMap<String, Vec<String>>,
+    pub run_commands: HashMap<String, Vec<String>>,
 }
 
 impl Config {
@@ -134,11 +325,33 @@ impl Config {
         Self::load_from_str(&config_content)
     }
 
-    pub fn load_from_system() -> anyhow::Result<Self> {
+    pub fn system_config_path() -> anyhow::Result<PathBuf> {
         let config_dir =
             dirs::config_dir().context("Failed to find the configuration directory")?;
+        Ok(config_dir.join("inlyne").join("inlyne.toml"))
+    }
 
-        let config_path = config_dir.join("inlyne").join("inlyne.toml");
+    /// Finds the closest `.inlyne.toml` in the directory of `path` or any of its parents
+    ///
+    /// The search stops at the repository root (the first directory with a `.git`) or the home
+    /// directory. Outside of both only the file's own directory is checked, so that a file in a
+    /// shared directory like `/tmp` doesn't pick up configs from above it
+    pub fn find_project_config(path: &Path) -> Option<PathBuf> {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        let home = dirs::home_dir().map(|home| home.canonicalize().unwrap_or(home));
+        let dirs: Vec<_> = path.ancestors().skip(1).collect();
+        let boundary = dirs
+            .iter()
+            .position(|dir| dir.join(".git").exists() || Some(*dir) == home.as_deref());
+        let searched = boundary.map_or(1, |boundary| boundary + 1);
+        dirs.into_iter()
+            .take(searched)
+            .map(|dir| dir.join(".inlyne.toml"))
+            .find(|config| config.is_file())
+    }
+
+    pub fn load_from_system() -> anyhow::Result<Self> {
+        let config_path = Self::system_config_path()?;
 
         if !config_path.is_file() {
             Self::create_default_config(&config_path)?
@@ -190,4 +403,78 @@ mod tests {
             color::Theme::light_default()
         );
     }
+
+    #[test]
+    fn plugin_sections() {
+        let config = Config::load_from_str(
+            r#"
+[plugins.plantuml]
+server = "http://localhost:8080"
+
+[plugins.word-count]
+enabled = false
+"#,
+        )
+        .unwrap();
+
+        let plantuml = &config.plugins["plantuml"];
+        assert!(plantuml.enabled);
+        assert_eq!(
+            plantuml.options.get("server"),
+            Some(&toml::Value::String("http://localhost:8080".to_owned()))
+        );
+        let word_count = &config.plugins["word-count"];
+        assert!(!word_count.enabled);
+        assert!(word_count.options.is_empty());
+    }
+
+    #[test]
+    fn link_handlers() {
+        let config = Config::load_from_str(
+            r#"
+[link-handlers]
+jira = { rewrite = "https://jira.example.com/browse/{path}" }
+zotero = { command = ["zotero-open", "{link}"] }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.link_handlers["jira"],
+            LinkHandler::Rewrite("https://jira.example.com/browse/{path}".to_owned())
+        );
+        assert_eq!(
+            config.link_handlers["zotero"],
+            LinkHandler::Command(vec!["zotero-open".to_owned(), "{link}".to_owned()])
+        );
+    }
+
+    #[test]
+    fn project_config_is_found_in_parents() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("docs").join("guide");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        let file = nested.join("intro.md");
+        assert_eq!(Config::find_project_config(&file), None);
+
+        let project_config = dir.path().join(".inlyne.toml");
+        std::fs::write(&project_config, "page-width = 600").unwrap();
+        let found = Config::find_project_config(&file).unwrap();
+        assert_eq!(found, project_config.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn project_config_search_stops_at_repo_root() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".inlyne.toml"), "page-width = 600").unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        assert_eq!(Config::find_project_config(&repo.join("README.md")), None);
+
+        // Without a repo, only the file's own directory counts
+        let loose = dir.path().join("loose");
+        std::fs::create_dir(&loose).unwrap();
+        assert_eq!(Config::find_project_config(&loose.join("notes.md")), None);
+    }
 }
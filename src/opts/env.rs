@@ -0,0 +1,261 @@
+//! Overriding top-level options with `INLYNE_<OPTION>` environment variables e.g.
+//! `INLYNE_PAGE_WIDTH=600`
+
+use super::config::{
+    Config, HistoryLimit, LinesToScroll, PageScrollFraction, ReloadAttention, ScrollMultiplier,
+    SearchHistoryLimit,
+};
+use super::ThemeType;
+use crate::table::TableCards;
+
+use anyhow::Context;
+use clap::ValueEnum;
+use serde::Deserialize;
+use toml::{Table, Value};
+
+/// The config options that can be overridden, as they're written in the config file
+const OPTIONS: &[&str] = &[
+    "theme",
+    "decorations",
+    "scale",
+    "page-width",
+    "lines-to-scroll",
+    "page-scroll-fraction",
+    "page-overlap-lines",
+    "invert-scroll",
+    "wheel-multiplier",
+    "touchpad-multiplier",
+    "copy-match-locations",
+    "history-limit",
+    "persist-history",
+    "wide-gamut",
+    "smart-punctuation",
+    "collapse-extras",
+    "table-cards",
+    "reload-attention",
+    "search-smart-case",
+    "search-whole-word",
+    "search-history-limit",
+    "search-match-offset",
+    "hide-cursor-after",
+    "image-max-width",
+    "image-max-height",
+    "zen-measure",
+    "link-dry-run",
+    "remember-scroll",
+    "toc-depth",
+    "title-template",
+    "code-line-numbers",
+    "image-max-texture-size",
+    "sticky-headings",
+    "code-wrap-guide",
+    "code-wrap-indicator",
+];
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct EnvOverrides {
+    theme: Option<ThemeType>,
+    decorations: Option<bool>,
+    scale: Option<f32>,
+    page_width: Option<f32>,
+    lines_to_scroll: Option<LinesToScroll>,
+    page_scroll_fraction: Option<PageScrollFraction>,
+    page_overlap_lines: Option<f32>,
+    invert_scroll: Option<bool>,
+    wheel_multiplier: Option<ScrollMultiplier>,
+    touchpad_multiplier: Option<ScrollMultiplier>,
+    copy_match_locations: Option<bool>,
+    history_limit: Option<HistoryLimit>,
+    persist_history: Option<bool>,
+    wide_gamut: Option<bool>,
+    smart_punctuation: Option<bool>,
+    collapse_extras: Option<bool>,
+    table_cards: Option<TableCards>,
+    reload_attention: Option<ReloadAttention>,
+    search_smart_case: Option<bool>,
+    search_whole_word: Option<bool>,
+    search_history_limit: Option<SearchHistoryLimit>,
+    search_match_offset: Option<f32>,
+    hide_cursor_after: Option<f32>,
+    image_max_width: Option<f32>,
+    image_max_height: Option<f32>,
+    zen_measure: Option<f32>,
+    link_dry_run: Option<bool>,
+    remember_scroll: Option<bool>,
+    toc_depth: Option<u8>,
+    title_template: Option<String>,
+    code_line_numbers: Option<bool>,
+    image_max_texture_size: Option<u32>,
+    sticky_headings: Option<bool>,
+    code_wrap_guide: Option<u32>,
+    code_wrap_indicator: Option<bool>,
+}
+
+impl EnvOverrides {
+    pub fn from_vars(vars: impl IntoIterator<Item = (String, String)>) -> anyhow::Result<Self> {
+        let overrides = Value::Table(as_table(vars))
+            .try_into()
+            .context("Invalid `INLYNE_*` environment variable")?;
+        Ok(overrides)
+    }
+
+    pub fn apply(self, config: Config) -> Config {
+        let Self {
+            theme,
+            decorations,
+            scale,
+            page_width,
+            lines_to_scroll,
+            page_scroll_fraction,
+            page_overlap_lines,
+            invert_scroll,
+            wheel_multiplier,
+            touchpad_multiplier,
+            copy_match_locations,
+            history_limit,
+            persist_history,
+            wide_gamut,
+            smart_punctuation,
+            collapse_extras,
+            table_cards,
+            reload_attention,
+            search_smart_case,
+            search_whole_word,
+            search_history_limit,
+            search_match_offset,
+            hide_cursor_after,
+            image_max_width,
+            image_max_height,
+            zen_measure,
+            link_dry_run,
+            remember_scroll,
+            toc_depth,
+            title_template,
+            code_line_numbers,
+            image_max_texture_size,
+            sticky_headings,
+            code_wrap_guide,
+            code_wrap_indicator,
+        } = self;
+
+        Config {
+            theme: theme.or(config.theme),
+            decorations: decorations.or(config.decorations),
+            scale: scale.or(config.scale),
+            page_width: page_width.or(config.page_width),
+            lines_to_scroll: lines_to_scroll.unwrap_or(config.lines_to_scroll),
+            page_scroll_fraction: page_scroll_fraction.unwrap_or(config.page_scroll_fraction),
+            page_overlap_lines: page_overlap_lines.unwrap_or(config.page_overlap_lines),
+            invert_scroll: invert_scroll.unwrap_or(config.invert_scroll),
+            wheel_multiplier: wheel_multiplier.unwrap_or(config.wheel_multiplier),
+            touchpad_multiplier: touchpad_multiplier.unwrap_or(config.touchpad_multiplier),
+            copy_match_locations: copy_match_locations.unwrap_or(config.copy_match_locations),
+            history_limit: history_limit.unwrap_or(config.history_limit),
+            persist_history: persist_history.unwrap_or(config.persist_history),
+            wide_gamut: wide_gamut.unwrap_or(config.wide_gamut),
+            smart_punctuation: smart_punctuation.or(config.smart_punctuation),
+            collapse_extras: collapse_extras.unwrap_or(config.collapse_extras),
+            table_cards: table_cards.unwrap_or(config.table_cards),
+            reload_attention: reload_attention.unwrap_or(config.reload_attention),
+            search_smart_case: search_smart_case.or(config.search_smart_case),
+            search_whole_word: search_whole_word.unwrap_or(config.search_whole_word),
+            search_history_limit: search_history_limit.unwrap_or(config.search_history_limit),
+            search_match_offset: search_match_offset.or(config.search_match_offset),
+            hide_cursor_after: hide_cursor_after.or(config.hide_cursor_after),
+            image_max_width: image_max_width.or(config.image_max_width),
+            image_max_height: image_max_height.or(config.image_max_height),
+            zen_measure: zen_measure.or(config.zen_measure),
+            link_dry_run: link_dry_run.unwrap_or(config.link_dry_run),
+            remember_scroll: remember_scroll.or(config.remember_scroll),
+            toc_depth: toc_depth.or(config.toc_depth),
+            title_template: title_template.or(config.title_template),
+            code_line_numbers: code_line_numbers.unwrap_or(config.code_line_numbers),
+            image_max_texture_size: image_max_texture_size.or(config.image_max_texture_size),
+            sticky_headings: sticky_headings.or(config.sticky_headings),
+            code_wrap_guide: code_wrap_guide.or(config.code_wrap_guide),
+            code_wrap_indicator: code_wrap_indicator.or(config.code_wrap_indicator),
+            ..config
+        }
+    }
+}
+
+/// The current process's environment, skipping any variables that aren't valid unicode
+pub fn process_vars() -> impl Iterator<Item = (String, String)> {
+    std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+}
+
+/// The overrides set in `vars` as they would be written in the config file
+///
+/// Unrelated `INLYNE_*` variables like `INLYNE_LOG` are ignored
+pub fn as_table(vars: impl IntoIterator<Item = (String, String)>) -> Table {
+    vars.into_iter()
+        .filter_map(|(name, raw)| {
+            let key = name
+                .strip_prefix("INLYNE_")?
+                .to_lowercase()
+                .replace('_', "-");
+            OPTIONS.contains(&key.as_str()).then(|| {
+                let value = parse_value(&key, &raw);
+                (key, value)
+            })
+        })
+        .collect()
+}
+
+fn parse_value(key: &str, raw: &str) -> Value {
+    let raw = raw.trim();
+    // Themes are matched case-insensitively like the `--theme` flag
+    if key == "theme" {
+        if let Ok(theme) = ThemeType::from_str(raw, true) {
+            return Value::String(format!("{theme:?}"));
+        }
+    }
+
+    // Anything that isn't a valid TOML value is treated as a plain string
+    toml::from_str::<Table>(&format!("value = {raw}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn overrides_config_values() {
+        let env = vars(&[
+            ("INLYNE_THEME", "dark"),
+            ("INLYNE_PAGE_WIDTH", "600"),
+            ("INLYNE_INVERT_SCROLL", "true"),
+            ("INLYNE_LOG", "debug"),
+            ("HOME", "/home/user"),
+        ]);
+        let config = Config {
+            theme: Some(ThemeType::Light),
+            scale: Some(1.5),
+            page_width: Some(800.0),
+            ..Default::default()
+        };
+
+        let config = EnvOverrides::from_vars(env).unwrap().apply(config);
+        assert_eq!(config.theme, Some(ThemeType::Dark));
+        assert_eq!(config.scale, Some(1.5));
+        assert_eq!(config.page_width, Some(600.0));
+        assert!(config.invert_scroll);
+    }
+
+    #[test]
+    fn invalid_values_error() {
+        let env = vars(&[("INLYNE_SCALE", "huge")]);
+        assert!(EnvOverrides::from_vars(env).is_err());
+    }
+}
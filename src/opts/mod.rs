@@ -1,23 +1,44 @@
 mod cli;
 mod config;
+mod env;
+mod provenance;
 #[cfg(test)]
 mod tests;
 
 use std::{
-    path::Path,
+    collections::HashMap,
+    path::{Path, PathBuf},
     sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
 };
 
 use crate::color;
-pub use cli::{Cli, Commands, ConfigCmd, Position, Size, ThemeType, View};
-pub use config::{Config, DebugSection, FontOptions, KeybindingsSection, MetricsExporter};
+pub use cli::{
+    Bench, Cli, Commands, ConfigCmd, Dump, ExportCmd, ExportHtml, InstallDesktop, Interval,
+    Position, Print, RemoteCmd, Screenshot, Serve, Size, ThemeCmd, ThemePreview, ThemeType, View,
+};
+pub use config::{
+    Config, DebugSection, FontOptions, KeybindingsSection, LinkHandler, MetricsExporter,
+    OptionalLayout, OptionalTheme, PluginSection, ReloadAttention,
+};
+pub use provenance::LayeredConfig;
 
 use crate::history::History;
-use anyhow::Result;
+use crate::image::MaxSize;
+use crate::layout_profile;
+use crate::positioner::ElementSpacing;
+use crate::search::SearchOptions;
+use crate::table::TableCards;
+use crate::toc;
+use crate::utils;
+use anyhow::{Context, Result};
 use clap::Parser;
+use env::EnvOverrides;
 use serde::Deserialize;
 use smart_debug::SmartDebug;
 
+const DEFAULT_CYCLE_INTERVAL: Duration = Duration::from_secs(30);
+
 static RENDER_ELEMENT_BOUNDS: AtomicBool = AtomicBool::new(false);
 
 #[must_use]
@@ -67,13 +88,82 @@ pub struct Opts {
     pub scale: Option<f32>,
     pub page_width: Option<f32>,
     pub lines_to_scroll: f32,
+    pub page_scroll_fraction: f32,
+    pub page_overlap_lines: f32,
+    pub invert_scroll: bool,
+    pub wheel_multiplier: f32,
+    pub touchpad_multiplier: f32,
+    pub copy_match_locations: bool,
+    pub persist_history: bool,
+    pub wide_gamut: bool,
+    /// Turn quotes into curly quotes and `--` into dashes, unless the front matter says otherwise
+    pub smart_punctuation: bool,
+    /// Leave out footnotes until the extras get revealed
+    pub collapse_extras: bool,
+    /// Unless the front matter says otherwise
+    pub table_cards: TableCards,
+    pub reload_attention: ReloadAttention,
+    /// What new searches start out with
+    pub search_options: SearchOptions,
+    /// The most past queries to remember
+    pub search_history_limit: usize,
+    /// How far down the window to put the search match that gets jumped to, as a fraction of its
+    /// height
+    pub search_match_offset: f32,
+    /// Hide the mouse cursor once it's been still over the window for this long
+    pub hide_cursor_after: Option<Duration>,
+    /// How much of the window an image can take up before it gets scaled down
+    pub image_max_size: MaxSize,
+    /// How many characters wide lines get in zen mode
+    pub zen_measure: f32,
+    /// Copy external links instead of opening them
+    pub link_dry_run: bool,
+    /// Pick up each file at the scroll position and zoom it was left at
+    pub remember_scroll: bool,
+    /// How many heading levels a generated table of contents lists
+    pub toc_depth: u8,
+    /// The window title, with `{title}` and `{path}` filled in
+    pub title_template: String,
+    /// Number the lines of code blocks that don't turn it off themselves
+    pub code_line_numbers: bool,
+    /// Scale images down to fit textures this many pixels on a side, besides the GPU's own limit
+    pub image_max_texture_size: Option<u32>,
+    /// Pin the heading of the section being read to the top of the window
+    pub sticky_headings: bool,
+    /// Draw a line down code blocks this many characters in
+    pub code_wrap_guide: Option<u32>,
+    /// Mark the rows of code that carry on a line too long to fit
+    pub code_wrap_indicator: bool,
     pub font_opts: FontOptions,
     pub keybindings: KeybindingsSection,
     pub color_scheme: Option<ResolvedTheme>,
     pub metrics: Option<MetricsExporter>,
     pub position: Option<Position>,
     pub size: Option<Size>,
-    pub element_padding: f32,
+    pub fit: bool,
+    /// Space left after each kind of element
+    pub element_spacing: ElementSpacing,
+    pub low_memory: bool,
+    pub tail: bool,
+    pub follow: bool,
+    pub kiosk: bool,
+    /// Pixels per second to auto-scroll by in kiosk mode
+    pub kiosk_scroll: Option<f32>,
+    pub find: Option<String>,
+    /// Every document that `--cycle` rotates through, starting with the opened file
+    pub cycle: Vec<PathBuf>,
+    pub cycle_interval: Duration,
+    /// The document shown in the right half of the window with `--side-by-side`
+    pub side_by_side: Option<PathBuf>,
+    pub remote_preview: bool,
+    /// Source line to scroll to once the file is laid out from a `file.md:42` style path
+    pub goto_line: Option<usize>,
+    pub plugins: HashMap<String, PluginSection>,
+    pub link_handlers: HashMap<String, LinkHandler>,
+    /// Fenced block languages mapped to the command that renders them to an image
+    pub external_renderers: HashMap<String, Vec<String>>,
+    /// Fenced block languages mapped to the command that runs blocks tagged with `run`
+    pub run_commands: HashMap<String, Vec<String>>,
 }
 
 impl Opts {
@@ -87,6 +177,8 @@ impl Opts {
         #[cfg(not(test))]
         {
             let system_color_scheme = ResolvedTheme::try_detect();
+            // Environment variables sit between the config file and command line flags
+            let config = EnvOverrides::from_vars(env::process_vars())?.apply(config);
             Self::parse_and_load_inner(args, config, system_color_scheme)
         }
     }
@@ -111,12 +203,48 @@ impl Opts {
             scale: config_scale,
             page_width: config_page_width,
             lines_to_scroll,
+            page_scroll_fraction,
+            page_overlap_lines,
+            invert_scroll,
+            wheel_multiplier,
+            touchpad_multiplier,
+            copy_match_locations,
+            history_limit,
+            persist_history,
+            wide_gamut,
+            smart_punctuation,
+            collapse_extras,
+            table_cards,
+            reload_attention,
+            search_smart_case,
+            search_whole_word,
+            search_history_limit,
+            search_match_offset,
+            hide_cursor_after,
+            image_max_width,
+            image_max_height,
+            zen_measure,
+            link_dry_run,
+            remember_scroll,
+            toc_depth,
+            title_template,
+            code_line_numbers,
+            image_max_texture_size,
+            sticky_headings,
+            code_wrap_guide,
+            code_wrap_indicator,
+            layout,
+            spacing,
             light_theme,
             dark_theme,
             font_options,
             keybindings,
             debug,
             window,
+            plugins,
+            link_handlers,
+            external_renderers,
+            run_commands,
         } = config;
 
         let View {
@@ -128,6 +256,19 @@ impl Opts {
             page_width: args_page_width,
             size: v_size,
             position: v_position,
+            fit,
+            low_memory,
+            tail,
+            follow,
+            kiosk,
+            kiosk_scroll,
+            line,
+            find,
+            cycle,
+            interval,
+            side_by_side,
+            remote_preview,
+            debug_bounds,
         } = args;
 
         let DebugSection {
@@ -135,9 +276,38 @@ impl Opts {
             render_element_bounds,
         } = debug;
 
-        set_render_element_bounds(render_element_bounds);
+        set_render_element_bounds(render_element_bounds || debug_bounds);
 
-        let history = History::new(&file_path)?;
+        // Allow for `file.md:42` to jump straight to a line as long as that isn't a real file
+        let (file_path, goto_line) = match file_path.to_str() {
+            Some(target) if !file_path.exists() => {
+                let (path, line) = crate::utils::split_line_suffix(target);
+                (path.into(), line)
+            }
+            _ => (file_path, None),
+        };
+        let goto_line = line.or(goto_line);
+        let history = History::new(&file_path)?.with_limit(history_limit.0);
+        let cycle = if cycle.is_empty() {
+            Vec::new()
+        } else {
+            let mut files = vec![history.get_path().to_owned()];
+            for path in cycle {
+                let path = path
+                    .canonicalize()
+                    .with_context(|| format!("Unable to canonicalize {}", path.display()))?;
+                if !files.contains(&path) {
+                    files.push(path);
+                }
+            }
+            files
+        };
+        let side_by_side = side_by_side
+            .map(|path| {
+                path.canonicalize()
+                    .with_context(|| format!("Unable to canonicalize {}", path.display()))
+            })
+            .transpose()?;
         let resolved_theme = args_theme
             .or(config_theme)
             .and_then(ResolvedTheme::new)
@@ -148,6 +318,8 @@ impl Opts {
                 None | Some(ResolvedTheme::Light) => (light_theme, color::Theme::light_default()),
             };
 
+            // Per-theme settings take priority over the shared layout
+            let fallback_values = layout.merge(fallback_values);
             match maybe_theme {
                 Some(theme) => theme.merge(fallback_values)?,
                 None => fallback_values,
@@ -159,6 +331,9 @@ impl Opts {
         let font_opts = font_options.unwrap_or_default();
         let page_width = args_page_width.or(config_page_width);
         let lines_to_scroll = lines_to_scroll.into();
+        let page_scroll_fraction = page_scroll_fraction.into();
+        let wheel_multiplier = wheel_multiplier.into();
+        let touchpad_multiplier = touchpad_multiplier.into();
 
         let (position, size) = if let Some(window) = window {
             (v_position.or(window.position), v_size.or(window.size))
@@ -173,23 +348,81 @@ impl Opts {
             scale,
             page_width,
             lines_to_scroll,
+            page_scroll_fraction,
+            page_overlap_lines,
+            invert_scroll,
+            wheel_multiplier,
+            touchpad_multiplier,
+            copy_match_locations,
+            persist_history,
+            wide_gamut,
+            smart_punctuation: smart_punctuation.unwrap_or(true),
+            collapse_extras,
+            table_cards,
+            reload_attention,
+            search_options: SearchOptions {
+                smart_case: search_smart_case.unwrap_or(true),
+                whole_word: search_whole_word,
+            },
+            search_history_limit: search_history_limit.0,
+            search_match_offset: search_match_offset.unwrap_or(0.5).clamp(0., 1.),
+            hide_cursor_after: hide_cursor_after
+                .and_then(|secs| Duration::try_from_secs_f32(secs).ok())
+                .filter(|delay| !delay.is_zero()),
+            image_max_size: MaxSize::new(image_max_width, image_max_height),
+            zen_measure: zen_measure
+                .filter(|&measure| measure > 0.)
+                .unwrap_or(layout_profile::DEFAULT_MEASURE),
+            link_dry_run,
+            remember_scroll: remember_scroll.unwrap_or(true),
+            toc_depth: toc_depth.unwrap_or(toc::DEFAULT_DEPTH),
+            title_template: title_template
+                .unwrap_or_else(|| utils::DEFAULT_TITLE_TEMPLATE.to_owned()),
+            code_line_numbers,
+            image_max_texture_size,
+            sticky_headings: sticky_headings.unwrap_or(true),
+            code_wrap_guide: code_wrap_guide.filter(|&column| column > 0),
+            code_wrap_indicator: code_wrap_indicator.unwrap_or(true),
             font_opts,
             keybindings,
             color_scheme: resolved_theme,
             metrics,
             position,
             size,
-            element_padding: 2.0,
+            fit,
+            element_spacing: spacing.merge(ElementSpacing::default()),
+            low_memory,
+            tail,
+            follow,
+            kiosk,
+            kiosk_scroll,
+            find,
+            cycle,
+            cycle_interval: interval.map_or(DEFAULT_CYCLE_INTERVAL, |interval| interval.0),
+            side_by_side,
+            remote_preview,
+            goto_line,
+            plugins,
+            link_handlers,
+            external_renderers,
+            run_commands,
         })
     }
 
     /// Arguments to supply to program that are opened externally.
-    pub fn program_args(file_path: &Path) -> Vec<String> {
-        let current_args = Cli::parse().into_view().expect("Should contain an view!");
-
-        let mut args = Vec::new();
+    ///
+    /// Windows opened by other subcommands (e.g. `inlyne serve`) have no view options to carry
+    /// over, so those only pass the file along
+    pub fn program_args(file_path: &Path, line: Option<usize>) -> Vec<String> {
+        let mut args = vec![file_path.display().to_string()];
+        if let Some(line) = line {
+            args.push("--line".to_owned());
+            args.push(line.to_string());
+        }
+        let Some(current_args) = Cli::try_parse().ok().and_then(|cli| cli.into_view().ok()) else {
+            return args;
+        };
 
-        args.push(file_path.display().to_string());
         if let Some(theme) = current_args.theme {
             args.push("--theme".to_owned());
             args.push(theme.as_str().to_owned());
@@ -210,6 +443,10 @@ impl Opts {
             args.push(page_width.to_string());
         }
 
+        if current_args.low_memory {
+            args.push("--low-memory".to_owned());
+        }
+
         args
     }
 }
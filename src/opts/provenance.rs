@@ -0,0 +1,311 @@
+//! Merging configuration layers while tracking where each value came from
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::{env, Config, Dump};
+
+use anyhow::Context;
+use toml::{Table, Value};
+
+/// The only top-level keys that a project's `.inlyne.toml` gets to set
+///
+/// Project files come along with whatever gets opened, so they're limited to how the document
+/// looks. Anything that runs commands, handles links, loads plugins, or changes input has to come
+/// from the user's own config
+const PROJECT_KEYS: &[&str] = &[
+    "theme",
+    "scale",
+    "page-width",
+    "smart-punctuation",
+    "collapse-extras",
+    "table-cards",
+    "image-max-width",
+    "image-max-height",
+    "zen-measure",
+    "toc-depth",
+    "title-template",
+    "code-line-numbers",
+    "sticky-headings",
+    "code-wrap-guide",
+    "code-wrap-indicator",
+    "layout",
+    "spacing",
+    "light-theme",
+    "dark-theme",
+    "font-options",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    Default,
+    ConfigFile(PathBuf),
+    ProjectFile(PathBuf),
+    Environment,
+    CommandLine,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::ConfigFile(path) => write!(f, "config file ({})", path.display()),
+            Self::ProjectFile(path) => write!(f, "project config ({})", path.display()),
+            Self::Environment => f.write_str("environment"),
+            Self::CommandLine => f.write_str("command line"),
+        }
+    }
+}
+
+/// The effective configuration as raw TOML along with the origin of every value, keyed by its
+/// dotted path (e.g. `dark-theme.text-color`)
+#[derive(Debug, Default)]
+pub struct LayeredConfig {
+    values: Table,
+    origins: HashMap<String, Origin>,
+}
+
+impl LayeredConfig {
+    /// Layers the defaults, config file, project config, environment variables, and command line
+    /// flags from `inlyne config dump`
+    ///
+    /// The project config is searched for from the current directory
+    pub fn load(args: &Dump) -> anyhow::Result<Self> {
+        let mut layered = Self::default();
+        layered.merge(toml::from_str(Config::default_config())?, &Origin::Default);
+        if let Some(path) = user_config_path(args.config.as_deref()) {
+            layered.merge_file(&path, Origin::ConfigFile(path.clone()))?;
+        }
+        if let Some(path) = Config::find_project_config(&std::env::current_dir()?) {
+            layered.merge_project_file(&path);
+        }
+        layered.merge(env::as_table(env::process_vars()), &Origin::Environment);
+        layered.merge(args.as_table(), &Origin::CommandLine);
+        Ok(layered)
+    }
+
+    /// Layers the project config at `project` over the user's config file
+    ///
+    /// Like loading the config on its own, a broken system config file is only warned about,
+    /// while a broken explicitly passed one is an error
+    pub fn with_project(config: Option<&Path>, project: &Path) -> anyhow::Result<Self> {
+        let mut layered = Self::default();
+        match config {
+            Some(path) => layered.merge_file(path, Origin::ConfigFile(path.to_owned()))?,
+            None => {
+                if let Some(path) = user_config_path(None) {
+                    if let Err(err) = layered.merge_file(&path, Origin::ConfigFile(path.clone())) {
+                        tracing::warn!(
+                            "Failed reading config file. Falling back to defaults. Error: {err:#}"
+                        );
+                    }
+                }
+            }
+        }
+        layered.merge_project_file(project);
+        Ok(layered)
+    }
+
+    pub fn into_config(self) -> anyhow::Result<Config> {
+        let config = Value::Table(self.values)
+            .try_into()
+            .context("Invalid merged configuration")?;
+        Ok(config)
+    }
+
+    fn merge_file(&mut self, path: &Path, origin: Origin) -> anyhow::Result<()> {
+        let table = read_table(path)?;
+        self.merge(table, &origin);
+        Ok(())
+    }
+
+    /// Merges the display and layout settings from a project config, warning about anything
+    /// else it tries to set
+    ///
+    /// Project configs can come from anything that gets opened, so a broken one is skipped rather
+    /// than stopping inlyne from starting
+    fn merge_project_file(&mut self, path: &Path) {
+        let table = match read_table(path) {
+            Ok(table) => table,
+            Err(err) => {
+                tracing::warn!("Skipping project config: {err:#}");
+                return;
+            }
+        };
+        let table = project_settings(table, |key| {
+            tracing::warn!(
+                "Ignoring `{key}` in project config at '{}'. Project configs can only change \
+                display and layout settings",
+                path.display()
+            );
+        });
+        self.merge(table, &Origin::ProjectFile(path.to_owned()));
+    }
+
+    /// Merges `layer` over the current values, recording `origin` for each value it sets
+    pub fn merge(&mut self, layer: Table, origin: &Origin) {
+        merge_table(&mut self.values, layer, "", origin, &mut self.origins);
+    }
+
+    /// Renders the values as TOML with a comment above each one noting where it came from
+    pub fn to_annotated_toml(&self) -> String {
+        let mut out = String::new();
+        self.write_table(&mut out, &self.values, "", "");
+        out
+    }
+
+    fn write_table(&self, out: &mut String, table: &Table, path: &str, header: &str) {
+        // Plain values come first so that they stay under the right `[header]`
+        for (key, value) in table.iter().filter(|(_, value)| !value.is_table()) {
+            if let Some(origin) = self.origins.get(&join(path, key)) {
+                let _ = writeln!(out, "# from {origin}");
+            }
+            let _ = writeln!(out, "{} = {value}", format_key(key));
+        }
+        for (key, value) in table {
+            if let Value::Table(inner) = value {
+                let header = join(header, &format_key(key));
+                let _ = writeln!(out, "\n[{header}]");
+                self.write_table(out, inner, &join(path, key), &header);
+            }
+        }
+    }
+}
+
+fn read_table(path: &Path) -> anyhow::Result<Table> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file at '{}'", path.display()))?;
+    let table = toml::from_str(&contents)
+        .with_context(|| format!("Invalid config file at '{}'", path.display()))?;
+    Ok(table)
+}
+
+/// Drops every key from a project config that isn't in [`PROJECT_KEYS`], passing each one to
+/// `on_ignored`
+fn project_settings(mut table: Table, mut on_ignored: impl FnMut(&str)) -> Table {
+    table.retain(|key, _| {
+        let allowed = PROJECT_KEYS.contains(&key);
+        if !allowed {
+            on_ignored(key);
+        }
+        allowed
+    });
+    table
+}
+
+/// The explicitly passed config file, falling back to the system one if it exists
+fn user_config_path(config: Option<&Path>) -> Option<PathBuf> {
+    match config {
+        Some(path) => Some(path.to_owned()),
+        None => Config::system_config_path()
+            .ok()
+            .filter(|path| path.is_file()),
+    }
+}
+
+fn merge_table(
+    base: &mut Table,
+    layer: Table,
+    path: &str,
+    origin: &Origin,
+    origins: &mut HashMap<String, Origin>,
+) {
+    for (key, value) in layer {
+        let path = join(path, &key);
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(layer)) => {
+                merge_table(base, layer, &path, origin, origins)
+            }
+            (_, value) => {
+                mark_origin(&path, &value, origin, origins);
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn mark_origin(path: &str, value: &Value, origin: &Origin, origins: &mut HashMap<String, Origin>) {
+    match value {
+        Value::Table(table) => {
+            for (key, value) in table {
+                mark_origin(&join(path, key), value, origin, origins);
+            }
+        }
+        _ => {
+            origins.insert(path.to_owned(), origin.to_owned());
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn format_key(key: &str) -> String {
+    let is_bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if is_bare {
+        key.to_owned()
+    } else {
+        Value::String(key.to_owned()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_layers_win() {
+        let mut layered = LayeredConfig::default();
+        let defaults = toml::from_str("page-width = 800.0\n[light-theme]\ntext-color = 1").unwrap();
+        layered.merge(defaults, &Origin::Default);
+        let file = toml::from_str("[light-theme]\ntext-color = 2").unwrap();
+        layered.merge(file, &Origin::ConfigFile("inlyne.toml".into()));
+        let mut cli = Table::new();
+        cli.insert("page-width".into(), Value::Float(600.0));
+        layered.merge(cli, &Origin::CommandLine);
+
+        insta::assert_snapshot!(layered.to_annotated_toml(), @r###"
+        # from command line
+        page-width = 600.0
+
+        [light-theme]
+        # from config file (inlyne.toml)
+        text-color = 2
+        "###);
+    }
+
+    #[test]
+    fn project_configs_only_set_display_settings() {
+        let project = toml::from_str(
+            "\
+page-width = 600.0
+run-commands = { sh = [\"sh\"] }
+[external-renderers]
+dot = [\"dot\"]
+[light-theme]
+text-color = 2
+",
+        )
+        .unwrap();
+        let mut ignored = Vec::new();
+        let table = project_settings(project, |key| ignored.push(key.to_owned()));
+        ignored.sort();
+        assert_eq!(ignored, ["external-renderers", "run-commands"]);
+
+        let mut layered = LayeredConfig::default();
+        layered.merge(table, &Origin::ProjectFile(".inlyne.toml".into()));
+        let config = layered.into_config().unwrap();
+        assert_eq!(config.page_width, Some(600.0));
+        assert!(config.run_commands.is_empty());
+        assert!(config.external_renderers.is_empty());
+    }
+}
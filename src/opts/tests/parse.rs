@@ -1,15 +1,23 @@
 use std::ffi::OsString;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use clap::{CommandFactory, Parser};
 use pretty_assertions::assert_eq;
 use tempfile::NamedTempFile;
 
-use crate::color::{SyntaxTheme, Theme, ThemeDefaults};
+use crate::color::{Palette, SyntaxTheme, Theme, ThemeDefaults};
 use crate::history::History;
-use crate::opts::config::{self, FontOptions, LinesToScroll};
-use crate::opts::{Cli, Opts, Position, ResolvedTheme, Size, ThemeType};
+use crate::image::MaxSize;
+use crate::layout_profile;
+use crate::opts::config::{self, FontOptions, LinesToScroll, PageScrollFraction, ScrollMultiplier};
+use crate::opts::{Cli, Interval, Opts, Position, ReloadAttention, ResolvedTheme, Size, ThemeType};
+use crate::positioner::ElementSpacing;
+use crate::search::{self, SearchOptions};
+use crate::table::TableCards;
 use crate::test_utils::log;
+use crate::toc;
+use crate::utils;
 
 fn gen_args(args: Vec<&str>) -> Vec<OsString> {
     std::iter::once("inlyne")
@@ -38,12 +46,55 @@ impl Opts {
             page_width: None,
             font_opts: FontOptions::default(),
             lines_to_scroll: LinesToScroll::default().0,
+            page_scroll_fraction: PageScrollFraction::default().0,
+            page_overlap_lines: 0.0,
+            invert_scroll: false,
+            wheel_multiplier: ScrollMultiplier::default().0,
+            touchpad_multiplier: ScrollMultiplier::default().0,
+            copy_match_locations: false,
+            persist_history: false,
+            wide_gamut: false,
+            smart_punctuation: true,
+            collapse_extras: false,
+            table_cards: TableCards::Auto,
+            reload_attention: ReloadAttention::None,
+            search_options: SearchOptions::default(),
+            search_history_limit: search::DEFAULT_HISTORY_LIMIT,
+            search_match_offset: 0.5,
+            hide_cursor_after: None,
+            image_max_size: MaxSize::default(),
+            zen_measure: layout_profile::DEFAULT_MEASURE,
+            link_dry_run: false,
+            remember_scroll: true,
+            toc_depth: toc::DEFAULT_DEPTH,
+            title_template: utils::DEFAULT_TITLE_TEMPLATE.to_owned(),
+            code_line_numbers: false,
+            image_max_texture_size: None,
+            sticky_headings: true,
+            code_wrap_guide: None,
+            code_wrap_indicator: true,
             keybindings: Default::default(),
             color_scheme: None,
             metrics: None,
             position: None,
             size: None,
-            element_padding: 2.0,
+            fit: false,
+            element_spacing: ElementSpacing::default(),
+            low_memory: false,
+            tail: false,
+            follow: false,
+            kiosk: false,
+            kiosk_scroll: None,
+            find: None,
+            cycle: Vec::new(),
+            cycle_interval: Duration::from_secs(30),
+            side_by_side: None,
+            remote_preview: false,
+            goto_line: None,
+            plugins: Default::default(),
+            link_handlers: Default::default(),
+            external_renderers: Default::default(),
+            run_commands: Default::default(),
         }
     }
 }
@@ -215,6 +266,7 @@ fn cli_kitchen_sink() {
         "--scale", "1.5",
         "--config", "/path/to/file.toml",
         "--page-width", "500",
+        "--low-memory",
         &md_file,
     ]);
     assert_eq!(
@@ -227,6 +279,7 @@ fn cli_kitchen_sink() {
         Opts {
             page_width: Some(500.0),
             scale: Some(1.5),
+            low_memory: true,
             theme: ResolvedTheme::Dark.as_theme(),
             color_scheme: Some(ResolvedTheme::Dark),
             ..Opts::mostly_default(&md_file)
@@ -234,6 +287,30 @@ fn cli_kitchen_sink() {
     );
 }
 
+#[test]
+fn goto_line_suffix() {
+    log::init();
+
+    let (_tmp, md_file) = temp_md_file();
+
+    let target = format!("{md_file}:42:5");
+    assert_eq!(
+        Opts::parse_and_load_with_system_theme(
+            Cli::try_parse_from(gen_args(vec![&target]))
+                .unwrap()
+                .into_view()
+                .unwrap(),
+            config::Config::default(),
+            None,
+        )
+        .unwrap(),
+        Opts {
+            goto_line: Some(42),
+            ..Opts::mostly_default(&md_file)
+        }
+    );
+}
+
 #[test]
 fn builtin_syntax_theme() {
     log::init();
@@ -257,11 +334,72 @@ fn builtin_syntax_theme() {
     .unwrap();
 
     assert_eq!(
-        opts.theme.code_highlighter.name.unwrap(),
+        opts.theme.palette.code_highlighter.name.unwrap(),
         "Solarized (light)"
     );
 }
 
+#[test]
+fn shared_layout() {
+    log::init();
+
+    let (_tmp, md_file) = temp_md_file();
+
+    let load_with_system_theme = |theme| {
+        let mut config = config::Config::default();
+        config.layout = config::OptionalLayout {
+            scrollbar_width: Some(8),
+            page_margin: Some(50),
+        };
+        config.dark_theme = Some(config::OptionalTheme {
+            page_margin: Some(120),
+            ..Default::default()
+        });
+        let args = Cli::try_parse_from(gen_args(vec![&md_file]))
+            .unwrap()
+            .into_view()
+            .unwrap();
+        Opts::parse_and_load_with_system_theme(args, config, Some(theme))
+            .unwrap()
+            .theme
+    };
+
+    let light = load_with_system_theme(ResolvedTheme::Light);
+    assert_eq!((light.scrollbar_width, light.page_margin), (8, 50));
+    assert_eq!(light.palette, Palette::light_default());
+
+    // Theme specific settings win over the shared layout
+    let dark = load_with_system_theme(ResolvedTheme::Dark);
+    assert_eq!((dark.scrollbar_width, dark.page_margin), (8, 120));
+    assert_eq!(dark.palette, Palette::dark_default());
+}
+
+#[test]
+fn element_spacing() {
+    log::init();
+
+    let (_tmp, md_file) = temp_md_file();
+
+    let config = config::Config::load_from_str(
+        "[spacing]\nheading = [20, 16, 12, 8, 4, 2]\ncode-block = 10\n",
+    )
+    .unwrap();
+    let args = Cli::try_parse_from(gen_args(vec![&md_file]))
+        .unwrap()
+        .into_view()
+        .unwrap();
+    let opts =
+        Opts::parse_and_load_with_system_theme(args, config, Some(ResolvedTheme::Light)).unwrap();
+    assert_eq!(
+        opts.element_spacing,
+        ElementSpacing {
+            headings: [20., 16., 12., 8., 4., 2.],
+            code_block: 10.,
+            ..Default::default()
+        }
+    );
+}
+
 #[test]
 fn custom_syntax_theme() {
     fn config_with_theme_at(path: PathBuf) -> config::Config {
@@ -301,7 +439,7 @@ fn custom_syntax_theme() {
     )
     .unwrap();
     assert_eq!(
-        opts.theme.code_highlighter.name.unwrap(),
+        opts.theme.palette.code_highlighter.name.unwrap(),
         "Example Color Scheme"
     );
 }
@@ -344,3 +482,69 @@ fn win_pos_and_size() {
         }
     );
 }
+
+#[test]
+fn cycle_files() {
+    log::init();
+
+    let (_tmp, md_file) = temp_md_file();
+    let (_other_tmp, other_file) = temp_md_file();
+
+    let cycle = format!("{other_file},{md_file}");
+    let args = gen_args(vec!["--cycle", &cycle, "--interval", "2m", &md_file]);
+    let canonical = |path: &str| Path::new(path).canonicalize().unwrap();
+    assert_eq!(
+        Opts::parse_and_load_with_system_theme(
+            Cli::try_parse_from(args).unwrap().into_view().unwrap(),
+            config::Config::default(),
+            None,
+        )
+        .unwrap(),
+        Opts {
+            cycle: vec![canonical(&md_file), canonical(&other_file)],
+            cycle_interval: Duration::from_secs(120),
+            ..Opts::mostly_default(&md_file)
+        }
+    );
+
+    // An interval only makes sense when cycling
+    assert!(Cli::try_parse_from(gen_args(vec!["--interval", "2m", &md_file])).is_err());
+}
+
+#[test]
+fn side_by_side_file() {
+    log::init();
+
+    let (_tmp, md_file) = temp_md_file();
+    let (_other_tmp, other_file) = temp_md_file();
+
+    let args = gen_args(vec!["--side-by-side", &other_file, &md_file]);
+    assert_eq!(
+        Opts::parse_and_load_with_system_theme(
+            Cli::try_parse_from(args).unwrap().into_view().unwrap(),
+            config::Config::default(),
+            None,
+        )
+        .unwrap(),
+        Opts {
+            side_by_side: Some(Path::new(&other_file).canonicalize().unwrap()),
+            ..Opts::mostly_default(&md_file)
+        }
+    );
+}
+
+#[test]
+fn interval_units() {
+    let secs = |input: &str| {
+        input
+            .parse::<Interval>()
+            .map(|interval| interval.0.as_secs())
+    };
+    assert_eq!(secs("45"), Ok(45));
+    assert_eq!(secs("30s"), Ok(30));
+    assert_eq!(secs("5m"), Ok(300));
+    assert_eq!(secs("1h"), Ok(3_600));
+    assert!(secs("0s").is_err());
+    assert!(secs("2d").is_err());
+    assert!(secs("soon").is_err());
+}
@@ -0,0 +1,311 @@
+//! External command plugins
+//!
+//! Each plugin is a directory in `<config dir>/inlyne/plugins/` containing a `plugin.toml`
+//! manifest. Plugins can pipe the whole markdown document through a pre-processor, and render
+//! fenced code blocks for specific languages (e.g. `plantuml`). Every command is run from the
+//! plugin's directory with its `[plugins.<name>]` config values exposed as
+//! `INLYNE_PLUGIN_<KEY>` environment variables. Commands that run past a timeout get killed, and
+//! the markdown is left as it was
+//!
+//! This is only part of a plugin API. Plugins are plain external commands rather than WASM
+//! components or dynamic libraries, so they aren't sandboxed and run with the user's
+//! permissions. They also can't add actions or keybindings
+
+use std::collections::HashMap;
+use std::fs::{self, read_to_string};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::opts::PluginSection;
+use crate::subprocess;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+/// Plugins run on every reload, so a stuck one gets killed rather than freezing the document
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Plugins emit markdown, so anything past this is almost certainly runaway output
+const MAX_OUTPUT: u64 = 16 * 1024 * 1024;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "kebab-case")]
+struct Manifest {
+    /// Command that the markdown document is piped through before rendering
+    preprocess: Option<Vec<String>>,
+    /// Fenced block languages mapped to the command that renders their contents to markdown
+    fenced: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug)]
+struct Plugin {
+    name: String,
+    dir: PathBuf,
+    manifest: Manifest,
+    env: Vec<(String, String)>,
+}
+
+impl Plugin {
+    fn load(dir: PathBuf, config: Option<&PluginSection>) -> anyhow::Result<Self> {
+        let name = dir
+            .file_name()
+            .context("Plugin directory has no name")?
+            .to_string_lossy()
+            .into_owned();
+        let manifest_path = dir.join("plugin.toml");
+        let manifest = read_to_string(&manifest_path)
+            .with_context(|| format!("Failed reading '{}'", manifest_path.display()))?;
+        let manifest = toml::from_str(&manifest)
+            .with_context(|| format!("Invalid manifest at '{}'", manifest_path.display()))?;
+        let env = config
+            .map(|section| {
+                section
+                    .options
+                    .iter()
+                    .map(|(key, value)| {
+                        let key = format!("INLYNE_PLUGIN_{}", key.to_uppercase().replace('-', "_"));
+                        let value = match value {
+                            toml::Value::String(s) => s.to_owned(),
+                            other => other.to_string(),
+                        };
+                        (key, value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Self {
+            name,
+            dir,
+            manifest,
+            env,
+        })
+    }
+
+    /// Runs one of the plugin's commands with `input` as stdin, returning its stdout
+    fn run(&self, command: &[String], input: &str) -> anyhow::Result<String> {
+        let (program, args) = command.split_first().context("Empty plugin command")?;
+        // Commands relative to the plugin are resolved from its directory
+        let program = if program.starts_with("./") || program.starts_with("../") {
+            self.dir.join(program)
+        } else {
+            PathBuf::from(program)
+        };
+
+        let mut command = Command::new(&program);
+        command
+            .args(args)
+            .current_dir(&self.dir)
+            .envs(self.env.iter().map(|(k, v)| (k, v)));
+        let output = subprocess::run(&mut command, input.as_bytes().to_vec(), TIMEOUT, MAX_OUTPUT)
+            .with_context(|| format!("Failed running '{}'", program.display()))?;
+
+        let status = output.status.with_context(|| {
+            format!(
+                "'{}' was killed after running for {}s",
+                program.display(),
+                TIMEOUT.as_secs()
+            )
+        })?;
+        anyhow::ensure!(
+            status.success(),
+            "'{}' exited with {status}: {}",
+            program.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        Ok(String::from_utf8(output.stdout)?)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Plugins(Vec<Plugin>);
+
+impl Plugins {
+    pub fn load_from_system(config: &HashMap<String, PluginSection>) -> Self {
+        match dirs::config_dir() {
+            Some(dir) => Self::discover(&dir.join("inlyne").join("plugins"), config),
+            None => Self::default(),
+        }
+    }
+
+    /// Loads every enabled plugin from subdirectories of `dir`
+    pub fn discover(dir: &Path, config: &HashMap<String, PluginSection>) -> Self {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Self::default();
+        };
+
+        let mut plugin_dirs: Vec<_> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        // Plugins apply in a stable order
+        plugin_dirs.sort();
+
+        let mut plugins = Vec::new();
+        for dir in plugin_dirs {
+            let name = dir.file_name().unwrap_or_default().to_string_lossy();
+            let section = config.get(name.as_ref());
+            if section.is_some_and(|section| !section.enabled) {
+                continue;
+            }
+            match Plugin::load(dir.clone(), section) {
+                Ok(plugin) => {
+                    tracing::info!("Loaded plugin: {}", plugin.name);
+                    plugins.push(plugin);
+                }
+                Err(err) => tracing::warn!("Skipping plugin at {}: {err:#}", dir.display()),
+            }
+        }
+
+        Self(plugins)
+    }
+
+    /// Runs the markdown through every plugin's pre-processor and fenced block renderers
+    ///
+    /// A failing plugin is logged and skipped, leaving the markdown as it was
+    pub fn preprocess(&self, md: String) -> String {
+        self.0.iter().fold(md, |md, plugin| {
+            let md = match &plugin.manifest.preprocess {
+                Some(command) => match plugin.run(command, &md) {
+                    Ok(processed) => processed,
+                    Err(err) => {
+                        tracing::warn!("Plugin {} failed pre-processing: {err:#}", plugin.name);
+                        md
+                    }
+                },
+                None => md,
+            };
+
+            if plugin.manifest.fenced.is_empty() {
+                return md;
+            }
+            replace_fenced_blocks(&md, |block| {
+                let command = plugin.manifest.fenced.get(block.lang)?;
+                plugin
+                    .run(command, block.body)
+                    .map_err(|err| {
+                        let lang = block.lang;
+                        tracing::warn!("Plugin {} failed rendering {lang}: {err:#}", plugin.name)
+                    })
+                    .ok()
+            })
+        })
+    }
+}
+
+/// A closed fenced code block with a language
+pub struct FencedBlock<'a> {
+    /// The first word of the info string
+    pub lang: &'a str,
+    /// Everything after the opening fence e.g. `python run`
+    pub info: &'a str,
+    pub body: &'a str,
+    /// The block as it was written, fences included
+    pub source: &'a str,
+}
+
+/// Replaces fenced code blocks with the output of `render`, leaving the block alone when it
+/// returns `None`
+pub fn replace_fenced_blocks(
+    md: &str,
+    mut render: impl FnMut(&FencedBlock<'_>) -> Option<String>,
+) -> String {
+    let mut out = String::with_capacity(md.len());
+    let mut lines = md.split_inclusive('\n');
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        let fence_char = match trimmed.chars().next() {
+            Some(c @ ('`' | '~')) => c,
+            _ => {
+                out.push_str(line);
+                continue;
+            }
+        };
+        let fence_len = trimmed.chars().take_while(|&c| c == fence_char).count();
+        if fence_len < 3 {
+            out.push_str(line);
+            continue;
+        }
+        let info = trimmed[fence_len..].trim();
+        let lang = info.split_whitespace().next().unwrap_or("");
+
+        let mut block = vec![line];
+        let mut body = String::new();
+        let mut closed = false;
+        for inner in lines.by_ref() {
+            block.push(inner);
+            let inner_trimmed = inner.trim();
+            if inner_trimmed.len() >= fence_len && inner_trimmed.chars().all(|c| c == fence_char) {
+                closed = true;
+                break;
+            }
+            body.push_str(inner);
+        }
+
+        let source = block.concat();
+        let rendered = if closed && !lang.is_empty() {
+            render(&FencedBlock {
+                lang,
+                info,
+                body: &body,
+                source: &source,
+            })
+        } else {
+            None
+        };
+        match rendered {
+            Some(rendered) => {
+                out.push_str(&rendered);
+                if !rendered.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+            None => out.push_str(&source),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_only_matching_fences() {
+        let md = "\
+# Diagram
+
+```plantuml
+A -> B
+```
+
+~~~rust
+fn main() {}
+~~~
+";
+        let replaced = replace_fenced_blocks(md, |block| {
+            (block.lang == "plantuml").then(|| format!("rendered: {}", block.body.trim()))
+        });
+        assert_eq!(
+            replaced,
+            "\
+# Diagram
+
+rendered: A -> B
+
+~~~rust
+fn main() {}
+~~~
+"
+        );
+    }
+
+    #[test]
+    fn unclosed_fence_is_left_alone() {
+        let md = "```plantuml\nA -> B\n";
+        let replaced = replace_fenced_blocks(md, |_| Some("rendered".to_owned()));
+        assert_eq!(replaced, md);
+    }
+}
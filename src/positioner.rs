@@ -5,7 +5,7 @@ use std::fmt;
 use anyhow::Context;
 use taffy::Taffy;
 
-use crate::image::Image;
+use crate::image::{Image, MaxSize};
 use crate::text::TextSystem;
 use crate::utils::{Align, Point, Rect, Size};
 use crate::{debug_impls, Element};
@@ -38,6 +38,90 @@ impl<T> Positioned<T> {
     }
 }
 
+/// The part of the window that a document gets laid out in, as fractions of the window's width
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Viewport {
+    pub const FULL: Self = Self::new(0., 1.);
+    pub const LEFT_HALF: Self = Self::new(0., 0.5);
+    pub const RIGHT_HALF: Self = Self::new(0.5, 1.);
+
+    pub const fn new(start: f32, end: f32) -> Self {
+        Self { start, end }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.end - self.start
+    }
+
+    pub fn contains_x(&self, x: f32, screen_width: f32) -> bool {
+        (self.start * screen_width..self.end * screen_width).contains(&x)
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+/// Space left after each kind of element, in pixels before scaling
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ElementSpacing {
+    /// Also what the document starts with above its first element
+    pub paragraph: f32,
+    /// Indexed by the heading's level, so `h1` is first
+    pub headings: [f32; 6],
+    /// The least space between a heading and whatever comes before it
+    pub before_heading: f32,
+    pub list: f32,
+    pub table: f32,
+    pub code_block: f32,
+    /// Between images in a row too
+    pub image: f32,
+}
+
+impl Default for ElementSpacing {
+    fn default() -> Self {
+        Self {
+            paragraph: DEFAULT_PADDING,
+            headings: [DEFAULT_PADDING; 6],
+            before_heading: 12.,
+            list: DEFAULT_PADDING,
+            table: 6.,
+            code_block: DEFAULT_PADDING,
+            image: DEFAULT_PADDING,
+        }
+    }
+}
+
+impl ElementSpacing {
+    pub fn after(&self, element: &Element) -> f32 {
+        match element {
+            Element::TextBox(text_box) if text_box.is_code_block => self.code_block,
+            Element::TextBox(text_box) if text_box.header_level.is_some() => {
+                let level = text_box.header_level.unwrap_or(1).clamp(1, 6);
+                self.headings[usize::from(level) - 1]
+            }
+            Element::TextBox(text_box) if text_box.is_list_item => self.list,
+            Element::Table(_) => self.table,
+            Element::Image(_) | Element::Row(_) => self.image,
+            _ => self.paragraph,
+        }
+    }
+
+    pub fn before(&self, element: &Element) -> f32 {
+        match element {
+            Element::TextBox(text_box) if text_box.is_header => self.before_heading,
+            _ => 0.,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Positioner {
     pub screen_size: Size,
@@ -47,52 +131,116 @@ pub struct Positioner {
     pub page_margin: f32,
     pub anchors: HashMap<String, f32>,
     pub taffy: Taffy,
+    pub viewport: Viewport,
+    /// Images bigger than this get scaled down to fit
+    pub image_max_size: MaxSize,
 }
 
 impl Positioner {
     pub fn new(screen_size: Size, hidpi_scale: f32, page_width: f32, page_margin: f32) -> Self {
-        Self::new_with_padding(screen_size, hidpi_scale, page_width, page_margin, DEFAULT_PADDING)
+        let spacing = ElementSpacing::default();
+        Self::new_with_spacing(screen_size, hidpi_scale, page_width, page_margin, &spacing)
     }
-    
-    pub fn new_with_padding(screen_size: Size, hidpi_scale: f32, page_width: f32, page_margin: f32, element_padding: f32) -> Self {
+
+    pub fn new_with_spacing(
+        screen_size: Size,
+        hidpi_scale: f32,
+        page_width: f32,
+        page_margin: f32,
+        spacing: &ElementSpacing,
+    ) -> Self {
         let mut taffy = Taffy::new();
         taffy.disable_rounding();
         Self {
-            reserved_height: element_padding * hidpi_scale,
+            reserved_height: spacing.paragraph * hidpi_scale,
             hidpi_scale,
             page_width,
             page_margin,
             screen_size,
             anchors: HashMap::new(),
             taffy,
+            viewport: Viewport::FULL,
+            image_max_size: MaxSize::default(),
         }
     }
 
+    /// Width taken from the left side of the screen before the page starts
+    pub fn left_inset(&self) -> f32 {
+        self.screen_size.0 * self.viewport.start
+    }
+
+    /// Width taken from the right side of the screen after the page ends
+    pub fn right_inset(&self) -> f32 {
+        self.screen_size.0 * (1. - self.viewport.end)
+    }
+
+    /// How wide the viewport is, margins included
+    pub fn viewport_width(&self) -> f32 {
+        self.screen_size.0 * self.viewport.width()
+    }
+
+    /// Space on each side of the page when the viewport is wider than `page_width`
+    pub fn centering(&self) -> f32 {
+        (self.viewport_width() - self.page_width).max(0.) / 2.
+    }
+
+    /// Where the page's content starts horizontally
+    pub fn left_edge(&self) -> f32 {
+        self.left_inset() + self.page_margin + self.centering()
+    }
+
+    /// Where the page's content ends horizontally
+    pub fn right_edge(&self) -> f32 {
+        self.screen_size.0 - self.right_inset() - self.page_margin - self.centering()
+    }
+
+    /// How big an image would be without `image_max_size` scaling it down
+    pub fn full_image_size(&self, image: &Image, zoom: f32) -> Size {
+        image
+            .size(
+                (
+                    self.viewport_width().min(self.page_width),
+                    self.screen_size.1,
+                ),
+                zoom,
+            )
+            .unwrap_or_default()
+    }
+
+    fn fit_image(&self, size: Size) -> Size {
+        self.image_max_size
+            .fit(size, (self.viewport_width(), self.screen_size.1))
+    }
+
+    /// Whether the image gets scaled down by `image_max_size`, so there's more of it to see
+    pub fn is_image_shrunk(&self, image: &Image, zoom: f32) -> bool {
+        let full_size = self.full_image_size(image, zoom);
+        self.fit_image(full_size) != full_size
+    }
+
     // Positions the element but does not update reserved_height
     pub fn position(
         &mut self,
         text_system: &mut TextSystem,
         element: &mut Positioned<Element>,
         zoom: f32,
-        element_padding: f32,
+        spacing: &ElementSpacing,
     ) -> anyhow::Result<()> {
-        let centering = (self.screen_size.0 - self.page_width).max(0.) / 2.;
+        let left_edge = self.left_edge();
+        let right_edge = self.right_edge();
 
         let bounds = match &mut element.inner {
             Element::TextBox(text_box) => {
-                let indent = text_box.indent;
-                let pos = (self.page_margin + indent + centering, self.reserved_height);
+                let indent = text_box.indent + text_box.gutter_width(text_system, zoom);
+                let pos = (left_edge + indent, self.reserved_height);
 
                 let size = text_box.size(
                     text_system,
-                    (
-                        (self.screen_size.0 - pos.0 - self.page_margin - centering).max(0.),
-                        f32::INFINITY,
-                    ),
+                    ((right_edge - pos.0).max(0.), f32::INFINITY),
                     zoom,
                 );
 
-                if let Some(ref anchor_name) = text_box.is_anchor {
+                for anchor_name in text_box.is_anchor.iter().chain(&text_box.more_anchors) {
                     let _ = self.anchors.insert(anchor_name.clone(), pos.1);
                 }
 
@@ -103,62 +251,66 @@ impl Positioner {
                 (0., spacer.space * self.hidpi_scale * zoom),
             ),
             Element::Image(image) => {
-                let size = image
-                    .size(
-                        (self.screen_size.0.min(self.page_width), self.screen_size.1),
-                        zoom,
-                    )
-                    .unwrap_or_default();
+                let size = self.fit_image(self.full_image_size(image, zoom));
                 match image.is_aligned {
-                    Some(Align::Center) => Rect::new(
-                        (self.screen_size.0 / 2. - size.0 / 2., self.reserved_height),
-                        size,
-                    ),
-                    _ => Rect::new((self.page_margin + centering, self.reserved_height), size),
+                    Some(Align::Center) => {
+                        let middle = self.left_inset() + self.viewport_width() / 2.;
+                        Rect::new((middle - size.0 / 2., self.reserved_height), size)
+                    }
+                    _ => Rect::new((left_edge, self.reserved_height), size),
                 }
             }
             Element::Table(table) => {
-                let pos = (self.page_margin + centering, self.reserved_height);
+                let pos = (left_edge, self.reserved_height);
                 let layout = table.layout(
                     text_system,
                     &mut self.taffy,
-                    (
-                        self.screen_size.0 - pos.0 - self.page_margin - centering,
-                        f32::INFINITY,
-                    ),
+                    (right_edge - pos.0, f32::INFINITY),
                     zoom,
                 )?;
-                Rect::new(
-                    (self.page_margin + centering, self.reserved_height),
-                    layout.size,
-                )
+                // Anchors within cells (e.g. footnote references) can be jumped to as well
+                let cells = table
+                    .rows
+                    .iter()
+                    .zip(&layout.rows)
+                    .flat_map(|(row, row_layout)| row.iter().zip(row_layout));
+                let caption = table.caption.as_ref().zip(layout.caption_layout.as_ref());
+                for (text_box, cell_layout) in cells.chain(caption) {
+                    let y = self.reserved_height + cell_layout.location.y;
+                    for anchor_name in text_box.is_anchor.iter().chain(&text_box.more_anchors) {
+                        let _ = self.anchors.insert(anchor_name.clone(), y);
+                    }
+                }
+                Rect::new((left_edge, self.reserved_height), layout.size)
+            }
+            Element::Math(math) => {
+                let size = math.layout(text_system, zoom).size;
+                // Display math gets centered in the space left over
+                let width = right_edge - left_edge;
+                let x = left_edge + ((width - size.0) / 2.).max(0.);
+                Rect::new((x, self.reserved_height), size)
             }
             Element::Row(row) => {
-                let mut reserved_width = self.page_margin + centering;
+                let mut reserved_width = left_edge;
                 let mut inner_reserved_height: f32 = 0.;
                 let mut max_height: f32 = 0.;
                 let mut max_width: f32 = 0.;
+                let padding = spacing.image * self.hidpi_scale * zoom;
                 for element in &mut row.elements {
-                    self.position(text_system, element, zoom, element_padding)?;
+                    self.position(text_system, element, zoom, spacing)?;
                     let element_bounds = element
                         .bounds
                         .as_mut()
                         .context("Element didn't have bounds")?;
 
-                    let target_width = reserved_width
-                        + element_padding * self.hidpi_scale * zoom
-                        + element_bounds.size.0;
+                    let target_width = reserved_width + padding + element_bounds.size.0;
                     // Row would be too long with this element so add another line
-                    if target_width > self.screen_size.0 - self.page_margin - centering {
+                    if target_width > right_edge {
                         max_width = max_width.max(reserved_width);
-                        reserved_width = self.page_margin
-                            + centering
-                            + element_padding * self.hidpi_scale * zoom
-                            + element_bounds.size.0;
-                        inner_reserved_height +=
-                            max_height + element_padding * self.hidpi_scale * zoom;
+                        reserved_width = left_edge + padding + element_bounds.size.0;
+                        inner_reserved_height += max_height + padding;
                         max_height = element_bounds.size.1;
-                        element_bounds.pos.0 = self.page_margin + centering;
+                        element_bounds.pos.0 = left_edge;
                     } else {
                         max_height = max_height.max(element_bounds.size.1);
                         element_bounds.pos.0 = reserved_width;
@@ -167,43 +319,37 @@ impl Positioner {
                     element_bounds.pos.1 = self.reserved_height + inner_reserved_height;
                 }
                 max_width = max_width.max(reserved_width);
-                inner_reserved_height += max_height + element_padding * self.hidpi_scale * zoom;
+                inner_reserved_height += max_height + padding;
                 Rect::new(
-                    (self.page_margin + centering, self.reserved_height),
-                    (
-                        max_width - self.page_margin - centering,
-                        inner_reserved_height,
-                    ),
+                    (left_edge, self.reserved_height),
+                    (max_width - left_edge, inner_reserved_height),
                 )
             }
             Element::Section(section) => {
-                let mut section_bounds =
-                    Rect::new((self.page_margin + centering, self.reserved_height), (0., 0.));
+                let mut section_bounds = Rect::new((left_edge, self.reserved_height), (0., 0.));
                 if let Some(ref mut summary) = *section.summary {
-                    self.position(text_system, summary, zoom, element_padding)?;
+                    self.position(text_system, summary, zoom, spacing)?;
                     let element_size = summary
                         .bounds
                         .as_mut()
                         .context("Element didn't have bounds")?
                         .size;
-                    self.reserved_height +=
-                        element_size.1 + element_padding * self.hidpi_scale * zoom;
-                    section_bounds.size.1 +=
-                        element_size.1 + element_padding * self.hidpi_scale * zoom;
+                    let padding = spacing.after(&summary.inner) * self.hidpi_scale * zoom;
+                    self.reserved_height += element_size.1 + padding;
+                    section_bounds.size.1 += element_size.1 + padding;
                     section_bounds.size.0 = section_bounds.size.0.max(element_size.0)
                 }
                 for element in &mut section.elements {
-                    self.position(text_system, element, zoom, element_padding)?;
+                    self.position(text_system, element, zoom, spacing)?;
                     let element_size = element
                         .bounds
                         .as_mut()
                         .context("Element didn't have bounds")?
                         .size;
-                    self.reserved_height +=
-                        element_size.1 + element_padding * self.hidpi_scale * zoom;
+                    let padding = spacing.after(&element.inner) * self.hidpi_scale * zoom;
+                    self.reserved_height += element_size.1 + padding;
                     if !*section.hidden.borrow() {
-                        section_bounds.size.1 +=
-                            element_size.1 + element_padding * self.hidpi_scale * zoom;
+                        section_bounds.size.1 += element_size.1 + padding;
                         section_bounds.size.0 = section_bounds.size.0.max(element_size.0)
                     }
                 }
@@ -215,65 +361,88 @@ impl Positioner {
         Ok(())
     }
 
+    /// How wide the elements would be laid out if the screen had no width limit, not counting the
+    /// page margins
+    pub fn natural_width(
+        &mut self,
+        text_system: &mut TextSystem,
+        elements: &mut [Positioned<Element>],
+        zoom: f32,
+        spacing: &ElementSpacing,
+    ) -> anyhow::Result<f32> {
+        let unbounded = (f32::INFINITY, f32::INFINITY);
+        let mut max_width: f32 = 0.;
+        for element in elements {
+            let width = match &mut element.inner {
+                Element::TextBox(text_box) => {
+                    text_box.indent
+                        + text_box.gutter_width(text_system, zoom)
+                        + text_box.size(text_system, unbounded, zoom).0
+                }
+                Element::Spacer(_) => 0.,
+                Element::Image(image) => image.size(unbounded, zoom).unwrap_or_default().0,
+                Element::Table(table) => {
+                    table
+                        .layout(text_system, &mut self.taffy, unbounded, zoom)?
+                        .size
+                        .0
+                }
+                Element::Math(math) => math.layout(text_system, zoom).size.0,
+                Element::Row(row) => {
+                    let mut width = 0.;
+                    for element in &mut row.elements {
+                        let element = std::slice::from_mut(element);
+                        width += self.natural_width(text_system, element, zoom, spacing)?
+                            + spacing.image * self.hidpi_scale * zoom;
+                    }
+                    width
+                }
+                Element::Section(section) => {
+                    let summary = (*section.summary).as_mut().map(std::slice::from_mut);
+                    let summary_width = match summary {
+                        Some(summary) => self.natural_width(text_system, summary, zoom, spacing)?,
+                        None => 0.,
+                    };
+                    self.natural_width(text_system, &mut section.elements, zoom, spacing)?
+                        .max(summary_width)
+                }
+            };
+            max_width = max_width.max(width);
+        }
+        Ok(max_width)
+    }
+
     // Resets reserved height and positions every element again
     pub fn reposition(
         &mut self,
         text_system: &mut TextSystem,
         elements: &mut [Positioned<Element>],
         zoom: f32,
-        element_padding: f32,
+        spacing: &ElementSpacing,
     ) -> anyhow::Result<()> {
-        self.reserved_height = element_padding * self.hidpi_scale * zoom;
+        self.reserved_height = spacing.paragraph * self.hidpi_scale * zoom;
         let mut last_element_bottom: Option<f32> = None;
 
-        for i in 0..elements.len() {
-            // Ensure minimum 12px spacing before headers (except at document start)
-            if matches!(&elements[i].inner, Element::TextBox(tb) if tb.is_header) {
-                if let Some(last_bottom) = last_element_bottom {
-                    // Ensure at least 12px gap from previous element to this header
-                    let min_spacing = 12.0 * self.hidpi_scale * zoom;
-                    let current_gap = self.reserved_height - last_bottom;
-                    
-                    if current_gap < min_spacing {
-                        // Add extra spacing to reach minimum
-                        self.reserved_height = last_bottom + min_spacing;
-                    }
-                }
+        for element in elements {
+            // Make sure headings have some room above them (except at document start)
+            if let Some(last_bottom) = last_element_bottom {
+                let min_spacing = spacing.before(&element.inner) * self.hidpi_scale * zoom;
+                self.reserved_height = self.reserved_height.max(last_bottom + min_spacing);
             }
-            
+
             // Position the element at current reserved_height
-            self.position(text_system, &mut elements[i], zoom, element_padding)?;
-            
+            self.position(text_system, element, zoom, spacing)?;
+
             // Update reserved_height to bottom of this element
-            let element_bounds = elements[i]
+            let element_bounds = element
                 .bounds
                 .as_ref()
                 .context("Element didn't have bounds")?;
-            
+
             let element_bottom = element_bounds.pos.1 + element_bounds.size.1;
-            self.reserved_height = element_bottom;
+            self.reserved_height =
+                element_bottom + spacing.after(&element.inner) * self.hidpi_scale * zoom;
             last_element_bottom = Some(element_bottom);
-            
-            // Determine padding based on element type
-            let padding = if matches!(&elements[i].inner, Element::TextBox(text_box) if text_box.is_header) {
-                // Headers always get at least 2px padding after them
-                let min_header_padding = 2.0 * self.hidpi_scale * zoom;
-                let normal_padding = element_padding * self.hidpi_scale * zoom;
-                normal_padding.max(min_header_padding)
-            } else if matches!(&elements[i].inner, Element::Table(_)) {
-                // Tables always get at least 6px padding for consistency
-                let min_table_padding = 6.0 * self.hidpi_scale * zoom;
-                let normal_padding = element_padding * self.hidpi_scale * zoom;
-                normal_padding.max(min_table_padding)
-            } else {
-                // Normal padding for other elements
-                element_padding * self.hidpi_scale * zoom
-            };
-            
-            // Add the calculated padding
-            if padding > 0.0 {
-                self.reserved_height += padding;
-            }
         }
 
         Ok(())
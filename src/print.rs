@@ -0,0 +1,465 @@
+//! `inlyne print` renders a markdown file as styled terminal output
+//!
+//! This uses the same markdown parsing as the viewer, but walks comrak's AST directly since
+//! there's no layout to do. Links are emitted as OSC 8 hyperlinks, and all styling gets stripped
+//! automatically when stdout isn't a terminal
+
+use std::borrow::Cow;
+use std::io::Write as _;
+use std::path::Path;
+
+use crate::utils::markdown_options;
+
+use anstyle::{AnsiColor, Reset, Style};
+use comrak::nodes::{AstNode, ListType, NodeValue};
+use comrak::{parse_document, Arena};
+use url::Url;
+
+const HEADER: Style = Style::new()
+    .bold()
+    .fg_color(Some(anstyle::Color::Ansi(AnsiColor::Cyan)));
+const CODE: Style = Style::new().fg_color(Some(anstyle::Color::Ansi(AnsiColor::Yellow)));
+const LINK: Style = Style::new()
+    .underline()
+    .fg_color(Some(anstyle::Color::Ansi(AnsiColor::Blue)));
+const DIM: Style = Style::new().dimmed();
+
+/// Swaps out control characters so that a document can't slip its own escape sequences into the
+/// terminal. Entities like `&#27;` get decoded by the parser, so this runs on the decoded text
+fn sanitize(text: &str) -> Cow<'_, str> {
+    let is_unsafe = |c: char| c.is_control() && c != '\n' && c != '\t';
+    if text.contains(is_unsafe) {
+        Cow::Owned(
+            text.chars()
+                .map(|c| if is_unsafe(c) { '\u{fffd}' } else { c })
+                .collect(),
+        )
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+pub fn run(md: &str, base_dir: &Path) -> anyhow::Result<()> {
+    let arena = Arena::new();
+    let root = parse_document(&arena, md, &markdown_options());
+
+    let mut printer = Printer::new(base_dir);
+    printer.block(root);
+
+    let stdout = anstream::stdout();
+    let mut stdout = stdout.lock();
+    stdout.write_all(printer.out.as_bytes())?;
+    Ok(())
+}
+
+/// Line prefix from an enclosing block quote or list item
+struct Prefix {
+    /// Used for the first line only e.g. a list item's bullet
+    first: Option<String>,
+    rest: String,
+}
+
+struct Printer<'path> {
+    base_dir: &'path Path,
+    out: String,
+    styles: Vec<Style>,
+    prefixes: Vec<Prefix>,
+    at_line_start: bool,
+    /// Whether a blank line separates the next block from the previous one
+    pending_blank: bool,
+    /// Inside of a tight list, so paragraphs aren't separated by blank lines
+    tight: bool,
+}
+
+impl<'path> Printer<'path> {
+    fn new(base_dir: &'path Path) -> Self {
+        Self {
+            base_dir,
+            out: String::new(),
+            styles: Vec::new(),
+            prefixes: Vec::new(),
+            at_line_start: true,
+            pending_blank: false,
+            tight: false,
+        }
+    }
+
+    fn push_style(&mut self, style: Style) {
+        self.styles.push(style);
+        self.out.push_str(&style.render().to_string());
+    }
+
+    fn pop_style(&mut self) {
+        self.styles.pop();
+        self.reapply_styles();
+    }
+
+    /// ANSI styles stack, so they get reset and then replayed to remove one
+    fn reapply_styles(&mut self) {
+        self.out.push_str(&Reset.render().to_string());
+        for style in &self.styles {
+            self.out.push_str(&style.render().to_string());
+        }
+    }
+
+    fn styled(&mut self, style: Style, f: impl FnOnce(&mut Self)) {
+        self.push_style(style);
+        f(self);
+        self.pop_style();
+    }
+
+    fn write_prefixes(&mut self) {
+        if self.prefixes.is_empty() {
+            return;
+        }
+
+        let mut line_prefix = String::new();
+        for prefix in &mut self.prefixes {
+            match prefix.first.take() {
+                Some(first) => line_prefix.push_str(&first),
+                None => line_prefix.push_str(&prefix.rest),
+            }
+        }
+        // Keep the prefixes themselves unstyled
+        self.out.push_str(&Reset.render().to_string());
+        self.out.push_str(&line_prefix);
+        self.reapply_styles();
+    }
+
+    fn write(&mut self, text: &str) {
+        self.write_raw(&sanitize(text));
+    }
+
+    /// Writes text that already went through [`sanitize`], like a rendered table cell
+    fn write_raw(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.newline();
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if self.at_line_start {
+                self.write_prefixes();
+                self.at_line_start = false;
+            }
+            self.out.push_str(line);
+        }
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.at_line_start = true;
+    }
+
+    fn start_block(&mut self) {
+        if std::mem::take(&mut self.pending_blank) {
+            // Quote bars carry on through blank lines, but list bullets wait for content
+            let blank: String = self
+                .prefixes
+                .iter()
+                .map(|prefix| prefix.rest.as_str())
+                .collect();
+            self.out.push_str(blank.trim_end());
+            self.out.push('\n');
+        }
+    }
+
+    fn end_block(&mut self) {
+        if !self.at_line_start {
+            self.newline();
+        }
+        self.pending_blank = !self.tight;
+    }
+
+    fn with_prefix(&mut self, first: Option<String>, rest: String, f: impl FnOnce(&mut Self)) {
+        self.prefixes.push(Prefix { first, rest });
+        f(self);
+        self.prefixes.pop();
+    }
+
+    fn block<'a>(&mut self, node: &'a AstNode<'a>) {
+        match &node.data.borrow().value {
+            NodeValue::Document => node.children().for_each(|child| self.block(child)),
+            NodeValue::FrontMatter(_) => {}
+            NodeValue::Heading(heading) => {
+                self.start_block();
+                self.styled(HEADER, |this| {
+                    this.write(&"#".repeat(heading.level.into()));
+                    this.write(" ");
+                    node.children().for_each(|child| this.inline(child));
+                });
+                self.end_block();
+                self.pending_blank = true;
+            }
+            NodeValue::Paragraph => {
+                self.start_block();
+                node.children().for_each(|child| self.inline(child));
+                self.end_block();
+            }
+            NodeValue::BlockQuote => {
+                self.start_block();
+                self.with_prefix(None, "│ ".to_owned(), |this| {
+                    node.children().for_each(|child| this.block(child));
+                });
+                self.pending_blank = true;
+            }
+            NodeValue::List(list) => {
+                self.start_block();
+                let was_tight = std::mem::replace(&mut self.tight, list.tight);
+                for (i, item) in node.children().enumerate() {
+                    let bullet = match list.list_type {
+                        ListType::Bullet => "• ".to_owned(),
+                        ListType::Ordered => format!("{}. ", list.start + i),
+                    };
+                    let bullet = match item.data.borrow().value {
+                        NodeValue::TaskItem(Some(_)) => format!("{bullet}☑ "),
+                        NodeValue::TaskItem(None) => format!("{bullet}☐ "),
+                        _ => bullet,
+                    };
+                    if self.tight {
+                        self.pending_blank = false;
+                    }
+                    self.start_block();
+                    let indent = " ".repeat(bullet.chars().count());
+                    self.with_prefix(Some(bullet), indent, |this| {
+                        item.children().for_each(|child| this.block(child));
+                    });
+                }
+                self.tight = was_tight;
+                self.pending_blank = !self.tight;
+            }
+            NodeValue::CodeBlock(code_block) => {
+                self.start_block();
+                self.with_prefix(None, "    ".to_owned(), |this| {
+                    this.styled(CODE, |this| {
+                        this.write(code_block.literal.trim_end_matches('\n'));
+                    });
+                });
+                self.end_block();
+            }
+            NodeValue::HtmlBlock(html) => {
+                self.start_block();
+                self.styled(DIM, |this| this.write(html.literal.trim_end_matches('\n')));
+                self.end_block();
+            }
+            NodeValue::ThematicBreak => {
+                self.start_block();
+                self.styled(DIM, |this| this.write(&"─".repeat(40)));
+                self.end_block();
+            }
+            NodeValue::Table(_) => {
+                self.start_block();
+                self.table(node);
+                self.end_block();
+            }
+            NodeValue::FootnoteDefinition(footnote) => {
+                self.start_block();
+                let label = format!("[^{}]: ", sanitize(&footnote.name));
+                let indent = " ".repeat(label.chars().count());
+                self.with_prefix(Some(label), indent, |this| {
+                    node.children().for_each(|child| this.block(child));
+                });
+            }
+            _ => {
+                self.start_block();
+                self.inline(node);
+                self.end_block();
+            }
+        }
+    }
+
+    fn inline<'a>(&mut self, node: &'a AstNode<'a>) {
+        match &node.data.borrow().value {
+            NodeValue::Text(text) => self.write(text),
+            NodeValue::SoftBreak => self.write(" "),
+            NodeValue::LineBreak => self.newline(),
+            NodeValue::Code(code) => self.styled(CODE, |this| this.write(&code.literal)),
+            NodeValue::HtmlInline(html) => self.styled(DIM, |this| this.write(html)),
+            NodeValue::Emph => {
+                self.styled(Style::new().italic(), |this| this.inline_children(node))
+            }
+            NodeValue::Strong => {
+                self.styled(Style::new().bold(), |this| this.inline_children(node))
+            }
+            NodeValue::Strikethrough => self.styled(Style::new().strikethrough(), |this| {
+                this.inline_children(node)
+            }),
+            NodeValue::Link(link) => self.hyperlink(&link.url, |this| this.inline_children(node)),
+            NodeValue::Image(image) => self.hyperlink(&image.url, |this| {
+                this.write("[image: ");
+                this.inline_children(node);
+                this.write("]");
+            }),
+            NodeValue::FootnoteReference(reference) => {
+                self.styled(LINK, |this| this.write(&format!("[^{}]", reference.name)))
+            }
+            _ => self.inline_children(node),
+        }
+    }
+
+    fn inline_children<'a>(&mut self, node: &'a AstNode<'a>) {
+        node.children().for_each(|child| self.inline(child));
+    }
+
+    /// Wraps the contents in an OSC 8 hyperlink
+    fn hyperlink(&mut self, url: &str, f: impl FnOnce(&mut Self)) {
+        match self.resolve_url(url) {
+            Some(url) => {
+                self.out.push_str(&format!("\x1b]8;;{url}\x1b\\"));
+                self.styled(LINK, f);
+                self.out.push_str("\x1b]8;;\x1b\\");
+            }
+            None => self.styled(LINK, f),
+        }
+    }
+
+    /// Terminals need absolute URIs, so relative paths are resolved against the file's directory
+    ///
+    /// URLs come back percent-encoded, so they can't end the escape sequence they sit in early
+    fn resolve_url(&self, url: &str) -> Option<String> {
+        let url = if url.starts_with('#') {
+            return None;
+        } else if url.contains("://") || url.starts_with("mailto:") {
+            Url::parse(url).ok()?
+        } else {
+            let path = self.base_dir.join(url);
+            let path = path
+                .canonicalize()
+                .or_else(|_| std::path::absolute(&path))
+                .ok()?;
+            Url::from_file_path(path).ok()?
+        };
+        let url = String::from(url);
+        (!url.contains(char::is_control)).then_some(url)
+    }
+
+    fn table<'a>(&mut self, table: &'a AstNode<'a>) {
+        // Render each cell on its own to figure out the column widths
+        let rows: Vec<Vec<String>> = table
+            .children()
+            .map(|row| {
+                row.children()
+                    .map(|cell| {
+                        let mut printer = Printer::new(self.base_dir);
+                        printer.inline_children(cell);
+                        printer.out
+                    })
+                    .collect()
+            })
+            .collect();
+        let visible_width = |cell: &str| {
+            anstream::adapter::strip_str(cell)
+                .to_string()
+                .chars()
+                .count()
+        };
+
+        let num_columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..num_columns)
+            .map(|col| {
+                rows.iter()
+                    .filter_map(|row| row.get(col))
+                    .map(|cell| visible_width(cell))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        for (i, row) in rows.iter().enumerate() {
+            for (col, cell) in row.iter().enumerate() {
+                if col > 0 {
+                    self.styled(DIM, |this| this.write(" │ "));
+                }
+                let padding = widths[col] - visible_width(cell);
+                if i == 0 {
+                    self.styled(Style::new().bold(), |this| this.write_raw(cell));
+                } else {
+                    self.write_raw(cell);
+                }
+                self.write(&" ".repeat(padding));
+            }
+            self.newline();
+
+            // Underline the header row
+            if i == 0 {
+                let rule: Vec<_> = widths.iter().map(|&width| "─".repeat(width)).collect();
+                self.styled(DIM, |this| this.write(&rule.join("─┼─")));
+                self.newline();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn print_plain(md: &str) -> String {
+        let arena = Arena::new();
+        let root = parse_document(&arena, md, &markdown_options());
+        let mut printer = Printer::new(Path::new("/docs"));
+        printer.block(root);
+        anstream::adapter::strip_str(&printer.out).to_string()
+    }
+
+    #[test]
+    fn block_layout() {
+        let md = "\
+# Title
+
+Some *styled* text
+
+- first
+- [x] done
+
+> quoted
+>
+> twice
+
+```rust
+fn main() {}
+```";
+        assert_eq!(
+            print_plain(md),
+            "\
+# Title
+
+Some styled text
+
+• first
+• ☑ done
+
+│ quoted
+│
+│ twice
+
+    fn main() {}
+"
+        );
+    }
+
+    #[test]
+    fn relative_links_become_file_uris() {
+        let printer = Printer::new(Path::new("/docs"));
+        assert_eq!(
+            printer.resolve_url("https://example.com").as_deref(),
+            Some("https://example.com")
+        );
+        assert_eq!(printer.resolve_url("#anchor"), None);
+        let relative = printer.resolve_url("other.md").unwrap();
+        assert!(relative.starts_with("file:///docs"));
+        assert!(relative.ends_with("other.md"));
+        let relative = printer.resolve_url("my notes#1%.md").unwrap();
+        assert!(relative.ends_with("/my%20notes%231%25.md"));
+        let escaped = printer.resolve_url("https://example.com/\x1b\\").unwrap();
+        assert!(!escaped.contains('\x1b'));
+    }
+
+    #[test]
+    fn control_characters_are_replaced() {
+        let printed = print_plain("a\x1b[31mb &#27;[2J &#x9b;c");
+        assert!(!printed.contains(['\x1b', '\u{9b}']));
+        assert_eq!(printed, "a\u{fffd}[31mb \u{fffd}[2J \u{fffd}c\n");
+    }
+}
@@ -0,0 +1,388 @@
+//! Talking to other inlyne windows over localhost sockets
+//!
+//! - `inlyne remote preview --stdin` previews unsaved editor buffers. The first push opens a
+//!   preview window, and later pushes replace the displayed contents without anything getting
+//!   written to disk
+//! - Windows opened with `--follow` mirror each other's scroll position and zoom
+//!
+//! Ports get noted in a per-user directory that only the user can read, along with a random
+//! token that has to be sent before anything else. That keeps other users on the same machine
+//! from pushing documents into a window or posing as one to receive them
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use crate::renderer::{MAX_ZOOM, MIN_ZOOM};
+use crate::InlyneEvent;
+
+use anyhow::Context;
+use winit::event_loop::EventLoopProxy;
+
+/// Sent before the token and contents, so that anything else connecting to the port gets ignored
+const HEADER: &str = "inlyne-preview\n";
+/// How long a connection gets to finish sending before it's dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Anything past this isn't a document anyone wants to preview
+const MAX_MESSAGE: u64 = 64 * 1024 * 1024;
+
+/// Holds the ports and tokens of running windows
+///
+/// This is the user's runtime dir where there is one and their cache dir otherwise. Either way
+/// it's private to the user, unlike the shared temp dir
+fn state_dir() -> anyhow::Result<PathBuf> {
+    let base = dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .context("Failed finding a per-user directory to note ports in")?;
+    let dir = base.join("inlyne-remote");
+    create_private_dir(&dir).with_context(|| format!("Failed creating '{}'", dir.display()))?;
+    Ok(dir)
+}
+
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)?;
+    // The directory may have been left behind by an older version with looser permissions
+    #[cfg(unix)]
+    fs::set_permissions(dir, std::os::unix::fs::PermissionsExt::from_mode(0o700))?;
+    Ok(())
+}
+
+/// Writes a fresh file that only the user can read, replacing whatever was at `path` instead of
+/// following it if it's a link
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+/// A secret for a listening window. Only processes that can read the state dir learn it
+fn new_token() -> anyhow::Result<String> {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes).context("Failed generating a token")?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+fn port_file() -> anyhow::Result<PathBuf> {
+    Ok(state_dir()?.join("preview.port"))
+}
+
+/// The file that a new preview window gets opened on
+///
+/// It goes in a fresh temp dir with a random name that only the user can access, so nothing can
+/// be planted at the path ahead of time. The dir is left for the window to read from and gets
+/// cleaned up along with the rest of the temp dir
+fn placeholder_file() -> anyhow::Result<PathBuf> {
+    let dir = tempfile::Builder::new()
+        .prefix("inlyne-preview-")
+        .tempdir()
+        .context("Failed creating a temp dir for the preview")?;
+    Ok(dir.into_path().join("preview.md"))
+}
+
+pub fn preview_stdin() -> anyhow::Result<()> {
+    let mut contents = String::new();
+    std::io::stdin()
+        .read_to_string(&mut contents)
+        .context("Failed reading the document from stdin")?;
+    match push(&contents) {
+        Ok(()) => return Ok(()),
+        Err(err) => tracing::debug!("No preview window to push to: {err}"),
+    }
+
+    // Nothing is listening yet, so open a new preview window
+    let placeholder = placeholder_file()?;
+    fs::write(&placeholder, &contents)
+        .with_context(|| format!("Failed writing to '{}'", placeholder.display()))?;
+    Command::new(std::env::current_exe().unwrap_or_else(|_| "inlyne".into()))
+        .arg("view")
+        .arg(&placeholder)
+        .arg("--remote-preview")
+        .spawn()
+        .context("Failed opening a preview window")?;
+    Ok(())
+}
+
+fn push(contents: &str) -> anyhow::Result<()> {
+    let noted = fs::read_to_string(port_file()?)?;
+    let (port, token) = noted
+        .trim()
+        .split_once(' ')
+        .context("Malformed preview port file")?;
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port.parse()?));
+    let mut stream = TcpStream::connect_timeout(&addr, READ_TIMEOUT)?;
+    stream.set_write_timeout(Some(READ_TIMEOUT))?;
+    stream.write_all(frame(HEADER, token, contents).as_bytes())?;
+    Ok(())
+}
+
+/// Listens for pushed documents, which get displayed like any other change to the file
+pub fn listen(proxy: EventLoopProxy<InlyneEvent>) -> anyhow::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let port = listener.local_addr()?.port();
+    let token = new_token()?;
+    write_private(&port_file()?, format!("{port} {token}").as_bytes())
+        .context("Failed saving the preview port")?;
+
+    serve(listener, HEADER, token, move |contents| {
+        let _ = proxy.send_event(InlyneEvent::FileChange { contents });
+    });
+    Ok(())
+}
+
+fn frame(header: &str, token: &str, body: &str) -> String {
+    format!("{header}{token}\n{body}")
+}
+
+/// The body of a message, as long as it starts with the right header and token
+fn unframe(mut received: String, header: &str, token: &str) -> Option<String> {
+    let prefix_len = header.len() + token.len() + 1;
+    let prefix = received.get(..prefix_len)?;
+    let matches = prefix.strip_prefix(header)?.strip_suffix('\n')? == token;
+    matches.then(|| {
+        received.drain(..prefix_len);
+        received
+    })
+}
+
+/// Handles each connection on its own thread, so that one that never finishes sending can't
+/// hold up the rest
+fn serve(
+    listener: TcpListener,
+    header: &'static str,
+    token: String,
+    on_message: impl Fn(String) + Clone + Send + 'static,
+) {
+    let token = Arc::new(token);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    tracing::warn!("Failed accepting a connection: {err}");
+                    continue;
+                }
+            };
+            let token = Arc::clone(&token);
+            let on_message = on_message.clone();
+            std::thread::spawn(move || {
+                let mut received = String::new();
+                let read = stream
+                    .set_read_timeout(Some(READ_TIMEOUT))
+                    .and_then(|()| Read::take(&stream, MAX_MESSAGE).read_to_string(&mut received));
+                if let Err(err) = read {
+                    tracing::warn!("Failed receiving from another window: {err}");
+                    return;
+                }
+                match unframe(received, header, &token) {
+                    Some(body) => on_message(body),
+                    None => tracing::warn!("Ignoring a connection without the right token"),
+                }
+            });
+        }
+    });
+}
+
+/// Sent before the view state of a window in follow mode
+const FOLLOW_HEADER: &str = "inlyne-follow\n";
+
+/// Holds a file named after the port of every window in follow mode, containing its token
+fn follow_dir() -> anyhow::Result<PathBuf> {
+    let dir = state_dir()?.join("follow");
+    create_private_dir(&dir)?;
+    Ok(dir)
+}
+
+/// How far down its document a window is scrolled along with its zoom
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewState {
+    /// From 0 at the top of the document to 1 at the bottom
+    pub scroll_fraction: f32,
+    pub zoom: f32,
+}
+
+impl ViewState {
+    fn encode(&self) -> String {
+        format!("{} {}", self.scroll_fraction, self.zoom)
+    }
+
+    /// Rejects anything that isn't a finite number, and keeps the rest in range
+    fn decode(received: &str) -> Option<Self> {
+        let (scroll_fraction, zoom) = received.trim().split_once(' ')?;
+        let scroll_fraction: f32 = scroll_fraction.parse().ok()?;
+        let zoom: f32 = zoom.parse().ok()?;
+        if !scroll_fraction.is_finite() || !zoom.is_finite() {
+            return None;
+        }
+        Some(Self {
+            scroll_fraction: scroll_fraction.clamp(0., 1.),
+            zoom: zoom.clamp(MIN_ZOOM, MAX_ZOOM),
+        })
+    }
+}
+
+/// Mirrors the view between every window opened with `--follow`. Whichever window gets moved
+/// leads, and the others follow along
+pub struct Follow {
+    registration: PathBuf,
+    sender: mpsc::Sender<ViewState>,
+}
+
+impl Follow {
+    pub fn join(proxy: EventLoopProxy<InlyneEvent>) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let port = listener.local_addr()?.port();
+        let dir = follow_dir()?;
+        let token = new_token()?;
+        let registration = dir.join(port.to_string());
+        write_private(&registration, token.as_bytes())
+            .context("Failed registering for follow mode")?;
+
+        serve(
+            listener,
+            FOLLOW_HEADER,
+            token,
+            move |received| match ViewState::decode(&received) {
+                Some(view) => {
+                    let _ = proxy.send_event(InlyneEvent::FollowView(view));
+                }
+                None => tracing::warn!("Ignoring a malformed followed view"),
+            },
+        );
+
+        // Sending happens off of the event loop since connecting to stale ports can take a bit
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok(mut view) = receiver.recv() {
+                // Only the latest view matters after falling behind
+                while let Ok(newer) = receiver.try_recv() {
+                    view = newer;
+                }
+                broadcast(&dir, port, view);
+            }
+        });
+
+        Ok(Self {
+            registration,
+            sender,
+        })
+    }
+
+    pub fn send(&self, view: ViewState) {
+        let _ = self.sender.send(view);
+    }
+
+    /// Stops other windows from following this one
+    pub fn leave(&self) {
+        let _ = fs::remove_file(&self.registration);
+    }
+}
+
+fn broadcast(dir: &Path, own_port: u16, view: ViewState) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let body = view.encode();
+    for entry in entries.flatten() {
+        let port = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok());
+        let Some(port) = port.filter(|&port| port != own_port) else {
+            continue;
+        };
+        let Ok(token) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let message = frame(FOLLOW_HEADER, token.trim(), &body);
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let sent = TcpStream::connect_timeout(&addr, Duration::from_millis(100))
+            .and_then(|mut stream| stream.write_all(message.as_bytes()));
+        if let Err(err) = sent {
+            // Most likely a window that exited without leaving
+            tracing::debug!("Dropping followed window on port {port}: {err}");
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framing() {
+        let pushed = frame(HEADER, "secret", "# Unsaved\n");
+        assert_eq!(
+            unframe(pushed.clone(), HEADER, "secret").as_deref(),
+            Some("# Unsaved\n")
+        );
+        assert_eq!(unframe(pushed, HEADER, "other"), None);
+        assert_eq!(
+            unframe(format!("{HEADER}# Unsaved\n"), HEADER, "secret"),
+            None
+        );
+        assert_eq!(
+            unframe("GET / HTTP/1.1\r\n".to_owned(), HEADER, "secret"),
+            None
+        );
+    }
+
+    #[test]
+    fn tokens_differ() {
+        let token = new_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert!(token.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_ne!(token, new_token().unwrap());
+    }
+
+    #[test]
+    fn view_state_round_trip() {
+        let view = ViewState {
+            scroll_fraction: 0.25,
+            zoom: 1.1,
+        };
+        assert_eq!(ViewState::decode(&view.encode()), Some(view));
+    }
+
+    #[test]
+    fn view_state_is_validated() {
+        assert_eq!(ViewState::decode("NaN 1.0"), None);
+        assert_eq!(ViewState::decode("0.5 inf"), None);
+        let view = ViewState::decode("2.0 1e30").unwrap();
+        assert_eq!(view.scroll_fraction, 1.0);
+        assert_eq!(view.zoom, MAX_ZOOM);
+        assert_eq!(ViewState::decode("0.5 0").unwrap().zoom, MIN_ZOOM);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_files_replace_links() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        fs::write(&target, "untouched").unwrap();
+        let path = dir.path().join("preview.md");
+        std::os::unix::fs::symlink(&target, &path).unwrap();
+
+        write_private(&path, b"# Preview").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "untouched");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# Preview");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
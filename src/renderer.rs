@@ -1,25 +1,28 @@
 use std::borrow::Cow;
+use std::ops::Range;
 use std::sync::Arc;
 use std::time::Instant;
 
-use crate::color::{native_color, Theme};
+use crate::color::{native_color, native_color_alpha, Theme};
+use crate::fold;
 use crate::fonts::get_fonts;
-use crate::image::ImageRenderer;
+use crate::image::{Image, ImageData, ImageRenderer};
+use crate::layout_profile::{self, LayoutProfile};
 use crate::metrics::{histogram, HistTag};
 use crate::opts::FontOptions;
-use crate::positioner::{Positioned, Positioner, DEFAULT_PADDING};
+use crate::positioner::{ElementSpacing, Positioned, Positioner, Viewport};
+use crate::search::SearchOptions;
 use crate::selection::Selection;
+use crate::source_map;
 use crate::table::TABLE_ROW_GAP;
-use crate::text::{CachedTextArea, TextCache, TextSystem};
+use crate::text::{CachedTextArea, Text, TextBox, TextCache, TextSystem};
 use crate::utils::{Point, Rect, Size};
 use crate::Element;
 
 use anyhow::{Context, Ok};
 use bytemuck::{Pod, Zeroable};
 use glyphon::{SwashCache, TextArea, TextAtlas, TextRenderer};
-use lyon::geom::euclid::Point2D;
-use lyon::geom::Box2D;
-use lyon::path::Polygon;
+use image::RgbaImage;
 use lyon::tessellation::*;
 use parking_lot::Mutex;
 use wgpu::util::DeviceExt;
@@ -33,25 +36,158 @@ pub struct Vertex {
     pub color: [f32; 4],
 }
 
+/// A filled rectangle drawn by the instanced quad pipeline
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable, Debug)]
+pub struct RectInstance {
+    /// Opposite corners in clip space
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    pub color: [f32; 4],
+}
+
+/// A run of shapes that get drawn by the same pipeline. Runs are drawn in the order they were
+/// added so that later shapes still paint over earlier ones
+#[derive(Clone, Debug)]
+enum Batch {
+    /// Indices into the tessellated shapes
+    Mesh(Range<u32>),
+    /// Rectangle instances
+    Rects(Range<u32>),
+}
+
+/// Everything the document layer looks different for without the document itself changing.
+/// Changes to the elements that keep their count, like a section getting folded, go through
+/// [`Renderer::invalidate_document()`] instead
+#[derive(Debug, PartialEq)]
+struct DocumentKey {
+    /// Where the elements live and how many there are, which tells the help page apart from the
+    /// document and picks up elements as they get laid out
+    elements: (usize, usize),
+    reserved_height: f32,
+    scroll_y: f32,
+    zoom: f32,
+    screen_size: (u32, u32),
+    search_query: Option<String>,
+    search_options: SearchOptions,
+    current_match: Vec<Rect>,
+    profile: LayoutProfile,
+    lightbox: bool,
+    /// The side pane's element count, height, and scroll position
+    side_pane: Option<(usize, f32, f32)>,
+}
+
+/// The document drawn into a texture of its own, along with what it was drawn for
+struct DocumentLayer {
+    view: wgpu::TextureView,
+    bind_group: Arc<BindGroup>,
+    size: (u32, u32),
+    key: Option<DocumentKey>,
+}
+
+/// Shapes uploaded for a single pass
+struct ShapeBuffers {
+    vertices: Buffer,
+    indices: Buffer,
+    rects: Buffer,
+}
+
+/// Where frames get drawn to
+pub enum RenderTarget {
+    /// A window's surface, presented after each frame
+    Surface(wgpu::Surface),
+    /// A texture that can be read back afterwards, for rendering without a window
+    Offscreen(wgpu::Texture),
+}
+
 pub struct Renderer {
     pub config: wgpu::SurfaceConfiguration,
-    pub surface: wgpu::Surface,
+    pub target: RenderTarget,
     pub surface_format: TextureFormat,
     pub device: wgpu::Device,
     pub render_pipeline: wgpu::RenderPipeline,
+    rect_pipeline: wgpu::RenderPipeline,
     pub queue: wgpu::Queue,
     pub text_system: TextSystem,
+    /// Lays out the banner and badge apart from the document's text, which is only laid out again
+    /// when the document changes
+    overlay_text_renderer: TextRenderer,
+    /// The last frame's document, drawn under the banner and badge
+    document_layer: Option<DocumentLayer>,
+    /// Set when the document changed in a way that its [`DocumentKey`] doesn't show
+    document_dirty: bool,
     pub scroll_y: f32,
+    /// Unquantized scroll offset, so that tiny touchpad deltas still add up
+    precise_scroll_y: f32,
     pub lyon_buffer: VertexBuffers<Vertex, u16>,
+    rect_instances: Vec<RectInstance>,
+    batches: Vec<Batch>,
+    /// A checkbox tick tessellated for the box size and stroke width it's keyed by
+    tick_shape: Option<((u32, u32), VertexBuffers<[f32; 2], u16>)>,
     pub hidpi_scale: f32,
     pub page_width: f32,
     pub image_renderer: ImageRenderer,
     pub theme: Theme,
     pub zoom: f32,
     pub positioner: Positioner,
-    pub element_padding: f32,
+    pub element_spacing: ElementSpacing,
+    /// Evict GPU resources as soon as they scroll offscreen
+    pub low_memory: bool,
+    /// Pin the H1 or H2 of the section being read under the banner
+    pub sticky_headings: bool,
+    /// Draw a line down code blocks this many characters in
+    pub code_wrap_guide: Option<u32>,
+    /// Mark the rows of code that carry on a line too long to fit
+    pub code_wrap_indicator: bool,
+    /// A message pinned to the top of the window over the document
+    pub banner: Option<TextBox>,
+    /// Byte offset into the banner's text to draw a caret at
+    pub banner_caret: Option<usize>,
+    /// A line under the banner that stays up while prompts come and go
+    pub badge: Option<TextBox>,
+    /// The raw markdown shown to the left of the document in split view
+    pub source_pane: Option<TextBox>,
+    /// Text to highlight every occurrence of while searching
+    pub search_query: Option<String>,
+    pub search_options: SearchOptions,
+    /// The search match that was jumped to last, in document coordinates
+    pub current_match: Vec<Rect>,
+    /// Another document shown next to this one in side-by-side mode
+    pub side_pane: Option<Pane>,
+    /// An image expanded over the whole window in place of the document
+    pub lightbox: Option<Image>,
+    pub profile: LayoutProfile,
+    /// Areas on screen to dim this frame
+    veils: Vec<Rect>,
+    /// The veil texture along with the background color it was made for
+    veil_bind_group: Option<(u32, Arc<BindGroup>)>,
 }
 
+/// A document drawn in its own part of the window, with a layout and scroll position apart from
+/// the main document's
+pub struct Pane {
+    pub elements: Vec<Positioned<Element>>,
+    positioner: Positioner,
+    scroll_y: f32,
+    precise_scroll_y: f32,
+}
+
+impl Pane {
+    pub fn new(positioner: Positioner) -> Self {
+        Self {
+            elements: Vec::new(),
+            positioner,
+            scroll_y: 0.,
+            precise_scroll_y: 0.,
+        }
+    }
+}
+
+const BANNER_PADDING: f32 = 8.;
+/// The zoom never goes past these, however it gets set
+pub const MIN_ZOOM: f32 = 0.1;
+pub const MAX_ZOOM: f32 = 10.;
+
 impl Renderer {
     pub const fn screen_height(&self) -> f32 {
         self.positioner.screen_size.1
@@ -67,37 +203,98 @@ impl Renderer {
         hidpi_scale: f32,
         page_width: f32,
         font_opts: FontOptions,
+        wide_gamut: bool,
     ) -> anyhow::Result<Self> {
         let size = window.inner_size();
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
-        });
+        let instance = instance();
         let surface = unsafe {
             instance
                 .create_surface(window)
                 .expect("Could not create surface")
         };
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .context("Failed to find an appropriate adapter")?;
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
-                },
-                None,
-            )
-            .await?;
+        let adapter = request_adapter(&instance, Some(&surface)).await?;
+        let (device, queue) = request_device(&adapter).await?;
+
+        let caps = surface.get_capabilities(&adapter);
+        // wgpu doesn't let us pick the surface's color space, but a float surface is how extended
+        // range output gets exposed where it's supported
+        let formats = || caps.formats.iter().copied();
+        let surface_format = formats()
+            .find(|f| wide_gamut && *f == wgpu::TextureFormat::Rgba16Float)
+            .or_else(|| formats().find(|f| f.is_srgb()))
+            .unwrap_or(caps.formats[0]);
+        tracing::debug!("Using surface format: {surface_format:?}");
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: caps.alpha_modes[0],
+            view_formats: vec![],
+        };
+        surface.configure(&device, &config);
+
+        Ok(Self::with_target(
+            device,
+            queue,
+            RenderTarget::Surface(surface),
+            config,
+            theme,
+            hidpi_scale,
+            page_width,
+            font_opts,
+        ))
+    }
+
+    /// Renders to an offscreen texture of `width`x`height` instead of a window
+    pub async fn headless(
+        (width, height): (u32, u32),
+        theme: Theme,
+        hidpi_scale: f32,
+        page_width: f32,
+        font_opts: FontOptions,
+    ) -> anyhow::Result<Self> {
+        let instance = instance();
+        let adapter = request_adapter(&instance, None).await?;
+        let (device, queue) = request_device(&adapter).await?;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: TextureFormat::Rgba8UnormSrgb,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        let texture = offscreen_texture(&device, &config);
 
+        Ok(Self::with_target(
+            device,
+            queue,
+            RenderTarget::Offscreen(texture),
+            config,
+            theme,
+            hidpi_scale,
+            page_width,
+            font_opts,
+        ))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn with_target(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        target: RenderTarget,
+        config: wgpu::SurfaceConfiguration,
+        theme: Theme,
+        hidpi_scale: f32,
+        page_width: f32,
+        font_opts: FontOptions,
+    ) -> Self {
+        let surface_format = config.format;
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: None,
             source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders/shader.wgsl"))),
@@ -109,14 +306,6 @@ impl Renderer {
             push_constant_ranges: &[],
         });
 
-        let caps = surface.get_capabilities(&adapter);
-        let surface_format = caps
-            .formats
-            .iter()
-            .copied()
-            .find(|f| f.is_srgb())
-            .unwrap_or(caps.formats[0]);
-
         let vertex_buffers = [wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
@@ -142,17 +331,7 @@ impl Renderer {
             multiview: None,
         });
 
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
-            alpha_mode: caps.alpha_modes[0],
-            view_formats: vec![],
-        };
-
-        surface.configure(&device, &config);
+        let rect_pipeline = rect_pipeline(&device, surface_format);
         let image_renderer = ImageRenderer::new(&device, &surface_format);
 
         let font_system = Arc::new(Mutex::new(get_fonts(&font_opts)));
@@ -160,6 +339,8 @@ impl Renderer {
         let mut text_atlas = TextAtlas::new(&device, &queue, surface_format);
         let text_renderer =
             TextRenderer::new(&mut text_atlas, &device, MultisampleState::default(), None);
+        let overlay_text_renderer =
+            TextRenderer::new(&mut text_atlas, &device, MultisampleState::default(), None);
         let text_cache = Arc::new(Mutex::new(TextCache::new()));
         let text_system = TextSystem {
             font_system,
@@ -171,29 +352,75 @@ impl Renderer {
 
         let lyon_buffer: VertexBuffers<Vertex, u16> = VertexBuffers::new();
 
-        let positioner = Positioner::new(window.inner_size().into(), hidpi_scale, page_width, theme.page_margin as f32);
-        Ok(Self {
+        let screen_size = (config.width as f32, config.height as f32);
+        let positioner = Positioner::new(
+            screen_size,
+            hidpi_scale,
+            page_width,
+            theme.page_margin as f32,
+        );
+        Self {
             config,
-            surface,
+            target,
             surface_format,
             device,
             render_pipeline,
+            rect_pipeline,
             queue,
             text_system,
+            overlay_text_renderer,
+            document_layer: None,
+            document_dirty: true,
             scroll_y: 0.,
+            precise_scroll_y: 0.,
             lyon_buffer,
+            rect_instances: Vec::new(),
+            batches: Vec::new(),
+            tick_shape: None,
             hidpi_scale,
             page_width,
             zoom: 1.,
             image_renderer,
             theme,
             positioner,
-            element_padding: DEFAULT_PADDING,
-        })
+            element_spacing: ElementSpacing::default(),
+            low_memory: false,
+            sticky_headings: false,
+            code_wrap_guide: None,
+            code_wrap_indicator: false,
+            banner: None,
+            banner_caret: None,
+            badge: None,
+            source_pane: None,
+            search_query: None,
+            search_options: SearchOptions::default(),
+            current_match: Vec::new(),
+            side_pane: None,
+            lightbox: None,
+            profile: LayoutProfile::Normal,
+            veils: Vec::new(),
+            veil_bind_group: None,
+        }
+    }
+
+    /// Resizes whatever gets drawn to
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.config.width = width;
+        self.config.height = height;
+        self.positioner.screen_size = (width as f32, height as f32);
+        if let Some(pane) = &mut self.side_pane {
+            pane.positioner.screen_size = self.positioner.screen_size;
+        }
+        match &mut self.target {
+            RenderTarget::Surface(surface) => surface.configure(&self.device, &self.config),
+            RenderTarget::Offscreen(texture) => {
+                *texture = offscreen_texture(&self.device, &self.config)
+            }
+        }
     }
 
     fn draw_scrollbar(&mut self) -> anyhow::Result<()> {
-        let scrollbar_width = self.theme.scrollbar_width as f32;
+        let scrollbar_width = self.scrollbar_width();
         // If scrollbar width is 0, hide the scrollbar
         if scrollbar_width == 0.0 {
             return Ok(());
@@ -207,12 +434,12 @@ impl Renderer {
         self.draw_rectangle(
             Rect::new(
                 (
-                    screen_width - scrollbar_width,
+                    screen_width - self.positioner.right_inset() - scrollbar_width,
                     ((self.scroll_y / self.positioner.reserved_height) * screen_height),
                 ),
                 (scrollbar_width, height),
             ),
-            native_color(self.theme.scrollbar_color, &self.surface_format),
+            native_color(self.theme.palette.scrollbar_color, &self.surface_format),
         )?;
         Ok(())
     }
@@ -222,7 +449,204 @@ impl Renderer {
     }
     
     pub fn scrollbar_width(&self) -> f32 {
-        self.theme.scrollbar_width as f32
+        if self.profile.hides_scrollbar() {
+            0.
+        } else {
+            self.theme.scrollbar_width as f32
+        }
+    }
+
+    fn banner_text_bounds(&self) -> Size {
+        let padding = BANNER_PADDING * self.hidpi_scale;
+        ((self.screen_size().0 - 2. * padding).max(0.), f32::INFINITY)
+    }
+
+    /// Height of the banner drawn across the top of the window, if there is one
+    pub fn banner_height(&mut self) -> Option<f32> {
+        let banner = self.banner.as_ref()?;
+        let bounds = self.banner_text_bounds();
+        let text_height = banner.size(&mut self.text_system, bounds, 1.0).1;
+        Some(text_height + 2. * BANNER_PADDING * self.hidpi_scale)
+    }
+
+    fn render_banner(&mut self, text_areas: &mut Vec<CachedTextArea>) -> anyhow::Result<()> {
+        let Some(height) = self.banner_height() else {
+            return Ok(());
+        };
+
+        let screen_width = self.screen_size().0;
+        self.draw_rectangle(
+            Rect::new((0., 0.), (screen_width, height)),
+            native_color(self.theme.palette.quote_block_color, &self.surface_format),
+        )?;
+
+        let padding = BANNER_PADDING * self.hidpi_scale;
+        let bounds = self.banner_text_bounds();
+        let banner = self.banner.as_ref().expect("Banner has a height");
+        text_areas.push(banner.text_areas(
+            &mut self.text_system,
+            (padding, padding),
+            bounds,
+            1.0,
+            0.0,
+        ));
+        if let Some(index) = self.banner_caret {
+            let (x, y) = banner.caret_position(&mut self.text_system, bounds, 1.0, index);
+            let size = (self.hidpi_scale.max(1.), banner.line_height(1.0));
+            self.draw_rectangle(
+                Rect::new((padding + x, padding + y), size),
+                native_color(self.theme.palette.text_color, &self.surface_format),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The bottom of the banner's caret in the window, for input methods to show their candidates
+    /// below
+    pub fn banner_caret_position(&mut self) -> Option<Point> {
+        let index = self.banner_caret?;
+        let bounds = self.banner_text_bounds();
+        let banner = self.banner.as_ref()?;
+        let (x, y) = banner.caret_position(&mut self.text_system, bounds, 1.0, index);
+        let padding = BANNER_PADDING * self.hidpi_scale;
+        Some((padding + x, padding + y + banner.line_height(1.0)))
+    }
+
+    /// Where the badge sits across the window just below the banner, if there is one
+    pub fn badge_bounds(&mut self) -> Option<Rect> {
+        let top = self.banner_height().unwrap_or(0.);
+        let badge = self.badge.as_ref()?;
+        let bounds = self.banner_text_bounds();
+        let text_height = badge.size(&mut self.text_system, bounds, 1.0).1;
+        let height = text_height + 2. * BANNER_PADDING * self.hidpi_scale;
+        Some(Rect::new((0., top), (self.screen_size().0, height)))
+    }
+
+    fn render_badge(&mut self, text_areas: &mut Vec<CachedTextArea>) -> anyhow::Result<()> {
+        let Some(badge_bounds) = self.badge_bounds() else {
+            return Ok(());
+        };
+
+        self.draw_rectangle(
+            badge_bounds,
+            native_color(self.theme.palette.quote_block_color, &self.surface_format),
+        )?;
+
+        let padding = BANNER_PADDING * self.hidpi_scale;
+        let bounds = self.banner_text_bounds();
+        let badge = self.badge.as_ref().expect("Badge has bounds");
+        text_areas.push(badge.text_areas(
+            &mut self.text_system,
+            (padding, badge_bounds.pos.1 + padding),
+            bounds,
+            1.0,
+            0.0,
+        ));
+        Ok(())
+    }
+
+    /// Draws a copy of the heading of the section at the top of the window, just under `top`, with
+    /// a background over the document. The next heading pushes it back up as it scrolls into its
+    /// place. Returns the bottom of what got drawn
+    fn render_sticky_heading(
+        &mut self,
+        elements: &[Positioned<Element>],
+        top: f32,
+        text_areas: &mut Vec<CachedTextArea>,
+    ) -> anyhow::Result<f32> {
+        let Some((heading, next_top)) = fold::sticky_heading(elements, self.scroll_y + top) else {
+            return Ok(top);
+        };
+        let (Element::TextBox(text_box), Some(bounds)) = (&heading.inner, &heading.bounds) else {
+            return Ok(top);
+        };
+
+        let padding = 4. * self.hidpi_scale * self.zoom;
+        let height = bounds.size.1 + 2. * padding;
+        let pushed_up = match next_top {
+            Some(next_top) => (next_top - self.scroll_y - top - height).min(0.),
+            None => 0.,
+        };
+        let strip_top = top + pushed_up;
+        let bottom = strip_top + height;
+        if bottom <= top {
+            return Ok(top);
+        }
+
+        let left = self.positioner.left_inset();
+        let right = self.screen_size().0 - self.positioner.right_inset() - self.scrollbar_width();
+        self.draw_rectangle(
+            Rect::from_min_max((left, top), (right, bottom)),
+            native_color(self.theme.palette.background_color, &self.surface_format),
+        )?;
+        let divider = self.hidpi_scale.max(1.);
+        self.draw_rectangle(
+            Rect::from_min_max((left, (bottom - divider).max(top)), (right, bottom)),
+            native_color(self.theme.palette.quote_block_color, &self.surface_format),
+        )?;
+
+        let pos = (bounds.pos.0, strip_top + padding);
+        let width = (self.positioner.right_edge() - pos.0).max(0.);
+        let text_bounds = (width, f32::INFINITY);
+        let area = text_box.text_areas(&mut self.text_system, pos, text_bounds, self.zoom, 0.);
+        text_areas.push(area.clip_top(top));
+        Ok(bottom)
+    }
+
+    fn source_pane_text_bounds(&self) -> Size {
+        let padding = BANNER_PADDING * self.hidpi_scale;
+        (
+            (self.positioner.left_inset() - 2. * padding).max(0.),
+            f32::INFINITY,
+        )
+    }
+
+    /// Draws the markdown source into the left half of the window
+    ///
+    /// The source scrolls so that the line at the top of the document sits at the top of the
+    /// pane. Documents where no element knows its source lines keep both the same fraction of the
+    /// way through instead
+    fn render_source_pane(
+        &mut self,
+        elements: &[Positioned<Element>],
+        text_areas: &mut Vec<CachedTextArea>,
+    ) -> anyhow::Result<()> {
+        let Some(pane) = self.source_pane.as_ref() else {
+            return Ok(());
+        };
+
+        let padding = BANNER_PADDING * self.hidpi_scale;
+        let bounds = self.source_pane_text_bounds();
+        let (inset, screen_height) = (self.positioner.left_inset(), self.screen_height());
+        let pane_height = pane.size(&mut self.text_system, bounds, 1.0).1 + 2. * padding;
+        let pane_max_scroll = (pane_height - screen_height).max(0.);
+        let pane_scroll = match source_map::line_at(elements, self.scroll_y) {
+            Some(line) => pane.line_top(&mut self.text_system, bounds, 1.0, line.saturating_sub(1)),
+            None => {
+                let max_scroll = (self.positioner.reserved_height - screen_height).max(0.);
+                let fraction = if max_scroll > 0. {
+                    (self.scroll_y / max_scroll).clamp(0., 1.)
+                } else {
+                    0.
+                };
+                fraction * pane_max_scroll
+            }
+        };
+        let pane_scroll = pane_scroll.clamp(0., pane_max_scroll).round();
+
+        self.draw_rectangle(
+            Rect::new((0., 0.), (inset, screen_height)),
+            native_color(self.theme.palette.quote_block_color, &self.surface_format),
+        )?;
+        let pane = self.source_pane.as_ref().expect("Checked above");
+        text_areas.push(pane.text_areas(
+            &mut self.text_system,
+            (padding, padding),
+            bounds,
+            1.0,
+            pane_scroll,
+        ));
+        Ok(())
     }
 
     fn render_elements(
@@ -243,7 +667,11 @@ impl Renderer {
                 break;
             }
 
-            let centering = (screen_size.0 - self.page_width).max(0.) / 2.;
+            let right_edge = self.positioner.right_edge();
+            if self.profile.dims(&element.inner) {
+                let veil = self.veil_bounds(&element.inner, scrolled_pos, *size);
+                self.veils.push(veil);
+            }
 
             match &element.inner {
                 Element::TextBox(text_box) => {
@@ -254,10 +682,7 @@ impl Renderer {
                         scrolled_pos.0 += box_size * 1.5;
                     }
 
-                    let bounds = (
-                        (screen_size.0 - pos.0 - self.positioner.page_margin - centering).max(0.),
-                        f32::INFINITY,
-                    );
+                    let bounds = ((right_edge - pos.0).max(0.), f32::INFINITY);
 
                     let areas = text_box.text_areas(
                         &mut self.text_system,
@@ -267,11 +692,22 @@ impl Renderer {
                         self.scroll_y,
                     );
                     text_areas.push(areas.clone());
+                    let gutter_width = text_box.gutter_width(&mut self.text_system, self.zoom);
+                    if let Some(gutter) = text_box.gutter() {
+                        let gutter_pos = (pos.0 - gutter_width, pos.1);
+                        text_areas.push(gutter.text_areas(
+                            &mut self.text_system,
+                            gutter_pos,
+                            (gutter_width, f32::INFINITY),
+                            self.zoom,
+                            self.scroll_y,
+                        ));
+                    }
                     if text_box.is_code_block || text_box.is_quote_block.is_some() {
                         let color = if let Some(bg_color) = text_box.background_color {
                             bg_color
                         } else {
-                            native_color(self.theme.quote_block_color, &self.surface_format)
+                            native_color(self.theme.palette.quote_block_color, &self.surface_format)
                         };
 
                         let mut min = (
@@ -289,8 +725,32 @@ impl Renderer {
                         if let Some(nest) = text_box.is_quote_block {
                             min.0 -= (nest - 1) as f32 * self.positioner.page_margin / 2.;
                         }
-                        if min.0 < screen_size.0 - self.positioner.page_margin - centering {
+                        min.0 -= gutter_width;
+                        if min.0 < right_edge {
                             self.draw_rectangle(Rect::from_min_max(min, max), color)?;
+                            if let Some(badge) = text_box.language_badge() {
+                                let unbounded = (f32::INFINITY, f32::INFINITY);
+                                let scale = self.hidpi_scale * self.zoom;
+                                let width =
+                                    badge.size(&mut self.text_system, unbounded, self.zoom).0;
+                                // Tucked into the top right corner of the background
+                                let badge_pos = (
+                                    max.0.min(right_edge) - width - 4. * scale,
+                                    pos.1 - 3. * scale,
+                                );
+                                text_areas.push(badge.text_areas(
+                                    &mut self.text_system,
+                                    badge_pos,
+                                    unbounded,
+                                    self.zoom,
+                                    self.scroll_y,
+                                ));
+                            }
+                            if text_box.is_code_block {
+                                let background = Rect::from_min_max(min, max);
+                                self.draw_wrap_guides(text_box, pos, bounds, background)?;
+                                self.push_wrap_indicators(text_box, pos, bounds, &mut text_areas);
+                            }
                         }
                     }
                     if let Some(nest) = text_box.is_quote_block {
@@ -301,17 +761,16 @@ impl Renderer {
                                     - 10.
                                     - 5. * self.hidpi_scale * self.zoom
                                     - nest_indent)
-                                    .min(screen_size.0 - self.positioner.page_margin - centering),
+                                    .min(right_edge),
                                 scrolled_pos.1,
                             );
                             let max = (
-                                (scrolled_pos.0 - 10. - nest_indent)
-                                    .min(screen_size.0 - self.positioner.page_margin - centering),
+                                (scrolled_pos.0 - 10. - nest_indent).min(right_edge),
                                 min.1 + size.1 + 5. * self.hidpi_scale * self.zoom,
                             );
                             self.draw_rectangle(
                                 Rect::from_min_max(min, max),
-                                native_color(self.theme.select_color, &self.surface_format),
+                                self.selection_color(),
                             )?;
                         }
                     }
@@ -325,22 +784,22 @@ impl Renderer {
                             scrolled_pos.0 + box_size - box_size * 1.5,
                             scrolled_pos.1 + line_height / 2. + box_size / 2.,
                         );
-                        if max.0 < screen_size.0 - self.positioner.page_margin - centering {
+                        if max.0 < right_edge {
                             if is_checked {
                                 self.draw_rectangle(
                                     Rect::from_min_max(min, max),
-                                    native_color(self.theme.checkbox_color, &self.surface_format),
+                                    native_color(self.theme.palette.checkbox_color, &self.surface_format),
                                 )?;
                                 self.draw_tick(
                                     min,
                                     box_size,
-                                    native_color(self.theme.text_color, &self.surface_format),
+                                    native_color(self.theme.palette.text_color, &self.surface_format),
                                     2. * self.hidpi_scale * self.zoom,
                                 )?;
                             }
                             self.stroke_rectangle(
                                 Rect::from_min_max(min, max),
-                                native_color(self.theme.text_color, &self.surface_format),
+                                native_color(self.theme.palette.text_color, &self.surface_format),
                                 1. * self.hidpi_scale * self.zoom,
                             )?;
                         }
@@ -356,6 +815,7 @@ impl Renderer {
                         let max = (line.max.0, line.max.1 + 2. * self.hidpi_scale * self.zoom);
                         self.draw_rectangle(Rect::from_min_max(min, max), line.color)?;
                     }
+                    self.draw_search_highlights(text_box, pos, bounds)?;
                     if let Some(selection_rects) = text_box.render_selection(
                         &mut self.text_system,
                         pos,
@@ -369,16 +829,13 @@ impl Renderer {
                                     (rect.pos.0, rect.pos.1 - self.scroll_y),
                                     (rect.max().0, rect.max().1 - self.scroll_y),
                                 ),
-                                native_color(self.theme.select_color, &self.surface_format),
+                                self.selection_color(),
                             )?;
                         }
                     }
                 }
                 Element::Table(table) => {
-                    let bounds = (
-                        (screen_size.0 - pos.0 - self.positioner.page_margin - centering).max(0.),
-                        f32::INFINITY,
-                    );
+                    let bounds = ((right_edge - pos.0).max(0.), f32::INFINITY);
                     let layout = table.layout(
                         &mut self.text_system,
                         &mut self.positioner.taffy,
@@ -396,6 +853,11 @@ impl Renderer {
                             self.scroll_y,
                         ));
 
+                        self.draw_search_highlights(
+                            caption,
+                            (pos.0 + caption_layout.location.x, pos.1 + caption_layout.location.y),
+                            (caption_layout.size.width, caption_layout.size.height),
+                        )?;
                         if let Some(selection_rects) = caption.render_selection(
                             &mut self.text_system,
                             (pos.0 + caption_layout.location.x, pos.1 + caption_layout.location.y),
@@ -409,15 +871,30 @@ impl Renderer {
                                         (rect.pos.0, rect.pos.1 - self.scroll_y),
                                         (rect.max().0, rect.max().1 - self.scroll_y),
                                     ),
-                                    native_color(
-                                        self.theme.select_color,
-                                        &self.surface_format,
-                                    ),
+                                    self.selection_color(),
                                 )?;
                             }
                         }
                     }
 
+                    let header = table.rows.first().map(Vec::as_slice).unwrap_or_default();
+                    for (col, label_layout) in &layout.labels {
+                        if let Some(label) = header.get(*col) {
+                            text_areas.push(label.text_areas(
+                                &mut self.text_system,
+                                (pos.0 + label_layout.location.x, pos.1 + label_layout.location.y),
+                                (label_layout.size.width, f32::MAX),
+                                self.zoom,
+                                self.scroll_y,
+                            ));
+                            self.draw_search_highlights(
+                                label,
+                                (pos.0 + label_layout.location.x, pos.1 + label_layout.location.y),
+                                (label_layout.size.width, label_layout.size.height),
+                            )?;
+                        }
+                    }
+
                     for (row, node_row) in layout.rows.iter().enumerate() {
                         for (col, node) in node_row.iter().enumerate() {
                             if let Some(row) = table.rows.get(row) {
@@ -430,6 +907,11 @@ impl Renderer {
                                         self.scroll_y,
                                     ));
 
+                                    self.draw_search_highlights(
+                                        text_box,
+                                        (pos.0 + node.location.x, pos.1 + node.location.y),
+                                        (node.size.width, node.size.height),
+                                    )?;
                                     if let Some(selection_rects) = text_box.render_selection(
                                         &mut self.text_system,
                                         (pos.0 + node.location.x, pos.1 + node.location.y),
@@ -443,10 +925,7 @@ impl Renderer {
                                                     (rect.pos.0, rect.pos.1 - self.scroll_y),
                                                     (rect.max().0, rect.max().1 - self.scroll_y),
                                                 ),
-                                                native_color(
-                                                    self.theme.select_color,
-                                                    &self.surface_format,
-                                                ),
+                                                self.selection_color(),
                                             )?;
                                         }
                                     }
@@ -465,7 +944,7 @@ impl Renderer {
                             .unwrap_or(0.);
                         {
                             let min = (
-                                scrolled_pos.0.max(self.positioner.page_margin + centering),
+                                scrolled_pos.0.max(self.positioner.left_edge()),
                                 scrolled_pos.1 + y,
                             );
                             let max = (
@@ -474,27 +953,43 @@ impl Renderer {
                             );
                             self.draw_rectangle(
                                 Rect::from_min_max(min, max),
-                                native_color(self.theme.text_color, &self.surface_format),
+                                native_color(self.theme.palette.text_color, &self.surface_format),
                             )?;
                         }
                     }
                 }
+                Element::Math(math) => {
+                    let layout = math.layout(&mut self.text_system, self.zoom);
+                    for (offset, text_box) in &layout.pieces {
+                        text_areas.push(text_box.text_areas(
+                            &mut self.text_system,
+                            (pos.0 + offset.0, pos.1 + offset.1),
+                            (f32::INFINITY, f32::INFINITY),
+                            self.zoom,
+                            self.scroll_y,
+                        ));
+                    }
+                    for rule in layout.rules {
+                        let min = (scrolled_pos.0 + rule.pos.0, scrolled_pos.1 + rule.pos.1);
+                        self.draw_rectangle(Rect::new(min, rule.size), math.color)?;
+                    }
+                }
                 Element::Image(_) => {}
                 Element::Spacer(spacer) => {
                     if spacer.visible {
                         self.draw_rectangle(
                             Rect::new(
                                 (
-                                    self.positioner.page_margin + centering,
+                                    self.positioner.left_edge(),
                                     scrolled_pos.1 + size.1 / 2.
                                         - 2. * self.hidpi_scale * self.zoom,
                                 ),
                                 (
-                                    screen_size.0 - 2. * (self.positioner.page_margin + centering),
+                                    right_edge - self.positioner.left_edge(),
                                     2. * self.hidpi_scale * self.zoom,
                                 ),
                             ),
-                            native_color(self.theme.text_color, &self.surface_format),
+                            native_color(self.theme.palette.text_color, &self.surface_format),
                         )?;
                     }
                 }
@@ -510,7 +1005,7 @@ impl Renderer {
                                 bounds.pos.1 + bounds.size.1 / 2. - self.scroll_y,
                             ),
                             10.,
-                            native_color(self.theme.text_color, &self.surface_format),
+                            native_color(self.theme.palette.text_color, &self.surface_format),
                             *section.hidden.borrow(),
                         )?;
                         text_areas.append(
@@ -538,6 +1033,67 @@ impl Renderer {
         Ok(text_areas)
     }
 
+    /// Draws the `code_wrap_guide` line down a code block's `background`, which is in window
+    /// coordinates, if the block is wide enough to reach it
+    fn draw_wrap_guides(
+        &mut self,
+        text_box: &TextBox,
+        pos: Point,
+        bounds: Size,
+        background: Rect,
+    ) -> anyhow::Result<()> {
+        let Some(column) = self.code_wrap_guide else {
+            return Ok(());
+        };
+        let Some(char_width) = text_box.code_char_width(&mut self.text_system, self.zoom) else {
+            return Ok(());
+        };
+
+        let x = pos.0 + column as f32 * char_width;
+        let right = (pos.0 + bounds.0).min(background.max().0);
+        if x >= right {
+            return Ok(());
+        }
+        let [r, g, b, a] = text_box.texts[0].default_color;
+        let width = self.hidpi_scale.max(1.);
+        let guide = Rect::new((x, background.pos.1), (width, background.size.1));
+        self.draw_rectangle(guide, [r, g, b, a * 0.25])
+    }
+
+    /// Puts a `↪` just left of each row of a code block that carries on a line that was too long
+    /// to fit
+    fn push_wrap_indicators(
+        &mut self,
+        text_box: &TextBox,
+        pos: Point,
+        bounds: Size,
+        text_areas: &mut Vec<CachedTextArea>,
+    ) {
+        if !self.code_wrap_indicator {
+            return;
+        }
+        let Some(indicator) = text_box.wrap_indicator() else {
+            return;
+        };
+
+        let unbounded = (f32::INFINITY, f32::INFINITY);
+        let (width, _) = indicator.size(&mut self.text_system, unbounded, self.zoom);
+        let line_height = text_box.line_height(self.zoom);
+        // Centered on the row since it's smaller than the code
+        let offset = (line_height - indicator.line_height(self.zoom)) / 2.;
+        let x = pos.0 - width - self.hidpi_scale * self.zoom;
+        for row in text_box.wrapped_rows(&mut self.text_system, bounds, self.zoom) {
+            let y = pos.1 + row as f32 * line_height + offset;
+            text_areas.push(indicator.text_areas(
+                &mut self.text_system,
+                (x, y),
+                unbounded,
+                self.zoom,
+                self.scroll_y,
+            ));
+        }
+    }
+
     fn draw_hidden_marker(
         &mut self,
         pos: Point,
@@ -558,51 +1114,231 @@ impl Renderer {
                 point(pos.0 - size, pos.1 + size / 2., self.screen_size()).into(),
             ]
         };
-        let triangle = Polygon {
-            points: &points,
-            closed: true,
+        self.push_mesh(&points, &[0, 1, 2], color)
+    }
+
+    fn selection_color(&self) -> [f32; 4] {
+        let palette = &self.theme.palette;
+        native_color_alpha(
+            palette.select_color,
+            palette.select_alpha,
+            &self.surface_format,
+        )
+    }
+
+    /// Marks every match of the search query within a text box laid out at `pos` (in document
+    /// coordinates)
+    fn draw_search_highlights(
+        &mut self,
+        text_box: &TextBox,
+        pos: Point,
+        bounds: Size,
+    ) -> anyhow::Result<()> {
+        let Some(query) = self.search_query.as_deref() else {
+            return Ok(());
         };
-        let mut fill_tessellator = FillTessellator::new();
-        fill_tessellator.tessellate_polygon(
-            triangle,
-            &FillOptions::default(),
-            &mut BuffersBuilder::new(&mut self.lyon_buffer, |vertex: FillVertex| Vertex {
-                pos: [vertex.position().x, vertex.position().y, 0.0],
+        let rects = text_box.search_highlights(
+            &mut self.text_system,
+            pos,
+            bounds,
+            self.zoom,
+            query,
+            self.search_options,
+        );
+        let palette = &self.theme.palette;
+        let color = native_color_alpha(
+            palette.search_color,
+            palette.search_alpha,
+            &self.surface_format,
+        );
+        for rect in rects.into_iter().flatten() {
+            self.draw_rectangle(
+                Rect::from_min_max(
+                    (rect.pos.0, rect.pos.1 - self.scroll_y),
+                    (rect.max().0, rect.max().1 - self.scroll_y),
+                ),
                 color,
-            }),
-        )?;
+            )?;
+        }
         Ok(())
     }
 
-    fn draw_rectangle(&mut self, rect: Rect, color: [f32; 4]) -> anyhow::Result<()> {
-        let min = point(rect.pos.0, rect.pos.1, self.screen_size());
-        let max = point(rect.max().0, rect.max().1, self.screen_size());
-        let mut fill_tessellator = FillTessellator::new();
-        fill_tessellator.tessellate_rectangle(
-            &Box2D::new(Point2D::from(min), Point2D::from(max)),
-            &FillOptions::default(),
-            &mut BuffersBuilder::new(&mut self.lyon_buffer, |vertex: FillVertex| Vertex {
-                pos: [vertex.position().x, vertex.position().y, 0.0],
+    fn draw_current_match(&mut self) -> anyhow::Result<()> {
+        let palette = &self.theme.palette;
+        let color = native_color_alpha(
+            palette.match_color,
+            palette.match_alpha,
+            &self.surface_format,
+        );
+        for rect in self.current_match.clone() {
+            self.draw_rectangle(
+                Rect::from_min_max(
+                    (rect.pos.0, rect.pos.1 - self.scroll_y),
+                    (rect.max().0, rect.max().1 - self.scroll_y),
+                ),
                 color,
-            }),
-        )?;
+            )?;
+        }
         Ok(())
     }
 
-    fn stroke_rectangle(&mut self, rect: Rect, color: [f32; 4], width: f32) -> anyhow::Result<()> {
-        let mut stroke_tessellator = StrokeTessellator::new();
-        let screen_size = self.screen_size();
-        stroke_tessellator.tessellate_rectangle(
-            &Box2D::new(Point2D::from(rect.pos), Point2D::from(rect.max())),
-            &StrokeOptions::default().with_line_width(width),
-            &mut BuffersBuilder::new(&mut self.lyon_buffer, |vertex: StrokeVertex| {
-                let point = point(vertex.position().x, vertex.position().y, screen_size);
-                Vertex {
-                    pos: [point[0], point[1], 0.0],
-                    color,
+    /// Every match of the search query in document order, with a box per line it spans. Laid out
+    /// the same way [`Self::render_elements()`] draws the text
+    pub fn search_matches(
+        &mut self,
+        elements: &[Positioned<Element>],
+    ) -> anyhow::Result<Vec<Vec<Rect>>> {
+        let Some(query) = self.search_query.clone() else {
+            return Ok(Vec::new());
+        };
+        let mut matches = Vec::new();
+        let right_edge = self.positioner.right_edge();
+        for element in elements {
+            let Rect { mut pos, .. } = element.bounds.as_ref().context("Element not positioned")?;
+            let mut text_boxes = Vec::new();
+            match &element.inner {
+                Element::TextBox(text_box) => {
+                    if text_box.is_checkbox.is_some() {
+                        pos.0 += text_box.font_size * self.hidpi_scale * self.zoom * 0.75 * 1.5;
+                    }
+                    let bounds = ((right_edge - pos.0).max(0.), f32::INFINITY);
+                    text_boxes.push((text_box, pos, bounds));
                 }
-            }),
-        )?;
+                Element::Table(table) => {
+                    let bounds = ((right_edge - pos.0).max(0.), f32::INFINITY);
+                    let layout = table.layout(
+                        &mut self.text_system,
+                        &mut self.positioner.taffy,
+                        bounds,
+                        self.zoom,
+                    )?;
+                    let placed = |node: &taffy::prelude::Layout| {
+                        (
+                            (pos.0 + node.location.x, pos.1 + node.location.y),
+                            (node.size.width, node.size.height),
+                        )
+                    };
+                    if let (Some(caption), Some(node)) = (&table.caption, &layout.caption_layout) {
+                        let (pos, bounds) = placed(node);
+                        text_boxes.push((caption, pos, bounds));
+                    }
+                    let header = table.rows.first().map(Vec::as_slice).unwrap_or_default();
+                    for (col, node) in &layout.labels {
+                        if let Some(label) = header.get(*col) {
+                            let (pos, bounds) = placed(node);
+                            text_boxes.push((label, pos, bounds));
+                        }
+                    }
+                    for (row, node_row) in layout.rows.iter().enumerate() {
+                        for (col, node) in node_row.iter().enumerate() {
+                            if let Some(text_box) = table.rows.get(row).and_then(|r| r.get(col)) {
+                                let (pos, bounds) = placed(node);
+                                text_boxes.push((text_box, pos, bounds));
+                            }
+                        }
+                    }
+                }
+                Element::Row(row) => matches.append(&mut self.search_matches(&row.elements)?),
+                Element::Section(section) => {
+                    if let Some(ref summary) = *section.summary {
+                        matches.append(&mut self.search_matches(std::slice::from_ref(summary))?);
+                    }
+                    if !*section.hidden.borrow() {
+                        matches.append(&mut self.search_matches(&section.elements)?);
+                    }
+                }
+                Element::Math(_) | Element::Image(_) | Element::Spacer(_) => {}
+            }
+            for (text_box, pos, bounds) in text_boxes {
+                matches.append(&mut text_box.search_highlights(
+                    &mut self.text_system,
+                    pos,
+                    bounds,
+                    self.zoom,
+                    &query,
+                    self.search_options,
+                ));
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Adds triangles with corners already in clip space to the shapes for this frame
+    fn push_mesh(
+        &mut self,
+        points: &[[f32; 2]],
+        indices: &[u16],
+        color: [f32; 4],
+    ) -> anyhow::Result<()> {
+        let base = self.lyon_buffer.vertices.len();
+        let base = u16::try_from(base)
+            .ok()
+            .filter(|base| base.checked_add(points.len() as u16).is_some())
+            .context("Too many vertices to draw in one frame")?;
+        self.lyon_buffer
+            .vertices
+            .extend(points.iter().map(|&[x, y]| Vertex {
+                pos: [x, y, 0.0],
+                color,
+            }));
+        let start = self.lyon_buffer.indices.len() as u32;
+        self.lyon_buffer
+            .indices
+            .extend(indices.iter().map(|index| base + index));
+        let end = self.lyon_buffer.indices.len() as u32;
+        match self.batches.last_mut() {
+            Some(Batch::Mesh(range)) => range.end = end,
+            _ => self.batches.push(Batch::Mesh(start..end)),
+        }
+        Ok(())
+    }
+
+    /// Adds a filled rectangle, which is in window coordinates, to the shapes for this frame
+    fn push_quad(&mut self, rect: Rect, color: [f32; 4]) -> anyhow::Result<()> {
+        let screen_size = self.screen_size();
+        let [left, top] = point(rect.pos.0, rect.pos.1, screen_size);
+        let [right, bottom] = point(rect.max().0, rect.max().1, screen_size);
+        let index = self.rect_instances.len() as u32;
+        self.rect_instances.push(RectInstance {
+            min: [left, top],
+            max: [right, bottom],
+            color,
+        });
+        match self.batches.last_mut() {
+            Some(Batch::Rects(range)) => range.end = index + 1,
+            _ => self.batches.push(Batch::Rects(index..index + 1)),
+        }
+        Ok(())
+    }
+
+    fn draw_rectangle(&mut self, rect: Rect, color: [f32; 4]) -> anyhow::Result<()> {
+        self.push_quad(rect.snap_to_pixels(), color)
+    }
+
+    fn stroke_rectangle(&mut self, rect: Rect, color: [f32; 4], width: f32) -> anyhow::Result<()> {
+        // Strokes straddle their path, so odd widths sit on pixel centers to stay crisp
+        let rect = rect.snap_to_pixels();
+        let offset = if width.round() as i32 % 2 == 1 { 0.5 } else { 0. };
+        let (min, max) = (rect.pos, rect.max());
+        let half = width / 2.;
+        let (outer_min, outer_max) = (
+            (min.0 + offset - half, min.1 + offset - half),
+            (max.0 + offset + half, max.1 + offset + half),
+        );
+        let (inner_min, inner_max) = (
+            (min.0 + offset + half, min.1 + offset + half),
+            (max.0 + offset - half, max.1 + offset - half),
+        );
+        // The four sides, with the corners going to the top and bottom ones like mitered joins
+        let sides = [
+            (outer_min, (outer_max.0, inner_min.1)),
+            ((outer_min.0, inner_max.1), outer_max),
+            ((outer_min.0, inner_min.1), (inner_min.0, inner_max.1)),
+            ((inner_max.0, inner_min.1), (outer_max.0, inner_max.1)),
+        ];
+        for (from, to) in sides {
+            self.push_quad(Rect::from_min_max(from, to), color)?;
+        }
         Ok(())
     }
 
@@ -613,26 +1349,21 @@ impl Renderer {
         color: [f32; 4],
         width: f32,
     ) -> anyhow::Result<()> {
+        // Every checkbox is the same size, so the tick only gets tessellated when that changes
+        let key = (box_size.to_bits(), width.to_bits());
+        let tick = match self.tick_shape.take() {
+            Some((cached, tick)) if cached == key => tick,
+            _ => tessellate_tick(box_size, width)?,
+        };
         let screen_size = self.screen_size();
-        let mut stroke_tessellator = StrokeTessellator::new();
-        let stroke_opts = StrokeOptions::default().with_line_width(width);
-        let mut vertex_builder =
-            BuffersBuilder::new(&mut self.lyon_buffer, |vertex: StrokeVertex| {
-                let point = point(vertex.position().x, vertex.position().y, screen_size);
-                Vertex {
-                    pos: [point[0], point[1], 0.0],
-                    color,
-                }
-            });
-        let mut builder = stroke_tessellator.builder(&stroke_opts, &mut vertex_builder);
-
-        // Build a simple path.
-        builder.begin((pos.0 + box_size * 0.2, pos.1 + box_size * 0.5).into());
-        builder.line_to((pos.0 + box_size * 0.4, pos.1 + box_size * 0.7).into());
-        builder.line_to((pos.0 + box_size * 0.8, pos.1 + box_size * 0.2).into());
-        builder.end(false);
-        builder.build()?;
-        Ok(())
+        let points: Vec<_> = tick
+            .vertices
+            .iter()
+            .map(|&[x, y]| point(pos.0 + x, pos.1 + y, screen_size))
+            .collect();
+        let pushed = self.push_mesh(&points, &tick.indices, color);
+        self.tick_shape = Some((key, tick));
+        pushed
     }
 
     fn image_bindgroups(
@@ -657,6 +1388,8 @@ impl Renderer {
                             &self.queue,
                             &self.image_renderer.sampler,
                             &self.image_renderer.bindgroup_layout,
+                            self.image_renderer.texture_format,
+                            self.image_renderer.max_texture_size,
                         )
                     }) {
                         let vertex_buf =
@@ -675,6 +1408,8 @@ impl Renderer {
                                     &self.queue,
                                     &self.image_renderer.sampler,
                                     &self.image_renderer.bindgroup_layout,
+                                    self.image_renderer.texture_format,
+                                    self.image_renderer.max_texture_size,
                                 )
                             }) {
                                 let vertex_buf = ImageRenderer::vertex_buf(
@@ -702,6 +1437,8 @@ impl Renderer {
                                     &self.queue,
                                     &self.image_renderer.sampler,
                                     &self.image_renderer.bindgroup_layout,
+                                    self.image_renderer.texture_format,
+                                    self.image_renderer.max_texture_size,
                                 )
                             }) {
                                 let vertex_buf = ImageRenderer::vertex_buf(
@@ -721,136 +1458,770 @@ impl Renderer {
         bind_groups
     }
 
-    pub fn redraw(
+    /// The area that dims an element, which covers any background drawn past its bounds
+    fn veil_bounds(&self, element: &Element, pos: Point, size: Size) -> Rect {
+        let scale = self.hidpi_scale * self.zoom;
+        let left_edge = self.positioner.left_edge();
+        let right_edge = self.positioner.right_edge();
+        match element {
+            Element::TextBox(text_box)
+                if text_box.is_code_block || text_box.is_quote_block.is_some() =>
+            {
+                let min = (pos.0.min(left_edge) - 10. - 5. * scale, pos.1 - 5. * scale);
+                let max = (right_edge.max(pos.0 + size.0) + 10., pos.1 + size.1 + 7. * scale);
+                Rect::from_min_max(min, max)
+            }
+            // Rules get drawn across the whole page
+            Element::Spacer(_) => {
+                Rect::from_min_max((left_edge, pos.1), (right_edge, pos.1 + size.1))
+            }
+            _ => Rect::new(pos, size),
+        }
+    }
+
+    /// Translucent quads in the background color over everything that the layout profile dims.
+    /// They go through the image pipeline since that's the one that blends
+    fn veil_bindgroups(&mut self) -> Vec<(Arc<BindGroup>, Buffer)> {
+        let veils = std::mem::take(&mut self.veils);
+        if veils.is_empty() {
+            return Vec::new();
+        }
+
+        let color = self.theme.palette.background_color;
+        let bind_group = match &self.veil_bind_group {
+            Some((veil_color, bind_group)) if *veil_color == color => bind_group.clone(),
+            _ => {
+                let data = ImageData::solid(layout_profile::veil_color(color));
+                let mut veil = Image::from_image_data(Arc::new(Mutex::new(Some(data))), 1.);
+                let Some(bind_group) = veil.create_bind_group(
+                    &self.device,
+                    &self.queue,
+                    &self.image_renderer.sampler,
+                    &self.image_renderer.bindgroup_layout,
+                    self.image_renderer.texture_format,
+                    self.image_renderer.max_texture_size,
+                ) else {
+                    return Vec::new();
+                };
+                self.veil_bind_group = Some((color, bind_group.clone()));
+                bind_group
+            }
+        };
+
+        let screen_size = self.screen_size();
+        veils
+            .into_iter()
+            .map(|veil| {
+                let vertex_buf =
+                    ImageRenderer::vertex_buf(&self.device, veil.pos, veil.size, screen_size);
+                (bind_group.clone(), vertex_buf)
+            })
+            .collect()
+    }
+
+    fn lightbox_bindgroup(&mut self) -> Option<(Arc<BindGroup>, Buffer)> {
+        let screen_size = self.screen_size();
+        let image = self.lightbox.as_mut()?;
+        let Rect { pos, size } = image.lightbox_bounds(screen_size, self.zoom)?;
+        let bind_group = image.bind_group.clone().or_else(|| {
+            image.create_bind_group(
+                &self.device,
+                &self.queue,
+                &self.image_renderer.sampler,
+                &self.image_renderer.bindgroup_layout,
+                self.image_renderer.texture_format,
+                self.image_renderer.max_texture_size,
+            )
+        })?;
+        let vertex_buf = ImageRenderer::vertex_buf(&self.device, pos, size, screen_size);
+        Some((bind_group, vertex_buf))
+    }
+
+    // Image data is kept compressed in memory, so the textures can be cheaply recreated once they
+    // scroll back into view
+    fn drop_offscreen_textures(&self, elements: &mut [Positioned<Element>]) {
+        let screen_height = self.screen_height();
+        let is_offscreen = |bounds: &Option<Rect>| {
+            bounds.as_ref().map_or(true, |Rect { pos, size }| {
+                let y = pos.1 - self.scroll_y;
+                y + size.1 <= 0. || y >= screen_height
+            })
+        };
+
+        for element in elements.iter_mut() {
+            let offscreen = is_offscreen(&element.bounds);
+            match &mut element.inner {
+                Element::Image(image) if offscreen => image.bind_group = None,
+                Element::Row(row) => self.drop_offscreen_textures(&mut row.elements),
+                Element::Section(section) => self.drop_offscreen_textures(&mut section.elements),
+                _ => {}
+            }
+        }
+    }
+
+    /// Lets the next frame know the document changed in a way that [`DocumentKey`] can't see, so
+    /// that its layer gets drawn again instead of reused
+    pub fn invalidate_document(&mut self) {
+        self.document_dirty = true;
+    }
+
+    fn document_key(&self, elements: &[Positioned<Element>]) -> DocumentKey {
+        DocumentKey {
+            elements: (elements.as_ptr() as usize, elements.len()),
+            reserved_height: self.positioner.reserved_height,
+            scroll_y: self.scroll_y,
+            zoom: self.zoom,
+            screen_size: (self.config.width, self.config.height),
+            search_query: self.search_query.clone(),
+            search_options: self.search_options,
+            current_match: self.current_match.clone(),
+            profile: self.profile,
+            lightbox: self.lightbox.is_some(),
+            side_pane: self.side_pane.as_ref().map(|pane| {
+                (
+                    pane.elements.len(),
+                    pane.positioner.reserved_height,
+                    pane.scroll_y,
+                )
+            }),
+        }
+    }
+
+    /// A texture the size of the window for the document to get drawn into
+    fn document_layer(&self) -> DocumentLayer {
+        let size = (self.config.width.max(1), self.config.height.max(1));
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Document Layer"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            // Matching the target means copying the layer over is a straight round trip
+            format: self.surface_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.image_renderer.bindgroup_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.image_renderer.sampler),
+                },
+            ],
+            label: Some("Document Layer Bind Group"),
+        });
+        DocumentLayer {
+            view,
+            bind_group: Arc::new(bind_group),
+            size,
+            key: None,
+        }
+    }
+
+    fn clear_shapes(&mut self) {
+        self.lyon_buffer.indices.clear();
+        self.lyon_buffer.vertices.clear();
+        self.rect_instances.clear();
+        self.batches.clear();
+    }
+
+    /// Uploads the shapes pushed since they were last cleared
+    fn shape_buffers(&self) -> ShapeBuffers {
+        let buffer = |label: &str, contents: &[u8], usage: wgpu::BufferUsages| {
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents,
+                    usage,
+                })
+        };
+        ShapeBuffers {
+            vertices: buffer(
+                "Vertex Buffer",
+                bytemuck::cast_slice(&self.lyon_buffer.vertices),
+                wgpu::BufferUsages::VERTEX,
+            ),
+            indices: buffer(
+                "Index Buffer",
+                bytemuck::cast_slice(&self.lyon_buffer.indices),
+                wgpu::BufferUsages::INDEX,
+            ),
+            rects: buffer(
+                "Rectangle Instance Buffer",
+                bytemuck::cast_slice(&self.rect_instances),
+                wgpu::BufferUsages::VERTEX,
+            ),
+        }
+    }
+
+    /// Draws rectangles and lyon elements in the order they were pushed
+    fn draw_shapes<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, buffers: &'a ShapeBuffers) {
+        for batch in &self.batches {
+            match batch {
+                Batch::Mesh(indices) => {
+                    rpass.set_pipeline(&self.render_pipeline);
+                    rpass.set_vertex_buffer(0, buffers.vertices.slice(..));
+                    rpass.set_index_buffer(buffers.indices.slice(..), IndexFormat::Uint16);
+                    rpass.draw_indexed(indices.clone(), 0, 0..1);
+                }
+                Batch::Rects(instances) => {
+                    rpass.set_pipeline(&self.rect_pipeline);
+                    rpass.set_vertex_buffer(0, buffers.rects.slice(..));
+                    rpass.draw(0..6, instances.clone());
+                }
+            }
+        }
+    }
+
+    fn draw_images<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        bindgroups: &'a [(Arc<BindGroup>, Buffer)],
+    ) {
+        if bindgroups.is_empty() {
+            return;
+        }
+        rpass.set_pipeline(&self.image_renderer.render_pipeline);
+        rpass.set_index_buffer(self.image_renderer.index_buf.slice(..), IndexFormat::Uint16);
+        for (bindgroup, vertex_buf) in bindgroups {
+            rpass.set_bind_group(0, bindgroup, &[]);
+            rpass.set_vertex_buffer(0, vertex_buf.slice(..));
+            rpass.draw_indexed(0..6, 0, 0..1);
+        }
+    }
+
+    /// Swaps the glyph atlas for an empty one
+    ///
+    /// Trimming only marks glyphs as unused, and the atlas texture never shrinks back down after
+    /// growing, so freeing it means starting over. Both text renderers get prepared again before
+    /// their next draw
+    fn reset_text_atlas(&mut self) {
+        let mut text_atlas = TextAtlas::new(&self.device, &self.queue, self.surface_format);
+        self.text_system.text_renderer = TextRenderer::new(
+            &mut text_atlas,
+            &self.device,
+            MultisampleState::default(),
+            None,
+        );
+        self.overlay_text_renderer = TextRenderer::new(
+            &mut text_atlas,
+            &self.device,
+            MultisampleState::default(),
+            None,
+        );
+        self.text_system.text_atlas = text_atlas;
+    }
+
+    /// Lays out `cached_text_areas` for the document's text renderer, or the overlay's
+    fn prepare_text(
+        &mut self,
+        cached_text_areas: &[CachedTextArea],
+        overlay: bool,
+    ) -> anyhow::Result<()> {
+        let text_cache = self.text_system.text_cache.lock();
+        let text_areas: Vec<TextArea> = cached_text_areas
+            .iter()
+            .map(|c| c.text_area(&text_cache))
+            .collect();
+        let text_renderer = if overlay {
+            &mut self.overlay_text_renderer
+        } else {
+            &mut self.text_system.text_renderer
+        };
+        text_renderer.prepare(
+            &self.device,
+            &self.queue,
+            &mut self.text_system.font_system.lock(),
+            &mut self.text_system.text_atlas,
+            glyphon::Resolution {
+                width: self.config.width,
+                height: self.config.height,
+            },
+            text_areas,
+            &mut self.text_system.swash_cache,
+        )?;
+        Ok(())
+    }
+
+    fn background_color(&self) -> wgpu::Color {
+        let c = native_color(self.theme.palette.background_color, &self.surface_format);
+        wgpu::Color {
+            r: c[0] as f64,
+            g: c[1] as f64,
+            b: c[2] as f64,
+            a: c[3] as f64,
+        }
+    }
+
+    /// Draws everything besides the banner, badge and sticky heading into the document layer
+    fn render_document(
         &mut self,
+        encoder: &mut wgpu::CommandEncoder,
         elements: &mut [Positioned<Element>],
         selection: &mut Selection,
     ) -> anyhow::Result<()> {
         selection.text.clear();
-        let frame = self
-            .surface
-            .get_current_texture()
-            .context("Failed to acquire next swap chain texture")?;
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.clear_shapes();
+        let mut cached_text_areas = Vec::new();
+        let mut image_bindgroups = Vec::new();
+        if self.lightbox.is_some() {
+            image_bindgroups.extend(self.lightbox_bindgroup());
+        } else {
+            cached_text_areas = self.render_elements(elements, selection)?;
+            self.draw_current_match()?;
+            if let Some(side_areas) = self.with_side_pane(|renderer, elements| {
+                renderer.render_elements(elements, &mut Selection::new())
+            }) {
+                cached_text_areas.append(&mut side_areas?);
+            }
+            self.render_source_pane(elements, &mut cached_text_areas)?;
+
+            if self.low_memory {
+                self.drop_offscreen_textures(elements);
+                self.with_side_pane(|renderer, elements| {
+                    renderer.drop_offscreen_textures(elements)
+                });
+            }
+            // Prepare image bind groups for drawing
+            image_bindgroups = self.image_bindgroups(elements);
+            if let Some(mut side_bindgroups) =
+                self.with_side_pane(|renderer, elements| renderer.image_bindgroups(elements))
+            {
+                image_bindgroups.append(&mut side_bindgroups);
+            }
+        }
+        let veil_bindgroups = self.veil_bindgroups();
+        let shape_buffers = self.shape_buffers();
+        self.prepare_text(&cached_text_areas, false)?;
+
+        let layer = self.document_layer.as_ref().context("No document layer")?;
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Document"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &layer.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.background_color()),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        self.draw_shapes(&mut rpass, &shape_buffers);
+        self.draw_images(&mut rpass, &image_bindgroups);
+        self.text_system
+            .text_renderer
+            .render(&self.text_system.text_atlas, &mut rpass)
+            .unwrap();
+        // Veils dim the text under them too, so they go on last
+        self.draw_images(&mut rpass, &veil_bindgroups);
+        Ok(())
+    }
+
+    /// Draws a frame, only redrawing the document when it changed since the last one. The banner,
+    /// badge and sticky heading go over the top of it fresh each frame so that a blinking caret or
+    /// a toast coming and going doesn't lay out the whole document again
+    pub fn redraw(
+        &mut self,
+        elements: &mut [Positioned<Element>],
+        selection: &mut Selection,
+    ) -> anyhow::Result<()> {
+        let (frame, view) = match &self.target {
+            RenderTarget::Surface(surface) => {
+                let frame = surface
+                    .get_current_texture()
+                    .context("Failed to acquire next swap chain texture")?;
+                let view = frame
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(frame), view)
+            }
+            RenderTarget::Offscreen(texture) => {
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                (None, view)
+            }
+        };
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-        // Prepare and render elements that use lyon
-        self.lyon_buffer.indices.clear();
-        self.lyon_buffer.vertices.clear();
-        let cached_text_areas = self.render_elements(elements, selection)?;
-        let vertex_buf = self
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(&self.lyon_buffer.vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-        let index_buffer = self
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(&self.lyon_buffer.indices),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-
-        // Prepare image bind groups for drawing
-        let image_bindgroups = self.image_bindgroups(elements);
-
-        {
-            let mut text_cache = self.text_system.text_cache.lock();
-            let text_areas: Vec<TextArea> = cached_text_areas
-                .iter()
-                .map(|c| c.text_area(&text_cache))
-                .collect();
+        let size = (self.config.width.max(1), self.config.height.max(1));
+        if self.document_layer.as_ref().map(|layer| layer.size) != Some(size) {
+            self.document_layer = Some(self.document_layer());
+        }
+        let key = self.document_key(elements);
+        let drawn_for = self
+            .document_layer
+            .as_ref()
+            .and_then(|layer| layer.key.as_ref());
+        let stale = self.document_dirty || drawn_for != Some(&key);
+        if stale {
+            self.document_dirty = false;
+            if self.low_memory {
+                self.reset_text_atlas();
+            }
+            self.render_document(&mut encoder, elements, selection)?;
+            if let Some(layer) = &mut self.document_layer {
+                layer.key = Some(key);
+            }
+        }
 
-            self.text_system.text_renderer.prepare(
-                &self.device,
-                &self.queue,
-                &mut self.text_system.font_system.lock(),
-                &mut self.text_system.text_atlas,
-                glyphon::Resolution {
-                    width: self.config.width,
-                    height: self.config.height,
-                },
-                text_areas,
-                &mut self.text_system.swash_cache,
-            )?;
-            text_cache.trim();
+        self.clear_shapes();
+        let mut overlay_text_areas = Vec::new();
+        self.render_badge(&mut overlay_text_areas)?;
+        self.render_banner(&mut overlay_text_areas)?;
+        let mut overlay_bottom = match self.badge_bounds() {
+            Some(badge_bounds) => badge_bounds.max().1,
+            None => self.banner_height().unwrap_or(0.),
+        };
+        if self.sticky_headings && self.lightbox.is_none() {
+            overlay_bottom =
+                self.render_sticky_heading(elements, overlay_bottom, &mut overlay_text_areas)?;
+        }
+        let shape_buffers = self.shape_buffers();
+        self.prepare_text(&overlay_text_areas, true)?;
+        if stale {
+            self.text_system.text_cache.lock().trim();
         }
 
+        let screen_size = self.screen_size();
+        let layer_bindgroup = [(
+            self.document_layer
+                .as_ref()
+                .expect("Document layer was just made")
+                .bind_group
+                .clone(),
+            ImageRenderer::vertex_buf(&self.device, (0., 0.), screen_size, screen_size),
+        )];
         {
-            let background_color = {
-                let c = native_color(self.theme.background_color, &self.surface_format);
-                wgpu::Color {
-                    r: c[0] as f64,
-                    g: c[1] as f64,
-                    b: c[2] as f64,
-                    a: c[3] as f64,
-                }
-            };
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: None,
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(background_color),
+                        load: wgpu::LoadOp::Clear(self.background_color()),
                         store: true,
                     },
                 })],
                 depth_stencil_attachment: None,
             });
 
-            // Draw lyon elements
-            rpass.set_pipeline(&self.render_pipeline);
-            rpass.set_vertex_buffer(0, vertex_buf.slice(..));
-            rpass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            rpass.draw_indexed(0..self.lyon_buffer.indices.len() as u32, 0, 0..1);
-
-            // Draw images
-            rpass.set_pipeline(&self.image_renderer.render_pipeline);
-            rpass.set_index_buffer(self.image_renderer.index_buf.slice(..), IndexFormat::Uint16);
-            for (bindgroup, vertex_buf) in image_bindgroups.iter() {
-                rpass.set_bind_group(0, bindgroup, &[]);
-                rpass.set_vertex_buffer(0, vertex_buf.slice(..));
-                rpass.draw_indexed(0..6, 0, 0..1);
-            }
+            // Frames from the swap chain start out blank, so the whole layer gets copied over
+            self.draw_images(&mut rpass, &layer_bindgroup);
 
-            self.text_system
-                .text_renderer
-                .render(&self.text_system.text_atlas, &mut rpass)
-                .unwrap();
+            // The banner, badge and sticky heading only ever cover a band across the top of the
+            // window
+            let overlay_height = (overlay_bottom.ceil() as u32).min(size.1);
+            if overlay_height > 0 {
+                rpass.set_scissor_rect(0, 0, size.0, overlay_height);
+                self.draw_shapes(&mut rpass, &shape_buffers);
+                self.overlay_text_renderer
+                    .render(&self.text_system.text_atlas, &mut rpass)
+                    .unwrap();
+            }
         }
 
         self.queue.submit(Some(encoder.finish()));
-        frame.present();
+        if let Some(frame) = frame {
+            frame.present();
+        }
         self.text_system.text_atlas.trim();
 
         Ok(())
     }
 
+    /// Copies out the last frame drawn to an offscreen target
+    pub fn read_pixels(&self) -> anyhow::Result<RgbaImage> {
+        let RenderTarget::Offscreen(texture) = &self.target else {
+            anyhow::bail!("Only offscreen targets can be read back");
+        };
+        let (width, height) = (self.config.width, self.config.height);
+        // Rows in the buffer have to be padded out to the copy alignment
+        let row_bytes = width * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row_bytes = row_bytes.div_ceil(align) * align;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: u64::from(padded_row_bytes) * u64::from(height),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |res| {
+            let _ = sender.send(res);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv()?.context("Failed reading back the frame")?;
+
+        let mut pixels = Vec::with_capacity((row_bytes * height) as usize);
+        for row in slice.get_mapped_range().chunks(padded_row_bytes as usize) {
+            pixels.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        buffer.unmap();
+        RgbaImage::from_raw(width, height, pixels).context("Frame has invalid dimensions")
+    }
+
+    /// Splits the window between the main document on the left and a new, empty pane on the right
+    pub fn open_side_pane(&mut self) {
+        let mut positioner = Positioner::new_with_spacing(
+            self.screen_size(),
+            self.hidpi_scale,
+            self.page_width,
+            self.positioner.page_margin,
+            &self.element_spacing,
+        );
+        positioner.viewport = Viewport::RIGHT_HALF;
+        positioner.image_max_size = self.positioner.image_max_size;
+        self.positioner.viewport = Viewport::LEFT_HALF;
+        self.side_pane = Some(Pane::new(positioner));
+    }
+
+    /// Whether the side pane is showing at `x` in window coordinates
+    pub fn side_pane_contains(&self, x: f32) -> bool {
+        self.side_pane.as_ref().is_some_and(|pane| {
+            pane.positioner
+                .viewport
+                .contains_x(x, pane.positioner.screen_size.0)
+        })
+    }
+
+    /// Runs `f` with the side pane's layout and scroll position standing in for the main
+    /// document's, so that everything that works on the main document works on the side pane too
+    pub fn with_side_pane<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self, &mut Vec<Positioned<Element>>) -> T,
+    ) -> Option<T> {
+        let mut pane = self.side_pane.take()?;
+        self.swap_pane(&mut pane);
+        let res = f(self, &mut pane.elements);
+        self.swap_pane(&mut pane);
+        self.side_pane = Some(pane);
+        Some(res)
+    }
+
+    fn swap_pane(&mut self, pane: &mut Pane) {
+        std::mem::swap(&mut self.positioner, &mut pane.positioner);
+        std::mem::swap(&mut self.scroll_y, &mut pane.scroll_y);
+        std::mem::swap(&mut self.precise_scroll_y, &mut pane.precise_scroll_y);
+    }
+
     pub fn reposition(&mut self, elements: &mut [Positioned<Element>]) -> anyhow::Result<()> {
+        let char_width = self.char_width();
+        self.positioner.page_width =
+            self.profile
+                .page_width(self.page_width, char_width, self.positioner.page_margin);
         let start = Instant::now();
         let res = self
             .positioner
-            .reposition(&mut self.text_system, elements, self.zoom, self.element_padding);
+            .reposition(&mut self.text_system, elements, self.zoom, &self.element_spacing);
         histogram!(HistTag::Reposition).record(start.elapsed());
+        let stats = self.text_system.text_cache.lock().measure_stats();
+        tracing::debug!(
+            "Text measurements: {} reused, {} reshaped, {} text boxes remembered",
+            stats.hits,
+            stats.misses,
+            stats.entries,
+        );
         res
     }
 
+    /// How wide a character of body text is on average
+    fn char_width(&mut self) -> f32 {
+        const SAMPLE: &str = "the quick brown fox jumps over the lazy dog";
+        let color = native_color(self.theme.palette.text_color, &self.surface_format);
+        let text = Text::new(SAMPLE.to_owned(), self.hidpi_scale, color);
+        let text_box = TextBox::new(vec![text], self.hidpi_scale);
+        let unbounded = (f32::INFINITY, f32::INFINITY);
+        let width = text_box.size(&mut self.text_system, unbounded, self.zoom).0;
+        width / SAMPLE.len() as f32
+    }
+
+    pub fn max_scroll_y(&self) -> f32 {
+        (self.positioner.reserved_height - self.screen_height()).max(0.)
+    }
+
     pub fn set_scroll_y(&mut self, scroll_y: f32) {
-        self.scroll_y = scroll_y.clamp(
-            0.,
-            (self.positioner.reserved_height - self.screen_height()).max(0.),
-        )
+        let max_scroll = self.max_scroll_y();
+        self.precise_scroll_y = scroll_y.clamp(0., max_scroll);
+        // Text and shapes both only ever move by whole pixels, so glyphs get rasterized at the
+        // same subpixel offset every frame instead of shimmering while scrolling
+        self.scroll_y = self.precise_scroll_y.round().min(max_scroll.floor());
+    }
+
+    pub fn scroll_by(&mut self, delta: f32) {
+        self.set_scroll_y(self.precise_scroll_y + delta);
+    }
+
+    /// Keeps the scroll offset in bounds after the document or window changed size
+    pub fn clamp_scroll_y(&mut self) {
+        self.set_scroll_y(self.precise_scroll_y);
+    }
+
+    /// How far down the document is scrolled, from 0 at the top to 1 at the bottom
+    pub fn scroll_fraction(&self) -> f32 {
+        let max_scroll = self.max_scroll_y();
+        if max_scroll > 0. {
+            self.precise_scroll_y / max_scroll
+        } else {
+            0.
+        }
+    }
+
+    pub fn set_scroll_fraction(&mut self, fraction: f32) {
+        self.set_scroll_y(fraction * self.max_scroll_y());
     }
 }
 
 // Translates points from pixel coordinates to wgpu coordinates
+fn instance() -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
+    })
+}
+
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    compatible_surface: Option<&wgpu::Surface>,
+) -> anyhow::Result<wgpu::Adapter> {
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            compatible_surface,
+        })
+        .await
+        .context("Failed to find an appropriate adapter")
+}
+
+async fn request_device(adapter: &wgpu::Adapter) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+    let device = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+            },
+            None,
+        )
+        .await?;
+    Ok(device)
+}
+
+fn offscreen_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen Target"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        view_formats: &[],
+    })
+}
+
+/// Draws [`RectInstance`]s as two triangles each, without any vertex data of their own. They blend
+/// so that highlights can let the background show through
+fn rect_pipeline(device: &wgpu::Device, surface_format: TextureFormat) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Rectangle Shader"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders/rect.wgsl"))),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[],
+        push_constant_ranges: &[],
+    });
+    let instance_buffers = [wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<RectInstance>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4],
+    }];
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Rectangle Pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &instance_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: surface_format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+/// A checkbox's tick as a stroke relative to the box's top-left corner
+fn tessellate_tick(box_size: f32, width: f32) -> anyhow::Result<VertexBuffers<[f32; 2], u16>> {
+    let mut tick = VertexBuffers::new();
+    let mut stroke_tessellator = StrokeTessellator::new();
+    let stroke_opts = StrokeOptions::default().with_line_width(width);
+    let mut vertex_builder = BuffersBuilder::new(&mut tick, |vertex: StrokeVertex| {
+        vertex.position().to_array()
+    });
+    let mut builder = stroke_tessellator.builder(&stroke_opts, &mut vertex_builder);
+    builder.begin((box_size * 0.2, box_size * 0.5).into());
+    builder.line_to((box_size * 0.4, box_size * 0.7).into());
+    builder.line_to((box_size * 0.8, box_size * 0.2).into());
+    builder.end(false);
+    builder.build()?;
+    Ok(tick)
+}
+
 pub fn point(x: f32, y: f32, screen: Size) -> [f32; 2] {
     let scale_x = 2. / screen.0;
     let scale_y = 2. / screen.1;
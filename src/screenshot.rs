@@ -0,0 +1,92 @@
+//! `inlyne screenshot` renders a whole document to a PNG without opening a window
+
+use std::path::Path;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use crate::encoding::read_to_string;
+use crate::interpreter::{HtmlInterpreter, Pending};
+use crate::opts::Opts;
+use crate::renderer::Renderer;
+use crate::selection::Selection;
+use crate::utils::ImageCache;
+use crate::Inlyne;
+
+use anyhow::Context;
+use parking_lot::Mutex;
+
+/// How long to wait on images before rendering without them
+const IMAGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn run(opts: Opts, output: &Path, width: u32) -> anyhow::Result<()> {
+    let file_path = opts.history.get_path().to_owned();
+    let md_string = read_to_string(&file_path)
+        .with_context(|| format!("Could not read file at '{}'", file_path.display()))?;
+    // The output stays relative to where we were launched from, even though relative images are
+    // loaded from the document's folder
+    let output = std::path::absolute(output)
+        .with_context(|| format!("Invalid output path '{}'", output.display()))?;
+    let _ = file_path.parent().map(std::env::set_current_dir);
+
+    let hidpi_scale = opts.scale.unwrap_or(1.0);
+    // The height only gets known after laying out, so start small and grow to fit
+    let mut renderer = pollster::block_on(Renderer::headless(
+        (width, 1),
+        opts.theme.clone(),
+        hidpi_scale,
+        opts.page_width.unwrap_or(f32::MAX),
+        opts.font_opts.clone(),
+    ))?;
+    renderer.element_spacing = opts.element_spacing;
+    renderer.code_wrap_guide = opts.code_wrap_guide;
+    renderer.code_wrap_indicator = opts.code_wrap_indicator;
+    renderer
+        .image_renderer
+        .limit_texture_size(opts.image_max_texture_size);
+
+    let element_queue = Arc::new(Mutex::new(Vec::new()));
+    let pending = Pending::default();
+    let interpreter = HtmlInterpreter::headless(
+        Arc::clone(&element_queue),
+        renderer.theme.clone(),
+        renderer.surface_format,
+        renderer.hidpi_scale,
+        ImageCache::default(),
+        opts.color_scheme,
+        pending.clone(),
+    )
+    .with_opts(&opts);
+    // Closing the channel lets the interpreter return after this one document
+    let (md_sender, md_receiver) = mpsc::channel();
+    md_sender.send(md_string.into())?;
+    drop(md_sender);
+    interpreter.interpret_md(md_receiver);
+
+    let deadline = Instant::now() + IMAGE_TIMEOUT;
+    while !pending.is_done() {
+        if Instant::now() >= deadline {
+            tracing::warn!("Some images are still loading, so they're left out");
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let mut elements = Vec::new();
+    renderer.positioner.reserved_height = opts.element_spacing.paragraph * renderer.hidpi_scale;
+    while Inlyne::position_queued_elements(&element_queue, &mut renderer, &mut elements) {}
+
+    let max_height = renderer.device.limits().max_texture_dimension_2d;
+    let height = renderer.positioner.reserved_height.ceil() as u32;
+    if height > max_height {
+        tracing::warn!("Cutting the screenshot off at the GPU's limit of {max_height}px tall");
+    }
+    renderer.resize(width, height.clamp(1, max_height));
+    renderer.reposition(&mut elements)?;
+    renderer.redraw(&mut elements, &mut Selection::new())?;
+
+    renderer
+        .read_pixels()?
+        .save(&output)
+        .with_context(|| format!("Failed writing to '{}'", output.display()))?;
+    Ok(())
+}
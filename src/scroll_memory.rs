@@ -0,0 +1,149 @@
+//! Remembers how far into each file the view was scrolled, along with its zoom, so that reopening
+//! a file or going back to it through the history picks up where it was left off
+//!
+//! Scroll positions are kept as a fraction of the document's height, which holds up better than
+//! pixels when the window gets opened at a different size
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+/// The most files to remember before forgetting the ones that were viewed longest ago
+const LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SavedView {
+    /// How far down the document the view starts, from 0 to 1
+    pub progress: f32,
+    pub zoom: f32,
+}
+
+impl SavedView {
+    pub fn new(scroll_y: f32, reserved_height: f32, zoom: f32) -> Self {
+        let progress = if reserved_height > 0. {
+            (scroll_y / reserved_height).clamp(0., 1.)
+        } else {
+            0.
+        };
+        Self { progress, zoom }
+    }
+}
+
+pub struct ScrollMemory {
+    /// Where the views get saved to
+    state_file: PathBuf,
+    /// The file on screen
+    current: PathBuf,
+    /// From the file viewed longest ago to the most recent one
+    views: Vec<(PathBuf, SavedView)>,
+}
+
+impl ScrollMemory {
+    /// The file that views are saved in
+    pub fn state_file() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("inlyne").join("scroll"))
+    }
+
+    /// Loads the saved views with `current` being the file that's open. Lines that can't be
+    /// parsed get dropped
+    pub fn load(state_file: PathBuf, current: PathBuf) -> Self {
+        let views = fs::read_to_string(&state_file)
+            .map(|saved| saved.lines().filter_map(parse_line).collect())
+            .unwrap_or_default();
+        Self {
+            state_file,
+            current,
+            views,
+        }
+    }
+
+    /// The view that the file on screen was left at last time
+    pub fn current_view(&self) -> Option<SavedView> {
+        self.get(&self.current)
+    }
+
+    fn get(&self, path: &Path) -> Option<SavedView> {
+        self.views
+            .iter()
+            .find(|(saved, _)| saved == path)
+            .map(|&(_, view)| view)
+    }
+
+    /// Remembers `view` for the file on screen
+    pub fn record(&mut self, view: SavedView) {
+        self.views.retain(|(saved, _)| *saved != self.current);
+        self.views.push((self.current.clone(), view));
+        let excess = self.views.len().saturating_sub(LIMIT);
+        self.views.drain(..excess);
+    }
+
+    /// Remembers `view` for the file that was on screen and returns the one to restore for `path`
+    pub fn switch(&mut self, path: &Path, view: SavedView) -> Option<SavedView> {
+        self.record(view);
+        self.current = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        self.current_view()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        if let Some(dir) = self.state_file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut contents = String::new();
+        for (path, view) in &self.views {
+            let Some(path) = path.to_str() else {
+                continue;
+            };
+            contents.push_str(&format!("{}\t{}\t{path}\n", view.progress, view.zoom));
+        }
+        fs::write(&self.state_file, contents).with_context(|| {
+            format!(
+                "Failed saving scroll positions to '{}'",
+                self.state_file.display()
+            )
+        })
+    }
+}
+
+/// Parses a `progress<TAB>zoom<TAB>path` line
+fn parse_line(line: &str) -> Option<(PathBuf, SavedView)> {
+    let mut parts = line.splitn(3, '\t');
+    let progress: f32 = parts.next()?.parse().ok()?;
+    let zoom: f32 = parts.next()?.parse().ok()?;
+    let path = PathBuf::from(parts.next()?);
+    let valid = (0. ..=1.).contains(&progress) && zoom.is_finite() && zoom > 0.;
+    valid.then_some((path, SavedView { progress, zoom }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn views_survive_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("scroll");
+        let (a, b) = (PathBuf::from("/docs/a.md"), PathBuf::from("/docs/b.md"));
+
+        let mut memory = ScrollMemory::load(state_file.clone(), a.clone());
+        assert_eq!(memory.current_view(), None);
+        let view = SavedView::new(250., 1_000., 1.5);
+        assert_eq!(memory.switch(&b, view), None);
+        assert_eq!(memory.switch(&a, SavedView::new(0., 0., 1.)), Some(view));
+        memory.save().unwrap();
+
+        let memory = ScrollMemory::load(state_file.clone(), a);
+        assert_eq!(
+            memory.current_view(),
+            Some(SavedView {
+                progress: 0.25,
+                zoom: 1.5
+            })
+        );
+
+        fs::write(&state_file, "oops\n2\t1\t/docs/a.md\n0.5\t1\t/docs/b.md\n").unwrap();
+        let memory = ScrollMemory::load(state_file, b);
+        assert_eq!(memory.views.len(), 1);
+        assert_eq!(memory.current_view().unwrap().progress, 0.5);
+    }
+}
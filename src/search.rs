@@ -0,0 +1,638 @@
+//! Searching through the markdown source of the current document
+
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::utils::Rect;
+
+use anyhow::Context;
+
+pub const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+/// How long the caret stays shown, and then hidden, while typing a query
+const CARET_BLINK: Duration = Duration::from_millis(530);
+
+/// How queries get matched against the text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Match case once the query contains an uppercase character. Otherwise case is always
+    /// ignored
+    pub smart_case: bool,
+    /// Only match the query where it isn't part of a longer word
+    pub whole_word: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            smart_case: true,
+            whole_word: false,
+        }
+    }
+}
+
+impl SearchOptions {
+    fn case_sensitive(&self, query: &str) -> bool {
+        self.smart_case && query.chars().any(char::is_uppercase)
+    }
+
+    /// A short summary of the non-default options for the search banner e.g. `[word]`
+    fn tags(&self) -> String {
+        let mut tags = Vec::new();
+        if !self.smart_case {
+            tags.push("ignore case");
+        }
+        if self.whole_word {
+            tags.push("word");
+        }
+        if tags.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", tags.join(", "))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Search {
+    pub query: String,
+    /// Byte offset of the caret within the query
+    cursor: usize,
+    /// Whether keys are still going to the query instead of keybindings
+    pub typing: bool,
+    pub options: SearchOptions,
+    /// The caret blinks in step with this, so it stays solid right after every edit
+    last_edit: Instant,
+    /// How far back in the search history the query came from, 0 being the latest search
+    recalled: Option<usize>,
+    /// What was typed before going back through the history
+    draft: String,
+    /// Text the input method is still composing, shown at the caret until it gets committed
+    preedit: String,
+    /// Byte offset of the input method's own cursor within `preedit`
+    preedit_cursor: usize,
+}
+
+impl Search {
+    pub fn start(options: SearchOptions) -> Self {
+        Self::with_query(String::new(), options).typing()
+    }
+
+    /// A search that's already been entered, like one from `--find`
+    pub fn with_query(query: String, options: SearchOptions) -> Self {
+        Self {
+            cursor: query.len(),
+            query,
+            typing: false,
+            options,
+            last_edit: Instant::now(),
+            recalled: None,
+            draft: String::new(),
+            preedit: String::new(),
+            preedit_cursor: 0,
+        }
+    }
+
+    /// Sends keys back to the query, e.g. to refine a search that's already been entered
+    pub fn typing(mut self) -> Self {
+        self.typing = true;
+        self
+    }
+
+    /// Inserts text at the caret, keeping only the first line of multi-line text like a paste
+    pub fn insert(&mut self, text: &str) {
+        let line = text.lines().next().unwrap_or_default();
+        let text: String = line.chars().filter(|c| !c.is_control()).collect();
+        self.query.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+        self.compose(String::new(), None);
+    }
+
+    /// Shows text the input method is still composing at the caret, without searching for it yet.
+    /// `cursor` is the input method's own selection within `text`
+    pub fn compose(&mut self, text: String, cursor: Option<(usize, usize)>) {
+        self.preedit_cursor = cursor.map_or(text.len(), |(_, end)| end.min(text.len()));
+        self.preedit = text;
+        self.last_edit = Instant::now();
+    }
+
+    /// Whether the input method is partway through composing, which leaves editing keys to it
+    pub fn composing(&self) -> bool {
+        !self.preedit.is_empty()
+    }
+
+    fn prev_boundary(&self) -> Option<usize> {
+        self.query[..self.cursor]
+            .char_indices()
+            .last()
+            .map(|(i, _)| i)
+    }
+
+    fn next_boundary(&self) -> Option<usize> {
+        let c = self.query[self.cursor..].chars().next()?;
+        Some(self.cursor + c.len_utf8())
+    }
+
+    fn move_to(&mut self, cursor: usize) {
+        self.cursor = cursor;
+        self.last_edit = Instant::now();
+    }
+
+    pub fn left(&mut self) {
+        self.move_to(self.prev_boundary().unwrap_or(0));
+    }
+
+    pub fn right(&mut self) {
+        self.move_to(self.next_boundary().unwrap_or(self.cursor));
+    }
+
+    pub fn home(&mut self) {
+        self.move_to(0);
+    }
+
+    pub fn end(&mut self) {
+        self.move_to(self.query.len());
+    }
+
+    /// Deletes the character before the caret
+    pub fn backspace(&mut self) {
+        if let Some(start) = self.prev_boundary() {
+            self.query.replace_range(start..self.cursor, "");
+            self.move_to(start);
+        }
+    }
+
+    /// Deletes the character after the caret
+    pub fn delete(&mut self) {
+        if let Some(end) = self.next_boundary() {
+            self.query.replace_range(self.cursor..end, "");
+            self.move_to(self.cursor);
+        }
+    }
+
+    /// Deletes the word before the caret along with any whitespace after it, like Ctrl+W in a
+    /// shell
+    pub fn delete_word(&mut self) {
+        let before = self.query[..self.cursor].trim_end();
+        let start = before
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| !c.is_whitespace())
+            .last()
+            .map_or(before.len(), |(i, _)| i);
+        self.query.replace_range(start..self.cursor, "");
+        self.move_to(start);
+    }
+
+    fn replace_query(&mut self, query: String) {
+        self.query = query;
+        self.end();
+    }
+
+    /// Swaps the query for the previous one in `history`, oldest first
+    pub fn recall_older(&mut self, history: &SearchHistory) {
+        let older = self.recalled.map_or(0, |recalled| recalled + 1);
+        let Some(query) = history.nth_latest(older) else {
+            return;
+        };
+        let query = query.to_owned();
+        if self.recalled.is_none() {
+            self.draft = std::mem::take(&mut self.query);
+        }
+        self.recalled = Some(older);
+        self.replace_query(query);
+    }
+
+    /// Swaps the query for the next one in `history`, ending up back at what was being typed
+    pub fn recall_newer(&mut self, history: &SearchHistory) {
+        match self.recalled {
+            None => {}
+            Some(0) => {
+                self.recalled = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.replace_query(draft);
+            }
+            Some(recalled) => {
+                let query = history
+                    .nth_latest(recalled - 1)
+                    .unwrap_or_default()
+                    .to_owned();
+                self.recalled = Some(recalled - 1);
+                self.replace_query(query);
+            }
+        }
+    }
+
+    /// Where to draw the caret as a byte offset into [`Search::prompt`], when it's blinked on
+    pub fn caret(&self) -> Option<usize> {
+        let blinks = self.last_edit.elapsed().as_millis() / CARET_BLINK.as_millis();
+        let blinked_off = blinks % 2 == 1;
+        let caret = '/'.len_utf8() + self.cursor + self.preedit_cursor;
+        (self.typing && !blinked_off).then_some(caret)
+    }
+
+    /// When the caret blinks next, while the query is being typed
+    pub fn next_blink(&self) -> Option<Instant> {
+        let blinks = self.last_edit.elapsed().as_millis() / CARET_BLINK.as_millis();
+        let blinks = u32::try_from(blinks + 1).ok()?;
+        self.typing.then(|| self.last_edit + CARET_BLINK * blinks)
+    }
+
+    /// Every source line matching the query, along with its 1-indexed line number
+    pub fn matches<'a>(&self, source: &'a str) -> Vec<(usize, &'a str)> {
+        if self.query.is_empty() {
+            return Vec::new();
+        }
+
+        source
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !match_ranges(line, &self.query, self.options).is_empty())
+            .map(|(i, line)| (i + 1, line))
+            .collect()
+    }
+
+    /// The matching lines joined for the clipboard, optionally prefixed with `path:line: `
+    pub fn copy_text(&self, source: &str, location: Option<&Path>) -> String {
+        self.matches(source)
+            .into_iter()
+            .map(|(line_num, line)| match location {
+                Some(path) => format!("{}:{line_num}: {line}", path.display()),
+                None => line.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Text for the search banner e.g. `/query (3 matches)`
+    pub fn prompt(&self, source: &str) -> String {
+        let count = self.matches(source).len();
+        let plural = if count == 1 { "" } else { "es" };
+        let tags = self.options.tags();
+        let (before, after) = self.query.split_at(self.cursor);
+        let preedit = &self.preedit;
+        format!("/{before}{preedit}{after} ({count} match{plural}){tags}")
+    }
+}
+
+/// Byte ranges of each occurrence of `query` in `haystack`
+pub fn match_ranges(haystack: &str, query: &str, options: SearchOptions) -> Vec<Range<usize>> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let case_sensitive = options.case_sensitive(query);
+    let fold = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+    let needle: Vec<char> = query.chars().map(fold).collect();
+    let chars: Vec<(usize, char)> = haystack.char_indices().collect();
+
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while !needle.is_empty() && i + needle.len() <= chars.len() {
+        let candidate = &chars[i..i + needle.len()];
+        let matched = candidate
+            .iter()
+            .zip(&needle)
+            .all(|(&(_, c), &n)| fold(c) == n);
+        let word_before = i > 0 && is_word(chars[i - 1].1);
+        let word_after = chars
+            .get(i + needle.len())
+            .is_some_and(|&(_, after)| is_word(after));
+        if matched && !(options.whole_word && (word_before || word_after)) {
+            let start = chars[i].0;
+            let end = chars
+                .get(i + needle.len())
+                .map_or(haystack.len(), |&(end, _)| end);
+            ranges.push(start..end);
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+    ranges
+}
+
+/// Where the current match was before the document got reloaded, for finding it again after
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MatchAnchor {
+    index: usize,
+    /// How many matches there were in all
+    count: usize,
+    /// The top of the match in document coordinates
+    y: f32,
+}
+
+impl MatchAnchor {
+    /// Notes down the match at `index`, if there is one
+    pub fn new(matches: &[Vec<Rect>], index: usize) -> Option<Self> {
+        let y = matches.get(index)?.first()?.pos.1;
+        Some(Self {
+            index,
+            count: matches.len(),
+            y,
+        })
+    }
+
+    /// The same match in the reloaded document. That's the one at the same index when no matches
+    /// came or went, and otherwise whichever is nearest to where it was
+    pub fn find(&self, matches: &[Vec<Rect>]) -> Option<usize> {
+        if matches.len() == self.count {
+            return Some(self.index);
+        }
+        matches
+            .iter()
+            .enumerate()
+            .filter_map(|(index, rects)| Some((index, (rects.first()?.pos.1 - self.y).abs())))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+}
+
+/// Past search queries, shared by every window and kept across sessions
+#[derive(Debug, Default)]
+pub struct SearchHistory {
+    /// Oldest first
+    queries: Vec<String>,
+    /// The most queries to keep before dropping the oldest ones
+    limit: usize,
+    /// Where the queries get saved to, if anywhere
+    file: Option<PathBuf>,
+}
+
+impl SearchHistory {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    /// The file searches are saved in, next to the saved back/forward history
+    pub fn default_file() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("inlyne").join("searches"))
+    }
+
+    /// Loads the queries saved in `file`, which also gets new queries saved to it from then on
+    pub fn load(mut self, file: PathBuf) -> Self {
+        if let Ok(saved) = fs::read_to_string(&file) {
+            self.queries = saved
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(ToOwned::to_owned)
+                .collect();
+            self.prune();
+        }
+        self.file = Some(file);
+        self
+    }
+
+    fn nth_latest(&self, n: usize) -> Option<&str> {
+        let index = self.queries.len().checked_sub(n + 1)?;
+        Some(&self.queries[index])
+    }
+
+    /// Remembers a query that was searched for, moving it to the front if it was already there
+    pub fn push(&mut self, query: &str) -> anyhow::Result<()> {
+        let query = query.trim();
+        if query.is_empty() || self.limit == 0 {
+            return Ok(());
+        }
+        self.queries.retain(|existing| existing != query);
+        self.queries.push(query.to_owned());
+        self.prune();
+        self.save()
+    }
+
+    fn prune(&mut self) {
+        let excess = self.queries.len().saturating_sub(self.limit);
+        self.queries.drain(..excess);
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut contents = self.queries.join("\n");
+        contents.push('\n');
+        fs::write(file, contents)
+            .with_context(|| format!("Failed saving searches to '{}'", file.display()))
+    }
+}
+
+/// Keeps the match count for the `--find` query in view, even after its search closes
+#[derive(Debug)]
+pub struct FindBadge {
+    search: Search,
+    /// Line numbers of the matches in the document they were found in
+    lines: Vec<usize>,
+    source_hash: Option<u64>,
+}
+
+impl FindBadge {
+    pub fn new(query: String, options: SearchOptions) -> Self {
+        Self {
+            search: Search::with_query(query, options),
+            lines: Vec::new(),
+            source_hash: None,
+        }
+    }
+
+    pub fn query(&self) -> &str {
+        &self.search.query
+    }
+
+    /// Matches the query differently from now on, finding the matches again on the next update
+    pub fn set_options(&mut self, options: SearchOptions) {
+        if self.search.options != options {
+            self.search.options = options;
+            self.source_hash = None;
+        }
+    }
+
+    /// Finds the matches again if the document changed since the last time
+    pub fn update(&mut self, source: &str, source_hash: u64) {
+        if self.source_hash != Some(source_hash) {
+            self.source_hash = Some(source_hash);
+            self.lines = self
+                .search
+                .matches(source)
+                .into_iter()
+                .map(|(line_num, _)| line_num)
+                .collect();
+        }
+    }
+
+    pub fn first_line(&self) -> Option<usize> {
+        self.lines.first().copied()
+    }
+
+    /// Text for the badge e.g. `Find "query": 2 of 5`, where the current match is the last one
+    /// at or above `top_line`
+    pub fn text(&self, top_line: usize) -> String {
+        let query = &self.search.query;
+        let total = self.lines.len();
+        let current = self.lines.partition_point(|&line| line <= top_line);
+        match (total, current) {
+            (0, _) => format!("Find \"{query}\": no matches"),
+            (1, 0) => format!("Find \"{query}\": 1 match"),
+            (_, 0) => format!("Find \"{query}\": {total} matches"),
+            _ => format!("Find \"{query}\": {current} of {total}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+# Title
+
+Some text
+more TEXT here
+nothing";
+
+    fn search(query: &str) -> Search {
+        Search::with_query(query.to_owned(), SearchOptions::default())
+    }
+
+    #[test]
+    fn smart_case() {
+        let lines = search("text").matches(SOURCE);
+        assert_eq!(lines, [(3, "Some text"), (4, "more TEXT here")]);
+        let lines = search("TEXT").matches(SOURCE);
+        assert_eq!(lines, [(4, "more TEXT here")]);
+        assert!(search("").matches(SOURCE).is_empty());
+
+        let mut search = search("TEXT");
+        search.options.smart_case = false;
+        assert_eq!(search.matches(SOURCE).len(), 2);
+    }
+
+    #[test]
+    fn whole_word() {
+        let options = SearchOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        let haystack = "text texts context (text) text_";
+        assert_eq!(match_ranges(haystack, "text", options), [0..4, 20..24]);
+        let ranges = match_ranges(haystack, "text", SearchOptions::default());
+        assert_eq!(ranges.len(), 5);
+    }
+
+    #[test]
+    fn copy_with_locations() {
+        let text = search("text").copy_text(SOURCE, Some(Path::new("notes.md")));
+        assert_eq!(text, "notes.md:3: Some text\nnotes.md:4: more TEXT here");
+        let text = search("title").copy_text(SOURCE, None);
+        assert_eq!(text, "# Title");
+    }
+
+    #[test]
+    fn find_badge_tracks_position() {
+        let mut badge = FindBadge::new("text".to_owned(), SearchOptions::default());
+        badge.update(SOURCE, 0);
+        assert_eq!(badge.first_line(), Some(3));
+        assert_eq!(badge.text(1), "Find \"text\": 2 matches");
+        assert_eq!(badge.text(3), "Find \"text\": 1 of 2");
+        assert_eq!(badge.text(5), "Find \"text\": 2 of 2");
+
+        badge.update("nothing here", 1);
+        assert_eq!(badge.text(1), "Find \"text\": no matches");
+    }
+
+    #[test]
+    fn ranges_within_a_line() {
+        let ranges = |haystack, query| match_ranges(haystack, query, SearchOptions::default());
+        assert_eq!(ranges("Text, text and more", "text"), [0..4, 6..10]);
+        assert_eq!(ranges("Text, text and Text", "Text"), [0..4, 15..19]);
+        assert_eq!(ranges("façade Façade", "façade"), [0..7, 8..15]);
+        assert!(ranges("anything", "").is_empty());
+    }
+
+    #[test]
+    fn editing() {
+        let mut search = Search::start(SearchOptions::default());
+        search.insert("héllo wörld");
+        search.left();
+        search.left();
+        search.backspace();
+        assert_eq!(search.query, "héllo wöld");
+        search.home();
+        search.right();
+        search.delete();
+        search.insert("a");
+        assert_eq!(search.query, "hallo wöld");
+        search.end();
+        search.insert("  ");
+        search.delete_word();
+        assert_eq!(search.query, "hallo ");
+        search.insert("pasted\nlines");
+        assert_eq!(search.query, "hallo pasted");
+        assert_eq!(search.caret(), Some("/hallo pasted".len()));
+    }
+
+    #[test]
+    fn composing_with_an_input_method() {
+        let mut search = Search::start(SearchOptions::default());
+        search.insert("ab");
+        search.left();
+        search.compose("にほ".to_owned(), Some(("に".len(), "に".len())));
+        assert!(search.composing());
+        assert_eq!(search.query, "ab");
+        assert!(search.prompt("").starts_with("/aにほb "));
+        assert_eq!(search.caret(), Some("/aに".len()));
+        search.insert("日本");
+        assert!(!search.composing());
+        assert_eq!(search.query, "a日本b");
+        assert_eq!(search.caret(), Some("/a日本".len()));
+    }
+
+    #[test]
+    fn anchored_match_after_reload() {
+        let at = |ys: &[f32]| -> Vec<Vec<Rect>> {
+            ys.iter()
+                .map(|&y| vec![Rect::new((0., y), (10., 10.))])
+                .collect()
+        };
+        let anchor = MatchAnchor::new(&at(&[0., 100., 200.]), 1).unwrap();
+        // Edits that don't add or remove matches keep the same one
+        assert_eq!(anchor.find(&at(&[0., 150., 300.])), Some(1));
+        // Otherwise it's the closest one to where it was
+        assert_eq!(anchor.find(&at(&[0., 40., 110., 200.])), Some(2));
+        assert_eq!(anchor.find(&[]), None);
+        assert_eq!(MatchAnchor::new(&at(&[0.]), 1), None);
+    }
+
+    #[test]
+    fn recalling_history() {
+        let mut history = SearchHistory::new(2);
+        for query in ["first", "second", "third", "second"] {
+            history.push(query).unwrap();
+        }
+        assert_eq!(history.queries, ["third", "second"]);
+
+        let mut search = Search::start(SearchOptions::default());
+        search.insert("draft");
+        search.recall_older(&history);
+        assert_eq!(search.query, "second");
+        search.recall_older(&history);
+        search.recall_older(&history);
+        assert_eq!(search.query, "third");
+        search.recall_newer(&history);
+        assert_eq!(search.query, "second");
+        search.recall_newer(&history);
+        assert_eq!(search.query, "draft");
+        search.recall_newer(&history);
+        assert_eq!(search.query, "draft");
+    }
+}
@@ -0,0 +1,78 @@
+//! Shareable links to a document's sections
+
+use std::path::Path;
+use std::process::Command;
+
+/// Builds a link to the heading with `anchor` (e.g. `#usage`) in `file`
+///
+/// Files in a git repo with a GitHub origin get a full GitHub URL, otherwise this falls back to
+/// `file.md#anchor`
+pub fn for_heading(file: &Path, anchor: &str) -> String {
+    github_blob_url(file)
+        .map(|url| format!("{url}{anchor}"))
+        .unwrap_or_else(|| {
+            let name = file.file_name().unwrap_or(file.as_os_str());
+            format!("{}{anchor}", name.to_string_lossy())
+        })
+}
+
+fn github_blob_url(file: &Path) -> Option<String> {
+    let dir = file.parent()?;
+    let name = file.file_name()?.to_str()?;
+    let repo = github_repo_url(&git(dir, &["remote", "get-url", "origin"])?)?;
+    let prefix = git(dir, &["rev-parse", "--show-prefix"])?;
+    let branch = match git(dir, &["rev-parse", "--abbrev-ref", "HEAD"])? {
+        // Detached HEADs link to the commit instead
+        detached if detached == "HEAD" => git(dir, &["rev-parse", "HEAD"])?,
+        branch => branch,
+    };
+    Some(format!("{repo}/blob/{branch}/{prefix}{name}"))
+}
+
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    output.status.success().then(|| stdout.trim().to_owned())
+}
+
+/// Normalizes the different forms of GitHub remote URLs to `https://github.com/<owner>/<repo>`
+fn github_repo_url(remote: &str) -> Option<String> {
+    let path = remote
+        .strip_prefix("git@github.com:")
+        .or_else(|| remote.strip_prefix("ssh://git@github.com/"))
+        .or_else(|| remote.strip_prefix("https://github.com/"))
+        .or_else(|| remote.strip_prefix("http://github.com/"))?;
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    Some(format!("https://github.com/{path}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn github_remotes() {
+        let expected = Some("https://github.com/owner/repo".to_owned());
+        assert_eq!(github_repo_url("git@github.com:owner/repo.git"), expected);
+        assert_eq!(github_repo_url("ssh://git@github.com/owner/repo"), expected);
+        assert_eq!(
+            github_repo_url("https://github.com/owner/repo.git"),
+            expected
+        );
+        assert_eq!(github_repo_url("https://github.com/owner/repo/"), expected);
+        assert_eq!(github_repo_url("https://gitlab.com/owner/repo.git"), None);
+    }
+
+    #[test]
+    fn plain_file_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.md");
+        assert_eq!(for_heading(&file, "#usage"), "notes.md#usage");
+    }
+}
@@ -25,6 +25,8 @@ pub enum SelectionKind {
         position: Point,
         time: Instant,
     },
+    /// Whole code blocks, identified by the y position of their top edge
+    Blocks(Vec<f32>),
     #[default]
     None,
 }
@@ -32,6 +34,8 @@ pub enum SelectionKind {
 #[derive(Default)]
 pub struct Selection {
     pub selection: SelectionKind,
+    /// Earlier ranges that were kept by holding Ctrl while starting a new one
+    pub extra_ranges: Vec<SelectionKind>,
     pub text: String,
 }
 
@@ -39,9 +43,27 @@ impl Selection {
     pub const fn new() -> Self {
         Self {
             selection: SelectionKind::None,
+            extra_ranges: Vec::new(),
             text: String::new(),
         }
     }
+
+    /// Every selected range, with the one currently being made last
+    pub fn ranges(&self) -> impl Iterator<Item = &SelectionKind> {
+        self.extra_ranges
+            .iter()
+            .chain(std::iter::once(&self.selection))
+    }
+
+    /// Moves the current selection over to the kept ranges, so a new one can be started
+    fn keep_current(&mut self) {
+        match std::mem::take(&mut self.selection) {
+            range @ (SelectionKind::Drag { .. }
+            | SelectionKind::Click { .. }
+            | SelectionKind::Blocks(_)) => self.extra_ranges.push(range),
+            SelectionKind::Start { .. } | SelectionKind::None => {}
+        }
+    }
     pub fn is_none(&self) -> bool {
         matches!(self.selection, SelectionKind::None)
     }
@@ -70,6 +92,7 @@ impl Selection {
 
     pub fn add_position(&mut self, new_position: Point) {
         self.text.clear();
+        self.extra_ranges.clear();
 
         match &self.selection {
             SelectionKind::Click {
@@ -107,6 +130,37 @@ impl Selection {
         }
     }
 
+    /// Starts a new range while keeping everything that's already selected
+    pub fn add_range(&mut self, position: Point) {
+        self.text.clear();
+        self.keep_current();
+        self.start(position);
+    }
+
+    /// Selects a whole code block, or toggles it among the selected blocks when `extend` is set
+    pub fn select_block(&mut self, top: f32, extend: bool) {
+        self.text.clear();
+
+        match &mut self.selection {
+            SelectionKind::Blocks(tops) if extend => {
+                match tops.iter().position(|&selected| selected == top) {
+                    Some(i) => {
+                        tops.remove(i);
+                    }
+                    None => tops.push(top),
+                }
+            }
+            _ => {
+                if extend {
+                    self.keep_current();
+                } else {
+                    self.extra_ranges.clear();
+                }
+                self.selection = SelectionKind::Blocks(vec![top]);
+            }
+        }
+    }
+
     pub fn add_line(&mut self, str: &str) {
         self.text.push_str(str);
         self.text.push('\n');
@@ -0,0 +1,346 @@
+//! `inlyne serve DIR` previews a directory of docs in a window while also serving them as HTML on
+//! localhost, like a bare-bones docs dev server
+//!
+//! Every request reads the file fresh from disk, so the served pages are as current as the window.
+//! Markdown files get rendered the same way as `inlyne export html`, and anything else (images and
+//! the like) is sent as is so that relative links keep working
+//!
+//! Only requests addressed to `localhost` or `127.0.0.1` get answered, so a web page can't reach
+//! the docs by rebinding its own domain to the loopback address. Hidden files and directories are
+//! never served
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::color::Theme;
+use crate::{encoding, export};
+
+use anyhow::Context;
+use percent_encoding::percent_decode_str;
+
+/// Files that get picked to show first, in order of preference
+const INDEX_FILES: &[&str] = &["README.md", "readme.md", "index.md"];
+/// A client that goes quiet mid-request gets dropped after this
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Requests are only ever a request line and some headers, so anything past this gets cut off
+const MAX_REQUEST: u64 = 64 * 1024;
+
+pub struct DocsServer {
+    root: PathBuf,
+    theme: Theme,
+    page_width: Option<f32>,
+    smart_punctuation: bool,
+}
+
+impl DocsServer {
+    pub fn new(
+        root: &Path,
+        theme: Theme,
+        page_width: Option<f32>,
+        smart_punctuation: bool,
+    ) -> anyhow::Result<Self> {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("Could not find the docs directory '{}'", root.display()))?;
+        Ok(Self {
+            root,
+            theme,
+            page_width,
+            smart_punctuation,
+        })
+    }
+
+    /// Answers requests on a background thread until the app exits
+    pub fn spawn(self, port: u16) -> anyhow::Result<SocketAddr> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .with_context(|| format!("Failed listening on port {port}"))?;
+        let addr = listener.local_addr()?;
+        let server = Arc::new(self);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                // A slow client only ever holds up its own request
+                let server = Arc::clone(&server);
+                std::thread::spawn(move || {
+                    if let Err(err) = server.answer(stream) {
+                        tracing::warn!("Failed answering a docs request: {err}");
+                    }
+                });
+            }
+        });
+        Ok(addr)
+    }
+
+    fn answer(&self, stream: TcpStream) -> anyhow::Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut reader = BufReader::new((&stream).take(MAX_REQUEST));
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Only the host matters out of the headers, but they all need reading before responding
+        let mut host = None;
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            if let Some((name, value)) = header.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("host") {
+                    host = Some(value.trim().to_owned());
+                }
+            }
+            header.clear();
+        }
+
+        let response = if !host.as_deref().is_some_and(is_local_host) {
+            Response::error(403, "Forbidden")
+        } else {
+            match request_path(&request_line) {
+                Some(path) => self.respond(&path),
+                None => Response::error(400, "Bad Request"),
+            }
+        };
+        response.write_to(&stream)
+    }
+
+    fn respond(&self, url_path: &str) -> Response {
+        if url_path == "/" {
+            return self.index();
+        }
+        let Some(path) = self.resolve(url_path) else {
+            return Response::error(404, "Not Found");
+        };
+        if path.is_dir() {
+            return match index_file(&path) {
+                Some(index) => self.page(&index),
+                None => Response::error(404, "Not Found"),
+            };
+        }
+        if is_markdown(&path) {
+            return self.page(&path);
+        }
+        match std::fs::read(&path) {
+            Ok(body) => Response::ok(content_type(&path), body),
+            Err(_) => Response::error(404, "Not Found"),
+        }
+    }
+
+    /// Maps a URL path onto a file within the docs directory, refusing anything that escapes it
+    /// or is hidden
+    fn resolve(&self, url_path: &str) -> Option<PathBuf> {
+        let relative = url_path.trim_start_matches('/');
+        if relative
+            .split(['/', '\\'])
+            .any(|segment| segment.starts_with('.'))
+        {
+            return None;
+        }
+        let path = self.root.join(relative).canonicalize().ok()?;
+        path.starts_with(&self.root).then_some(path)
+    }
+
+    fn page(&self, path: &Path) -> Response {
+        let Ok(md) = encoding::read_to_string(path) else {
+            return Response::error(404, "Not Found");
+        };
+        let title = path
+            .file_stem()
+            .map_or_else(|| "Inlyne".into(), |stem| stem.to_string_lossy());
+        let page = self.render(&md, &title);
+        Response::ok("text/html; charset=utf-8", page.into_bytes())
+    }
+
+    /// Lists every markdown file, for when no particular page was asked for
+    fn index(&self) -> Response {
+        let mut md = String::from("# Docs\n\n");
+        for path in markdown_files(&self.root) {
+            let Ok(relative) = path.strip_prefix(&self.root) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            let link = relative.replace(' ', "%20");
+            md.push_str(&format!("- [{relative}](/{link})\n"));
+        }
+        let page = self.render(&md, "Docs");
+        Response::ok("text/html; charset=utf-8", page.into_bytes())
+    }
+
+    fn render(&self, md: &str, title: &str) -> String {
+        export::html(
+            md,
+            title,
+            &self.theme,
+            self.page_width,
+            self.smart_punctuation,
+        )
+    }
+}
+
+struct Response {
+    status: u16,
+    reason: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            reason: "OK",
+            content_type,
+            body,
+        }
+    }
+
+    fn error(status: u16, reason: &'static str) -> Self {
+        Self {
+            status,
+            reason,
+            content_type: "text/plain; charset=utf-8",
+            body: reason.as_bytes().to_owned(),
+        }
+    }
+
+    fn write_to(&self, mut stream: &TcpStream) -> anyhow::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+             Cache-Control: no-cache\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.reason,
+            self.content_type,
+            self.body.len(),
+        )?;
+        stream.write_all(&self.body)?;
+        Ok(())
+    }
+}
+
+/// The decoded path from a `GET` request's first line, without any query string
+fn request_path(request_line: &str) -> Option<String> {
+    let mut parts = request_line.split_whitespace();
+    if parts.next()? != "GET" {
+        return None;
+    }
+    let target = parts.next()?;
+    let path = target.split(['?', '#']).next()?;
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    path.starts_with('/').then(|| path.into_owned())
+}
+
+/// Whether a `Host` header names the loopback address the server listens on
+fn is_local_host(host: &str) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    name.eq_ignore_ascii_case("localhost") || name == "127.0.0.1"
+}
+
+/// The file to open the window on, preferring a README or index page
+pub fn default_file(dir: &Path) -> Option<PathBuf> {
+    index_file(dir).or_else(|| markdown_files(dir).into_iter().next())
+}
+
+fn index_file(dir: &Path) -> Option<PathBuf> {
+    INDEX_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Every markdown file under `dir` in a stable order, skipping hidden files and directories
+fn markdown_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                dirs.push(path);
+            } else if is_markdown(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_request_line() {
+        assert_eq!(
+            request_path("GET /guide/my%20page.md?raw=1 HTTP/1.1\r\n").as_deref(),
+            Some("/guide/my page.md")
+        );
+        assert_eq!(request_path("POST / HTTP/1.1\r\n"), None);
+        assert_eq!(request_path("GET\r\n"), None);
+    }
+
+    #[test]
+    fn only_local_hosts() {
+        assert!(is_local_host("localhost:8080"));
+        assert!(is_local_host("LOCALHOST"));
+        assert!(is_local_host("127.0.0.1:3000"));
+        assert!(!is_local_host("evil.example:8080"));
+        assert!(!is_local_host("localhost.evil.example"));
+        assert!(!is_local_host("127.0.0.1.nip.io"));
+    }
+
+    #[test]
+    fn stays_within_the_docs_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let docs = dir.path().join("docs");
+        std::fs::create_dir_all(docs.join("guide")).unwrap();
+        std::fs::write(docs.join("guide").join("intro.md"), "# Intro").unwrap();
+        std::fs::write(dir.path().join("secret.md"), "# Secret").unwrap();
+        std::fs::create_dir_all(docs.join(".git")).unwrap();
+        std::fs::write(docs.join(".git").join("config"), "").unwrap();
+        std::fs::write(docs.join(".env"), "").unwrap();
+
+        let server = DocsServer::new(&docs, Theme::dark_default(), None, true).unwrap();
+        assert!(server.resolve("/guide/intro.md").is_some());
+        assert_eq!(server.resolve("/../secret.md"), None);
+        assert_eq!(server.resolve("/guide/missing.md"), None);
+        assert_eq!(server.resolve("/.git/config"), None);
+        assert_eq!(server.resolve("/.env"), None);
+        assert_eq!(
+            default_file(&docs),
+            Some(docs.join("guide").join("intro.md"))
+        );
+    }
+}
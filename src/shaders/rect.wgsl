@@ -0,0 +1,36 @@
+struct InstanceInput {
+    @location(0) min: vec2<f32>,
+    @location(1) max: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    rect: InstanceInput,
+) -> VertexOutput {
+    // Two triangles covering the rectangle, as fractions of the way from its min to its max
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+    );
+    var out: VertexOutput;
+    out.color = rect.color;
+    let position = mix(rect.min, rect.max, corners[index]);
+    out.clip_position = vec4<f32>(position, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
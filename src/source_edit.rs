@@ -0,0 +1,201 @@
+//! Lightweight editing of a single paragraph's markdown source
+//!
+//! The paragraph's source takes its place in the document while it's being edited
+
+use std::ops::Range;
+
+#[derive(Debug)]
+pub struct Edit {
+    /// Byte range of the paragraph within the source
+    range: Range<usize>,
+    original: String,
+    pub text: String,
+}
+
+impl Edit {
+    /// Starts editing the source paragraph that rendered as `rendered`, which came from `lines`
+    /// of the source when those are known
+    ///
+    /// Plugins and the like can shift lines around before the markdown gets rendered, so the
+    /// lines are only used while they still contain all of the rendered text's letters and
+    /// digits in order. Otherwise this falls back to [`Self::by_text`]
+    pub fn for_paragraph(
+        source: &str,
+        rendered: &str,
+        lines: Option<(usize, usize)>,
+    ) -> anyhow::Result<Self> {
+        let needle: Vec<char> = rendered.chars().filter(|c| c.is_alphanumeric()).collect();
+        anyhow::ensure!(!needle.is_empty(), "Nothing to edit in this element");
+
+        let from_lines = lines.and_then(|lines| line_range(source, lines));
+        match from_lines {
+            Some(range) if contains_in_order(&source[range.clone()], &needle) => {
+                Ok(Self::new(source, range))
+            }
+            _ => Self::by_text(source, &needle),
+        }
+    }
+
+    /// Looks for paragraphs that contain all of the rendered text's letters and digits in order,
+    /// which skips over any markup like emphasis or link URLs. A paragraph with exactly those
+    /// letters and digits beats one that only contains them. Anything short of a single best
+    /// match is refused, since editing the wrong paragraph would silently change the user's file
+    fn by_text(source: &str, needle: &[char]) -> anyhow::Result<Self> {
+        let candidates: Vec<_> = paragraphs(source)
+            .filter(|range| contains_in_order(&source[range.clone()], needle))
+            .collect();
+        let exact: Vec<_> = candidates
+            .iter()
+            .filter(|range| {
+                source[(*range).clone()]
+                    .chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .eq(needle.iter().copied())
+            })
+            .cloned()
+            .collect();
+        let range = match (exact.as_slice(), candidates.as_slice()) {
+            ([range], _) | ([], [range]) => range.clone(),
+            ([], []) => anyhow::bail!("Couldn't find this paragraph in the source"),
+            _ => anyhow::bail!(
+                "Several paragraphs in the source match this one, so it can't be edited"
+            ),
+        };
+        Ok(Self::new(source, range))
+    }
+
+    fn new(source: &str, range: Range<usize>) -> Self {
+        let original = source[range.clone()].to_owned();
+        Self {
+            range,
+            text: original.clone(),
+            original,
+        }
+    }
+
+    /// Splices the edited paragraph back into `source`
+    pub fn apply(&self, source: &str) -> anyhow::Result<String> {
+        anyhow::ensure!(
+            source.get(self.range.clone()) == Some(self.original.as_str()),
+            "The file changed while the paragraph was being edited"
+        );
+        let mut edited = source.to_owned();
+        edited.replace_range(self.range.clone(), &self.text);
+        Ok(edited)
+    }
+
+    /// Text for the editing banner, while the source itself is shown in the document
+    pub fn prompt(&self) -> String {
+        "Editing: Enter to save, Shift+Enter for a new line, Esc to cancel".to_owned()
+    }
+}
+
+/// Byte range of the 1-based, inclusive `lines`, without the last one's newline
+fn line_range(source: &str, (first, last): (usize, usize)) -> Option<Range<usize>> {
+    let mut offset = 0;
+    let mut range: Option<Range<usize>> = None;
+    for (number, line) in (1..).zip(source.split_inclusive('\n')) {
+        if number > last {
+            break;
+        }
+        if number >= first {
+            let end = offset + line.trim_end_matches(['\n', '\r']).len();
+            range = Some(range.map_or(offset..end, |range| range.start..end));
+        }
+        offset += line.len();
+    }
+    range
+}
+
+/// Byte ranges of each blank line separated block, without its trailing newline
+fn paragraphs(source: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut start = None;
+    let mut end = 0;
+    let mut offset = 0;
+    let mut lines = source.split_inclusive('\n');
+    std::iter::from_fn(move || loop {
+        let Some(line) = lines.next() else {
+            return start.take().map(|start| start..end);
+        };
+        let line_start = offset;
+        offset += line.len();
+        if line.trim().is_empty() {
+            if let Some(start) = start.take() {
+                return Some(start..end);
+            }
+        } else {
+            start.get_or_insert(line_start);
+            end = line_start + line.trim_end_matches(['\n', '\r']).len();
+        }
+    })
+}
+
+fn contains_in_order(haystack: &str, needle: &[char]) -> bool {
+    let mut needle = needle.iter().peekable();
+    for c in haystack.chars() {
+        if needle.peek() == Some(&&c) {
+            needle.next();
+        }
+    }
+    needle.peek().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+# Title
+
+Some *emphasized* text with a [link](https://example.org).
+
+Some text
+";
+
+    #[test]
+    fn uses_source_lines() {
+        let duplicated = "Same text\n\nOther\n\nSame text\n";
+        let edit = Edit::for_paragraph(duplicated, "Same text", Some((5, 5))).unwrap();
+        assert_eq!(edit.range, 18..27);
+
+        // Lines that no longer hold the paragraph fall back to looking for its text
+        let edit = Edit::for_paragraph(SOURCE, "Some text", Some((1, 1))).unwrap();
+        assert_eq!(edit.text, "Some text");
+        assert!(Edit::for_paragraph(duplicated, "Same text", Some((3, 3))).is_err());
+    }
+
+    #[test]
+    fn finds_paragraph_through_markup() {
+        let edit = Edit::for_paragraph(SOURCE, "Some emphasized text with a link.", None).unwrap();
+        assert_eq!(
+            edit.text,
+            "Some *emphasized* text with a [link](https://example.org)."
+        );
+
+        // An exact match wins over paragraphs that only contain the same letters
+        let edit = Edit::for_paragraph(SOURCE, "Some text", None).unwrap();
+        assert_eq!(edit.text, "Some text");
+    }
+
+    #[test]
+    fn refuses_ambiguous_paragraphs() {
+        let duplicated = "Same text\n\nOther\n\nSame text\n";
+        assert!(Edit::for_paragraph(duplicated, "Same text", None).is_err());
+
+        // Neither paragraph is an exact match, so there's no telling which one was clicked
+        let loose = "Some long text\n\nSome other text\n";
+        assert!(Edit::for_paragraph(loose, "Some text", None).is_err());
+
+        assert!(Edit::for_paragraph(SOURCE, "Missing", None).is_err());
+    }
+
+    #[test]
+    fn applies_edit() {
+        let mut edit = Edit::for_paragraph(SOURCE, "Some text", None).unwrap();
+        edit.text = "Fixed text".to_owned();
+        let edited = edit.apply(SOURCE).unwrap();
+        assert!(edited.ends_with("\n\nFixed text\n"));
+
+        assert!(edit.apply("# Different file").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+//! Mapping lines of the markdown source to where they ended up in the laid out document, and back
+//!
+//! Text boxes know which lines they came from. Lines within one get spread evenly over its
+//! height, and lines between two (images, tables, and the like) over the gap between them
+
+use crate::positioner::Positioned;
+use crate::utils::Rect;
+use crate::Element;
+
+/// The source lines and bounds of every laid out text box that knows where it came from
+fn spans(elements: &[Positioned<Element>]) -> impl Iterator<Item = ((usize, usize), &Rect)> {
+    elements
+        .iter()
+        .filter_map(|element| match (&element.inner, &element.bounds) {
+            (Element::TextBox(text_box), Some(bounds)) => Some((text_box.source_lines?, bounds)),
+            _ => None,
+        })
+}
+
+/// How far `at` is from `start` to `end`, clamped to the range
+fn fraction(start: f32, end: f32, at: f32) -> f32 {
+    if end > start {
+        ((at - start) / (end - start)).clamp(0., 1.)
+    } else {
+        0.
+    }
+}
+
+/// How far down the document a line of the markdown source starts, or `None` when no element
+/// knows its source lines
+pub fn line_y(elements: &[Positioned<Element>], line: usize) -> Option<f32> {
+    // The first line and height after the last text box that came before `line`
+    let mut previous: Option<(usize, f32)> = None;
+    for ((start, end), bounds) in spans(elements) {
+        let (top, height) = (bounds.pos.1, bounds.size.1);
+        if line < start {
+            return Some(match previous {
+                Some((after, bottom)) => {
+                    let gap = fraction(after as f32, start as f32, line as f32);
+                    bottom + gap * (top - bottom)
+                }
+                None => top,
+            });
+        }
+        if line <= end {
+            let lines = (end - start + 1) as f32;
+            return Some(top + height * (line - start) as f32 / lines);
+        }
+        previous = Some((end + 1, top + height));
+    }
+    previous.map(|(_, bottom)| bottom)
+}
+
+/// The line of the markdown source shown at `y`, the inverse of [`line_y`]
+pub fn line_at(elements: &[Positioned<Element>], y: f32) -> Option<usize> {
+    let mut previous: Option<(usize, f32)> = None;
+    for ((start, end), bounds) in spans(elements) {
+        let (top, height) = (bounds.pos.1, bounds.size.1);
+        if y < top {
+            return Some(match previous {
+                Some((after, bottom)) => {
+                    let gap = fraction(bottom, top, y);
+                    after + (gap * start.saturating_sub(after) as f32) as usize
+                }
+                None => start,
+            });
+        }
+        if y < top + height {
+            let lines = (end - start + 1) as f32;
+            let line = start + (fraction(top, top + height, y) * lines) as usize;
+            return Some(line.min(end));
+        }
+        previous = Some((end + 1, top + height));
+    }
+    previous.map(|(after, _)| after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::TextBox;
+
+    fn text_box(lines: Option<(usize, usize)>, top: f32, height: f32) -> Positioned<Element> {
+        let mut text_box = TextBox::new(Vec::new(), 1.);
+        text_box.source_lines = lines;
+        let mut element = Positioned::new(text_box);
+        element.bounds = Some(Rect::new((0., top), (100., height)));
+        element
+    }
+
+    #[test]
+    fn lines_follow_the_layout() {
+        // A tall table with no source lines sits between the two paragraphs
+        let elements = vec![
+            text_box(Some((1, 2)), 0., 40.),
+            text_box(None, 40., 1000.),
+            text_box(Some((10, 10)), 1040., 20.),
+        ];
+
+        assert_eq!(line_y(&elements, 1), Some(0.));
+        assert_eq!(line_y(&elements, 2), Some(20.));
+        assert_eq!(line_y(&elements, 10), Some(1040.));
+        // Lines 3 to 9 spread over the gap that the table fills
+        assert_eq!(line_y(&elements, 6), Some(40. + 3. / 7. * 1000.));
+        assert_eq!(line_y(&elements, 50), Some(1060.));
+
+        assert_eq!(line_at(&elements, 25.), Some(2));
+        assert_eq!(line_at(&elements, 1045.), Some(10));
+        assert_eq!(line_at(&elements, 540.), Some(6));
+        assert_eq!(line_at(&elements, 5000.), Some(11));
+        assert_eq!(line_at(&[], 0.), None);
+    }
+}
@@ -0,0 +1,97 @@
+//! Running external commands with a deadline and a cap on how much output gets kept
+//!
+//! Shared by run blocks, plugins, and external renderers, so that one hung or chatty program
+//! can't stall whatever is waiting on it
+
+use std::io::{self, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What a command left behind once it exited or got killed
+pub struct Output {
+    /// `None` when the command got killed for running past its timeout
+    pub status: Option<ExitStatus>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Runs `command` with `input` on stdin, killing it once `timeout` passes
+///
+/// Only the first `max_output` bytes of each stream are kept. The rest is still read, so the
+/// command doesn't stall on a full pipe
+pub fn run(
+    command: &mut Command,
+    input: Vec<u8>,
+    timeout: Duration,
+    max_output: u64,
+) -> io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // Write from another thread so that a large input can't deadlock on a full stdout pipe
+    thread::spawn(move || stdin.write_all(&input));
+    let stdout = capture(child.stdout.take().expect("stdout is piped"), max_output);
+    let stderr = capture(child.stderr.take().expect("stderr is piped"), max_output);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().expect("Reader thread panicked"),
+        stderr: stderr.join().expect("Reader thread panicked"),
+    })
+}
+
+/// Reads a stream to its end on another thread, keeping the start of it
+fn capture(mut stream: impl Read + Send + 'static, max: u64) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut kept = Vec::new();
+        let _ = stream.by_ref().take(max).read_to_end(&mut kept);
+        let _ = io::copy(&mut stream, &mut io::sink());
+        kept
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kills_after_timeout() {
+        let mut command = Command::new("sleep");
+        command.arg("10");
+        let start = Instant::now();
+        let output = run(&mut command, Vec::new(), Duration::from_millis(100), 1024).unwrap();
+        assert!(output.status.is_none());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn caps_output() {
+        let output = run(
+            &mut Command::new("cat"),
+            b"0123456789".to_vec(),
+            Duration::from_secs(5),
+            4,
+        )
+        .unwrap();
+        assert!(output.status.unwrap().success());
+        assert_eq!(output.stdout, b"0123");
+    }
+}
@@ -1,22 +1,62 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::sync::Arc;
 
 use crate::text::{Text, TextBox, TextBoxMeasure, TextSystem};
-use crate::utils::{default, Point, Rect, Size};
+use crate::utils::{default, Align, Point, Rect, Size};
 
+use parking_lot::Mutex;
+use serde::Deserialize;
+use taffy::geometry::{Line, Point as TaffyPoint};
 use taffy::node::MeasureFunc;
 use taffy::prelude::{
-    auto, line, points, AvailableSpace, Display, Layout, Size as TaffySize, Style, Taffy,
+    auto, line, points, span, AvailableSpace, Display, Layout, Size as TaffySize, Style, Taffy,
 };
 use taffy::style::FlexDirection;
 use taffy::style::JustifyContent;
+use taffy::style::JustifySelf;
 
 pub const TABLE_ROW_GAP: f32 = 20.;
 pub const TABLE_COL_GAP: f32 = 20.;
 
-#[derive(Debug)]
+/// When tables get shown as a card per row, with each cell beside its column's header
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TableCards {
+    /// Only for tables that don't fit even with their cells wrapped
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Text formats that a table can be copied as
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TableFormat {
+    Markdown,
+    Csv,
+    Tsv,
+}
+
+/// How many columns and rows a cell stretches across, from its `colspan` and `rowspan`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CellSpan {
+    pub cols: u16,
+    pub rows: u16,
+}
+
+impl Default for CellSpan {
+    fn default() -> Self {
+        Self { cols: 1, rows: 1 }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct TableLayout {
     pub rows: Vec<Vec<Layout>>,
     pub caption_layout: Option<Layout>,
+    /// Header cells repeated as labels within cards, by column
+    pub labels: Vec<(usize, Layout)>,
     pub size: Size,
 }
 
@@ -24,6 +64,48 @@ pub struct TableLayout {
 pub struct Table {
     pub rows: Vec<Vec<TextBox>>,
     pub caption: Option<TextBox>,
+    pub cards: TableCards,
+    /// How each column's cells line up, going by the first row like markdown's delimiter row
+    pub column_aligns: Vec<Align>,
+    /// Cells that span more than one column or row, by their row and place within it
+    pub spans: BTreeMap<(usize, usize), CellSpan>,
+    layout_cache: LayoutCache,
+}
+
+/// The last layout worked out for a table along with the bounds and zoom it was for. Positioning,
+/// rendering and hovering all ask for the same one over and over, and running taffy each time
+/// adds up. Tables don't change once they're built, so nothing else can make it stale
+#[derive(Default)]
+struct LayoutCache(Mutex<Option<(Size, f32, TableLayout)>>);
+
+impl LayoutCache {
+    fn get(&self, bounds: Size, zoom: f32) -> Option<TableLayout> {
+        match &*self.0.lock() {
+            Some((cached_bounds, cached_zoom, layout))
+                if *cached_bounds == bounds && *cached_zoom == zoom =>
+            {
+                Some(layout.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn set(&self, bounds: Size, zoom: f32, layout: &TableLayout) {
+        *self.0.lock() = Some((bounds, zoom, layout.clone()));
+    }
+}
+
+// The cache is just a shortcut, so it doesn't make tables any different
+impl PartialEq for LayoutCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for LayoutCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LayoutCache")
+    }
 }
 
 impl Table {
@@ -35,6 +117,52 @@ impl Table {
         self.caption = Some(caption);
     }
 
+    pub fn span(&self, row: usize, index: usize) -> CellSpan {
+        self.spans.get(&(row, index)).copied().unwrap_or_default()
+    }
+
+    /// The grid column that each cell starts at, along with every slot of the grid that's taken.
+    /// Like in HTML, cells skip over the slots that cells from the rows above span down into
+    fn grid(&self) -> (Vec<Vec<usize>>, HashSet<(usize, usize)>) {
+        let mut taken = HashSet::new();
+        let mut columns = Vec::new();
+        for (y, row) in self.rows.iter().enumerate() {
+            let mut row_columns = Vec::new();
+            let mut x = 0;
+            for index in 0..row.len() {
+                while taken.contains(&(y, x)) {
+                    x += 1;
+                }
+                let span = self.span(y, index);
+                for dy in 0..usize::from(span.rows) {
+                    for dx in 0..usize::from(span.cols) {
+                        taken.insert((y + dy, x + dx));
+                    }
+                }
+                row_columns.push(x);
+                x += usize::from(span.cols);
+            }
+            columns.push(row_columns);
+        }
+        (columns, taken)
+    }
+
+    /// The grid column that the next cell pushed onto the last row starts at
+    pub fn next_column(&self) -> usize {
+        let (columns, taken) = self.grid();
+        let Some(y) = self.rows.len().checked_sub(1) else {
+            return 0;
+        };
+        let mut x = match columns[y].last() {
+            Some(&x) => x + usize::from(self.span(y, columns[y].len() - 1).cols),
+            None => 0,
+        };
+        while taken.contains(&(y, x)) {
+            x += 1;
+        }
+        x
+    }
+
     pub fn find_hoverable<'a>(
         &'a self,
         text_system: &mut TextSystem,
@@ -92,10 +220,103 @@ impl Table {
         bounds: Size,
         zoom: f32,
     ) -> anyhow::Result<TableLayout> {
-        let max_columns = self
-            .rows
+        if let Some(layout) = self.layout_cache.get(bounds, zoom) {
+            return Ok(layout);
+        }
+        let layout = self.compute_layout(text_system, taffy, bounds, zoom)?;
+        self.layout_cache.set(bounds, zoom, &layout);
+        Ok(layout)
+    }
+
+    fn compute_layout(
+        &self,
+        text_system: &mut TextSystem,
+        taffy: &mut Taffy,
+        bounds: Size,
+        zoom: f32,
+    ) -> anyhow::Result<TableLayout> {
+        match self.cards {
+            TableCards::Never => self.grid_layout(text_system, taffy, bounds, zoom),
+            TableCards::Always => Ok(self.card_layout(text_system, bounds, zoom)),
+            TableCards::Auto => {
+                let grid = self.grid_layout(text_system, taffy, bounds, zoom)?;
+                // Cells already wrap as much as they can, so anything past the bounds is cut off
+                let overflows = grid
+                    .rows
+                    .iter()
+                    .flatten()
+                    .any(|cell| cell.location.x + cell.size.width > bounds.0 + 1.);
+                if overflows {
+                    Ok(self.card_layout(text_system, bounds, zoom))
+                } else {
+                    Ok(grid)
+                }
+            }
+        }
+    }
+
+    /// Lays out each row after the header as a card with a `header | cell` line per column
+    fn card_layout(&self, text_system: &mut TextSystem, bounds: Size, zoom: f32) -> TableLayout {
+        let header = self.rows.first().map(Vec::as_slice).unwrap_or_default();
+        let unbounded = (f32::INFINITY, f32::INFINITY);
+        let label_width = header
             .iter()
-            .fold(0, |max, row| std::cmp::max(row.len(), max));
+            .map(|label| label.size(text_system, unbounded, zoom).0)
+            .fold(0., f32::max)
+            .min(bounds.0 * 0.4);
+        let value_x = label_width + TABLE_COL_GAP;
+        let value_width = (bounds.0 - value_x).max(0.);
+
+        let mut y = 0.;
+        let caption_layout = self
+            .caption
+            .as_ref()
+            .filter(|caption| !caption.texts.is_empty())
+            .map(|caption| {
+                let size = caption.size(text_system, (bounds.0, f32::INFINITY), zoom);
+                y = size.1 + 10.;
+                cell_layout((0., 0.), size)
+            });
+
+        // The header only shows up as the cards' labels
+        let mut rows = vec![Vec::new()];
+        let mut labels = Vec::new();
+        for row in self.rows.iter().skip(1) {
+            let mut cells = Vec::new();
+            for (col, cell) in row.iter().enumerate() {
+                let label_height = header.get(col).map_or(0., |label| {
+                    let height = label
+                        .size(text_system, (label_width, f32::INFINITY), zoom)
+                        .1;
+                    labels.push((col, cell_layout((0., y), (label_width, height))));
+                    height
+                });
+                let height = cell.size(text_system, (value_width, f32::INFINITY), zoom).1;
+                cells.push(cell_layout((value_x, y), (value_width, height)));
+                y += height.max(label_height) + TABLE_ROW_GAP / 4.;
+            }
+            rows.push(cells);
+            // Leaves room for the line between cards
+            y += TABLE_ROW_GAP * 3. / 4.;
+        }
+
+        TableLayout {
+            rows,
+            caption_layout,
+            labels,
+            size: (bounds.0, y),
+        }
+    }
+
+    fn grid_layout(
+        &self,
+        text_system: &mut TextSystem,
+        taffy: &mut Taffy,
+        bounds: Size,
+        zoom: f32,
+    ) -> anyhow::Result<TableLayout> {
+        let (columns, taken) = self.grid();
+        let max_columns = taken.iter().map(|&(_, x)| x + 1).max().unwrap_or_default();
 
         // Create caption node if present and non-empty
         let caption_node = if let Some(ref caption) = self.caption {
@@ -150,7 +371,9 @@ impl Table {
         let mut node_row = Vec::new();
 
         for (y, row) in self.rows.iter().enumerate() {
-            for (x, item) in row.iter().enumerate() {
+            for (index, item) in row.iter().enumerate() {
+                let x = columns[y][index];
+                let cell_span = self.span(y, index);
                 let item = item.clone();
                 let textbox_measure = TextBoxMeasure {
                     font_system: text_system.font_system.clone(),
@@ -158,10 +381,23 @@ impl Table {
                     textbox: Arc::new(item.clone()),
                     zoom,
                 };
+                // Left aligned cells still fill their column so that rows span the whole table
+                let justify_self = match self.column_aligns.get(x).copied().unwrap_or_default() {
+                    Align::Left => JustifySelf::Stretch,
+                    Align::Center => JustifySelf::Center,
+                    Align::Right => JustifySelf::End,
+                };
                 node_row.push(taffy.new_leaf_with_measure(
                     Style {
-                        grid_row: line(y as i16 + 1),
-                        grid_column: line(x as i16 + 1),
+                        grid_row: Line {
+                            start: line(y as i16 + 1),
+                            end: span(cell_span.rows),
+                        },
+                        grid_column: Line {
+                            start: line(x as i16 + 1),
+                            end: span(cell_span.cols),
+                        },
+                        justify_self: Some(justify_self),
                         ..default()
                     },
                     MeasureFunc::Boxed(Box::new(move |known_dimensions, available_space| {
@@ -234,6 +470,7 @@ impl Table {
         Ok(TableLayout {
             rows: rows_layout,
             caption_layout,
+            labels: Vec::new(),
             size: (size.width, size.height),
         })
     }
@@ -241,4 +478,139 @@ impl Table {
     pub fn push_row(&mut self, row: Vec<TextBox>) {
         self.rows.push(row);
     }
+
+    /// Writes the table back out as text. The first row is taken to be the header. None of the
+    /// formats can span cells, so each cell goes in the column it starts at and the slots it
+    /// spans over are left empty
+    pub fn serialize(&self, format: TableFormat) -> String {
+        let (starts, taken) = self.grid();
+        let columns = taken.iter().map(|&(_, x)| x + 1).max().unwrap_or_default();
+        let mut out = String::new();
+        for (i, row) in self.rows.iter().enumerate() {
+            let mut cells = vec![String::new(); columns];
+            for (cell, &x) in row.iter().zip(&starts[i]) {
+                let text = cell.plain_text();
+                let text = text.trim();
+                cells[x] = match format {
+                    TableFormat::Markdown => text.replace(['\r', '\n'], " ").replace('|', "\\|"),
+                    TableFormat::Csv if text.contains([',', '"', '\r', '\n']) => {
+                        format!("\"{}\"", text.replace('"', "\"\""))
+                    }
+                    TableFormat::Csv => text.to_owned(),
+                    TableFormat::Tsv => text.replace(['\t', '\r', '\n'], " "),
+                };
+            }
+            match format {
+                TableFormat::Markdown => {
+                    out.push_str(&format!("| {} |\n", cells.join(" | ")));
+                    if i == 0 {
+                        let delimiters: Vec<_> = (0..columns)
+                            .map(|col| match self.column_aligns.get(col) {
+                                Some(Align::Left) => ":---",
+                                Some(Align::Center) => ":---:",
+                                Some(Align::Right) => "---:",
+                                None => "---",
+                            })
+                            .collect();
+                        out.push_str(&format!("| {} |\n", delimiters.join(" | ")));
+                    }
+                }
+                TableFormat::Csv => out.push_str(&format!("{}\n", cells.join(","))),
+                TableFormat::Tsv => out.push_str(&format!("{}\n", cells.join("\t"))),
+            }
+        }
+        out
+    }
+}
+
+fn cell_layout(location: Point, size: Size) -> Layout {
+    Layout {
+        order: 0,
+        size: TaffySize {
+            width: size.0,
+            height: size.1,
+        },
+        location: TaffyPoint {
+            x: location.0,
+            y: location.1,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(rows: &[&[&str]]) -> Table {
+        let mut table = Table::new();
+        for row in rows {
+            let row = row
+                .iter()
+                .map(|cell| TextBox::new(vec![Text::new(cell.to_string(), 1., [0.; 4])], 1.))
+                .collect();
+            table.push_row(row);
+        }
+        table
+    }
+
+    #[test]
+    fn spans_push_cells_over() {
+        let mut table = table(&[&["wide", "tall"], &["a"], &["b", "c"]]);
+        table.spans.insert((0, 0), CellSpan { cols: 2, rows: 1 });
+        table.spans.insert((0, 1), CellSpan { cols: 1, rows: 2 });
+        let (columns, taken) = table.grid();
+        assert_eq!(columns, [vec![0, 2], vec![0], vec![0, 1]]);
+        assert!(taken.contains(&(1, 2)));
+        table.push_row(Vec::new());
+        assert_eq!(table.next_column(), 0);
+        table.rows[3].push(TextBox::new(Vec::new(), 1.));
+        table.spans.insert((3, 0), CellSpan { cols: 3, rows: 1 });
+        assert_eq!(table.next_column(), 3);
+    }
+
+    #[test]
+    fn serialize_formats() {
+        let table = table(&[
+            &["Name", "Notes"],
+            &["a|b", "says \"hi\", twice"],
+            &["tab\tbed"],
+        ]);
+        assert_eq!(
+            table.serialize(TableFormat::Markdown),
+            "| Name | Notes |\n| --- | --- |\n| a\\|b | says \"hi\", twice |\n| tab\tbed |  |\n"
+        );
+        assert_eq!(
+            table.serialize(TableFormat::Csv),
+            "Name,Notes\na|b,\"says \"\"hi\"\", twice\"\ntab\tbed,\n"
+        );
+        assert_eq!(
+            table.serialize(TableFormat::Tsv),
+            "Name\tNotes\na|b\tsays \"hi\", twice\ntab bed\t\n"
+        );
+    }
+
+    #[test]
+    fn serialize_pads_spans() {
+        let mut table = table(&[&["wide", "tall"], &["a"], &["b", "c\rd"]]);
+        table.spans.insert((0, 0), CellSpan { cols: 2, rows: 1 });
+        table.spans.insert((0, 1), CellSpan { cols: 1, rows: 2 });
+        assert_eq!(
+            table.serialize(TableFormat::Csv),
+            "wide,,tall\na,,\nb,\"c\rd\",\n"
+        );
+        assert_eq!(
+            table.serialize(TableFormat::Tsv),
+            "wide\t\ttall\na\t\t\nb\tc d\t\n"
+        );
+    }
+
+    #[test]
+    fn markdown_keeps_alignment() {
+        let mut table = table(&[&["a", "b", "c"], &["1", "2", "3"]]);
+        table.column_aligns = vec![Align::Left, Align::Center, Align::Right];
+        assert_eq!(
+            table.serialize(TableFormat::Markdown),
+            "| a | b | c |\n| :--- | :---: | ---: |\n| 1 | 2 | 3 |\n"
+        );
+    }
 }
@@ -1,4 +1,4 @@
-use std::borrow::BorrowMut;
+use std::borrow::{BorrowMut, Cow};
 use std::collections::hash_map;
 use std::fmt;
 use std::hash::{BuildHasher, Hash, Hasher};
@@ -15,6 +15,7 @@ use smart_debug::SmartDebug;
 use taffy::prelude::{AvailableSpace, Size as TaffySize};
 
 use crate::debug_impls::{self, DebugInline, DebugInlineMaybeF32Color};
+use crate::search::{self, SearchOptions};
 use crate::selection::{Selection, SelectionKind, SelectionMode};
 use crate::utils::{Align, Line, Point, Rect, Size};
 
@@ -64,14 +65,29 @@ pub struct TextBox {
     #[debug(wrapper = DebugInlineMaybeF32Color)]
     pub background_color: Option<[f32; 4]>,
     pub is_code_block: bool,
+    /// Numbers each line of a code block in a gutter to its left
+    pub line_numbers: bool,
+    /// What a code block is written in, shown in a corner of its background
+    #[debug(wrapper = DebugInline)]
+    pub language: Option<String>,
     #[debug(wrapper = DebugInline)]
     pub is_quote_block: Option<usize>,
     #[debug(wrapper = DebugInline)]
     pub is_checkbox: Option<bool>,
+    /// Anything within a list, which gets the list's spacing rather than a paragraph's
+    pub is_list_item: bool,
     #[debug(wrapper = DebugInline)]
     pub is_anchor: Option<String>,
+    /// Anchors set after the first one e.g. from several footnote references in one paragraph
+    pub more_anchors: Vec<String>,
     #[debug(wrapper = DebugInline)]
     pub is_header: bool,
+    /// Which of `h1` to `h6` a header is, used to tell what folding it hides
+    #[debug(wrapper = DebugInline)]
+    pub header_level: Option<u8>,
+    /// The first and last lines of the markdown this came from, when they're being kept track of
+    #[debug(wrapper = DebugInline)]
+    pub source_lines: Option<(usize, usize)>,
     #[debug(no_skip)]
     pub texts: Vec<Text>,
     #[debug(skip)]
@@ -85,10 +101,16 @@ impl Default for TextBox {
             font_size: 16.0,
             texts: Vec::new(),
             is_code_block: false,
+            line_numbers: false,
+            language: None,
             is_quote_block: None,
             is_checkbox: None,
+            is_list_item: false,
             is_anchor: None,
+            more_anchors: Vec::new(),
             is_header: false,
+            header_level: None,
+            source_lines: None,
             align: Align::default(),
             hidpi_scale: 1.0,
             padding_height: 0.0,
@@ -117,6 +139,12 @@ impl CachedTextArea {
             scale: 1.,
         }
     }
+
+    /// Cuts off anything that would get drawn above `top` on screen
+    pub fn clip_top(mut self, top: f32) -> Self {
+        self.bounds.top = top.ceil() as i32;
+        self
+    }
 }
 
 impl TextBox {
@@ -132,6 +160,129 @@ impl TextBox {
         self.is_code_block = is_code_block;
     }
 
+    pub fn set_line_numbers(&mut self, line_numbers: bool) {
+        self.line_numbers = line_numbers;
+    }
+
+    /// The line numbers for a code block, right-aligned in the code's own font. They're a
+    /// separate box so that they never get selected or copied along with the code
+    pub fn gutter(&self) -> Option<TextBox> {
+        if !self.is_code_block || !self.line_numbers {
+            return None;
+        }
+        let first = self.texts.first()?;
+
+        let code = self.plain_text();
+        let line_count = code.trim_end_matches('\n').lines().count().max(1);
+        let digits = line_count.to_string().len();
+        let numbers = (1..=line_count)
+            .map(|n| format!("{n:>digits$}\n"))
+            .collect::<String>();
+
+        let [r, g, b, a] = first.default_color;
+        let text = Text::new(numbers, self.hidpi_scale, [r, g, b, a / 2.])
+            .with_family(first.font_family.clone());
+        let mut gutter = TextBox::new(vec![text], self.hidpi_scale);
+        gutter.font_size = self.font_size;
+        gutter.set_code_block(true);
+        Some(gutter)
+    }
+
+    /// A small label with the code block's language, in the code's own font
+    pub fn language_badge(&self) -> Option<TextBox> {
+        let language = self.language.as_ref().filter(|_| self.is_code_block)?;
+        let first = self.texts.first()?;
+
+        let [r, g, b, a] = first.default_color;
+        let text = Text::new(language.to_owned(), self.hidpi_scale, [r, g, b, a * 0.6])
+            .with_family(first.font_family.clone());
+        let mut badge = TextBox::new(vec![text], self.hidpi_scale);
+        badge.font_size = self.font_size * 0.75;
+        Some(badge)
+    }
+
+    /// The `↪` that marks where a soft wrapped line of code carries on, in the code's own font
+    pub fn wrap_indicator(&self) -> Option<TextBox> {
+        let first = self.texts.first().filter(|_| self.is_code_block)?;
+
+        let [r, g, b, a] = first.default_color;
+        let text = Text::new("↪".to_owned(), self.hidpi_scale, [r, g, b, a / 2.])
+            .with_family(first.font_family.clone());
+        let mut indicator = TextBox::new(vec![text], self.hidpi_scale);
+        indicator.font_size = self.font_size * 0.75;
+        Some(indicator)
+    }
+
+    /// How wide a character of a code block is in its own font
+    pub fn code_char_width(&self, text_system: &mut TextSystem, zoom: f32) -> Option<f32> {
+        const SAMPLE: &str = "0123456789";
+        let first = self.texts.first().filter(|_| self.is_code_block)?;
+
+        let text = Text::new(SAMPLE.to_owned(), self.hidpi_scale, first.default_color)
+            .with_family(first.font_family.clone());
+        let mut sample = TextBox::new(vec![text], self.hidpi_scale);
+        sample.font_size = self.font_size;
+        let unbounded = (f32::INFINITY, f32::INFINITY);
+        Some(sample.size(text_system, unbounded, zoom).0 / SAMPLE.len() as f32)
+    }
+
+    /// The rows, counting from the top, that carry on a line that was too long to fit in `bounds`
+    pub fn wrapped_rows(
+        &self,
+        text_system: &mut TextSystem,
+        bounds: Size,
+        zoom: f32,
+    ) -> Vec<usize> {
+        let mut cache = text_system.text_cache.lock();
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
+        let mut previous_line = None;
+        let mut rows = Vec::new();
+        for (row, run) in buffer.layout_runs().enumerate() {
+            if previous_line == Some(run.line_i) {
+                rows.push(row);
+            }
+            previous_line = Some(run.line_i);
+        }
+        rows
+    }
+
+    /// How far down the top of the `line`th line (counting from 0) sits once wrapped to `bounds`
+    pub fn line_top(
+        &self,
+        text_system: &mut TextSystem,
+        bounds: Size,
+        zoom: f32,
+        line: usize,
+    ) -> f32 {
+        let mut cache = text_system.text_cache.lock();
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
+        let rows = buffer
+            .layout_runs()
+            .take_while(|run| run.line_i < line)
+            .count();
+        rows as f32 * self.line_height(zoom)
+    }
+
+    /// How far the code gets pushed right to make room for its line numbers
+    pub fn gutter_width(&self, text_system: &mut TextSystem, zoom: f32) -> f32 {
+        match self.gutter() {
+            Some(gutter) => {
+                let unbounded = (f32::INFINITY, f32::INFINITY);
+                gutter.size(text_system, unbounded, zoom).0
+                    + self.font_size * self.hidpi_scale * zoom
+            }
+            None => 0.,
+        }
+    }
+
     pub fn set_quote_block(&mut self, nest: usize) {
         self.is_quote_block = Some(nest);
     }
@@ -144,7 +295,10 @@ impl TextBox {
         self.is_checkbox = Some(is_checked);
     }
     pub fn set_anchor(&mut self, anchor: String) {
-        self.is_anchor = Some(anchor);
+        match self.is_anchor {
+            Some(_) => self.more_anchors.push(anchor),
+            None => self.is_anchor = Some(anchor),
+        }
     }
 
     pub fn set_background_color(&mut self, color: [f32; 4]) {
@@ -168,11 +322,17 @@ impl TextBox {
         self.font_size * 1.1 * self.hidpi_scale * zoom
     }
 
+    /// All of the text without any of its styling
+    pub fn plain_text(&self) -> String {
+        self.texts.iter().map(|text| text.text.as_str()).collect()
+    }
+
     pub fn key(&self, bounds: Size, zoom: f32) -> Key<'_> {
         let mut lines = Vec::new();
         let mut sections = Vec::new();
         for (i, text) in self.texts.iter().enumerate() {
-            sections.append(&mut text.section_keys(i));
+            // Code blocks keep their own line breaks and scroll sideways instead
+            sections.append(&mut text.section_keys(i, !self.is_code_block));
             if text.text.ends_with('\n') {
                 lines.push(sections.clone());
                 sections.clear();
@@ -191,7 +351,9 @@ impl TextBox {
         }
     }
 
-    /// Returns the [`Text`] in the given [`TextSystem`] with the cursor over it, if any.
+    /// Returns the [`Text`] in the given [`TextSystem`] with the cursor over it, if any. Only the
+    /// glyphs themselves count, so the space past the end of a line doesn't pick up a link that
+    /// happens to end it
     pub fn find_hoverable<'a>(
         &'a self,
         text_system: &mut TextSystem,
@@ -204,6 +366,7 @@ impl TextBox {
             return None;
         }
 
+        let line_height = self.line_height(zoom);
         let mut cache = text_system.text_cache.lock();
 
         let (_, buffer) = cache.allocate(
@@ -211,17 +374,50 @@ impl TextBox {
             self.key(bounds, zoom),
         );
 
-        if let Some(cursor) = buffer.hit(loc.0 - screen_position.0, loc.1 - screen_position.1) {
-            let line = &buffer.lines[cursor.line];
-            let mut index = cursor.index;
-            if cursor.affinity == Affinity::Before {
-                index = index.saturating_sub(1);
+        let x = loc.0 - screen_position.0;
+        let mut y = screen_position.1;
+        for run in buffer.layout_runs() {
+            if loc.1 >= y && loc.1 < y + line_height {
+                return run
+                    .glyphs
+                    .iter()
+                    .find(|glyph| x >= glyph.x && x < glyph.x + glyph.w)
+                    .map(|glyph| &self.texts[glyph.metadata]);
             }
-            let text = &self.texts[line.attrs_list().get_span(index).metadata];
-            Some(text)
-        } else {
-            None
+            y += line_height;
         }
+
+        None
+    }
+
+    /// Whether `loc` lands on a line's text rather than the space around it
+    pub fn is_over_text(
+        &self,
+        text_system: &mut TextSystem,
+        loc: Point,
+        screen_position: Point,
+        bounds: Size,
+        zoom: f32,
+    ) -> bool {
+        let line_height = self.line_height(zoom);
+        let mut cache = text_system.text_cache.lock();
+
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
+        let x = loc.0 - screen_position.0;
+        let mut y = screen_position.1;
+        for run in buffer.layout_runs() {
+            if loc.1 >= y && loc.1 < y + line_height {
+                let width = run.glyphs.last().map_or(0., |glyph| glyph.x + glyph.w);
+                return x >= 0. && x <= width;
+            }
+            y += line_height;
+        }
+
+        false
     }
 
     pub fn size(&self, text_system: &mut TextSystem, bounds: Size, zoom: f32) -> Size {
@@ -248,15 +444,8 @@ impl TextBox {
 
         let line_height = self.line_height(zoom);
 
-        let (_, paragraph) =
-            cache.allocate(font_system.lock().borrow_mut(), self.key(bounds, zoom));
-
-        let (total_lines, max_width) = paragraph
-            .layout_runs()
-            .enumerate()
-            .fold((0, 0.0), |(_, max), (i, buffer)| {
-                (i + 1, buffer.line_w.max(max))
-            });
+        let (total_lines, max_width) =
+            cache.measure(font_system.lock().borrow_mut(), self.key(bounds, zoom));
 
         (
             max_width,
@@ -417,9 +606,43 @@ impl TextBox {
         zoom: f32,
         selection: &mut Selection,
     ) -> Option<Vec<Rect>> {
+        let line_height = self.line_height(zoom);
+        let mut cache = text_system.text_cache.lock();
+
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
         let mut rects = Vec::new();
-        let mut selected_text = String::new();
+        let mut selected_texts = Vec::new();
+        for range in selection.ranges() {
+            if let Some((mut range_rects, text)) =
+                self.select_range(buffer, screen_position, bounds, line_height, range)
+            {
+                rects.append(&mut range_rects);
+                selected_texts.push(text);
+            }
+        }
+        if selected_texts.is_empty() {
+            return None;
+        }
+        for text in &selected_texts {
+            selection.add_line(text);
+        }
 
+        Some(rects)
+    }
+
+    /// Where a caret before byte `index` of the first line goes, relative to the top left of the
+    /// text box. Anything past the end of the line puts the caret after the last character
+    pub fn caret_position(
+        &self,
+        text_system: &mut TextSystem,
+        bounds: Size,
+        zoom: f32,
+        index: usize,
+    ) -> Point {
         let line_height = self.line_height(zoom);
         let mut cache = text_system.text_cache.lock();
 
@@ -428,7 +651,131 @@ impl TextBox {
             self.key(bounds, zoom),
         );
 
-        let (start_cursor, end_cursor, start_y, end_y) = match &selection.selection {
+        // The index doesn't count any soft breaks that got added for shaping
+        let index = buffer.lines.first().map_or(index, |line| {
+            let (_, offsets) = without_soft_breaks(line.text());
+            offsets[index.min(offsets.len() - 1)]
+        });
+        let mut caret = (0., 0.);
+        let mut y = 0.;
+        for run in buffer.layout_runs().filter(|run| run.line_i == 0) {
+            for glyph in run.glyphs {
+                if glyph.end > index {
+                    return (glyph.x, y);
+                }
+                caret = (glyph.x + glyph.w, y);
+            }
+            y += line_height;
+        }
+        caret
+    }
+
+    /// Highlights every occurrence of the search `query`, using the shaped glyph positions so
+    /// that the boxes line up with the text no matter the font, wrapping, or alignment. Each
+    /// match gets a box per line it spans
+    pub fn search_highlights(
+        &self,
+        text_system: &mut TextSystem,
+        screen_position: Point,
+        bounds: Size,
+        zoom: f32,
+        query: &str,
+        options: SearchOptions,
+    ) -> Vec<Vec<Rect>> {
+        let line_height = self.line_height(zoom);
+        let mut cache = text_system.text_cache.lock();
+
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
+        let matches: Vec<_> = buffer
+            .lines
+            .iter()
+            .map(|line| {
+                let (text, offsets) = without_soft_breaks(line.text());
+                search::match_ranges(&text, query, options)
+                    .into_iter()
+                    .map(|range| offsets[range.start]..offsets[range.end])
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        if matches.iter().all(Vec::is_empty) {
+            return Vec::new();
+        }
+
+        // Where each line's matches start among all of them
+        let firsts: Vec<_> = matches
+            .iter()
+            .scan(0, |first, line| {
+                let this = *first;
+                *first += line.len();
+                Some(this)
+            })
+            .collect();
+        let mut highlights = vec![Vec::new(); matches.iter().map(Vec::len).sum()];
+        let mut y = screen_position.1;
+        for run in buffer.layout_runs() {
+            for (i, range) in matches[run.line_i].iter().enumerate() {
+                let start = Cursor::new(run.line_i, range.start);
+                let end = Cursor::new(run.line_i, range.end);
+                // Matches that wrap onto the next run get highlighted piecewise
+                if let Some((highlight_x, highlight_w)) = run.highlight(start, end) {
+                    let x = screen_position.0 + highlight_x;
+                    highlights[firsts[run.line_i] + i].push(Rect::from_min_max(
+                        (x.floor(), y),
+                        ((x + highlight_w).ceil(), y + line_height),
+                    ));
+                }
+            }
+            y += line_height;
+        }
+
+        highlights.retain(|rects| !rects.is_empty());
+        highlights
+    }
+
+    /// Highlights and extracts the text covered by a single selected range
+    fn select_range(
+        &self,
+        buffer: &glyphon::Buffer,
+        screen_position: Point,
+        bounds: Size,
+        line_height: f32,
+        range: &SelectionKind,
+    ) -> Option<(Vec<Rect>, String)> {
+        let mut rects = Vec::new();
+        let mut selected_text = String::new();
+
+        if let SelectionKind::Blocks(tops) = range {
+            let is_selected =
+                self.is_code_block && tops.iter().any(|top| (top - screen_position.1).abs() < 0.5);
+            if !is_selected {
+                return None;
+            }
+
+            let last_line = buffer.lines.len().checked_sub(1)?;
+            let start = Cursor::new(0, 0);
+            let end = Cursor::new(last_line, buffer.lines[last_line].text().len());
+            let mut y = screen_position.1;
+            for run in buffer.layout_runs() {
+                if let Some((highlight_x, highlight_w)) = run.highlight(start, end) {
+                    let x = screen_position.0 + highlight_x;
+                    rects.push(Rect::from_min_max(
+                        (x.floor(), y),
+                        ((x + highlight_w).ceil(), y + line_height),
+                    ));
+                }
+                y += line_height;
+            }
+
+            // Keep the code's own line breaks instead of joining lines with spaces
+            let code: Vec<_> = buffer.lines.iter().map(|line| line.text()).collect();
+            return Some((rects, code.join("\n")));
+        }
+
+        let (start_cursor, end_cursor, start_y, end_y) = match range {
             SelectionKind::Drag { mut start, mut end } => {
                 if start.1 > end.1 || start.0 > end.0 {
                     std::mem::swap(&mut start, &mut end);
@@ -553,7 +900,8 @@ impl TextBox {
                     if (left_glyph_cursor >= start_cursor && left_glyph_cursor <= end_cursor)
                         && (right_glyph_cursor >= start_cursor && right_glyph_cursor <= end_cursor)
                     {
-                        selected_text.push_str(&line.text[glyph.start..glyph.end]);
+                        let glyph_text = &line.text[glyph.start..glyph.end];
+                        selected_text.extend(glyph_text.chars().filter(|&c| c != SOFT_BREAK));
                     }
                 }
                 if end_y > y + line_height {
@@ -563,9 +911,7 @@ impl TextBox {
             y += line_height;
         }
 
-        selection.add_line(&selected_text);
-
-        Some(rects)
+        Some((rects, selected_text))
     }
 }
 
@@ -666,7 +1012,16 @@ impl Text {
         }
     }
 
-    pub fn section_keys(&self, index: usize) -> Vec<SectionKey<'_>> {
+    /// Inline code and URLs are long runs without spaces that would overflow the text box
+    fn wants_soft_breaks(&self) -> bool {
+        self.font_family == FamilyOwned::Monospace
+            || self
+                .text
+                .split_whitespace()
+                .any(|word| word.contains("://") || word.starts_with("www."))
+    }
+
+    pub fn section_keys(&self, index: usize, allow_soft_breaks: bool) -> Vec<SectionKey<'_>> {
         let color = self.color();
         let color = Color::rgba(
             (color[0] * 255.) as u8,
@@ -679,10 +1034,15 @@ impl Text {
             weight: self.weight(),
             style: self.style(),
         };
+        let soft_breaks = allow_soft_breaks && self.wants_soft_breaks();
         self.text
             .lines()
             .map(|line| SectionKey {
-                content: line,
+                content: if soft_breaks {
+                    with_soft_breaks(line)
+                } else {
+                    Cow::Borrowed(line)
+                },
                 font,
                 color,
                 index,
@@ -698,9 +1058,49 @@ struct Font<'a> {
     style: glyphon::Style,
 }
 
-#[derive(Clone, Copy, Hash)]
+/// A zero-width space, used as an extra line break opportunity
+const SOFT_BREAK: char = '\u{200B}';
+
+/// Lets long URLs and paths wrap after `/`, `-`, `.` and the like. Breaks only go between two
+/// characters of the same word, so `//` and trailing punctuation stay together
+fn with_soft_breaks(text: &str) -> Cow<'_, str> {
+    let is_break = |c: char| matches!(c, '/' | '-' | '.' | '_' | '?' | '&' | '=' | '#');
+    if !text.contains(is_break) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut broken = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        broken.push(c);
+        let next = chars.peek().copied();
+        if is_break(c) && next.is_some_and(|next| !next.is_whitespace() && !is_break(next)) {
+            broken.push(SOFT_BREAK);
+        }
+    }
+    if broken.len() == text.len() {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(broken)
+    }
+}
+
+/// `text` without the soft breaks, along with the byte offset in `text` of each byte of the
+/// result (and its end)
+fn without_soft_breaks(text: &str) -> (String, Vec<usize>) {
+    let mut stripped = String::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    for (i, c) in text.char_indices().filter(|&(_, c)| c != SOFT_BREAK) {
+        stripped.push(c);
+        offsets.extend(i..i + c.len_utf8());
+    }
+    offsets.push(text.len());
+    (stripped, offsets)
+}
+
+#[derive(Clone, Hash)]
 pub struct SectionKey<'a> {
-    content: &'a str,
+    content: Cow<'a, str>,
     font: Font<'a>,
     color: Color,
     index: usize,
@@ -714,10 +1114,38 @@ pub struct Key<'a> {
     bounds: Size,
 }
 
+/// How many text boxes to keep measurements for before starting over
+const MEASURED_LIMIT: usize = 4_096;
+/// How many ranges of widths to keep measurements for per text box
+const RANGES_PER_BOX: usize = 8;
+
+/// How a text box wraps over a range of widths. Lines only break where the next word doesn't fit,
+/// so every width from the longest line's up to the one it got wrapped to breaks it the same way
+#[derive(Clone, Copy, Debug)]
+struct Measurement {
+    /// The longest line's width
+    min_width: f32,
+    max_width: f32,
+    lines: usize,
+}
+
+/// How often text boxes' sizes came from earlier measurements instead of reshaping them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MeasureStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// How many text boxes have measurements kept
+    pub entries: usize,
+}
+
 #[derive(Default)]
 pub struct TextCache {
     entries: FxHashMap<KeyHash, glyphon::Buffer>,
     recently_used: FxHashSet<KeyHash>,
+    /// Measurements by everything about a text box besides the width it's wrapped to. Unlike the
+    /// shaped buffers they outlive a frame, so that resizing doesn't reshape everything
+    measured: FxHashMap<KeyHash, Vec<Measurement>>,
+    stats: MeasureStats,
     hasher: HashBuilder,
 }
 
@@ -760,7 +1188,7 @@ impl TextCache {
                 let mut attrs_list = AttrsList::new(Attrs::new());
                 for section in line {
                     let start = line_str.len();
-                    line_str.push_str(section.content);
+                    line_str.push_str(&section.content);
                     let end = line_str.len();
                     attrs_list.add_span(
                         start..end,
@@ -786,6 +1214,62 @@ impl TextCache {
         (hash, self.entries.get_mut(&hash).unwrap())
     }
 
+    /// The number of lines and the longest line's width for the text wrapped to `key`'s bounds
+    fn measure(&mut self, font_system: &mut glyphon::FontSystem, key: Key<'_>) -> (usize, f32) {
+        let hash = {
+            let mut hasher = self.hasher.build_hasher();
+
+            key.lines.hash(&mut hasher);
+            key.size.to_bits().hash(&mut hasher);
+            key.line_height.to_bits().hash(&mut hasher);
+            key.bounds.1.to_bits().hash(&mut hasher);
+
+            hasher.finish()
+        };
+        let width = key.bounds.0;
+
+        let measured = self.measured.get(&hash).and_then(|ranges| {
+            ranges
+                .iter()
+                .find(|range| range.min_width <= width && width <= range.max_width)
+        });
+        if let Some(measured) = measured {
+            self.stats.hits += 1;
+            return (measured.lines, measured.min_width);
+        }
+        self.stats.misses += 1;
+
+        let (_, buffer) = self.allocate(font_system, key);
+        let (lines, line_width) = buffer
+            .layout_runs()
+            .enumerate()
+            .fold((0, 0.0), |(_, max), (i, run)| (i + 1, run.line_w.max(max)));
+
+        // Words too long for a line spill past its end, which narrower widths wrap differently
+        if line_width <= width {
+            if self.measured.len() >= MEASURED_LIMIT {
+                self.measured.clear();
+            }
+            let ranges = self.measured.entry(hash).or_default();
+            if ranges.len() >= RANGES_PER_BOX {
+                ranges.remove(0);
+            }
+            ranges.push(Measurement {
+                min_width: line_width,
+                max_width: width,
+                lines,
+            });
+        }
+        (lines, line_width)
+    }
+
+    pub fn measure_stats(&self) -> MeasureStats {
+        MeasureStats {
+            entries: self.measured.len(),
+            ..self.stats
+        }
+    }
+
     pub fn trim(&mut self) {
         self.entries
             .retain(|key, _| self.recently_used.contains(key));
@@ -801,3 +1285,24 @@ pub struct TextSystem {
     pub text_cache: Arc<Mutex<TextCache>>,
     pub swash_cache: SwashCache,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soft_breaks_in_urls() {
+        let url = "https://example.com/some-path?q=1";
+        let broken = with_soft_breaks(url);
+        assert_eq!(
+            broken,
+            "https://\u{200B}example.\u{200B}com/\u{200B}some-\u{200B}path?\u{200B}q=\u{200B}1"
+        );
+        assert!(matches!(with_soft_breaks("plain words."), Cow::Borrowed(_)));
+
+        let (stripped, offsets) = without_soft_breaks(&broken);
+        assert_eq!(stripped, url);
+        let com = stripped.find("com").unwrap();
+        assert_eq!(&broken[offsets[com]..offsets[com + "com".len()]], "com");
+    }
+}
@@ -0,0 +1,34 @@
+//! Short-lived messages in the banner about things that happened in the background, like a link
+//! that failed to open
+
+use std::time::{Duration, Instant};
+
+/// How long a toast stays up
+const DURATION: Duration = Duration::from_secs(4);
+
+pub struct Toast {
+    message: String,
+    expires: Instant,
+}
+
+impl Toast {
+    pub fn new(message: String) -> Self {
+        Self {
+            message,
+            expires: Instant::now() + DURATION,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// When the event loop should wake up to take the toast down
+    pub fn expires(&self) -> Instant {
+        self.expires
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires <= Instant::now()
+    }
+}
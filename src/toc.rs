@@ -0,0 +1,191 @@
+//! A `[TOC]` or `<!-- toc -->` placeholder on a line of its own gets swapped for a table of
+//! contents linking to the document's headings, like a lot of wikis and static site generators do
+//!
+//! The links are anchorized the same way as the headings get anchors during interpretation, so
+//! they resolve like any other in-document link
+
+use crate::utils::{markdown_options, Directives};
+
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{parse_document, Anchorizer, Arena};
+
+/// How many heading levels get listed unless configured otherwise
+pub const DEFAULT_DEPTH: u8 = 3;
+
+/// Replaces every placeholder in `md` with a table of contents that lists headings down to
+/// `depth` levels deep, unless the front matter sets its own `toc-depth`
+pub fn insert(md: String, depth: u8, smart_punctuation: bool) -> String {
+    let directives = Directives::from_md(&md);
+    let depth = directives.toc_depth.unwrap_or(depth);
+    let mut options = markdown_options();
+    // Smart punctuation changes the heading text that anchors get made from
+    options.parse.smart = directives.smart_punctuation.unwrap_or(smart_punctuation);
+    let arena = Arena::new();
+    let root = parse_document(&arena, &md, &options);
+
+    // The 1-indexed lines holding placeholders
+    let mut placeholders = Vec::new();
+    let mut headings = Vec::new();
+    for node in root.descendants() {
+        let ast = node.data.borrow();
+        let is_placeholder = match &ast.value {
+            NodeValue::Heading(heading) => {
+                headings.push(Heading {
+                    level: heading.level,
+                    text: text(node),
+                    label: label(node),
+                });
+                false
+            }
+            NodeValue::Paragraph => text(node).trim().eq_ignore_ascii_case("[toc]"),
+            NodeValue::HtmlBlock(html) => is_toc_comment(&html.literal),
+            _ => false,
+        };
+        // Placeholders nested in lists or quotes are left alone
+        let (start, end) = (ast.sourcepos.start, ast.sourcepos.end);
+        if is_placeholder && start.column == 1 && start.line == end.line {
+            placeholders.push(start.line);
+        }
+    }
+    if placeholders.is_empty() {
+        return md;
+    }
+
+    let toc = render(&headings, depth);
+    let mut out = String::with_capacity(md.len() + toc.len() * placeholders.len());
+    for (i, line) in md.split_inclusive('\n').enumerate() {
+        if placeholders.contains(&(i + 1)) {
+            out.push_str(&toc);
+        } else {
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+struct Heading {
+    level: u8,
+    /// What the heading's anchor gets made from
+    text: String,
+    /// Markdown for the heading's text in its link
+    label: String,
+}
+
+fn is_toc_comment(html: &str) -> bool {
+    html.trim()
+        .strip_prefix("<!--")
+        .and_then(|comment| comment.strip_suffix("-->"))
+        .is_some_and(|comment| comment.trim().eq_ignore_ascii_case("toc"))
+}
+
+/// The plain text within a node, which is what headings get anchorized from
+pub fn text<'a>(node: &'a AstNode<'a>) -> String {
+    let mut text = String::new();
+    for descendant in node.descendants() {
+        match &descendant.data.borrow().value {
+            NodeValue::Text(literal) => text.push_str(literal),
+            NodeValue::Code(code) => text.push_str(&code.literal),
+            NodeValue::SoftBreak | NodeValue::LineBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// The heading's text as markdown, keeping its inline code but nothing else
+fn label<'a>(node: &'a AstNode<'a>) -> String {
+    let mut label = String::new();
+    for descendant in node.descendants() {
+        match &descendant.data.borrow().value {
+            NodeValue::Text(literal) => label.push_str(&escape(literal)),
+            NodeValue::Code(code) if code.literal.contains('`') => {
+                label.push_str(&format!("`` {} ``", code.literal));
+            }
+            NodeValue::Code(code) => label.push_str(&format!("`{}`", code.literal)),
+            NodeValue::SoftBreak | NodeValue::LineBreak => label.push(' '),
+            _ => {}
+        }
+    }
+    label
+}
+
+/// A nested list of links to the headings, followed by a blank line so that nothing after it
+/// gets pulled into the last item
+fn render(headings: &[Heading], depth: u8) -> String {
+    let mut anchorizer = Anchorizer::new();
+    let mut toc = String::new();
+    // The levels of the headings that the current one nests under
+    let mut parents: Vec<u8> = Vec::new();
+    for heading in headings {
+        // Every heading gets anchorized, even the ones too deep to list, to keep the
+        // deduplicated anchors in step with the interpreter's
+        let anchor = anchorizer.anchorize(heading.text.clone());
+        if heading.level > depth {
+            continue;
+        }
+        while parents
+            .last()
+            .is_some_and(|&parent| parent >= heading.level)
+        {
+            parents.pop();
+        }
+        let indent = "  ".repeat(parents.len());
+        toc.push_str(&format!("{indent}- [{}](#{anchor})\n", heading.label));
+        parents.push(heading.level);
+    }
+    toc.push('\n');
+    toc
+}
+
+/// Escapes anything in heading text that would turn into markup in a link's text
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '[' | ']' | '*' | '_' | '`' | '<' | '>' | '~' | '$'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_placeholders() {
+        let md = "\
+# Guide
+
+[TOC]
+
+## Install `inlyne`
+### From source
+#### Deep
+## Install `inlyne`
+
+```
+[TOC]
+```
+";
+        let toc = "\
+- [Guide](#guide)
+  - [Install `inlyne`](#install-inlyne)
+    - [From source](#from-source)
+  - [Install `inlyne`](#install-inlyne-1)
+
+";
+        let expected = md.replacen("[TOC]\n", toc, 1);
+        assert_eq!(insert(md.to_owned(), DEFAULT_DEPTH, true), expected);
+
+        let md = "## A\n<!-- TOC -->\n# B\n";
+        assert_eq!(insert(md.to_owned(), 1, true), "## A\n- [B](#b)\n\n# B\n");
+
+        let md = "No placeholder [toc] here\n";
+        assert_eq!(insert(md.to_owned(), DEFAULT_DEPTH, true), md);
+    }
+}
@@ -3,22 +3,52 @@ use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, OnceLock};
 
+use crate::encoding::Encoding;
 use crate::image::ImageData;
+use crate::table::TableCards;
+use crate::toc;
 
 use comrak::adapters::SyntaxHighlighterAdapter;
+use comrak::nodes::NodeValue;
 use comrak::plugins::syntect::{SyntectAdapter, SyntectAdapterBuilder};
-use comrak::{markdown_to_html_with_plugins, ComrakOptions};
+use comrak::{markdown_to_html_with_plugins, parse_document, Arena, ComrakOptions};
 use indexmap::IndexMap;
 use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use syntect::highlighting::{Theme as SyntectTheme, ThemeSet as SyntectThemeSet};
 use syntect::parsing::SyntaxSet;
 use winit::window::CursorIcon;
 
-pub fn format_title(file_path: &Path) -> String {
-    match root_filepath_to_vcs_dir(file_path) {
-        Some(path) => format!("Inlyne - {}", path.to_string_lossy()),
-        None => "Inlyne".to_owned(),
+/// The window title unless configured otherwise
+pub const DEFAULT_TITLE_TEMPLATE: &str = "Inlyne - {title}";
+
+/// Fills in a window title template. `{path}` is the file's path relative to its repo and
+/// `{title}` is the document's own title, falling back to the path when it doesn't have one
+pub fn format_title(
+    template: &str,
+    file_path: &Path,
+    doc_title: Option<&str>,
+    encoding: Encoding,
+) -> String {
+    let path = root_filepath_to_vcs_dir(file_path).map(|path| path.to_string_lossy().into_owned());
+    let title = match (doc_title, path) {
+        (None, None) => "Inlyne".to_owned(),
+        (doc_title, path) => {
+            let path = path.unwrap_or_default();
+            let doc_title = doc_title.unwrap_or(&path);
+            // Filled in one piece at a time so a title can't pull in a `{path}` of its own
+            template
+                .split("{path}")
+                .map(|part| part.replace("{title}", doc_title))
+                .collect::<Vec<_>>()
+                .join(&path)
+        }
+    };
+    // UTF-8 is the norm, so only other encodings are worth pointing out
+    match encoding {
+        Encoding::Utf8 => title,
+        _ => format!("{title} ({encoding})"),
     }
 }
 
@@ -55,6 +85,26 @@ fn root_filepath_to_vcs_dir(path: &Path) -> Option<PathBuf> {
     }
 }
 
+/// Splits a trailing `:line` or `:line:column` off of a link target like the ones compilers print
+///
+/// The column is accepted but dropped since we only scroll vertically
+pub fn split_line_suffix(target: &str) -> (&str, Option<usize>) {
+    let parse_suffix = |s: &str| {
+        let (rest, num) = s.rsplit_once(':')?;
+        let num = num.parse::<usize>().ok()?;
+        Some((rest, num))
+    };
+
+    match parse_suffix(target) {
+        Some((rest, last)) => match parse_suffix(rest) {
+            Some((path, line)) if !path.is_empty() => (path, Some(line)),
+            _ if !rest.is_empty() => (rest, Some(last)),
+            _ => (target, None),
+        },
+        None => (target, None),
+    }
+}
+
 pub(crate) fn default<T: Default>() -> T {
     Default::default()
 }
@@ -110,6 +160,22 @@ impl Rect {
     pub fn contains(&self, loc: Point) -> bool {
         self.pos.0 <= loc.0 && loc.0 <= self.max().0 && self.pos.1 <= loc.1 && loc.1 <= self.max().1
     }
+
+    /// Rounds the edges to whole physical pixels, so thin lines don't blur or shimmer when
+    /// scrolling. Anything visible stays at least a pixel wide
+    pub fn snap_to_pixels(&self) -> Rect {
+        let snap_axis = |start: f32, len: f32| {
+            let snapped_start = start.round();
+            let mut snapped_end = (start + len).round();
+            if len > 0. && snapped_end <= snapped_start {
+                snapped_end = snapped_start + 1.;
+            }
+            (snapped_start, snapped_end - snapped_start)
+        };
+        let (x, width) = snap_axis(self.pos.0, self.size.0);
+        let (y, height) = snap_axis(self.pos.1, self.size.1);
+        Rect::new((x, y), (width, height))
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
@@ -181,17 +247,63 @@ impl SyntaxHighlighterAdapter for CustomSyntectAdapter {
     }
 }
 
-pub fn markdown_to_html(md: &str, syntax_theme: SyntectTheme) -> String {
+/// The markdown flavor that we support
+pub fn markdown_options() -> ComrakOptions<'static> {
     let mut options = ComrakOptions::default();
     options.extension.autolink = true;
     options.extension.table = true;
     options.extension.strikethrough = true;
     options.extension.tasklist = true;
-    // options.extension.footnotes = true;
+    options.extension.footnotes = true;
     options.extension.front_matter_delimiter = Some("---".to_owned());
     options.extension.shortcodes = true;
+    options.extension.math_dollars = true;
     options.parse.smart = true;
     options.render.unsafe_ = true;
+    // Like pandoc's implicit figures, but captioned by the image's title
+    options.render.figure_with_caption = true;
+    // Keeps the rest of a fenced block's info string around e.g. for `line-numbers`
+    options.render.full_info_string = true;
+    options
+}
+
+/// Converts markdown to HTML, with smart punctuation unless the front matter sets
+/// `smart-punctuation: false`
+pub fn markdown_to_html(md: &str, syntax_theme: SyntectTheme, smart_punctuation: bool) -> String {
+    to_html(md, syntax_theme, smart_punctuation, false)
+}
+
+/// Like [`markdown_to_html`], but with each block's lines in the markdown kept in a
+/// `data-sourcepos` attribute
+pub fn markdown_to_html_with_sourcepos(
+    md: &str,
+    syntax_theme: SyntectTheme,
+    smart_punctuation: bool,
+) -> String {
+    to_html(md, syntax_theme, smart_punctuation, true)
+}
+
+fn to_html(
+    md: &str,
+    syntax_theme: SyntectTheme,
+    smart_punctuation: bool,
+    sourcepos: bool,
+) -> String {
+    let front_matter = front_matter(md);
+    let directives = Directives::from_md(md);
+    // Comrak only knows about `---` front matter, so anything else gets blanked out before it
+    // sees it
+    let blanked;
+    let md = match &front_matter {
+        Some(block) if block.format != FrontMatterFormat::Yaml => {
+            blanked = block.blank_out(md);
+            blanked.as_str()
+        }
+        _ => md,
+    };
+    let mut options = markdown_options();
+    options.parse.smart = directives.smart_punctuation.unwrap_or(smart_punctuation);
+    options.render.sourcepos = sourcepos;
 
     // TODO(cosmic): gonna send a PR so that a plugin can pass in a single theme too
     let dummy_name = "theme";
@@ -225,28 +337,19 @@ pub fn markdown_to_html(md: &str, syntax_theme: SyntectTheme) -> String {
     // {YAML value}
     // ---
     // {Markdown}
-    let html_front_matter = if md.starts_with("---") {
-        let mut parts = md.split("---");
-        let _ = parts.next();
-        parts
-            .next()
-            .and_then(
-                |front_matter| match serde_yaml::from_str::<FrontMatter>(front_matter) {
-                    Ok(front_matter) => Some(front_matter.to_table()),
-                    Err(err) => {
-                        tracing::warn!(
-                            "Failed parsing front matter. Error: {}\n{}",
-                            err,
-                            front_matter
-                        );
-                        None
-                    }
-                },
-            )
-            .unwrap_or_default()
-    } else {
-        String::new()
-    };
+    let html_front_matter = front_matter
+        .and_then(|block| match block.parse::<FrontMatter>() {
+            Ok(front_matter) => Some(front_matter.to_table()),
+            Err(err) => {
+                tracing::warn!(
+                    "Failed parsing front matter. Error: {}\n{}",
+                    err,
+                    block.contents
+                );
+                None
+            }
+        })
+        .unwrap_or_default();
 
     format!("{html_front_matter}{htmlified}")
 }
@@ -363,15 +466,155 @@ fn add_pandoc_table_captions(html: String) -> String {
     result
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FrontMatterFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+#[derive(Debug)]
+struct FrontMatterBlock<'a> {
+    format: FrontMatterFormat,
+    /// What's between the delimiters
+    contents: &'a str,
+    /// The whole block, delimiters and all
+    source: &'a str,
+}
+
+impl FrontMatterBlock<'_> {
+    fn parse<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        let parsed = match self.format {
+            // JSON is a subset of YAML
+            FrontMatterFormat::Yaml | FrontMatterFormat::Json => {
+                serde_yaml::from_str(self.contents)?
+            }
+            FrontMatterFormat::Toml => toml::from_str(self.contents)?,
+        };
+        Ok(parsed)
+    }
+
+    /// Swaps the block for blank lines, so that the rest of the document keeps its line numbers
+    fn blank_out(&self, md: &str) -> String {
+        let lines = self.source.matches('\n').count();
+        format!("{}{}", "\n".repeat(lines), &md[self.source.len()..])
+    }
+}
+
+/// The front matter at the start of the document, if there is any. That's YAML between `---`
+/// delimiters, TOML between `+++` ones, or a JSON object that's closed on a line of its own like
+/// Hugo and Zola allow
+fn front_matter(md: &str) -> Option<FrontMatterBlock<'_>> {
+    if let Some(rest) = md.strip_prefix("---") {
+        let contents = rest.split("---").next()?;
+        let source = &md[..(3 + contents.len() + 3).min(md.len())];
+        return Some(FrontMatterBlock {
+            format: FrontMatterFormat::Yaml,
+            contents,
+            source,
+        });
+    }
+    if let Some(rest) = md.strip_prefix("+++") {
+        let end = rest.find("\n+++")?;
+        let contents = &rest[..end];
+        // Through the end of the closing delimiter's line
+        let close = 3 + end + "\n+++".len();
+        let source_len = md[close..].find('\n').map_or(md.len(), |i| close + i + 1);
+        return Some(FrontMatterBlock {
+            format: FrontMatterFormat::Toml,
+            contents,
+            source: &md[..source_len],
+        });
+    }
+    // JSON keys are always quoted, unlike YAML's flow mappings which would take most anything
+    let is_json_object = md
+        .strip_prefix('{')
+        .is_some_and(|rest| rest.trim_start().starts_with(['"', '}']));
+    if is_json_object {
+        let end = md.find("\n}")? + "\n}".len();
+        let contents = &md[..end];
+        let source_len = md[end..].find('\n').map_or(md.len(), |i| end + i + 1);
+        let block = FrontMatterBlock {
+            format: FrontMatterFormat::Json,
+            contents,
+            source: &md[..source_len],
+        };
+        // Anything that doesn't parse was never meant as front matter
+        let parses = block.parse::<serde_yaml::Mapping>().is_ok();
+        return parses.then_some(block);
+    }
+    None
+}
+
+/// Front matter keys that change how the document renders instead of getting displayed
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct Directives {
+    pub smart_punctuation: Option<bool>,
+    pub table_cards: Option<TableCards>,
+    pub toc_depth: Option<u8>,
+}
+
+impl Directives {
+    pub fn from_md(md: &str) -> Self {
+        front_matter(md)
+            .and_then(|block| block.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+/// The document's title, which is the front matter's `title` if it sets one or else the text of
+/// the first top-level heading
+pub fn document_title(md: &str) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Titled {
+        title: Option<String>,
+    }
+
+    let front_matter = front_matter(md);
+    let titled = front_matter
+        .as_ref()
+        .and_then(|block| block.parse::<Titled>().ok())
+        .and_then(|titled| titled.title);
+    if let Some(title) = titled.filter(|title| !title.trim().is_empty()) {
+        return Some(title.trim().to_owned());
+    }
+
+    let blanked;
+    let md = match &front_matter {
+        Some(block) if block.format != FrontMatterFormat::Yaml => {
+            blanked = block.blank_out(md);
+            blanked.as_str()
+        }
+        _ => md,
+    };
+    let arena = Arena::new();
+    let root = parse_document(&arena, md, &markdown_options());
+    root.descendants()
+        .find(|node| matches!(&node.data.borrow().value, NodeValue::Heading(h) if h.level == 1))
+        .map(|heading| toc::text(heading).trim().to_owned())
+        .filter(|title| !title.is_empty())
+}
+
+const DIRECTIVE_KEYS: &[&str] = &["smart-punctuation", "table-cards", "toc-depth"];
+
 #[derive(Deserialize, Debug)]
 struct FrontMatter(IndexMap<String, Cell>);
 
 impl FrontMatter {
     fn to_table(&self) -> String {
+        let shown: Vec<_> = self
+            .0
+            .iter()
+            .filter(|(key, _)| !DIRECTIVE_KEYS.contains(&key.as_str()))
+            .collect();
+        if shown.is_empty() {
+            return String::new();
+        }
         let mut table = String::from("<table>\n");
 
         table.push_str("<thead>\n<tr>\n");
-        for key in self.0.keys() {
+        for (key, _) in &shown {
             table.push_str("<th align=\"center\">");
             html_escape::encode_safe_to_string(key, &mut table);
             table.push_str("</th>\n");
@@ -379,7 +622,7 @@ impl FrontMatter {
         table.push_str("</tr>\n</thead>\n");
 
         table.push_str("<tbody>\n<tr>\n");
-        for cell in self.0.values() {
+        for (_, cell) in &shown {
             table.push_str("<td align=\"center\">");
             cell.render_into(&mut table);
             table.push_str("</td>\n");
@@ -395,6 +638,9 @@ impl FrontMatter {
 #[serde(untagged)]
 enum Cell {
     Str(String),
+    Bool(bool),
+    Int(i64),
+    Float(f64),
     Table(Vec<String>),
 }
 
@@ -404,6 +650,9 @@ impl Cell {
             Self::Str(s) => {
                 html_escape::encode_safe_to_string(s, buf);
             }
+            Self::Bool(b) => buf.push_str(&b.to_string()),
+            Self::Int(i) => buf.push_str(&i.to_string()),
+            Self::Float(f) => buf.push_str(&f.to_string()),
             Self::Table(_v) => {
                 tracing::warn!("Nested tables aren't supported yet. Skipping");
                 buf.push_str("{Skipped nested table}");
@@ -425,7 +674,7 @@ mod tests {
 | Data 1   | Data 2   | Data 3   |
 | Data 4   | Data 5   | Data 6   |"#;
 
-        let html = markdown_to_html(md, SyntectTheme::default());
+        let html = markdown_to_html(md, SyntectTheme::default(), true);
         println!("Caption before table HTML:\n{}", html);
         
         // Check that the caption was converted to an HTML caption tag
@@ -443,7 +692,7 @@ mod tests {
 
 : This is a caption after the table"#;
 
-        let html = markdown_to_html(md, SyntectTheme::default());
+        let html = markdown_to_html(md, SyntectTheme::default(), true);
         println!("Caption after table HTML:\n{}", html);
         
         // Check that the caption was converted to an HTML caption tag
@@ -468,7 +717,7 @@ mod tests {
 </tr>
 </table>"#;
 
-        let html = markdown_to_html(md, SyntectTheme::default());
+        let html = markdown_to_html(md, SyntectTheme::default(), true);
         println!("HTML table with caption:\n{}", html);
         
         // HTML captions should be preserved
@@ -484,7 +733,7 @@ mod tests {
 | Banana   | Yellow | $0.50 |
 | Orange   | Orange | $0.75 |"#;
 
-        let html = markdown_to_html(md, SyntectTheme::default());
+        let html = markdown_to_html(md, SyntectTheme::default(), true);
         println!("Regular table HTML:\n{}", html);
         
         // Should have a table but no caption
@@ -506,7 +755,7 @@ mod tests {
 </tr>
 </table>"#;
 
-        let html = markdown_to_html(md, SyntectTheme::default());
+        let html = markdown_to_html(md, SyntectTheme::default(), true);
         println!("Empty caption HTML:\n{}", html);
         
         // Empty caption should not be in the output
@@ -530,7 +779,7 @@ mod tests {
 </tr>
 </table>"#;
 
-        let html = markdown_to_html(md, SyntectTheme::default());
+        let html = markdown_to_html(md, SyntectTheme::default(), true);
         println!("Whitespace-only caption HTML:\n{}", html);
         
         // Whitespace-only caption should be preserved in HTML
@@ -538,5 +787,85 @@ mod tests {
         assert!(html.contains("<caption>   </caption>"));
     }
 
+    #[test]
+    fn smart_punctuation_override() {
+        let md = "\"quoted\" -- text";
+        let html = markdown_to_html(md, SyntectTheme::default(), true);
+        assert!(html.contains("“quoted” – text"));
+        let html = markdown_to_html(md, SyntectTheme::default(), false);
+        assert!(html.contains("&quot;quoted&quot; -- text"));
+
+        let md = "---\nsmart-punctuation: false\ntitle: Hi\n---\n\"quoted\"";
+        let html = markdown_to_html(md, SyntectTheme::default(), true);
+        assert!(html.contains("&quot;quoted&quot;"));
+        assert!(html.contains("<th align=\"center\">title</th>"));
+        assert!(!html.contains("smart-punctuation"));
+    }
+
+    #[test]
+    fn toml_and_json_front_matter() {
+        let md = "+++\ntitle = \"Hi\"\nweight = 10\nsmart-punctuation = false\n+++\n\"quoted\"";
+        let html = markdown_to_html(md, SyntectTheme::default(), true);
+        assert!(html.contains("<th align=\"center\">title</th>"));
+        assert!(html.contains("<td align=\"center\">10</td>"));
+        assert!(html.contains("&quot;quoted&quot;"));
+        assert!(!html.contains("+++"));
+
+        let md = "{\n  \"title\": \"Hi\",\n  \"draft\": true\n}\n# Heading";
+        let html = markdown_to_html(md, SyntectTheme::default(), true);
+        assert!(html.contains("<th align=\"center\">draft</th>"));
+        assert!(html.contains("<h1>Heading</h1>"));
+        assert!(!html.contains("&quot;title&quot;"));
+
+        // Blanking out the front matter keeps the rest of the document on the same lines
+        let block = front_matter(md).unwrap();
+        assert_eq!(block.blank_out(md), "\n\n\n\n# Heading");
+
+        // Braces that don't open a JSON object are left alone
+        let md = "{not front matter\n}\ntext";
+        assert!(front_matter(md).is_none());
+    }
 
+    #[test]
+    fn document_titles() {
+        let title = |md| document_title(md);
+        let real = "Intro\n\n# The *Real* `Title`\n# Other";
+        assert_eq!(title(real).as_deref(), Some("The Real Title"));
+        let yaml = "---\ntitle: From YAML\n---\n# Heading";
+        assert_eq!(title(yaml).as_deref(), Some("From YAML"));
+        let toml = "+++\ndraft = true\n+++\n# Heading";
+        assert_eq!(title(toml).as_deref(), Some("Heading"));
+        assert_eq!(title("## Only a subheading"), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("repo").join(".git")).unwrap();
+        let file = dir.path().join("repo").join("notes.md");
+        std::fs::write(&file, "").unwrap();
+        let path = Path::new("repo").join("notes.md");
+        let path = path.to_string_lossy();
+        let title = format_title("{title} ({path})", &file, Some("{path}"), Encoding::Utf8);
+        assert_eq!(title, format!("{{path}} ({path})"));
+        let title = format_title(DEFAULT_TITLE_TEMPLATE, &file, None, Encoding::Latin1);
+        assert_eq!(title, format!("Inlyne - {path} (Latin-1)"));
+    }
+
+    #[test]
+    fn snap_to_pixels() {
+        let rect = Rect::new((10.4, 20.6), (100.3, 0.4));
+        assert_eq!(rect.snap_to_pixels(), Rect::new((10., 21.), (101., 1.)));
+        let empty = Rect::new((0.5, 0.5), (0., 0.));
+        assert_eq!(empty.snap_to_pixels(), Rect::new((1., 1.), (0., 0.)));
+    }
+
+    #[test]
+    fn split_line_suffix() {
+        assert_eq!(super::split_line_suffix("file.md"), ("file.md", None));
+        assert_eq!(super::split_line_suffix("file.md:42"), ("file.md", Some(42)));
+        assert_eq!(
+            super::split_line_suffix("path/to/file.rs:10:5"),
+            ("path/to/file.rs", Some(10))
+        );
+        assert_eq!(super::split_line_suffix("file.md:abc"), ("file.md:abc", None));
+        assert_eq!(super::split_line_suffix(":42"), (":42", None));
+    }
 }